@@ -3,6 +3,7 @@ use copyd_protocol::*;
 use anyhow::Result;
 use indicatif::{ProgressBar, ProgressStyle};
 use console::style;
+use std::io::{BufRead, Write};
 use tokio::time::{interval, Duration};
 
 pub async fn handle_copy(
@@ -10,7 +11,24 @@ pub async fn handle_copy(
     args: crate::CopyMoveArgs,
     format: &str,
 ) -> Result<()> {
-    let request = CreateJobRequest {
+    submit_job(client, args, format, JobOperation::Copy).await
+}
+
+pub async fn handle_move(
+    client: CopyClient,
+    args: crate::CopyMoveArgs,
+    format: &str,
+) -> Result<()> {
+    submit_job(client, args, format, JobOperation::Move).await
+}
+
+async fn submit_job(
+    client: CopyClient,
+    args: crate::CopyMoveArgs,
+    format: &str,
+    operation: JobOperation,
+) -> Result<()> {
+    let mut request = CreateJobRequest {
         sources: args.sources.iter().map(|p| p.to_string_lossy().to_string()).collect(),
         destination: args.destination.to_string_lossy().to_string(),
         recursive: args.recursive,
@@ -32,9 +50,136 @@ pub async fn handle_copy(
         block_size: args.block_size.unwrap_or(0),
         compress: args.compress,
         encrypt: args.encrypt,
+        delete_extraneous: args.delete,
+        delete_dry_run: args.delete_dry_run,
+        skip_destinations: Vec::new(),
+        device_source: args.device,
+        atime: args.atime as i32,
+        job_class: args.class as i32,
+        xattr_namespaces: args.xattrs.iter().map(|&namespace| namespace as i32).collect(),
+        tags: args.tags,
+        skip_plan: args.no_plan,
+        stream_fifo: args.stream_fifo,
+        create_parents: args.parents,
+        max_rate_percent: args.max_rate_percent.unwrap_or(0),
+        chmod: args.chmod,
+        dirs_only: args.dirs_only,
+        error_policy: args.on_error as i32,
+        packed: args.packed,
+        description: args.note.unwrap_or_default(),
+        atomic: args.atomic,
+        temp_dir: args.temp_dir.map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default(),
+        destinations: args.mirrors.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+        on_source_change: source_change_action(args.abort_on_change, args.restart_on_change) as i32,
+        best_effort_metadata: args.best_effort_metadata,
+        copy_parallelism: args.copy_parallelism.unwrap_or(0),
+        dedup: args.dedup,
+        verify_retries: args.verify_retries,
+        operation_type: operation as i32,
+        key_id: args.key_id.unwrap_or_default(),
     };
 
-    let job_id = client.create_job(request).await?;
+    if args.interactive {
+        let plan = client.analyze_job(request.clone()).await?;
+        let stdin = std::io::stdin();
+        request.skip_destinations = prompt_overwrites(&plan.conflicts, stdin.lock(), std::io::stderr())?;
+    }
+
+    let (job_id, plan) = client.create_job_with_plan(request).await?;
+
+    if format == "json" {
+        let mut output = serde_json::json!({
+            "job_id": job_id,
+            "status": "created"
+        });
+        if let Some(plan) = &plan {
+            output["plan"] = serde_json::json!({
+                "total_files": plan.total_files,
+                "total_bytes": plan.total_bytes,
+                "total_directories": plan.total_directories,
+                "conflicts": plan.conflicts,
+                "conflict_count": plan.conflict_count,
+            });
+        }
+        println!("{}", output);
+    } else {
+        println!("{} Created {} job: {}",
+            style("✓").green(),
+            if operation == JobOperation::Move { "move" } else { "copy" },
+            style(&job_id).cyan()
+        );
+        if let Some(plan) = &plan {
+            print!("{}", format_plan(plan));
+        }
+    }
+
+    if args.monitor {
+        monitor_job(&client, &job_id, format, &args.progress_format).await?;
+    }
+
+    Ok(())
+}
+
+/// One line of totals for a job plan, followed by the destinations it
+/// overwrites.
+pub fn format_plan(plan: &JobPlan) -> String {
+    let mut text = format!(
+        "Copying {} files ({})\n",
+        format_count(plan.total_files),
+        format_bytes(plan.total_bytes)
+    );
+    if plan.conflict_count > 0 {
+        text.push_str(&format!("Overwriting {} existing files:\n", format_count(plan.conflict_count)));
+        for conflict in &plan.conflicts {
+            text.push_str(&format!("  {}\n", conflict));
+        }
+        let unlisted = plan.conflict_count.saturating_sub(plan.conflicts.len() as u64);
+        if unlisted > 0 {
+            text.push_str(&format!("  ... and {} more\n", format_count(unlisted)));
+        }
+    }
+    text
+}
+
+/// Asks about each conflicting destination in turn, `cp -i` style, and
+/// returns the ones the user declined to overwrite. Anything other than an
+/// explicit yes, including end of input, keeps the existing file.
+pub fn prompt_overwrites<R: BufRead, W: Write>(
+    conflicts: &[String],
+    mut input: R,
+    mut output: W,
+) -> Result<Vec<String>> {
+    let mut skipped = Vec::new();
+    let mut line = String::new();
+
+    for conflict in conflicts {
+        write!(output, "copyctl: overwrite '{}'? [y/N] ", conflict)?;
+        output.flush()?;
+
+        line.clear();
+        input.read_line(&mut line)?;
+        let answer = line.trim().to_lowercase();
+        if answer != "y" && answer != "yes" {
+            skipped.push(conflict.clone());
+        }
+    }
+
+    Ok(skipped)
+}
+
+pub async fn handle_verify_job(
+    client: CopyClient,
+    source: std::path::PathBuf,
+    destination: std::path::PathBuf,
+    mode: VerifyMode,
+    monitor: Option<&str>,
+    format: &str,
+) -> Result<()> {
+    let job_id = client.verify_job(
+        source.to_string_lossy().to_string(),
+        destination.to_string_lossy().to_string(),
+        mode,
+    ).await?;
 
     if format == "json" {
         println!("{}", serde_json::json!({
@@ -42,35 +187,82 @@ pub async fn handle_copy(
             "status": "created"
         }));
     } else {
-        println!("{} Created copy job: {}", 
-            style("✓").green(), 
+        println!("{} Created verify job: {}",
+            style("✓").green(),
             style(&job_id).cyan()
         );
     }
 
-    if args.monitor {
-        monitor_job(&client, &job_id, format).await?;
+    if let Some(progress_format) = monitor {
+        monitor_job(&client, &job_id, format, progress_format).await?;
     }
 
     Ok(())
 }
 
-pub async fn handle_move(
+pub async fn handle_mv(
     client: CopyClient,
-    args: crate::CopyMoveArgs,
+    sources: Vec<std::path::PathBuf>,
+    destination: std::path::PathBuf,
     format: &str,
 ) -> Result<()> {
-    println!("{} Move operation will copy then delete source files", style("⚠").yellow());
-    
-    handle_copy(client, args, format).await
+    if sources.len() > 1 && !destination.is_dir() {
+        anyhow::bail!("Target {:?} is not a directory", destination);
+    }
+
+    for source in &sources {
+        let target = crate::mv::move_target(source, &destination)?;
+        let renamed = crate::mv::try_rename(source, &target)?;
+
+        if !renamed {
+            // The daemon resolves paths itself, so it needs absolute ones
+            let request = CreateJobRequest {
+                sources: vec![std::path::absolute(source)?.to_string_lossy().to_string()],
+                destination: std::path::absolute(crate::mv::copy_destination(&target))?.to_string_lossy().to_string(),
+                recursive: true,
+                preserve_metadata: true,
+                preserve_links: true,
+                preserve_sparse: true,
+                exists_action: ExistsAction::Overwrite as i32,
+                priority: 100,
+                skip_plan: true,
+                ..Default::default()
+            };
+            let job_id = client.create_job(request).await?;
+            let status = wait_for_job(&client, &job_id).await?;
+            if status != JobStatus::Completed {
+                anyhow::bail!("Copy job {} for {:?} ended {:?}; the source was left in place", job_id, source, status);
+            }
+            crate::mv::remove_source(source)?;
+        }
+
+        if format == "json" {
+            println!("{}", serde_json::json!({
+                "source": source,
+                "destination": target,
+                "method": if renamed { "rename" } else { "copy" },
+            }));
+        } else {
+            println!("{} Moved {} -> {}{}",
+                style("✓").green(),
+                source.display(),
+                target.display(),
+                if renamed { "" } else { " (copied across filesystems)" }
+            );
+        }
+    }
+
+    Ok(())
 }
 
 pub async fn handle_list(
     client: CopyClient,
     completed: bool,
+    tags: Vec<String>,
+    absolute: bool,
     format: &str,
 ) -> Result<()> {
-    let jobs = client.list_jobs(completed).await?;
+    let jobs = client.list_jobs(completed, tags).await?;
 
     if format == "json" {
         println!("{}", serde_json::to_string_pretty(&jobs)?);
@@ -80,10 +272,14 @@ pub async fn handle_list(
             return Ok(());
         }
 
-        println!("{:<36} {:<8} {:<20} {:<20} {:<10}", "Job ID", "Status", "Source", "Destination", "Progress");
-        println!("{}", "-".repeat(100));
+        let now = chrono::Utc::now().timestamp();
+        let when_width = if absolute { 28 } else { 16 };
+        println!("{:<36} {:<8} {:<when_width$} {:<20} {:<20} {:<10}",
+            "Job ID", "Status", "When", "Source", "Destination", "Progress");
+        println!("{}", "-".repeat(101 + when_width));
 
         for job in jobs {
+            let when = latest_job_event(job.created_at, job.started_at, job.completed_at, now, absolute);
             let job_id = job.job_id.map(|j| j.uuid).unwrap_or_default();
             let status = styled_job_status(job.progress.as_ref().map(|p| p.status).unwrap_or(0));
 
@@ -103,7 +299,7 @@ pub async fn handle_list(
 
             let progress = if let Some(p) = job.progress {
                 if p.total_bytes > 0 {
-                    format!("{:.1}%", (p.bytes_copied as f64 / p.total_bytes as f64) * 100.0)
+                    format!("{:.1}%", p.overall_percent)
                 } else {
                     "N/A".to_string()
                 }
@@ -112,13 +308,17 @@ pub async fn handle_list(
             };
 
             let short_id = job_id.get(..8).unwrap_or(&job_id);
-            println!("{:<36} {:<8} {:<20} {:<20} {:<10}",
+            println!("{:<36} {:<8} {:<when_width$} {:<20} {:<20} {:<10}",
                 style(short_id).dim(),
                 status,
+                when,
                 source,
                 destination,
                 progress
             );
+            if !job.description.is_empty() {
+                println!("  {}", style(&job.description).dim());
+            }
         }
     }
 
@@ -127,25 +327,189 @@ pub async fn handle_list(
 
 pub async fn handle_status(
     client: CopyClient,
-    job_id: String,
-    monitor: bool,
+    job_ids: Vec<String>,
+    monitor: Option<&str>,
+    absolute: bool,
     format: &str,
 ) -> Result<()> {
-    if monitor {
-        monitor_job(&client, &job_id, format).await?;
+    if let [job_id] = job_ids.as_slice() {
+        if let Some(progress_format) = monitor {
+            monitor_job(&client, job_id, format, progress_format).await?;
+        } else {
+            let status = client.get_job_status(job_id).await?;
+
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                print_job_status(&status, absolute);
+            }
+        }
+        return Ok(());
+    }
+
+    if monitor.is_some() {
+        anyhow::bail!("--monitor follows a single job");
+    }
+    let statuses = client.get_job_statuses(&job_ids).await?;
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&statuses)?);
     } else {
-        let status = client.get_job_status(&job_id).await?;
+        for (i, status) in statuses.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            print_job_status(status, absolute);
+        }
+    }
 
-        if format == "json" {
-            println!("{}", serde_json::to_string_pretty(&status)?);
-        } else {
-            print_job_status(&status);
+    Ok(())
+}
+
+pub async fn handle_attach(
+    client: CopyClient,
+    job_id: String,
+    format: &str,
+) -> Result<()> {
+    let mut events = client.attach(&job_id).await?;
+
+    if format == "json" {
+        while let Some(event) = events.next_event().await? {
+            println!("{}", serde_json::to_string(&event)?);
+        }
+        return Ok(());
+    }
+
+    println!("{} Attached to job {}", style("🔗").blue(), style(&job_id).cyan());
+    // Positions are hundredths of a percent of the job's copy and verify work
+    let pb = ProgressBar::new(10_000);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {percent}% {msg}")
+            .expect("valid indicatif progress bar template")
+            .progress_chars("#>-")
+    );
+
+    let mut final_status = None;
+    while let Some(event) = events.next_event().await? {
+        match event.event_type {
+            Some(job_event::EventType::ProgressUpdate(progress)) => {
+                pb.set_position((progress.overall_percent * 100.0) as u64);
+                pb.set_message(analysis_message(&progress).unwrap_or_else(|| format!("({}/{}) {} / {} files, {:.1} MB/s",
+                    format_bytes(progress.bytes_copied),
+                    format_bytes(progress.total_bytes),
+                    progress.files_copied,
+                    progress.total_files,
+                    progress.throughput_mbps
+                )));
+                if let Ok(status) = JobStatus::try_from(progress.status) {
+                    if status.is_terminal() {
+                        final_status = Some(status);
+                    }
+                }
+            }
+            Some(job_event::EventType::StatusChange(status)) => {
+                if let Ok(status) = JobStatus::try_from(status) {
+                    if status.is_terminal() {
+                        final_status = Some(status);
+                    } else {
+                        pb.println(format!("  Job is now {:?}", status));
+                    }
+                }
+            }
+            Some(job_event::EventType::LogMessage(message)) => pb.println(format!("  {}", message)),
+            Some(job_event::EventType::FileError(error)) => {
+                pb.println(format!("  {} {}: {}", style("✗").red(), error.file_path, error.error));
+            }
+            Some(job_event::EventType::DirectoryProgress(directories)) => {
+                pb.set_message(format!("Creating directories ({}/{})", directories.created, directories.total));
+            }
+            Some(job_event::EventType::SourceRemoved(path)) => pb.println(format!("  Removed source {}", path)),
+            Some(job_event::EventType::FileCompleted(_)) | None => {}
         }
     }
 
+    match final_status {
+        Some(JobStatus::Completed) => pb.finish_with_message("Completed!"),
+        Some(JobStatus::Failed) => pb.finish_with_message("Failed!"),
+        Some(JobStatus::Cancelled) => pb.finish_with_message("Cancelled!"),
+        _ => pb.abandon_with_message("Event stream closed"),
+    }
+
     Ok(())
 }
 
+/// Exit code of `copyctl wait` when the job doesn't finish in time, as used
+/// by timeout(1).
+pub const WAIT_TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Exit code of `copyctl wait` for a job that ended in `status`.
+pub fn wait_exit_code(status: JobStatus) -> i32 {
+    match status {
+        JobStatus::Completed => 0,
+        JobStatus::Failed => 2,
+        JobStatus::Cancelled => 3,
+        _ => 1,
+    }
+}
+
+/// Follows the job's event stream until it reaches a terminal status.
+pub async fn wait_for_job(client: &CopyClient, job_id: &str) -> Result<JobStatus> {
+    let mut events = client.attach(job_id).await?;
+    while let Some(event) = events.next_event().await? {
+        let status = match event.event_type {
+            Some(job_event::EventType::ProgressUpdate(progress)) => progress.status,
+            Some(job_event::EventType::StatusChange(status)) => status,
+            _ => continue,
+        };
+        if let Ok(status) = JobStatus::try_from(status) {
+            if status.is_terminal() {
+                return Ok(status);
+            }
+        }
+    }
+
+    // The daemon also ends the stream if it drops a subscriber that fell behind
+    let status = client.get_job_status(job_id).await?
+        .progress
+        .and_then(|progress| JobStatus::try_from(progress.status).ok())
+        .unwrap_or(JobStatus::Pending);
+    if !status.is_terminal() {
+        anyhow::bail!("Lost the event stream of job {} before it finished", job_id);
+    }
+    Ok(status)
+}
+
+/// Waits for the job and returns the exit code for its outcome.
+pub async fn handle_wait(
+    client: CopyClient,
+    job_id: String,
+    timeout: Option<Duration>,
+    format: &str,
+) -> Result<i32> {
+    let outcome = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, wait_for_job(&client, &job_id)).await.ok(),
+        None => Some(wait_for_job(&client, &job_id).await),
+    };
+    let status = outcome.transpose()?;
+
+    if format == "json" {
+        println!("{}", serde_json::json!({
+            "job_id": job_id,
+            "status": status.map(|s| format!("{:?}", s)),
+            "timed_out": status.is_none(),
+        }));
+    } else {
+        match status {
+            Some(JobStatus::Completed) => println!("{} Job {} completed", style("✓").green(), style(&job_id).cyan()),
+            Some(JobStatus::Failed) => println!("{} Job {} failed", style("✗").red(), style(&job_id).cyan()),
+            Some(status) => println!("{} Job {} was {}", style("ℹ").blue(), style(&job_id).cyan(), format!("{:?}", status).to_lowercase()),
+            None => println!("{} Timed out waiting for job {}", style("⏱").yellow(), style(&job_id).cyan()),
+        }
+    }
+
+    Ok(status.map_or(WAIT_TIMEOUT_EXIT_CODE, wait_exit_code))
+}
+
 pub async fn handle_cancel(
     client: CopyClient,
     job_id: String,
@@ -168,6 +532,36 @@ pub async fn handle_cancel(
     Ok(())
 }
 
+pub async fn handle_cancel_matching(
+    client: CopyClient,
+    source_prefix: std::path::PathBuf,
+    format: &str,
+) -> Result<()> {
+    let source_prefix = std::path::absolute(&source_prefix)?;
+    let cancelled = client.cancel_matching(source_prefix.to_string_lossy().to_string()).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::json!({
+            "source_prefix": source_prefix,
+            "cancelled_job_ids": cancelled,
+            "action": "cancelled"
+        }));
+    } else if cancelled.is_empty() {
+        println!("{} No active jobs under {}", style("ℹ").blue(), source_prefix.display());
+    } else {
+        println!("{} Cancelled {} jobs under {}",
+            style("✓").green(),
+            cancelled.len(),
+            source_prefix.display()
+        );
+        for job_id in &cancelled {
+            println!("  {}", style(job_id).cyan());
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn handle_pause(
     client: CopyClient,
     job_id: String,
@@ -193,9 +587,10 @@ pub async fn handle_pause(
 pub async fn handle_resume(
     client: CopyClient,
     job_id: String,
+    source_root_remaps: Vec<SourceRootRemap>,
     format: &str,
 ) -> Result<()> {
-    client.resume_job(&job_id).await?;
+    client.resume_job(&job_id, source_root_remaps).await?;
 
     if format == "json" {
         println!("{}", serde_json::json!({
@@ -212,6 +607,88 @@ pub async fn handle_resume(
     Ok(())
 }
 
+/// Pauses (`pause`) or resumes every applicable job and lists them.
+pub async fn handle_pause_all(client: CopyClient, pause: bool, format: &str) -> Result<()> {
+    let (job_ids, action) = if pause {
+        (client.pause_all().await?, "paused")
+    } else {
+        (client.resume_all().await?, "resumed")
+    };
+
+    if format == "json" {
+        println!("{}", serde_json::json!({
+            "job_ids": job_ids,
+            "action": action
+        }));
+    } else if job_ids.is_empty() {
+        let none = if pause { "No running or queued jobs to pause" } else { "No paused jobs to resume" };
+        println!("{} {}", style("ℹ").blue(), none);
+    } else {
+        let symbol = if pause { style("⏸").yellow() } else { style("▶").green() };
+        let verb = if pause { "Paused" } else { "Resumed" };
+        println!("{} {} {} jobs", symbol, verb, job_ids.len());
+        for job_id in &job_ids {
+            println!("  {}", style(job_id).cyan());
+        }
+    }
+
+    Ok(())
+}
+
+/// What `stats --format json` prints: the daemon's totals plus the window
+/// they cover and when they were fetched.
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct StatsOutput {
+    #[serde(flatten)]
+    pub stats: StatsResponse,
+    pub days_back: i32,
+    /// RFC 3339 timestamp
+    pub generated_at: String,
+}
+
+/// Commands whose `--format json` output has a published schema.
+pub const SCHEMA_OUTPUTS: [&str; 3] = ["status", "list", "stats"];
+
+/// JSON Schema for the `--format json` output of `status`, `list` or `stats`.
+/// Enum fields are written as their protobuf numbers, as in the output.
+/// `status` with several job IDs prints an array of these objects.
+pub fn output_schema(command: &str) -> Result<schemars::schema::RootSchema> {
+    Ok(match command {
+        "status" => schemars::schema_for!(JobStatusResponse),
+        "list" => schemars::schema_for!(Vec<JobInfo>),
+        "stats" => schemars::schema_for!(StatsOutput),
+        _ => anyhow::bail!("no schema for '{}', expected one of {}", command, SCHEMA_OUTPUTS.join(", ")),
+    })
+}
+
+pub fn handle_schema(command: &str) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&output_schema(command)?)?);
+    Ok(())
+}
+
+pub fn handle_gen(dir: &std::path::Path, spec: &crate::gen::TreeSpec, format: &str) -> Result<()> {
+    let started = std::time::Instant::now();
+    let summary = crate::gen::generate_tree(dir, spec)?;
+    if format == "json" {
+        println!("{}", serde_json::json!({
+            "dir": dir,
+            "files": summary.files,
+            "bytes": summary.bytes,
+            "sparse": spec.sparse,
+            "seed": spec.seed,
+        }));
+    } else {
+        println!("{} Generated {} files, {} in {:?} ({:.1}s)",
+            style("✓").green(),
+            format_count(summary.files),
+            format_bytes(summary.bytes),
+            dir,
+            started.elapsed().as_secs_f64()
+        );
+    }
+    Ok(())
+}
+
 pub async fn handle_stats(
     client: CopyClient,
     days: i32,
@@ -220,7 +697,12 @@ pub async fn handle_stats(
     let stats = client.get_stats(days).await?;
 
     if format == "json" {
-        println!("{}", serde_json::to_string_pretty(&stats)?);
+        let output = StatsOutput {
+            stats,
+            days_back: days,
+            generated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        println!("{}", serde_json::to_string_pretty(&output)?);
     } else {
         println!("{} Statistics for the last {} days:", style("📊").blue(), days);
         println!("  Total bytes copied: {}", format_bytes(stats.total_bytes_copied));
@@ -254,91 +736,948 @@ pub async fn handle_stats(
     Ok(())
 }
 
-pub async fn handle_health(
+pub async fn handle_purge(
     client: CopyClient,
+    older_than_days: u32,
+    statuses: Vec<JobStatus>,
     format: &str,
 ) -> Result<()> {
-    let health = client.health_check().await?;
+    let response = client.purge_jobs(older_than_days, statuses).await?;
 
     if format == "json" {
-        println!("{}", serde_json::to_string_pretty(&health)?);
+        println!("{}", serde_json::to_string_pretty(&response)?);
+    } else if response.purged_count == 0 {
+        println!("{} No jobs older than {} days to purge", style("ℹ").blue(), older_than_days);
     } else {
-        let status_icon = if health.healthy {
-            style("✓").green()
-        } else {
-            style("✗").red()
-        };
-
-        println!("{} Daemon status: {}", 
-            status_icon,
-            if health.healthy { style("HEALTHY").green() } else { style("UNHEALTHY").red() }
-        );
-        println!("  Version: {}", health.version);
-        println!("  Uptime: {}", format_duration(health.uptime_seconds));
-        println!("  Active jobs: {}", health.active_jobs);
-        println!("  Queued jobs: {}", health.queued_jobs);
-        println!("  Memory usage: {}", format_bytes(health.memory_usage_bytes));
-        println!("  CPU usage: {:.1}%", health.cpu_usage_percent);
+        println!("{} Purged {} jobs", style("✓").green(), response.purged_count);
+        for job_id in response.purged_job_ids {
+            println!("  {}", style(job_id.uuid).dim());
+        }
     }
 
     Ok(())
 }
 
-async fn monitor_job(client: &CopyClient, job_id: &str, format: &str) -> Result<()> {
+pub async fn handle_history(
+    client: CopyClient,
+    query: QueryJobsRequest,
+    format: &str,
+) -> Result<()> {
+    let response = client.query_jobs(query.clone()).await?;
+
     if format == "json" {
-        // For JSON format, just poll and output status updates
-        let mut interval = interval(Duration::from_secs(1));
-        loop {
-            interval.tick().await;
-            
-            match client.get_job_status(job_id).await {
-                Ok(status) => {
-                    println!("{}", serde_json::to_string_pretty(&status)?);
-                    
-                    if let Some(progress) = &status.progress {
-                        if let Ok(status) = JobStatus::try_from(progress.status) {
-                            match status {
-                                JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled => break,
-                                _ => {}
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("{}", serde_json::json!({
-                        "error": format!("Error getting job status: {}", e)
-                    }));
-                    break;
-                }
-            }
+        println!("{}", serde_json::to_string_pretty(&response)?);
+        return Ok(());
+    }
+
+    if response.jobs.is_empty() {
+        println!("{} No finished jobs match", style("ℹ").blue());
+        return Ok(());
+    }
+
+    println!("{:<10} {:<10} {:<17} {:<24} {:<24} {:>8} {:>10}",
+        "Job ID", "Status", "Finished", "Source", "Destination", "Files", "Size");
+    println!("{}", "-".repeat(109));
+
+    for job in &response.jobs {
+        let job_id = job.job_id.as_ref().map(|j| j.uuid.as_str()).unwrap_or_default();
+        let progress = job.progress.clone().unwrap_or_default();
+        let finished = chrono::DateTime::from_timestamp(job.completed_at, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let mut source = job.sources.first().cloned().unwrap_or_default();
+        if job.sources.len() > 1 {
+            source = format!("{} (+{})", source, job.sources.len() - 1);
         }
-    } else {
-        // For text format, show a nice progress bar
-        let pb = ProgressBar::new(100);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {percent}% ({bytes}/{total_bytes}) {msg}")
-                .expect("valid indicatif progress bar template")
-                .progress_chars("#>-")
+
+        println!("{:<10} {:<10} {:<17} {:<24} {:<24} {:>8} {:>10}",
+            style(job_id.get(..8).unwrap_or(job_id)).dim(),
+            styled_job_status(progress.status),
+            finished,
+            shorten(&source, 24),
+            shorten(&job.destination, 24),
+            progress.files_copied,
+            format_bytes(progress.bytes_copied)
         );
-        pb.set_length(100);
+        if !job.description.is_empty() {
+            println!("  {}", style(&job.description).dim());
+        }
+    }
 
-        let mut interval = interval(Duration::from_secs(1));
-        loop {
-            interval.tick().await;
-            
-            match client.get_job_status(job_id).await {
-                Ok(status) => {
-                    if let Some(progress) = &status.progress {
-                        let percent = if progress.total_bytes > 0 {
-                            (progress.bytes_copied as f64 / progress.total_bytes as f64 * 100.0) as u64
-                        } else {
-                            0
-                        };
+    let first = query.offset as u64 + 1;
+    let last = query.offset as u64 + response.jobs.len() as u64;
+    print!("\nShowing {}-{} of {} jobs", first, last, response.total_matches);
+    if last < response.total_matches && query.limit > 0 {
+        print!(" (--page {} for more)", query.offset / query.limit + 2);
+    }
+    println!();
 
-                        pb.set_position(percent);
-                        
-                        let msg = if progress.throughput_mbps > 0.0 {
+    Ok(())
+}
+
+/// The request `job` was created with, changed by `overrides`.
+pub fn replay_request(job: &JobInfo, overrides: &crate::ReplayOverrides) -> Result<CreateJobRequest> {
+    let job_id = job.job_id.as_ref().map(|id| id.uuid.as_str()).unwrap_or_default();
+    let mut request = job.request.clone()
+        .ok_or_else(|| anyhow::anyhow!("Job {} has no recorded request to replay (verify-only or resumed job)", job_id))?;
+    // Answers to --interactive prompts were about the destination as it was then
+    request.skip_destinations.clear();
+
+    if let Some(destination) = &overrides.destination {
+        request.destination = destination.to_string_lossy().to_string();
+    }
+    if let Some(verify) = overrides.verify {
+        request.verify = verify as i32;
+    }
+    if let Some(exists) = overrides.exists {
+        request.exists_action = exists as i32;
+    }
+    if let Some(priority) = overrides.priority {
+        request.priority = priority;
+    }
+    if let Some(rate) = overrides.max_rate {
+        request.max_rate_bps = rate.checked_mul(1024 * 1024)
+            .ok_or_else(|| anyhow::anyhow!("--max-rate is too large"))?;
+    }
+    if let Some(engine) = overrides.engine {
+        request.engine = engine as i32;
+    }
+    if let Some(class) = overrides.class {
+        request.job_class = class as i32;
+    }
+    request.dry_run |= overrides.dry_run;
+    Ok(request)
+}
+
+/// Failures of `job_id` from the daemon's jobs, or from the history once
+/// the daemon no longer holds the job.
+async fn find_error_summary(client: &CopyClient, job_id: &str) -> Result<(String, ErrorSummary)> {
+    let status = client.get_job_status(job_id).await?;
+    if status.error.is_empty() {
+        return Ok((job_id.to_string(), status.error_summary.unwrap_or_default()));
+    }
+    let response = client.query_jobs(QueryJobsRequest { job_id: job_id.to_string(), ..Default::default() }).await?;
+    match response.jobs.as_slice() {
+        [job] => Ok((
+            job.job_id.as_ref().map(|id| id.uuid.clone()).unwrap_or_default(),
+            job.error_summary.clone().unwrap_or_default(),
+        )),
+        [] => anyhow::bail!("Job {} is neither known to the daemon nor in the job history", job_id),
+        _ => anyhow::bail!("{} jobs in the history start with {}; give more of the ID", response.jobs.len(), job_id),
+    }
+}
+
+pub async fn handle_show_errors(
+    client: CopyClient,
+    job_id: String,
+    format: &str,
+) -> Result<()> {
+    let (job_id, summary) = find_error_summary(&client, &job_id).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+            "job_id": job_id,
+            "error_count": summary.error_count,
+            "errors": summary.errors,
+        }))?);
+        return Ok(());
+    }
+
+    if summary.error_count == 0 {
+        println!("{} No files failed in job {}", style("✓").green(), style(&job_id).cyan());
+        return Ok(());
+    }
+    println!("{} {} files failed in job {}:", style("⚠️").yellow(), summary.error_count, style(&job_id).cyan());
+    for error in &summary.errors {
+        println!("  {}: {}", error.file_path, error.error);
+    }
+    let unlisted = summary.error_count.saturating_sub(summary.errors.len() as u64);
+    if unlisted > 0 {
+        println!("  ... and {} more that were only counted", unlisted);
+    }
+    Ok(())
+}
+
+pub async fn handle_results(
+    client: CopyClient,
+    job_id: String,
+    format: &str,
+) -> Result<()> {
+    let results = client.get_job_results(&job_id).await?;
+
+    if format == "json" {
+        let files: Vec<_> = results.iter().map(|result| serde_json::json!({
+            "source": result.source,
+            "destination": result.destination,
+            "bytes": result.bytes,
+            "engine": format!("{:?}", result.engine()),
+            "verify_mode": format!("{:?}", result.verify_mode()),
+            "verify_result": format!("{:?}", result.verify_result()),
+            "duration_us": result.duration_us,
+            "error": result.error,
+            "completed_at": result.completed_at,
+        })).collect();
+        println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+            "job_id": job_id,
+            "files": files,
+        }))?);
+        return Ok(());
+    }
+
+    if results.is_empty() {
+        println!("{} No file results for job {}", style("ℹ").blue(), style(&job_id).cyan());
+        return Ok(());
+    }
+    let failed = results.iter().filter(|result| !result.error.is_empty()).count();
+    println!("{} files in job {} ({} failed):", results.len(), style(&job_id).cyan(), failed);
+    for result in &results {
+        let verification = match result.verify_result() {
+            VerifyResult::NotVerified => "not verified".to_string(),
+            VerifyResult::Verified => format!("{:?} verified", result.verify_mode()),
+            VerifyResult::Mismatch => format!("{:?} mismatch", result.verify_mode()),
+        };
+        let mark = if result.error.is_empty() { style("✓").green() } else { style("✗").red() };
+        println!("  {} {} -> {}", mark, result.source, result.destination);
+        println!("      {}, {}, {}, {:.1} ms",
+            format_bytes(result.bytes),
+            result.engine(),
+            verification,
+            result.duration_us as f64 / 1000.0,
+        );
+        if !result.error.is_empty() {
+            println!("      {}", style(&result.error).red());
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_replay(
+    client: CopyClient,
+    job_id: String,
+    overrides: crate::ReplayOverrides,
+    format: &str,
+) -> Result<()> {
+    let response = client.query_jobs(QueryJobsRequest { job_id: job_id.clone(), ..Default::default() }).await?;
+    let job = match response.jobs.as_slice() {
+        [job] => job,
+        [] => anyhow::bail!("Job {} is not in the job history", job_id),
+        _ => anyhow::bail!("{} jobs in the history start with {}; give more of the ID", response.jobs.len(), job_id),
+    };
+    let request = replay_request(job, &overrides)?;
+    let new_job_id = client.create_job(request).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::json!({
+            "job_id": new_job_id,
+            "replayed": job_id,
+            "status": "created"
+        }));
+    } else {
+        println!("{} Replayed job {} as {}",
+            style("✓").green(),
+            style(&job_id).dim(),
+            style(&new_job_id).cyan()
+        );
+    }
+
+    if overrides.monitor {
+        monitor_job(&client, &new_job_id, format, &overrides.progress_format).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn handle_config_set(
+    client: CopyClient,
+    settings: Vec<ConfigSetting>,
+    format: &str,
+) -> Result<()> {
+    let update = client.set_config(settings).await?;
+    print_config_update(&update, format)
+}
+
+pub async fn handle_set_concurrency(
+    client: CopyClient,
+    max: u32,
+    format: &str,
+) -> Result<()> {
+    let update = client.set_concurrency(max).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&update)?);
+        return Ok(());
+    }
+
+    println!("{} Concurrent job limit changed from {} to {}",
+        style("✓").green(),
+        update.previous_max,
+        update.max_concurrent_jobs
+    );
+    if update.running_jobs > update.max_concurrent_jobs {
+        println!("{} {} jobs are running; they finish normally and no new job starts until fewer than {} remain",
+            style("ℹ").blue(),
+            update.running_jobs,
+            update.max_concurrent_jobs
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn handle_config_reload(
+    client: CopyClient,
+    format: &str,
+) -> Result<()> {
+    let update = client.reload_config().await?;
+    print_config_update(&update, format)
+}
+
+pub async fn handle_config_migrate(path: &std::path::Path, format: &str) -> Result<()> {
+    let migration = copyd::Config::migrate_file(path).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+            "from_version": migration.from_version,
+            "to_version": migration.to_version,
+            "added": migration.added.iter()
+                .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+                .collect::<Vec<_>>(),
+            "rewritten": migration.changed(),
+        }))?);
+        return Ok(());
+    }
+
+    if !migration.changed() {
+        println!("{} {} is already at version {}", style("ℹ").blue(), path.display(), migration.to_version);
+        return Ok(());
+    }
+    println!("{} Migrated {} from version {} to {}",
+        style("✓").green(),
+        path.display(),
+        migration.from_version,
+        migration.to_version
+    );
+    for (key, value) in &migration.added {
+        println!("  {} {} = {}", style("+").green(), style(key).cyan(), value);
+    }
+
+    Ok(())
+}
+
+fn print_config_update(update: &ConfigUpdateResponse, format: &str) -> Result<()> {
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(update)?);
+        return Ok(());
+    }
+
+    if update.applied.is_empty() && update.restart_required.is_empty() {
+        println!("{} Configuration unchanged", style("ℹ").blue());
+    }
+    for key in &update.applied {
+        println!("{} Applied {}", style("✓").green(), style(key).cyan());
+    }
+    for key in &update.restart_required {
+        println!("{} {} changes after the daemon restarts", style("⚠").yellow(), style(key).cyan());
+    }
+
+    Ok(())
+}
+
+pub async fn handle_import(
+    client: CopyClient,
+    spec_path: std::path::PathBuf,
+    format: &str,
+) -> Result<()> {
+    let content = std::fs::read_to_string(&spec_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", spec_path.display(), e))?;
+    let requests = crate::spec::parse_job_spec(&content)?;
+
+    let mut job_ids = Vec::new();
+    for request in requests {
+        job_ids.push(client.create_job(request).await?);
+    }
+
+    if format == "json" {
+        println!("{}", serde_json::json!({
+            "job_ids": job_ids,
+            "status": "created"
+        }));
+    } else {
+        println!("{} Imported {} jobs from {}",
+            style("✓").green(),
+            job_ids.len(),
+            spec_path.display()
+        );
+        for job_id in &job_ids {
+            println!("  {}", style(job_id).cyan());
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn handle_tree_diff(
+    client: CopyClient,
+    source: std::path::PathBuf,
+    destination: std::path::PathBuf,
+    format: &str,
+) -> Result<()> {
+    let source = std::path::absolute(&source)?;
+    let destination = std::path::absolute(&destination)?;
+    let diff = client.tree_diff(
+        source.to_string_lossy().to_string(),
+        destination.to_string_lossy().to_string(),
+    ).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    for path in &diff.new_files {
+        println!("{} {}", style("+").green(), path);
+    }
+    for path in &diff.changed_files {
+        println!("{} {}", style("~").yellow(), path);
+    }
+    for path in &diff.extraneous_files {
+        println!("{} {}", style("-").red(), path);
+    }
+
+    println!("\n{} {} new, {} changed, {} unchanged, {} to delete with --delete",
+        style("ℹ").blue(),
+        diff.new_files.len(),
+        diff.changed_files.len(),
+        diff.unchanged_count,
+        diff.extraneous_files.len()
+    );
+
+    Ok(())
+}
+
+pub async fn handle_engine_stats(client: CopyClient, format: &str) -> Result<()> {
+    let stats = client.get_engine_stats().await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    if stats.engines.is_empty() {
+        println!("{} No copies have run since the daemon started", style("ℹ").blue());
+        return Ok(());
+    }
+    println!("{:<16} {:>12} {:>11} {:>8} {:>10}", "Engine", "Throughput", "Operations", "Errors", "Copied");
+    for engine in &stats.engines {
+        println!(
+            "{:<16} {:>7.1} MB/s {:>11} {:>7.1}% {:>10}",
+            engine.engine,
+            engine.throughput_mbps,
+            engine.operations,
+            engine.error_rate,
+            format_bytes(engine.total_bytes),
+        );
+    }
+    if !stats.recommendations.is_empty() {
+        println!();
+        println!("{} Recommendations:", style("⚠").yellow());
+        for recommendation in &stats.recommendations {
+            println!("  [{}] {}: {}", recommendation.severity, recommendation.category, recommendation.issue);
+            println!("      {}", recommendation.suggestion);
+        }
+    }
+    Ok(())
+}
+
+/// Totals of `copyctl size`.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct SourceSize {
+    pub total_files: u64,
+    pub total_bytes: u64,
+    pub directories: u64,
+    pub symlinks: u64,
+}
+
+pub async fn handle_size(
+    sources: Vec<std::path::PathBuf>,
+    recursive: bool,
+    exclude: Vec<String>,
+    format: &str,
+) -> Result<()> {
+    let filter = copyd::ExcludeFilter::new(&exclude)?;
+    // Symlinks are collected either way; destinations don't matter here
+    let traversal = copyd::DirectoryHandler::analyze_sources_excluding(&sources, std::path::Path::new(""), recursive, true, &filter).await?;
+    let size = SourceSize {
+        total_files: traversal.total_files,
+        total_bytes: traversal.total_size,
+        directories: traversal.directories.len() as u64,
+        symlinks: traversal.symlinks.len() as u64,
+    };
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&size)?);
+    } else {
+        print!("{}", format_size(&size));
+    }
+    Ok(())
+}
+
+/// Totals of `copyctl size`, one per line.
+pub fn format_size(size: &SourceSize) -> String {
+    format!(
+        "Files:       {}\nBytes:       {} ({})\nDirectories: {}\nSymlinks:    {}\n",
+        format_count(size.total_files),
+        format_count(size.total_bytes),
+        format_bytes(size.total_bytes),
+        format_count(size.directories),
+        format_count(size.symlinks),
+    )
+}
+
+pub async fn handle_estimate(
+    client: CopyClient,
+    sources: Vec<std::path::PathBuf>,
+    destination: std::path::PathBuf,
+    engine: CopyEngine,
+    format: &str,
+) -> Result<()> {
+    let destination = std::path::absolute(&destination)?;
+    let recursive = sources.iter().any(|source| source.is_dir());
+    let traversal = copyd::DirectoryHandler::analyze_sources(&sources, &destination, recursive, true).await?;
+    let estimate = client.estimate(
+        traversal.total_files,
+        traversal.total_size,
+        copyd::device::device_label(&destination),
+        engine,
+    ).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&estimate)?);
+    } else {
+        print!("{}", format_estimate(&estimate));
+    }
+    Ok(())
+}
+
+/// Prediction of `copyctl estimate`: totals, the throughput it rests on and
+/// the expected duration with its range, without and with verification.
+pub fn format_estimate(estimate: &EstimateResponse) -> String {
+    let duration = |seconds: f64| format_duration(seconds.ceil() as i64);
+    let range = |factor: f64| format!(
+        "{} ({} to {})",
+        duration(estimate.seconds * factor),
+        duration(estimate.min_seconds * factor),
+        duration(estimate.max_seconds * factor),
+    );
+    let mut text = format!(
+        "Files:       {}\nBytes:       {} ({})\nThroughput:  {:.1} MB/s ({:.1} to {:.1} MB/s over {} copies)\nCopy:        {}\nWith verify: {}\n",
+        format_count(estimate.total_files),
+        format_count(estimate.total_bytes),
+        format_bytes(estimate.total_bytes),
+        estimate.throughput_mbps,
+        estimate.low_throughput_mbps,
+        estimate.high_throughput_mbps,
+        estimate.samples,
+        range(1.0),
+        range(1.0 + estimate.verify_cost),
+    );
+    if estimate.other_devices {
+        text.push_str("No copies have reached the destination's device yet; this goes by copies to other devices\n");
+    }
+    text
+}
+
+pub async fn handle_recommend(
+    client: CopyClient,
+    sources: Vec<std::path::PathBuf>,
+    destination: std::path::PathBuf,
+    run: bool,
+    format: &str,
+) -> Result<()> {
+    let sources = sources.iter().map(std::path::absolute).collect::<std::io::Result<Vec<_>>>()?;
+    let destination = std::path::absolute(&destination)?;
+    let recommendation = client.recommend(
+        sources.iter().map(|source| source.to_string_lossy().to_string()).collect(),
+        destination.to_string_lossy().to_string(),
+    ).await?;
+    let command = recommended_command(&sources, &destination, &recommendation);
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&recommendation)?);
+        return Ok(());
+    }
+    print!("{}", format_recommendation(&recommendation, &command));
+    if !run {
+        return Ok(());
+    }
+
+    print!("Start this copy? [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        return Ok(());
+    }
+    let crate::Commands::Copy { args } = <crate::Cli as clap::Parser>::try_parse_from(&command)?.command else {
+        unreachable!("the recommended command is a copy");
+    };
+    handle_copy(client, args, format).await
+}
+
+/// Name `copyctl copy --engine` takes for `engine`.
+fn engine_flag(engine: CopyEngine) -> &'static str {
+    match engine {
+        CopyEngine::Auto => "auto",
+        CopyEngine::IoUring => "io_uring",
+        CopyEngine::CopyFileRange => "copyfilerange",
+        CopyEngine::Sendfile => "sendfile",
+        CopyEngine::Reflink => "reflink",
+        CopyEngine::ReadWrite => "readwrite",
+        CopyEngine::Splice => "splice",
+    }
+}
+
+/// Arguments of the `copyctl copy` that carries out `recommendation`.
+pub fn recommended_command(
+    sources: &[std::path::PathBuf],
+    destination: &std::path::Path,
+    recommendation: &RecommendResponse,
+) -> Vec<String> {
+    let mut command = vec!["copyctl".to_string(), "copy".to_string()];
+    let recursive = sources.iter().any(|source| source.is_dir());
+    if recursive {
+        command.push("-r".to_string());
+    }
+    command.extend(["--engine".to_string(), engine_flag(recommendation.engine()).to_string()]);
+    if recommendation.block_size > 0 {
+        command.extend(["--block-size".to_string(), recommendation.block_size.to_string()]);
+    }
+    // Packed mode only applies to trees
+    if recommendation.packed && recursive {
+        command.push("--packed".to_string());
+    }
+    command.extend(sources.iter().map(|source| source.to_string_lossy().to_string()));
+    command.push(destination.to_string_lossy().to_string());
+    command
+}
+
+/// `arg` as a shell would need it typed.
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// What `copyctl recommend` found, what it suggests and why.
+pub fn format_recommendation(recommendation: &RecommendResponse, command: &[String]) -> String {
+    let mut out = String::new();
+    out.push_str(&format!(
+        "Sources:     {} files, {} (median file {}, {} of at most 64 KB)\n",
+        format_count(recommendation.total_files),
+        format_bytes(recommendation.total_bytes),
+        format_bytes(recommendation.median_file_size),
+        format_count(recommendation.small_files),
+    ));
+    out.push_str(&format!(
+        "Filesystem:  {}, {}\n",
+        if recommendation.same_filesystem { "same as the destination" } else { "different from the destination" },
+        if recommendation.reflink { "destination supports reflinks" } else { "no reflinks at the destination" },
+    ));
+    out.push_str(&format!("io_uring:    {}\n\n", if recommendation.io_uring { "available" } else { "not available" }));
+
+    out.push_str(&format!("Engine:      {}\n", engine_flag(recommendation.engine())));
+    if recommendation.block_size > 0 {
+        out.push_str(&format!("Block size:  {}\n", format_bytes(recommendation.block_size)));
+    }
+    if recommendation.packed {
+        out.push_str("Packed:      yes\n");
+    }
+    out.push_str("Why:\n");
+    for reason in &recommendation.reasons {
+        out.push_str(&format!("  - {}\n", reason));
+    }
+    let command: Vec<String> = command.iter().map(|arg| shell_quote(arg)).collect();
+    out.push_str(&format!("\nCommand:     {}\n", command.join(" ")));
+    out
+}
+
+pub async fn handle_diff_verify(
+    client: CopyClient,
+    a: std::path::PathBuf,
+    b: std::path::PathBuf,
+    mode: VerifyMode,
+    details: bool,
+    format: &str,
+) -> Result<()> {
+    let a = std::path::absolute(&a)?;
+    let b = std::path::absolute(&b)?;
+    let comparison = client.diff_verify(
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+        mode,
+    ).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&comparison)?);
+    } else {
+        print!("{}", format_diff_verify(&comparison, details));
+    }
+
+    let differences = comparison.differing.len() + comparison.only_left.len() + comparison.only_right.len();
+    if differences > 0 {
+        anyhow::bail!("{} and {} differ in {} files", a.display(), b.display(), differences);
+    }
+    Ok(())
+}
+
+/// Per-file lines when `details` is set, `=` matching, `≠` differing, `<`
+/// only in the first tree and `>` only in the second, then the totals.
+pub fn format_diff_verify(comparison: &DiffVerifyResponse, details: bool) -> String {
+    let mut text = String::new();
+    if details {
+        for (marker, paths) in [
+            ("=", &comparison.matching),
+            ("≠", &comparison.differing),
+            ("<", &comparison.only_left),
+            (">", &comparison.only_right),
+        ] {
+            for path in paths {
+                text.push_str(&format!("{} {}\n", marker, path));
+            }
+        }
+        if !text.is_empty() {
+            text.push('\n');
+        }
+    }
+    text.push_str(&format!(
+        "{} matching, {} differing, {} only in first, {} only in second\n",
+        comparison.matching.len(),
+        comparison.differing.len(),
+        comparison.only_left.len(),
+        comparison.only_right.len()
+    ));
+    text
+}
+
+pub async fn handle_snapshot(
+    client: CopyClient,
+    source: std::path::PathBuf,
+    destination: std::path::PathBuf,
+    format: &str,
+) -> Result<()> {
+    let source = std::path::absolute(&source)?;
+    let destination = std::path::absolute(&destination)?;
+    let snapshot = client.snapshot(
+        source.to_string_lossy().to_string(),
+        destination.to_string_lossy().to_string(),
+    ).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&snapshot)?);
+        return Ok(());
+    }
+
+    println!("{} Snapshot of {} created at {}",
+        style("✓").green(),
+        source.display(),
+        style(destination.display()).cyan()
+    );
+    println!("  {} files ({}), {} hard links, {} symlinks, {} directories",
+        snapshot.files,
+        format_bytes(snapshot.bytes),
+        snapshot.hard_links,
+        snapshot.symlinks,
+        snapshot.directories
+    );
+
+    Ok(())
+}
+
+pub async fn handle_verify(
+    client: CopyClient,
+    path: std::path::PathBuf,
+    sidecar: Option<std::path::PathBuf>,
+    algorithm: Option<VerifyMode>,
+    format: &str,
+) -> Result<()> {
+    let path = std::fs::canonicalize(&path).unwrap_or(path);
+    let response = client.verify_file(
+        path.to_string_lossy().to_string(),
+        sidecar.map(|p| std::fs::canonicalize(&p).unwrap_or(p).to_string_lossy().to_string()),
+        algorithm,
+    ).await?;
+    let algorithm = VerifyMode::try_from(response.algorithm).unwrap_or(VerifyMode::None);
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+    } else if response.verified {
+        println!("{} {} matches {} ({})",
+            style("✓").green(),
+            path.display(),
+            response.sidecar_path,
+            algorithm
+        );
+    } else {
+        println!("{} {} does not match {} ({})",
+            style("✗").red(),
+            path.display(),
+            response.sidecar_path,
+            algorithm
+        );
+        println!("  Expected:   {}", response.expected_checksum);
+        println!("  Calculated: {}", response.calculated_checksum);
+    }
+
+    if !response.verified {
+        anyhow::bail!("Checksum mismatch for {}", path.display());
+    }
+
+    Ok(())
+}
+
+pub async fn handle_health(
+    client: CopyClient,
+    format: &str,
+) -> Result<()> {
+    let health = client.health_check().await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&health)?);
+    } else {
+        let status_icon = if health.healthy {
+            style("✓").green()
+        } else {
+            style("✗").red()
+        };
+
+        println!("{} Daemon status: {}", 
+            status_icon,
+            if health.healthy { style("HEALTHY").green() } else { style("UNHEALTHY").red() }
+        );
+        println!("  Version: {}", health.version);
+        println!("  Uptime: {}", format_duration(health.uptime_seconds));
+        println!("  Active jobs: {}", health.active_jobs);
+        println!("  Queued jobs: {}", health.queued_jobs);
+        println!("  Memory usage: {}", format_bytes(health.memory_usage_bytes));
+        println!("  CPU usage: {:.1}%", health.cpu_usage_percent);
+    }
+
+    Ok(())
+}
+
+/// Longest a restart waits for the new daemon beyond the grace period.
+const RESTART_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub async fn handle_shutdown(client: CopyClient, grace_secs: u32, restart: bool, format: &str) -> Result<()> {
+    let response = client.shutdown(grace_secs, restart).await?;
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+    } else {
+        println!(
+            "{} the daemon; {} running jobs have up to {} to finish",
+            if restart { "Restarting" } else { "Stopping" },
+            response.running_jobs,
+            format_duration(grace_secs as i64),
+        );
+    }
+    if !restart {
+        return Ok(());
+    }
+
+    // The old daemon stops listening at once; the new one listens after
+    // the old one's jobs have drained
+    let limit = Duration::from_secs(grace_secs.into()) + RESTART_TIMEOUT;
+    let deadline = tokio::time::Instant::now() + limit;
+    let mut old_gone = false;
+    loop {
+        let answering = CopyClient::new(client.socket_path()).await.is_ok();
+        old_gone |= !answering;
+        if old_gone && answering {
+            break;
+        }
+        if tokio::time::Instant::now() > deadline {
+            anyhow::bail!("The daemon did not come back within {}", format_duration(limit.as_secs() as i64));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    if format != "json" {
+        println!("{} Daemon restarted", style("✓").green());
+    }
+    Ok(())
+}
+
+/// Follows a job until it ends: a progress bar, a `key=value` line a second
+/// with `progress_format` "kv", or its status as JSON a second.
+async fn monitor_job(client: &CopyClient, job_id: &str, format: &str, progress_format: &str) -> Result<()> {
+    if progress_format == "kv" {
+        let mut interval = interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let status = client.get_job_status(job_id).await?;
+            let Some(progress) = &status.progress else {
+                continue;
+            };
+            println!("{}", format_progress_kv(job_id, progress));
+            std::io::stdout().flush()?;
+            if JobStatus::try_from(progress.status).is_ok_and(|status| status.is_terminal()) {
+                break;
+            }
+        }
+    } else if format == "json" {
+        // For JSON format, just poll and output status updates
+        let mut interval = interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            
+            match client.get_job_status(job_id).await {
+                Ok(status) => {
+                    println!("{}", serde_json::to_string_pretty(&status)?);
+                    
+                    if let Some(progress) = &status.progress {
+                        if let Ok(JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled) = JobStatus::try_from(progress.status) {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    println!("{}", serde_json::json!({
+                        "error": format!("Error getting job status: {}", e)
+                    }));
+                    break;
+                }
+            }
+        }
+    } else {
+        // For text format, show a nice progress bar
+        let pb = ProgressBar::new(100);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {percent}% ({bytes}/{total_bytes}) {msg}")
+                .expect("valid indicatif progress bar template")
+                .progress_chars("#>-")
+        );
+        pb.set_length(100);
+
+        let mut interval = interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            
+            match client.get_job_status(job_id).await {
+                Ok(status) => {
+                    if let Some(progress) = &status.progress {
+                        pb.set_position(progress.overall_percent as u64);
+                        
+                        let msg = if let Some(analyzing) = analysis_message(progress) {
+                            analyzing
+                        } else if progress.directories_created < progress.total_directories {
+                            format!("Creating directories ({}/{})",
+                                progress.directories_created,
+                                progress.total_directories)
+                        } else if progress.throughput_mbps > 0.0 {
                             format!("{:.1} MB/s, ETA: {}s", 
                                 progress.throughput_mbps, 
                                 progress.eta_seconds)
@@ -378,25 +1717,70 @@ async fn monitor_job(client: &CopyClient, job_id: &str, format: &str) -> Result<
     Ok(())
 }
 
-fn print_job_status(status: &JobStatusResponse) {
+/// One line of `--progress-format kv`: `job=<id> bytes=<n> total=<n>
+/// pct=<f> mbps=<f> eta=<n> analyzed=<n> verified=<n>`, where `bytes` are
+/// the bytes copied.
+pub fn format_progress_kv(job_id: &str, progress: &Progress) -> String {
+    format!(
+        "job={} bytes={} total={} pct={:.1} mbps={:.1} eta={} analyzed={} verified={}",
+        job_id,
+        progress.bytes_copied,
+        progress.total_bytes,
+        progress.overall_percent,
+        progress.throughput_mbps,
+        progress.eta_seconds,
+        progress.bytes_analyzed,
+        progress.bytes_verified,
+    )
+}
+
+/// What a job's analysis has found so far, while it is still running and
+/// the totals aren't known yet.
+fn analysis_message(progress: &Progress) -> Option<String> {
+    (progress.total_files == 0 && progress.files_analyzed > 0).then(|| format!(
+        "Analyzing sources ({} files, {} found)",
+        progress.files_analyzed,
+        format_bytes(progress.bytes_analyzed)
+    ))
+}
+
+fn print_job_status(status: &JobStatusResponse, absolute: bool) {
     let job_id = status.job_id.as_ref()
         .map(|j| j.uuid.clone())
         .unwrap_or_default();
 
     println!("{} Job Status: {}", style("📋").blue(), style(&job_id).cyan());
+    if !status.description.is_empty() {
+        println!("  Note: {}", status.description);
+    }
+    let now = chrono::Utc::now().timestamp();
+    for (label, timestamp) in [("Created", status.created_at), ("Started", status.started_at), ("Finished", status.completed_at)] {
+        if let Some(time) = format_time(timestamp, now, absolute) {
+            println!("  {}: {}", label, time);
+        }
+    }
 
     if let Some(progress) = &status.progress {
         let status_text = styled_job_status(progress.status);
 
         println!("  Status: {}", status_text);
+        if let Some(analyzing) = analysis_message(progress) {
+            println!("  {}", analyzing);
+        }
         
         if progress.total_bytes > 0 {
-            let percent = (progress.bytes_copied as f64 / progress.total_bytes as f64) * 100.0;
-            println!("  Progress: {:.1}% ({} / {})",
-                percent,
+            println!("  Progress: {:.1}% ({} / {} copied)",
+                progress.overall_percent,
                 format_bytes(progress.bytes_copied),
                 format_bytes(progress.total_bytes)
             );
+            if progress.bytes_verified > 0 {
+                println!("  Verified: {} / {} ({} files)",
+                    format_bytes(progress.bytes_verified),
+                    format_bytes(progress.total_bytes),
+                    progress.files_verified
+                );
+            }
         }
 
         if progress.throughput_mbps > 0.0 {
@@ -412,6 +1796,23 @@ fn print_job_status(status: &JobStatusResponse) {
         }
     }
 
+    if !status.rate_samples.is_empty() {
+        let rates: Vec<f64> = status.rate_samples.iter().map(|s| s.throughput_mbps).collect();
+        let peak = rates.iter().cloned().fold(0.0, f64::max);
+        println!("  Rate history: {} (peak {:.1} MB/s)", sparkline(&rates), peak);
+    }
+
+    if let Some(summary) = status.error_summary.as_ref().filter(|s| s.error_count > 0) {
+        println!("\n{} {} files failed to copy:", style("⚠️").yellow(), summary.error_count);
+        for error in &summary.errors {
+            println!("  {}: {}", error.file_path, error.error);
+        }
+        let unlisted = summary.error_count.saturating_sub(summary.errors.len() as u64);
+        if unlisted > 0 {
+            println!("  ... and {} more", unlisted);
+        }
+    }
+
     if !status.log_entries.is_empty() {
         println!("\n{} Recent log entries:", style("📝").blue());
         for entry in status.log_entries.iter().rev().take(5) {
@@ -424,6 +1825,124 @@ fn print_job_status(status: &JobStatusResponse) {
     }
 }
 
+/// Parse an age such as `30d` or `30` into a number of days.
+pub fn parse_days(value: &str) -> Result<u32, String> {
+    value.trim_end_matches('d')
+        .parse()
+        .map_err(|_| format!("invalid age '{}', expected a number of days such as 30d", value))
+}
+
+/// Parse a byte count such as `1G`, `512M` or `4096`; suffixes are binary.
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size '{}', expected a number of bytes such as 4096, 512M or 1G", value);
+    let upper = value.trim().to_uppercase();
+    let digits = upper.trim_end_matches(['B', 'I']);
+    let (digits, shift) = match digits.char_indices().last() {
+        Some((index, 'K')) => (&digits[..index], 10),
+        Some((index, 'M')) => (&digits[..index], 20),
+        Some((index, 'G')) => (&digits[..index], 30),
+        Some((index, 'T')) => (&digits[..index], 40),
+        _ => (digits, 0),
+    };
+    let amount: u64 = digits.trim().parse().map_err(|_| invalid())?;
+    amount.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+/// Parse an octal permission mode such as `644` or `0755`.
+pub fn parse_mode(value: &str) -> Result<u32, String> {
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("invalid mode '{}', expected octal permissions such as 644", value)),
+    }
+}
+
+/// Parse a job end state name such as `completed`.
+pub fn parse_job_status(value: &str) -> Result<JobStatus, String> {
+    match JobStatus::from_str_name(&value.to_uppercase()) {
+        Some(status @ (JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)) => Ok(status),
+        Some(_) => Err(format!("'{}' is not an end state, expected completed, failed or cancelled", value)),
+        None => Err(format!("invalid job status '{}'", value)),
+    }
+}
+
+/// Parse a point in time as a Unix timestamp: an age such as `7d`, a local
+/// date such as `2026-10-01`, or an RFC 3339 timestamp.
+pub fn parse_time(value: &str) -> Result<i64, String> {
+    use chrono::{DateTime, Local, NaiveDate};
+
+    if let Some(days) = value.strip_suffix('d') {
+        if let Ok(days) = days.parse::<u32>() {
+            return Ok((Local::now() - chrono::Duration::days(days as i64)).timestamp());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        if let Some(midnight) = date.and_hms_opt(0, 0, 0).and_then(|t| t.and_local_timezone(Local).earliest()) {
+            return Ok(midnight.timestamp());
+        }
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.timestamp())
+        .map_err(|_| format!("invalid time '{}', expected an age such as 7d, a date such as 2026-10-01, or an RFC 3339 timestamp", value))
+}
+
+/// Parse an `old=new` source root remapping.
+pub fn parse_source_root_remap(value: &str) -> Result<SourceRootRemap, String> {
+    match value.split_once('=') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() => Ok(SourceRootRemap {
+            from: from.to_string(),
+            to: to.to_string(),
+        }),
+        _ => Err(format!("invalid remap '{}', expected OLD=NEW", value)),
+    }
+}
+
+pub fn parse_config_setting(value: &str) -> Result<ConfigSetting, String> {
+    match value.split_once('=') {
+        Some((key, setting)) if !key.trim().is_empty() => Ok(ConfigSetting {
+            key: key.trim().to_string(),
+            value: setting.trim().to_string(),
+        }),
+        _ => Err(format!("invalid setting '{}', expected KEY=VALUE", value)),
+    }
+}
+
+/// One bar per value, scaled to the largest.
+pub fn sparkline(values: &[f64]) -> String {
+    const BARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    values.iter().map(|v| {
+        if max <= 0.0 {
+            BARS[0]
+        } else {
+            BARS[((v / max) * (BARS.len() - 1) as f64).round() as usize]
+        }
+    }).collect()
+}
+
+/// Cuts `text` to at most `width` characters, marking the cut with `...`.
+fn shorten(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let kept: String = text.chars().take(width.saturating_sub(3)).collect();
+        format!("{}...", kept)
+    }
+}
+
+/// Groups digits in thousands, e.g. 1,234,567.
+fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
 fn format_bytes(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB", "PB"];
     let mut size = bytes as f64;
@@ -451,6 +1970,73 @@ fn format_duration(seconds: i64) -> String {
     }
 }
 
+/// The action `--abort-on-change` or `--restart-on-change` asks for.
+pub fn source_change_action(abort: bool, restart: bool) -> SourceChangeAction {
+    if restart {
+        SourceChangeAction::RestartCopy
+    } else if abort {
+        SourceChangeAction::AbortCopy
+    } else {
+        SourceChangeAction::IgnoreChange
+    }
+}
+
+/// How long ago something happened `seconds` before now, in the largest
+/// whole unit: "45s ago", "3m ago", "1h ago" or "2d ago".
+fn format_ago(seconds: i64) -> String {
+    match seconds {
+        ..=0 => "just now".to_string(),
+        1..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+/// A Unix time as how long before `now` it was, or with `absolute` as a
+/// local date and time; `None` for a time that isn't set.
+fn format_time(timestamp: i64, now: i64, absolute: bool) -> Option<String> {
+    if timestamp == 0 {
+        return None;
+    }
+    if absolute {
+        return chrono::DateTime::from_timestamp(timestamp, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+    Some(format_ago(now - timestamp))
+}
+
+/// The latest thing that happened to a job and when, e.g. "started 3m ago".
+fn latest_job_event(created_at: i64, started_at: i64, completed_at: i64, now: i64, absolute: bool) -> String {
+    let (event, timestamp) = if completed_at != 0 {
+        ("finished", completed_at)
+    } else if started_at != 0 {
+        ("started", started_at)
+    } else {
+        ("created", created_at)
+    };
+    format_time(timestamp, now, absolute)
+        .map(|time| format!("{} {}", event, time))
+        .unwrap_or_default()
+}
+
+/// Whether output is coloured for a `--color` choice: `always` and `never`
+/// as they say, `auto` only on a terminal and when `NO_COLOR` isn't set.
+pub fn colors_wanted(choice: &str, no_color: bool, terminal: bool) -> bool {
+    match choice {
+        "always" => true,
+        "never" => false,
+        _ => terminal && !no_color,
+    }
+}
+
+/// Turns the styling of everything copyctl prints on or off for `--color`.
+pub fn apply_color_choice(choice: &str) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    console::set_colors_enabled(colors_wanted(choice, no_color, console::Term::stdout().is_term()));
+    console::set_colors_enabled_stderr(colors_wanted(choice, no_color, console::Term::stderr().is_term()));
+}
+
 /// Convert a numeric `JobStatus` code into a coloured, human-readable string.
 fn styled_job_status(code: i32) -> console::StyledObject<&'static str> {
     match JobStatus::try_from(code) {
@@ -462,4 +2048,461 @@ fn styled_job_status(code: i32) -> console::StyledObject<&'static str> {
         Ok(JobStatus::Cancelled) => style("CANCELLED").red(),
         _ => style("UNKNOWN").dim(),
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_overwrites_scripted_responses() {
+        let conflicts: Vec<String> = ["/dst/a", "/dst/b", "/dst/c", "/dst/d", "/dst/e"]
+            .iter().map(|s| s.to_string()).collect();
+        // Blank answers and unrecognised input default to keeping the file,
+        // and the script runs out before the last prompt.
+        let input = std::io::Cursor::new("y\nn\nYES\n\n");
+        let mut output = Vec::new();
+
+        let skipped = prompt_overwrites(&conflicts, input, &mut output).unwrap();
+        assert_eq!(skipped, vec!["/dst/b", "/dst/d", "/dst/e"]);
+
+        let prompts = String::from_utf8(output).unwrap();
+        assert_eq!(prompts.matches("[y/N]").count(), conflicts.len());
+        assert!(prompts.starts_with("copyctl: overwrite '/dst/a'?"));
+    }
+
+    #[test]
+    fn test_color_choice() {
+        assert!(colors_wanted("auto", false, true));
+        assert!(!colors_wanted("auto", false, false));
+        assert!(!colors_wanted("auto", true, true));
+        assert!(colors_wanted("always", true, false));
+        assert!(!colors_wanted("never", false, true));
+
+        // Nothing else in these tests expects escape codes, so turning
+        // styling off for the whole process is safe
+        console::set_colors_enabled(false);
+        for code in [JobStatus::Pending, JobStatus::Failed, JobStatus::Completed] {
+            let status = styled_job_status(code as i32).to_string();
+            assert!(!status.contains('\x1b'), "{:?}", status);
+        }
+        assert_eq!(style("done").green().bold().to_string(), "done");
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = chrono::Utc::now().timestamp();
+        let week_ago = parse_time("7d").unwrap();
+        assert!((now - 7 * 86400 - week_ago).abs() <= 5);
+
+        assert_eq!(parse_time("2026-10-01T12:00:00Z").unwrap(), 1_790_856_000);
+        let date = parse_time("2026-10-01").unwrap();
+        assert!((date - 1_790_812_800).abs() <= 14 * 3600, "local midnight {}", date);
+
+        assert!(parse_time("yesterday").is_err());
+        assert!(parse_time("2026-13-01").is_err());
+    }
+
+    #[test]
+    fn test_replay_request_applies_overrides() {
+        let original = CreateJobRequest {
+            sources: vec!["/data/photos".to_string()],
+            destination: "/backup".to_string(),
+            recursive: true,
+            verify: VerifyMode::Sha256 as i32,
+            priority: 100,
+            max_rate_bps: 10 * 1024 * 1024,
+            tags: vec!["nightly".to_string()],
+            skip_destinations: vec!["/backup/photos/keep.jpg".to_string()],
+            ..Default::default()
+        };
+        let job = JobInfo {
+            job_id: Some(JobId { uuid: "a1b2".to_string() }),
+            request: Some(original.clone()),
+            ..Default::default()
+        };
+        let no_overrides = crate::ReplayOverrides {
+            destination: None,
+            verify: None,
+            exists: None,
+            priority: None,
+            max_rate: None,
+            engine: None,
+            class: None,
+            dry_run: false,
+            monitor: false,
+            progress_format: "bar".to_string(),
+        };
+
+        let replayed = replay_request(&job, &no_overrides).unwrap();
+        assert_eq!(replayed, CreateJobRequest { skip_destinations: Vec::new(), ..original.clone() });
+
+        let replayed = replay_request(&job, &crate::ReplayOverrides {
+            destination: Some("/offsite".into()),
+            priority: Some(10),
+            max_rate: Some(5),
+            dry_run: true,
+            progress_format: "bar".to_string(),
+            ..no_overrides
+        }).unwrap();
+        assert_eq!(replayed.destination, "/offsite");
+        assert_eq!(replayed.priority, 10);
+        assert_eq!(replayed.max_rate_bps, 5 * 1024 * 1024);
+        assert!(replayed.dry_run);
+        assert_eq!(replayed.verify, VerifyMode::Sha256 as i32);
+        assert_eq!(replayed.sources, original.sources);
+
+        let err = replay_request(&JobInfo::default(), &no_overrides).unwrap_err();
+        assert!(err.to_string().contains("no recorded request"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("640").unwrap(), 0o640);
+        assert_eq!(parse_mode("0755").unwrap(), 0o755);
+        assert_eq!(parse_mode("0o2775").unwrap(), 0o2775);
+        assert!(parse_mode("rwxr-x---").is_err());
+        assert!(parse_mode("789").is_err());
+        assert!(parse_mode("17777").is_err());
+    }
+
+    #[test]
+    fn test_parse_config_setting() {
+        let setting = parse_config_setting("allowed_engines = [\"ReadWrite\"]").unwrap();
+        assert_eq!(setting.key, "allowed_engines");
+        assert_eq!(setting.value, "[\"ReadWrite\"]");
+        assert!(parse_config_setting("max_concurrent_jobs").is_err());
+        assert!(parse_config_setting("=4").is_err());
+    }
+
+    #[test]
+    fn test_format_plan() {
+        let plan = JobPlan {
+            total_files: 1234,
+            total_bytes: 5 * 1024 * 1024 * 1024,
+            total_directories: 7,
+            conflicts: vec!["/dst/a".to_string(), "/dst/b".to_string()],
+            conflict_count: 1002,
+        };
+        assert_eq!(
+            format_plan(&plan),
+            "Copying 1,234 files (5.00 GB)\nOverwriting 1,002 existing files:\n  /dst/a\n  /dst/b\n  ... and 1,000 more\n"
+        );
+
+        let plan = JobPlan { total_files: 3, total_bytes: 10, ..Default::default() };
+        assert_eq!(format_plan(&plan), "Copying 3 files (10 B)\n");
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("4096"), Ok(4096));
+        assert_eq!(parse_byte_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_byte_size("1G"), Ok(1 << 30));
+        assert_eq!(parse_byte_size("2gib"), Ok(2 << 30));
+        assert_eq!(parse_byte_size("10KB"), Ok(10 << 10));
+        for invalid in ["", "G", "1.5G", "1X", "99999999999T"] {
+            assert!(parse_byte_size(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_format_size() {
+        let size = SourceSize { total_files: 1234, total_bytes: 5 * 1024 * 1024, directories: 12, symlinks: 3 };
+        assert_eq!(
+            format_size(&size),
+            "Files:       1,234\nBytes:       5,242,880 (5.00 MB)\nDirectories: 12\nSymlinks:    3\n"
+        );
+    }
+
+    #[test]
+    fn test_format_progress_kv() {
+        let progress = Progress {
+            bytes_copied: 52_428_800,
+            total_bytes: 209_715_200,
+            throughput_mbps: 123.456,
+            eta_seconds: 2,
+            status: JobStatus::Running as i32,
+            overall_percent: 25.0,
+            bytes_analyzed: 209_715_200,
+            bytes_verified: 10_485_760,
+            ..Default::default()
+        };
+        let line = format_progress_kv("3f2a9c1e", &progress);
+        assert_eq!(line, "job=3f2a9c1e bytes=52428800 total=209715200 pct=25.0 mbps=123.5 eta=2 analyzed=209715200 verified=10485760");
+
+        // Every field splits cleanly on spaces and '='
+        let fields: Vec<(&str, &str)> = line.split(' ').map(|field| field.split_once('=').unwrap()).collect();
+        let keys: Vec<&str> = fields.iter().map(|&(key, _)| key).collect();
+        assert_eq!(keys, ["job", "bytes", "total", "pct", "mbps", "eta", "analyzed", "verified"]);
+    }
+
+    #[test]
+    fn test_format_estimate() {
+        let estimate = EstimateResponse {
+            total_files: 1234,
+            total_bytes: 1000 * 1024 * 1024,
+            throughput_mbps: 1000.0 / 11.0,
+            low_throughput_mbps: 50.0,
+            high_throughput_mbps: 100.0,
+            samples: 10,
+            seconds: 11.0,
+            min_seconds: 10.0,
+            max_seconds: 20.0,
+            verify_cost: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_estimate(&estimate),
+            "Files:       1,234\nBytes:       1,048,576,000 (1000.00 MB)\n\
+             Throughput:  90.9 MB/s (50.0 to 100.0 MB/s over 10 copies)\n\
+             Copy:        11s (10s to 20s)\nWith verify: 22s (20s to 40s)\n"
+        );
+
+        let elsewhere = EstimateResponse { other_devices: true, ..estimate };
+        assert!(format_estimate(&elsewhere).ends_with("this goes by copies to other devices\n"));
+    }
+
+    #[test]
+    fn test_format_diff_verify() {
+        let comparison = DiffVerifyResponse {
+            matching: vec!["docs/a.txt".to_string(), "b.txt".to_string()],
+            differing: vec!["c.bin".to_string()],
+            only_left: vec!["old.log".to_string()],
+            only_right: vec!["new/d.txt".to_string(), "e.txt".to_string()],
+            error: String::new(),
+        };
+
+        assert_eq!(
+            format_diff_verify(&comparison, false),
+            "2 matching, 1 differing, 1 only in first, 2 only in second\n"
+        );
+        assert_eq!(
+            format_diff_verify(&comparison, true),
+            "= docs/a.txt\n= b.txt\n≠ c.bin\n< old.log\n> new/d.txt\n> e.txt\n\n\
+             2 matching, 1 differing, 1 only in first, 2 only in second\n"
+        );
+        assert_eq!(
+            format_diff_verify(&DiffVerifyResponse::default(), true),
+            "0 matching, 0 differing, 0 only in first, 0 only in second\n"
+        );
+    }
+
+    /// Daemon on a temporary socket that answers health checks and streams a
+    /// running job that ends in `outcome` after `delay`, or never ends.
+    fn fake_daemon(outcome: Option<JobStatus>, delay: Duration) -> std::path::PathBuf {
+        let socket = std::env::temp_dir().join(format!("copyctl-test-{}.sock", uuid::Uuid::new_v4()));
+        let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let Ok(request) = receive_request(&mut stream).await else { return };
+                    let respond = |response_type| Response { response_type: Some(response_type) };
+                    let event = |event_type| respond(response::ResponseType::JobEvent(JobEventResponse {
+                        event: Some(JobEvent { job_id: None, event_type: Some(event_type) }),
+                        error: String::new(),
+                    }));
+                    match request.request_type {
+                        Some(request::RequestType::HealthCheck(_)) => {
+                            let health = HealthCheckResponse { healthy: true, ..Default::default() };
+                            let _ = send_response(&mut stream, &respond(response::ResponseType::HealthCheck(health))).await;
+                        }
+                        Some(request::RequestType::SubscribeEvents(_)) => {
+                            let running = Progress { status: JobStatus::Running.into(), ..Default::default() };
+                            let _ = send_response(&mut stream, &event(job_event::EventType::ProgressUpdate(running))).await;
+                            tokio::time::sleep(delay).await;
+                            match outcome {
+                                Some(status) => {
+                                    let _ = send_response(&mut stream, &event(job_event::EventType::StatusChange(status.into()))).await;
+                                }
+                                None => std::future::pending::<()>().await,
+                            }
+                        }
+                        _ => {}
+                    }
+                });
+            }
+        });
+        socket
+    }
+
+    #[tokio::test]
+    async fn test_wait_returns_when_job_finishes() {
+        for (outcome, code) in [(JobStatus::Completed, 0), (JobStatus::Failed, 2), (JobStatus::Cancelled, 3)] {
+            let socket = fake_daemon(Some(outcome), Duration::from_millis(100));
+            let client = CopyClient::new(&socket).await.unwrap();
+
+            let started = std::time::Instant::now();
+            assert_eq!(wait_for_job(&client, "job").await.unwrap(), outcome);
+            assert!(started.elapsed() < Duration::from_secs(2), "wait took {:?}", started.elapsed());
+            assert_eq!(wait_exit_code(outcome), code);
+            std::fs::remove_file(socket).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_times_out() {
+        let socket = fake_daemon(None, Duration::ZERO);
+        let client = CopyClient::new(&socket).await.unwrap();
+
+        let started = std::time::Instant::now();
+        let code = handle_wait(client, "job".to_string(), Some(Duration::from_millis(300)), "json").await.unwrap();
+        assert_eq!(code, WAIT_TIMEOUT_EXIT_CODE);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        std::fs::remove_file(socket).unwrap();
+    }
+
+    #[test]
+    fn test_json_outputs_match_their_schemas() {
+        let validate = |command: &str, output: serde_json::Value| {
+            let schema = serde_json::to_value(output_schema(command).unwrap()).unwrap();
+            let validator = jsonschema::validator_for(&schema).unwrap();
+            let errors: Vec<String> = validator.iter_errors(&output).map(|e| e.to_string()).collect();
+            assert!(errors.is_empty(), "{} output fails its schema: {:?}", command, errors);
+            validator
+        };
+
+        let progress = Progress {
+            bytes_copied: 4096,
+            total_bytes: 8192,
+            files_copied: 1,
+            total_files: 2,
+            throughput_mbps: 12.5,
+            eta_seconds: 3,
+            status: JobStatus::Running.into(),
+            ..Default::default()
+        };
+        let status = JobStatusResponse {
+            job_id: Some(JobId { uuid: "job".to_string() }),
+            progress: Some(progress.clone()),
+            error: String::new(),
+            log_entries: vec!["Copying".to_string()],
+            rate_samples: vec![RateSample { timestamp: 1_700_000_000, throughput_mbps: 12.5, ops_per_second: 1.0 }],
+            error_summary: Some(ErrorSummary {
+                error_count: 1,
+                errors: vec![FileError { file_path: "/src/bad".to_string(), error: "denied".to_string() }],
+            }),
+            description: "Nightly photo sync".to_string(),
+            created_at: 1_700_000_000,
+            started_at: 1_700_000_005,
+            completed_at: 0,
+            not_found: false,
+        };
+        let validator = validate("status", serde_json::to_value(&status).unwrap());
+        assert!(!validator.is_valid(&serde_json::json!({ "progress": { "bytes_copied": "lots" } })));
+
+        let jobs = vec![JobInfo {
+            job_id: Some(JobId { uuid: "job".to_string() }),
+            sources: vec!["/src".to_string()],
+            destination: "/dst".to_string(),
+            progress: Some(progress),
+            created_at: 1_700_000_000,
+            tags: vec!["nightly".to_string()],
+            request: Some(CreateJobRequest {
+                sources: vec!["/src".to_string()],
+                destination: "/dst".to_string(),
+                recursive: true,
+                chmod: Some(0o640),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }];
+        validate("list", serde_json::to_value(&jobs).unwrap());
+
+        let stats = StatsOutput {
+            stats: StatsResponse {
+                total_bytes_copied: 1 << 30,
+                total_files_copied: 10,
+                total_jobs: 2,
+                daily_stats: vec![DailyStats { date: "2026-10-01".to_string(), bytes_copied: 1 << 30, files_copied: 10, jobs_completed: 2 }],
+                ..Default::default()
+            },
+            days_back: 7,
+            generated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        validate("stats", serde_json::to_value(&stats).unwrap());
+
+        assert!(output_schema("health").is_err());
+    }
+
+    #[test]
+    fn test_prompt_overwrites_no_conflicts() {
+        let mut output = Vec::new();
+        let skipped = prompt_overwrites(&[], std::io::Cursor::new("y\n"), &mut output).unwrap();
+        assert!(skipped.is_empty());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_recommended_command_runs_as_a_copy() {
+        let tree = std::env::temp_dir();
+        let destination = std::path::PathBuf::from("/mnt/backup/it's here");
+        let recommendation = RecommendResponse {
+            engine: CopyEngine::Reflink.into(),
+            reasons: vec!["Source and destination are on one filesystem that supports reflinks".to_string()],
+            total_files: 40,
+            total_bytes: 80 << 30,
+            median_file_size: 2 << 30,
+            same_filesystem: true,
+            reflink: true,
+            ..Default::default()
+        };
+        let command = recommended_command(std::slice::from_ref(&tree), &destination, &recommendation);
+
+        let crate::Commands::Copy { args } = <crate::Cli as clap::Parser>::try_parse_from(&command).unwrap().command else {
+            panic!("{:?}", command);
+        };
+        assert_eq!(args.engine, CopyEngine::Reflink);
+        assert!(args.recursive);
+        assert_eq!((args.block_size, args.packed), (None, false));
+        assert_eq!((args.sources, args.destination), (vec![tree], destination.clone()));
+
+        let text = format_recommendation(&recommendation, &command);
+        assert!(text.contains("Engine:      reflink\n"), "{}", text);
+        assert!(text.contains("  - Source and destination are on one filesystem"), "{}", text);
+        assert!(text.contains("'/mnt/backup/it'\\''s here'\n"), "{}", text);
+        assert!(!text.contains("Block size"), "{}", text);
+
+        // Every engine the daemon may suggest is one copy accepts
+        for engine in [CopyEngine::Auto, CopyEngine::IoUring, CopyEngine::CopyFileRange] {
+            let recommendation = RecommendResponse { engine: engine.into(), block_size: 1 << 20, ..Default::default() };
+            let mut command = recommended_command(&[], &destination, &recommendation);
+            command.insert(command.len() - 1, "/src/file".to_string());
+            let cli = <crate::Cli as clap::Parser>::try_parse_from(&command).unwrap();
+            let crate::Commands::Copy { args } = cli.command else { unreachable!() };
+            assert_eq!((args.engine, args.block_size), (engine, Some(1 << 20)));
+        }
+    }
+
+    #[test]
+    fn test_format_ago_uses_the_largest_whole_unit() {
+        let cases = [
+            (-30, "just now"),
+            (0, "just now"),
+            (1, "1s ago"),
+            (59, "59s ago"),
+            (60, "1m ago"),
+            (3599, "59m ago"),
+            (3600, "1h ago"),
+            (86399, "23h ago"),
+            (86400, "1d ago"),
+            (10 * 86400 + 5, "10d ago"),
+        ];
+        for (seconds, expected) in cases {
+            assert_eq!(format_ago(seconds), expected, "{} seconds", seconds);
+        }
+    }
+
+    #[test]
+    fn test_job_times_show_the_latest_event() {
+        let now = 1_700_000_000;
+        assert_eq!(format_time(0, now, false), None);
+        assert_eq!(format_time(0, now, true), None);
+        assert!(format_time(now - 30, now, true).unwrap().starts_with("2023-11-1"));
+
+        assert_eq!(latest_job_event(now - 300, 0, 0, now, false), "created 5m ago");
+        assert_eq!(latest_job_event(now - 300, now - 180, 0, now, false), "started 3m ago");
+        assert_eq!(latest_job_event(now - 9000, now - 8000, now - 3600, now, false), "finished 1h ago");
+        assert_eq!(latest_job_event(0, 0, 0, now, false), "");
+    }
+}
@@ -37,6 +37,10 @@ impl CopyClient {
         Ok(Self { socket_path })
     }
 
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
     async fn send_request(&self, request: Request) -> Result<Response> {
         let mut stream = UnixStream::connect(&self.socket_path).await
             .with_context(|| format!("Failed to connect to daemon at {:?}", self.socket_path))?;
@@ -47,7 +51,29 @@ impl CopyClient {
         Ok(response)
     }
 
+    /// Attaches to the live event stream of `job_id`. The first event is the
+    /// job's current progress; the stream ends once the job finishes.
+    pub async fn attach(&self, job_id: &str) -> Result<EventStream> {
+        let mut stream = UnixStream::connect(&self.socket_path).await
+            .with_context(|| format!("Failed to connect to daemon at {:?}", self.socket_path))?;
+
+        let request = Request {
+            request_type: Some(request::RequestType::SubscribeEvents(SubscribeEventsRequest {
+                job_id: Some(JobId { uuid: job_id.to_string() }),
+            })),
+        };
+        send_request(&mut stream, &request).await?;
+
+        Ok(EventStream { stream, finished: false })
+    }
+
     pub async fn create_job(&self, request: CreateJobRequest) -> Result<String> {
+        Ok(self.create_job_with_plan(request).await?.0)
+    }
+
+    /// Creates a job and returns the plan the daemon computed for it, unless
+    /// the request set `skip_plan`.
+    pub async fn create_job_with_plan(&self, request: CreateJobRequest) -> Result<(String, Option<JobPlan>)> {
         let request = Request {
             request_type: Some(request::RequestType::CreateJob(request)),
         };
@@ -61,7 +87,7 @@ impl CopyClient {
                 }
                 
                 match create_response.job_id {
-                    Some(job_id) => Ok(job_id.uuid),
+                    Some(job_id) => Ok((job_id.uuid, create_response.plan)),
                     None => anyhow::bail!("No job ID returned"),
                 }
             }
@@ -86,10 +112,27 @@ impl CopyClient {
         }
     }
 
-    pub async fn list_jobs(&self, include_completed: bool) -> Result<Vec<JobInfo>> {
+    /// Statuses of several jobs in one request, in the order given.
+    pub async fn get_job_statuses(&self, job_ids: &[String]) -> Result<Vec<JobStatusResponse>> {
+        let request = Request {
+            request_type: Some(request::RequestType::BatchJobStatus(BatchJobStatusRequest {
+                job_ids: job_ids.iter().map(|uuid| JobId { uuid: uuid.clone() }).collect(),
+            })),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::BatchJobStatus(batch)) => Ok(batch.statuses),
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn list_jobs(&self, include_completed: bool, tags: Vec<String>) -> Result<Vec<JobInfo>> {
         let request = Request {
             request_type: Some(request::RequestType::ListJobs(ListJobsRequest {
                 include_completed,
+                tags,
             })),
         };
         
@@ -123,6 +166,26 @@ impl CopyClient {
         }
     }
 
+    pub async fn cancel_matching(&self, path_prefix: String) -> Result<Vec<String>> {
+        let request = Request {
+            request_type: Some(request::RequestType::CancelMatching(CancelMatchingRequest {
+                path_prefix,
+            })),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::CancelMatching(cancel_response)) => {
+                if !cancel_response.error.is_empty() {
+                    anyhow::bail!("{}", cancel_response.error);
+                }
+                Ok(cancel_response.cancelled_job_ids.into_iter().map(|id| id.uuid).collect())
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
     pub async fn pause_job(&self, job_id: &str) -> Result<()> {
         let request = Request {
             request_type: Some(request::RequestType::PauseJob(PauseJobRequest {
@@ -143,10 +206,70 @@ impl CopyClient {
         }
     }
 
-    pub async fn resume_job(&self, job_id: &str) -> Result<()> {
+    /// Per-file results of a job, in the order its files finished.
+    pub async fn get_job_results(&self, job_id: &str) -> Result<Vec<FileResult>> {
+        let request = Request {
+            request_type: Some(request::RequestType::GetJobResults(GetJobResultsRequest {
+                job_id: Some(JobId { uuid: job_id.to_string() }),
+            })),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::GetJobResults(results_response)) => {
+                if !results_response.error.is_empty() {
+                    anyhow::bail!("{}", results_response.error);
+                }
+                Ok(results_response.results)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    /// Pauses every running and queued job, returning their IDs.
+    pub async fn pause_all(&self) -> Result<Vec<String>> {
+        let request = Request {
+            request_type: Some(request::RequestType::PauseAll(PauseAllRequest {})),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::PauseAll(pause_response)) => {
+                if !pause_response.error.is_empty() {
+                    anyhow::bail!("{}", pause_response.error);
+                }
+                Ok(pause_response.paused_job_ids.into_iter().map(|id| id.uuid).collect())
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    /// Resumes every paused job, returning their IDs.
+    pub async fn resume_all(&self) -> Result<Vec<String>> {
+        let request = Request {
+            request_type: Some(request::RequestType::ResumeAll(ResumeAllRequest {})),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::ResumeAll(resume_response)) => {
+                if !resume_response.error.is_empty() {
+                    anyhow::bail!("{}", resume_response.error);
+                }
+                Ok(resume_response.resumed_job_ids.into_iter().map(|id| id.uuid).collect())
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn resume_job(&self, job_id: &str, source_root_remaps: Vec<SourceRootRemap>) -> Result<()> {
         let request = Request {
             request_type: Some(request::RequestType::ResumeJob(ResumeJobRequest {
                 job_id: Some(JobId { uuid: job_id.to_string() }),
+                source_root_remaps,
             })),
         };
         
@@ -180,6 +303,308 @@ impl CopyClient {
         }
     }
 
+    pub async fn purge_jobs(&self, older_than_days: u32, status_filter: Vec<JobStatus>) -> Result<PurgeJobsResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::PurgeJobs(PurgeJobsRequest {
+                older_than_days,
+                status_filter: status_filter.into_iter().map(|s| s as i32).collect(),
+            })),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::PurgeJobs(purge_response)) => {
+                if !purge_response.error.is_empty() {
+                    anyhow::bail!("Failed to purge jobs: {}", purge_response.error);
+                }
+                Ok(purge_response)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn verify_job(&self, source: String, destination: String, mode: VerifyMode) -> Result<String> {
+        let request = Request {
+            request_type: Some(request::RequestType::VerifyJob(VerifyJobRequest {
+                source,
+                destination,
+                mode: mode as i32,
+            })),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::VerifyJob(create_response)) => {
+                if !create_response.error.is_empty() {
+                    anyhow::bail!("Failed to create verify job: {}", create_response.error);
+                }
+
+                match create_response.job_id {
+                    Some(job_id) => Ok(job_id.uuid),
+                    None => anyhow::bail!("No job ID returned"),
+                }
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn query_jobs(&self, query: QueryJobsRequest) -> Result<QueryJobsResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::QueryJobs(query)),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::QueryJobs(query_response)) => {
+                if !query_response.error.is_empty() {
+                    anyhow::bail!("Failed to query job history: {}", query_response.error);
+                }
+                Ok(query_response)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn set_config(&self, settings: Vec<ConfigSetting>) -> Result<ConfigUpdateResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::SetConfig(SetConfigRequest { settings })),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::SetConfig(update)) => {
+                if !update.error.is_empty() {
+                    anyhow::bail!("{}", update.error);
+                }
+                Ok(update)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn set_concurrency(&self, max_concurrent_jobs: u32) -> Result<SetConcurrencyResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::SetConcurrency(SetConcurrencyRequest { max_concurrent_jobs })),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::SetConcurrency(update)) => {
+                if !update.error.is_empty() {
+                    anyhow::bail!("{}", update.error);
+                }
+                Ok(update)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn reload_config(&self) -> Result<ConfigUpdateResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::ReloadConfig(ReloadConfigRequest {})),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::ReloadConfig(update)) => {
+                if !update.error.is_empty() {
+                    anyhow::bail!("{}", update.error);
+                }
+                Ok(update)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn analyze_job(&self, job: CreateJobRequest) -> Result<AnalyzeJobResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::AnalyzeJob(AnalyzeJobRequest {
+                job: Some(job),
+            })),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::AnalyzeJob(analyze_response)) => {
+                if !analyze_response.error.is_empty() {
+                    anyhow::bail!("{}", analyze_response.error);
+                }
+                Ok(analyze_response)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn tree_diff(&self, source: String, destination: String) -> Result<TreeDiffResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::TreeDiff(TreeDiffRequest {
+                source,
+                destination,
+            })),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::TreeDiff(diff_response)) => {
+                if !diff_response.error.is_empty() {
+                    anyhow::bail!("{}", diff_response.error);
+                }
+                Ok(diff_response)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn get_engine_stats(&self) -> Result<EngineStatsResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::GetEngineStats(GetEngineStatsRequest {})),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::GetEngineStats(stats_response)) => Ok(stats_response),
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn shutdown(&self, grace_secs: u32, restart: bool) -> Result<ShutdownResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::Shutdown(ShutdownRequest { grace_secs, restart })),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::Shutdown(shutdown_response)) => {
+                if !shutdown_response.error.is_empty() {
+                    anyhow::bail!("{}", shutdown_response.error);
+                }
+                Ok(shutdown_response)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn recommend(&self, sources: Vec<String>, destination: String) -> Result<RecommendResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::Recommend(RecommendRequest { sources, destination })),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::Recommend(recommendation)) => {
+                if !recommendation.error.is_empty() {
+                    anyhow::bail!("{}", recommendation.error);
+                }
+                Ok(recommendation)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn estimate(&self, total_files: u64, total_bytes: u64, device: String, engine: CopyEngine) -> Result<EstimateResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::Estimate(EstimateRequest {
+                total_files,
+                total_bytes,
+                device,
+                engine: engine as i32,
+            })),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::Estimate(estimate)) => {
+                if !estimate.error.is_empty() {
+                    anyhow::bail!("{}", estimate.error);
+                }
+                Ok(estimate)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn diff_verify(&self, left: String, right: String, mode: VerifyMode) -> Result<DiffVerifyResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::DiffVerify(DiffVerifyRequest {
+                left,
+                right,
+                mode: mode as i32,
+            })),
+        };
+
+        let response = self.send_request(request).await?;
+
+        match response.response_type {
+            Some(response::ResponseType::DiffVerify(diff_response)) => {
+                if !diff_response.error.is_empty() {
+                    anyhow::bail!("{}", diff_response.error);
+                }
+                Ok(diff_response)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn snapshot(&self, source: String, destination: String) -> Result<SnapshotResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::Snapshot(SnapshotRequest {
+                source,
+                destination,
+            })),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::Snapshot(snapshot_response)) => {
+                if !snapshot_response.error.is_empty() {
+                    anyhow::bail!("{}", snapshot_response.error);
+                }
+                Ok(snapshot_response)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
+    pub async fn verify_file(
+        &self,
+        path: String,
+        sidecar_path: Option<String>,
+        algorithm: Option<VerifyMode>,
+    ) -> Result<VerifyFileResponse> {
+        let request = Request {
+            request_type: Some(request::RequestType::VerifyFile(VerifyFileRequest {
+                path,
+                sidecar_path: sidecar_path.unwrap_or_default(),
+                algorithm: algorithm.unwrap_or(VerifyMode::None) as i32,
+            })),
+        };
+        
+        let response = self.send_request(request).await?;
+        
+        match response.response_type {
+            Some(response::ResponseType::VerifyFile(verify_response)) => {
+                if !verify_response.error.is_empty() {
+                    anyhow::bail!("{}", verify_response.error);
+                }
+                Ok(verify_response)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+
     pub async fn health_check(&self) -> Result<HealthCheckResponse> {
         let request = Request {
             request_type: Some(request::RequestType::HealthCheck(HealthCheckRequest {})),
@@ -194,4 +619,43 @@ impl CopyClient {
             _ => anyhow::bail!("Unexpected response type"),
         }
     }
-} 
\ No newline at end of file
+} 
+
+/// Events of one job, read from a dedicated daemon connection.
+pub struct EventStream {
+    stream: UnixStream,
+    finished: bool,
+}
+
+impl EventStream {
+    /// Next event, or `None` once the job has finished and the daemon closed
+    /// the stream.
+    pub async fn next_event(&mut self) -> Result<Option<JobEvent>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let response = match receive_response(&mut self.stream).await {
+            Ok(response) => response,
+            Err(e) => {
+                let closed = e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|io| io.kind() == std::io::ErrorKind::UnexpectedEof);
+                if closed {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                return Err(e);
+            }
+        };
+
+        match response.response_type {
+            Some(response::ResponseType::JobEvent(event_response)) => {
+                if !event_response.error.is_empty() {
+                    anyhow::bail!("{}", event_response.error);
+                }
+                Ok(event_response.event)
+            }
+            _ => anyhow::bail!("Unexpected response type"),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_SOCKET: &str = "/run/copyd/copyd.sock";
+
+/// Client settings read from `~/.config/copyctl/config.toml`, or the file
+/// named by `COPYCTL_CONFIG_PATH`.
+///
+/// ```toml
+/// [client]
+/// socket_path = "/run/copyd/copyd.sock"
+///
+/// [profile.prod]
+/// socket = "/srv/prod/copyd.sock"
+/// ```
+///
+/// Other sections are left to other tools and ignored here.
+#[derive(Debug, Default, Deserialize)]
+pub struct ClientConfig {
+    #[serde(default)]
+    pub client: ClientSection,
+    #[serde(default)]
+    pub profile: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ClientSection {
+    /// Daemon used when neither `--socket` nor `--profile` is given
+    pub socket_path: Option<PathBuf>,
+}
+
+/// Connection settings for one daemon, selected with `--profile <name>`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub socket: PathBuf,
+}
+
+impl ClientConfig {
+    pub fn config_path() -> Option<PathBuf> {
+        std::env::var_os("COPYCTL_CONFIG_PATH")
+            .map(PathBuf::from)
+            .or_else(|| dirs::config_dir().map(|dir| dir.join("copyctl/config.toml")))
+    }
+
+    /// Reads the client configuration; a missing file is an empty one.
+    pub fn load() -> Result<Self> {
+        match Self::config_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Failed to parse client configuration {:?}", path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read client configuration {:?}", path)),
+        }
+    }
+
+    /// Socket to connect to: an explicit `--socket` wins, then the selected
+    /// profile, then `[client] socket_path`, then the daemon's default.
+    pub fn resolve_socket(&self, socket: Option<PathBuf>, profile: Option<&str>) -> Result<PathBuf> {
+        let profile = match profile {
+            Some(name) => Some(self.profile.get(name).with_context(|| {
+                let known: Vec<&str> = self.profile.keys().map(String::as_str).collect();
+                if known.is_empty() {
+                    format!("Unknown profile '{}'; no profiles are configured", name)
+                } else {
+                    format!("Unknown profile '{}'; configured profiles: {}", name, known.join(", "))
+                }
+            })?),
+            None => None,
+        };
+
+        Ok(socket
+            .or_else(|| profile.map(|profile| profile.socket.clone()))
+            .or_else(|| self.client.socket_path.clone())
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SOCKET)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ClientConfig {
+        toml::from_str(r#"
+            [client]
+            socket_path = "/run/local/copyd.sock"
+            show_progress = true
+
+            [profile.prod]
+            socket = "/srv/prod/copyd.sock"
+
+            [profile.container]
+            socket = "/var/lib/containers/copyd/copyd.sock"
+
+            [display]
+            use_color = true
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_profile_resolution() {
+        let config = config();
+        assert_eq!(config.resolve_socket(None, Some("prod")).unwrap(), PathBuf::from("/srv/prod/copyd.sock"));
+        assert_eq!(
+            config.resolve_socket(None, Some("container")).unwrap(),
+            PathBuf::from("/var/lib/containers/copyd/copyd.sock")
+        );
+        assert_eq!(config.resolve_socket(None, None).unwrap(), PathBuf::from("/run/local/copyd.sock"));
+        assert_eq!(ClientConfig::default().resolve_socket(None, None).unwrap(), PathBuf::from(DEFAULT_SOCKET));
+
+        let err = config.resolve_socket(None, Some("staging")).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown profile 'staging'; configured profiles: container, prod");
+        let err = ClientConfig::default().resolve_socket(None, Some("prod")).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown profile 'prod'; no profiles are configured");
+    }
+
+    #[test]
+    fn test_explicit_socket_overrides_profile() {
+        let config = config();
+        let explicit = PathBuf::from("/tmp/debug.sock");
+        assert_eq!(config.resolve_socket(Some(explicit.clone()), Some("prod")).unwrap(), explicit);
+        assert_eq!(config.resolve_socket(Some(explicit.clone()), None).unwrap(), explicit);
+        // A misspelt profile is still reported rather than silently ignored
+        assert!(config.resolve_socket(Some(explicit), Some("prd")).is_err());
+    }
+
+    #[test]
+    fn test_missing_config_file_is_empty() {
+        let config = ClientConfig::load_from(Path::new("/nonexistent/copyctl/config.toml")).unwrap();
+        assert!(config.profile.is_empty());
+        assert!(config.client.socket_path.is_none());
+
+        let bad: Result<ClientConfig, _> = toml::from_str("[profile.prod]\nsokcet = \"/x\"\n");
+        assert!(bad.is_err());
+    }
+}
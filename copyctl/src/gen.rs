@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Files placed in each subdirectory of a generated tree.
+const FILES_PER_DIR: u64 = 100;
+/// Data written at each end of a sparse file; the rest is a hole.
+const SPARSE_EXTENT: u64 = 64 * 1024;
+const WRITE_CHUNK: usize = 1024 * 1024;
+
+/// What `copyctl gen` creates.
+#[derive(Debug, Clone)]
+pub struct TreeSpec {
+    /// Apparent size of all files together
+    pub total_bytes: u64,
+    pub files: u64,
+    /// Leave the middle of files larger than two extents as a hole
+    pub sparse: bool,
+    /// Same seed, same tree
+    pub seed: u64,
+}
+
+/// Files and bytes actually written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeSummary {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// xorshift64*: fast and reproducible, which is all test data needs.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// Splits `total` bytes over `files` files with sizes spread across several
+/// orders of magnitude, like a real tree of small and large files.
+fn file_sizes(total: u64, files: u64, rng: &mut Rng) -> Vec<u64> {
+    let weights: Vec<u64> = (0..files).map(|_| 1 << (rng.next() % 16)).collect();
+    let weight_sum: u64 = weights.iter().sum();
+    let mut sizes: Vec<u64> = weights.iter()
+        .map(|&weight| (total as u128 * weight as u128 / weight_sum as u128) as u64)
+        .collect();
+    // Rounding leaves a few bytes over; the largest file takes them
+    let remainder = total - sizes.iter().sum::<u64>();
+    if let Some(largest) = sizes.iter_mut().max() {
+        *largest += remainder;
+    }
+    sizes
+}
+
+fn write_file(path: &Path, size: u64, sparse: bool, rng: &mut Rng, buf: &mut [u8]) -> Result<()> {
+    let mut file = File::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+    let extents = if sparse && size > 2 * SPARSE_EXTENT {
+        vec![(0, SPARSE_EXTENT), (size - SPARSE_EXTENT, SPARSE_EXTENT)]
+    } else {
+        vec![(0, size)]
+    };
+    for (offset, length) in extents {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut remaining = length;
+        while remaining > 0 {
+            let chunk = &mut buf[..remaining.min(WRITE_CHUNK as u64) as usize];
+            rng.fill(chunk);
+            file.write_all(chunk).with_context(|| format!("Failed to write {:?}", path))?;
+            remaining -= chunk.len() as u64;
+        }
+    }
+    file.set_len(size)?;
+    Ok(())
+}
+
+/// Fills `dir`, which must be missing or empty, with `spec.files` files of
+/// random data in subdirectories of [`FILES_PER_DIR`].
+pub fn generate_tree(dir: &Path, spec: &TreeSpec) -> Result<TreeSummary> {
+    if spec.files == 0 {
+        anyhow::bail!("At least one file is needed");
+    }
+    if dir.exists() && std::fs::read_dir(dir)?.next().is_some() {
+        anyhow::bail!("{:?} is not empty; test data is only generated into a new directory", dir);
+    }
+
+    let mut rng = Rng::new(spec.seed);
+    let sizes = file_sizes(spec.total_bytes, spec.files, &mut rng);
+    let mut buf = vec![0u8; WRITE_CHUNK];
+    for (index, &size) in sizes.iter().enumerate() {
+        let subdir = dir.join(format!("dir{:04}", index as u64 / FILES_PER_DIR));
+        if (index as u64).is_multiple_of(FILES_PER_DIR) {
+            std::fs::create_dir_all(&subdir).with_context(|| format!("Failed to create {:?}", subdir))?;
+        }
+        write_file(&subdir.join(format!("file{:06}.bin", index)), size, spec.sparse, &mut rng, &mut buf)?;
+    }
+
+    Ok(TreeSummary { files: sizes.len() as u64, bytes: sizes.iter().sum() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::PathBuf;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("copyctl-gen-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn walk(dir: &Path) -> (u64, u64, u64) {
+        let mut totals = (0, 0, 0);
+        for entry in walkdir::WalkDir::new(dir) {
+            let entry = entry.unwrap();
+            if entry.file_type().is_file() {
+                let metadata = entry.metadata().unwrap();
+                totals.0 += 1;
+                totals.1 += metadata.len();
+                totals.2 += metadata.blocks() * 512;
+            }
+        }
+        totals
+    }
+
+    #[test]
+    fn test_generated_tree_matches_request() {
+        let dir = Scratch::new();
+        let spec = TreeSpec { total_bytes: 3 * 1024 * 1024 + 17, files: 250, sparse: false, seed: 7 };
+        let summary = generate_tree(&dir.path().join("tree"), &spec).unwrap();
+        assert_eq!(summary, TreeSummary { files: 250, bytes: 3 * 1024 * 1024 + 17 });
+
+        let (files, bytes, _) = walk(&dir.path().join("tree"));
+        assert_eq!((files, bytes), (250, 3 * 1024 * 1024 + 17));
+        assert_eq!(std::fs::read_dir(dir.path().join("tree")).unwrap().count(), 3);
+
+        // The same seed reproduces the same tree
+        generate_tree(&dir.path().join("again"), &spec).unwrap();
+        let name = "dir0001/file000123.bin";
+        assert_eq!(
+            std::fs::read(dir.path().join("tree").join(name)).unwrap(),
+            std::fs::read(dir.path().join("again").join(name)).unwrap(),
+        );
+
+        // Only into a new or empty directory
+        assert!(generate_tree(&dir.path().join("tree"), &spec).is_err());
+    }
+
+    #[test]
+    fn test_sparse_tree_keeps_apparent_size() {
+        let dir = Scratch::new();
+        let spec = TreeSpec { total_bytes: 64 * 1024 * 1024, files: 4, sparse: true, seed: 1 };
+        generate_tree(dir.path(), &spec).unwrap();
+
+        let (files, bytes, allocated) = walk(dir.path());
+        assert_eq!((files, bytes), (4, 64 * 1024 * 1024));
+        // Filesystems without holes allocate everything; skip the check there
+        if allocated < bytes {
+            assert!(allocated <= 4 * 2 * SPARSE_EXTENT + 4 * 64 * 1024, "{} bytes allocated", allocated);
+        }
+    }
+}
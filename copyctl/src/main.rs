@@ -6,26 +6,39 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 mod client;
 mod tui;
 mod cli;
+mod config;
+mod gen;
+mod mv;
+mod spec;
 
 use client::CopyClient;
-use copyd_protocol::{VerifyMode, ExistsAction, CopyEngine};
+use copyd_protocol::{VerifyMode, ExistsAction, CopyEngine, AtimeMode, ErrorPolicy, JobClass, JobStatus, QueryJobsRequest, XattrNamespace};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(name = "copyctl")]
 struct Cli {
-    /// Socket path to connect to copyd daemon
-    #[arg(short, long, default_value = "/run/copyd/copyd.sock")]
-    socket: PathBuf,
+    /// Socket path to connect to copyd daemon [default: /run/copyd/copyd.sock]
+    #[arg(short, long, env = "COPYCTL_SOCKET")]
+    socket: Option<PathBuf>,
+
+    /// Daemon profile from the client configuration; --socket overrides it
+    #[arg(long, env = "COPYCTL_PROFILE")]
+    profile: Option<String>,
 
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
 
     /// Output format (text, json)
-    #[arg(short, long, default_value = "text")]
+    #[arg(short, long, default_value = "text", env = "COPYCTL_FORMAT")]
     format: String,
 
+    /// When to colour output: auto (only on a terminal, and not when
+    /// NO_COLOR is set), always or never
+    #[arg(long, default_value = "auto", value_parser = ["auto", "always", "never"])]
+    color: String,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -33,6 +46,7 @@ struct Cli {
 #[derive(clap::Args)]
 struct CopyMoveArgs {
     /// Source files or directories
+    #[arg(required = true)]
     sources: Vec<PathBuf>,
     /// Destination
     destination: PathBuf,
@@ -48,7 +62,7 @@ struct CopyMoveArgs {
     /// Preserve sparse file regions
     #[arg(long)]
     preserve_sparse: bool,
-    /// Verification method
+    /// Verification method: none, size, md5, sha256, blake3 or fingerprint
     #[arg(long, default_value = "none")]
     verify: VerifyMode,
     /// What to do if destination exists
@@ -60,8 +74,12 @@ struct CopyMoveArgs {
     /// Maximum transfer rate in MB/s
     #[arg(long)]
     max_rate: Option<u64>,
+    /// Maximum transfer rate as a percentage of the destination disk's
+    /// bandwidth, measured by a short calibration the first time
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=100))]
+    max_rate_percent: Option<u32>,
     /// Copy engine to use
-    #[arg(long, default_value = "auto")]
+    #[arg(long, default_value = "auto", env = "COPYCTL_ENGINE")]
     engine: CopyEngine,
     /// Dry run - don't actually copy files
     #[arg(long)]
@@ -75,15 +93,159 @@ struct CopyMoveArgs {
     /// Block size for I/O operations
     #[arg(long)]
     block_size: Option<u64>,
-    /// Enable compression
-    #[arg(long)]
+    /// Write each file zstd-compressed, as <name>.zst; verification checks
+    /// the decompressed content
+    #[arg(long, conflicts_with_all = ["atomic", "packed", "delete", "delete_dry_run"])]
     compress: bool,
-    /// Enable encryption
-    #[arg(long)]
+    /// Write each file encrypted with ChaCha20-Poly1305, as <name>.enc,
+    /// under a key derived from the daemon's COPYD_ENCRYPTION_PASSPHRASE or
+    /// the --key-id keyring entry; verification checks the decrypted content
+    #[arg(long, conflicts_with_all = ["atomic", "packed", "delete", "delete_dry_run", "compress"])]
     encrypt: bool,
+    /// Name of the daemon's keyring entry (a user key) holding the
+    /// encryption passphrase
+    #[arg(long, requires = "encrypt")]
+    key_id: Option<String>,
+    /// Delete destination files that don't exist in the source
+    #[arg(long, requires = "recursive")]
+    delete: bool,
+    /// Create the directory tree only, with its metadata when preserving,
+    /// and copy no files
+    #[arg(long, requires = "recursive", conflicts_with_all = ["delete", "delete_dry_run"])]
+    dirs_only: bool,
+    /// Copy files of up to 64 KiB relative to held directory handles, much
+    /// faster for trees of many tiny files
+    #[arg(long)]
+    packed: bool,
+    /// Copy the source directory into a staging tree and swap it in place of
+    /// the destination only if every file copies; on failure nothing changes
+    #[arg(long, requires = "recursive", conflicts_with_all = ["delete", "delete_dry_run", "dirs_only"])]
+    atomic: bool,
+    /// Stage an atomic copy in this directory instead of beside the
+    /// destination; it must be on the destination's filesystem
+    #[arg(long, requires = "atomic")]
+    temp_dir: Option<PathBuf>,
+    /// Also copy to this destination, laid out like the main one, from the
+    /// same reads of the sources; repeat for more
+    #[arg(long = "mirror", value_name = "DESTINATION",
+          conflicts_with_all = ["atomic", "packed", "delete", "delete_dry_run", "compress", "encrypt", "device",
+                                "abort_on_change", "restart_on_change"])]
+    mirrors: Vec<PathBuf>,
+    /// Watch each source file while it is copied and fail the file, removing
+    /// the partial copy, if the source is written to meanwhile
+    #[arg(long, conflicts_with = "packed")]
+    abort_on_change: bool,
+    /// Preserve only the metadata the daemon may set, e.g. skipping
+    /// ownership when it doesn't run as root, and report what was left out
+    /// once for the job instead of for every file
+    #[arg(long, requires = "preserve")]
+    best_effort_metadata: bool,
+    /// Like --abort-on-change, but copy a changed file again from the start,
+    /// up to 3 times, before failing it
+    #[arg(long, conflicts_with_all = ["packed", "abort_on_change"])]
+    restart_on_change: bool,
+    /// Copy up to this many files of the job at once, within the daemon's
+    /// max_copy_parallelism
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "packed")]
+    copy_parallelism: Option<u32>,
+    /// Reflink files whose content an earlier --dedup copy wrote to the same
+    /// device, or hard-link them when preserved metadata already matches,
+    /// instead of copying them again; for incremental snapshots
+    #[arg(long, conflicts_with_all = ["packed", "mirrors"])]
+    dedup: bool,
+    /// Copy a file that fails verification again, from fresh reads, up to
+    /// this many times before failing it (at most 10)
+    #[arg(long, default_value = "0", value_parser = clap::value_parser!(u32).range(0..=10), conflicts_with = "mirrors")]
+    verify_retries: u32,
+    /// When a file fails to copy: skip it, abort the job, or retry it a few
+    /// times before skipping it
+    #[arg(long, default_value = "skip")]
+    on_error: ErrorPolicy,
+    /// Report destination files that --delete would remove, without removing them
+    #[arg(long, requires = "recursive")]
+    delete_dry_run: bool,
+    /// Allow block devices as sources, copying the whole device (e.g. to image a partition)
+    #[arg(long)]
+    device: bool,
+    /// Access time for copied files when preserving metadata: preserve, now or mtime
+    #[arg(long, default_value = "preserve", requires = "preserve")]
+    atime: AtimeMode,
+    /// Extended attribute namespaces to preserve besides user, comma separated:
+    /// system, security, trusted
+    #[arg(long, value_delimiter = ',', requires = "preserve")]
+    xattrs: Vec<XattrNamespace>,
+    /// Scheduling class: background jobs run at the lowest CPU priority and
+    /// only use the disk when nothing else does
+    #[arg(long, default_value = "foreground")]
+    class: JobClass,
+    /// Label for grouping jobs in list and history; repeat for several
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Why the job exists, shown by status, list and history
+    #[arg(long)]
+    note: Option<String>,
+    /// Prompt before overwriting each existing destination file
+    #[arg(short, long)]
+    interactive: bool,
+    /// Create missing parent directories of the destination, like install -D
+    #[arg(long)]
+    parents: bool,
+    /// Octal mode for every destination file and directory, e.g. 640,
+    /// overriding default and preserved permissions
+    #[arg(long, value_parser = cli::parse_mode)]
+    chmod: Option<u32>,
+    /// Read named pipe sources until their writers close them and store the
+    /// data in regular files
+    #[arg(long)]
+    stream_fifo: bool,
+    /// Don't walk the sources to print totals before copying; for very
+    /// large trees
+    #[arg(long)]
+    no_plan: bool,
+    /// Monitor job progress
+    #[arg(short, long)]
+    monitor: bool,
+    /// With --monitor: bar, or kv for one `job=<id> bytes=<n> total=<n>
+    /// pct=<f> mbps=<f> eta=<n>` line a second, for shell scripts
+    #[arg(long, default_value = "bar", value_parser = ["bar", "kv"], requires = "monitor")]
+    progress_format: String,
+}
+
+/// Options `copyctl replay` can change from the original job; anything not
+/// given is submitted as it was.
+#[derive(clap::Args)]
+struct ReplayOverrides {
+    /// Copy into this destination instead
+    #[arg(long)]
+    destination: Option<PathBuf>,
+    /// Verification method
+    #[arg(long)]
+    verify: Option<VerifyMode>,
+    /// What to do if destination exists
+    #[arg(long)]
+    exists: Option<ExistsAction>,
+    /// Job priority (higher = processed first)
+    #[arg(long)]
+    priority: Option<u32>,
+    /// Maximum transfer rate in MB/s
+    #[arg(long)]
+    max_rate: Option<u64>,
+    /// Copy engine to use
+    #[arg(long)]
+    engine: Option<CopyEngine>,
+    /// Scheduling class
+    #[arg(long)]
+    class: Option<JobClass>,
+    /// Only report what the replay would copy
+    #[arg(long)]
+    dry_run: bool,
     /// Monitor job progress
     #[arg(short, long)]
     monitor: bool,
+    /// With --monitor: bar, or kv for one `job=<id> bytes=<n> total=<n>
+    /// pct=<f> mbps=<f> eta=<n>` line a second, for shell scripts
+    #[arg(long, default_value = "bar", value_parser = ["bar", "kv"], requires = "monitor")]
+    progress_format: String,
 }
 
 #[derive(Subcommand)]
@@ -93,35 +255,76 @@ enum Commands {
         #[command(flatten)]
         args: CopyMoveArgs,
     },
-    /// Move files or directories
+    /// Move files or directories, removing the sources once copied
     Move {
         #[command(flatten)]
         args: CopyMoveArgs,
     },
+    /// Move files or directories with mv(1) semantics: renamed in place on
+    /// the same filesystem, copied by the daemon and then removed otherwise
+    Mv {
+        /// Files or directories to move
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
+        /// New path, or an existing directory to move the sources into
+        destination: PathBuf,
+    },
     /// List jobs
     List {
         /// Include completed jobs
         #[arg(short, long)]
         completed: bool,
+        /// Only jobs with this tag; repeat to require several
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Show dates and times instead of how long ago
+        #[arg(long)]
+        absolute: bool,
         /// Output in JSON format
         #[arg(long)]
         json: bool,
     },
     /// Show job status
     Status {
-        /// Job ID
-        job_id: String,
+        /// Job IDs; several are fetched in one request
+        #[arg(required = true)]
+        job_ids: Vec<String>,
         /// Output in JSON format
         #[arg(long)]
         json: bool,
         /// Monitor job progress
         #[arg(short, long)]
         monitor: bool,
+        /// With --monitor: bar, or kv for one `job=<id> bytes=<n> total=<n>
+        /// pct=<f> mbps=<f> eta=<n>` line a second, for shell scripts
+        #[arg(long, default_value = "bar", value_parser = ["bar", "kv"], requires = "monitor")]
+        progress_format: String,
+        /// Show dates and times instead of how long ago
+        #[arg(long)]
+        absolute: bool,
+    },
+    /// Follow the live progress of a job started elsewhere
+    Attach {
+        /// Job ID
+        job_id: String,
+    },
+    /// Block until a job finishes. Exits 0 if it completed, 2 if it failed,
+    /// 3 if it was cancelled and 124 on timeout
+    Wait {
+        /// Job ID
+        job_id: String,
+        /// Give up after this many seconds
+        #[arg(long)]
+        timeout: Option<u64>,
     },
     /// Cancel a job
     Cancel {
         /// Job ID
-        job_id: String,
+        #[arg(required_unless_present = "source_prefix", conflicts_with = "source_prefix")]
+        job_id: Option<String>,
+        /// Cancel every active job with a source under this path
+        #[arg(long)]
+        source_prefix: Option<PathBuf>,
     },
     /// Pause a job
     Pause {
@@ -132,7 +335,14 @@ enum Commands {
     Resume {
         /// Job ID
         job_id: String,
+        /// Rewrite checkpointed source paths from OLD to NEW (e.g. after a remount)
+        #[arg(long, value_name = "OLD=NEW", value_parser = cli::parse_source_root_remap)]
+        source_root_remap: Vec<copyd_protocol::SourceRootRemap>,
     },
+    /// Pause every running and queued job, keeping their progress
+    PauseAll,
+    /// Resume every paused job
+    ResumeAll,
     /// Show daemon statistics
     Stats {
         /// Number of days to include
@@ -142,17 +352,239 @@ enum Commands {
         #[arg(long)]
         json: bool,
     },
+    /// Show each copy engine's throughput, operations and error rate since
+    /// the daemon started, with tuning recommendations
+    EngineStats,
+    /// Remove finished jobs from the daemon's history
+    Purge {
+        /// Only purge jobs that finished longer ago than this (e.g. 30d)
+        #[arg(long, default_value = "30d", value_parser = cli::parse_days)]
+        older_than: u32,
+        /// Only purge jobs in these end states (completed, failed, cancelled)
+        #[arg(long, value_parser = cli::parse_job_status)]
+        status: Vec<JobStatus>,
+    },
+    /// Search finished jobs recorded by the daemon
+    History {
+        /// Only jobs that finished at or after this time (e.g. 7d or 2026-10-01)
+        #[arg(long, value_parser = cli::parse_time)]
+        since: Option<i64>,
+        /// Only jobs that finished at or before this time
+        #[arg(long, value_parser = cli::parse_time)]
+        until: Option<i64>,
+        /// Only jobs in these end states (completed, failed, cancelled)
+        #[arg(long, value_parser = cli::parse_job_status)]
+        status: Vec<JobStatus>,
+        /// Only jobs with a source or destination containing this text
+        #[arg(long)]
+        path: Option<String>,
+        /// Only jobs with this tag; repeat to require several
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Jobs shown per page
+        #[arg(long, default_value = "20", value_parser = clap::value_parser!(u32).range(1..))]
+        limit: u32,
+        /// Page of results to show, starting at 1
+        #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+        page: u32,
+    },
+    /// List the files a job failed to copy and why
+    ShowErrors {
+        /// Job ID; for finished jobs also a unique prefix, as history shows
+        job_id: String,
+    },
+    /// Show what happened to each file of a job: bytes, engine,
+    /// verification and time taken
+    Results {
+        /// Job ID
+        job_id: String,
+    },
+    /// Submit a finished job from the history again with the same options
+    Replay {
+        /// Job ID, or a unique prefix of it such as the one history shows
+        job_id: String,
+        #[command(flatten)]
+        overrides: ReplayOverrides,
+    },
+    /// Enqueue every job listed in a TOML spec file
+    Import {
+        /// Spec file with one [[job]] table per copy or move
+        spec: PathBuf,
+    },
+    /// Preview what a recursive sync of SOURCE into DESTINATION would change
+    TreeDiff {
+        /// Source directory
+        source: PathBuf,
+        /// Destination
+        destination: PathBuf,
+    },
+    /// Compare two existing directory trees file by file with checksums
+    DiffVerify {
+        /// First directory
+        a: PathBuf,
+        /// Second directory
+        b: PathBuf,
+        /// Checksum used to compare files
+        #[arg(long, default_value = "sha256")]
+        verify: VerifyMode,
+        /// List every file with its outcome, not just the totals
+        #[arg(long)]
+        details: bool,
+    },
+    /// Print the JSON Schema of a command's --format json output, for
+    /// validating it in scripts and integrations
+    Schema {
+        /// Command whose output to describe
+        #[arg(value_parser = cli::SCHEMA_OUTPUTS)]
+        command: String,
+    },
+    /// Generate a reproducible tree of random files to benchmark copies
+    /// with, e.g. `copyctl gen --size 1G --files 1000 --dir /tmp/bench`
+    #[command(hide = true)]
+    Gen {
+        /// Total apparent size of the files, e.g. 1G or 512M
+        #[arg(long, value_parser = cli::parse_byte_size)]
+        size: u64,
+        /// Number of files, spread over subdirectories of 100
+        #[arg(long, default_value = "1000", value_parser = clap::value_parser!(u64).range(1..))]
+        files: u64,
+        /// New or empty directory to create the tree in
+        #[arg(long)]
+        dir: PathBuf,
+        /// Write only the ends of larger files and leave holes between
+        #[arg(long)]
+        sparse: bool,
+        /// Seed for file sizes and contents; the same seed makes the same tree
+        #[arg(long, default_value = "1")]
+        seed: u64,
+    },
+    /// Total the files, bytes, directories and symlinks a copy of the
+    /// sources would cover, without copying anything
+    Size {
+        /// Files or directories to size
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
+        /// Descend into directories, as copy -r does
+        #[arg(short, long)]
+        recursive: bool,
+        /// Leave out entries matching this glob; patterns with a slash match
+        /// paths relative to the source, and a trailing slash matches only
+        /// directories. Repeat for several
+        #[arg(long)]
+        exclude: Vec<String>,
+    },
+    /// Predict how long a copy would take from the throughput earlier copies
+    /// to the destination's device reached, with and without verification
+    Estimate {
+        /// Files or directories to copy
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
+        /// Where they would be copied to
+        destination: PathBuf,
+        /// Copy engine the copy would use; auto goes by every engine's copies
+        #[arg(long, default_value = "auto")]
+        engine: CopyEngine,
+    },
+    /// Inspect a copy and suggest an engine and block size for it, with
+    /// the reasons and the command that runs it
+    Recommend {
+        /// Files or directories to copy
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
+        /// Where they would be copied to
+        destination: PathBuf,
+        /// Offer to start the recommended copy
+        #[arg(long)]
+        run: bool,
+    },
+    /// Snapshot a directory tree by reflinking it (Btrfs, XFS with reflink=1)
+    Snapshot {
+        /// Directory to snapshot
+        source: PathBuf,
+        /// New directory to hold the snapshot, on the same filesystem
+        destination: PathBuf,
+    },
+    /// Verify a file against its checksum sidecar (e.g. file.sha256)
+    Verify {
+        /// File to verify
+        path: PathBuf,
+        /// Sidecar file (defaults to <path>.sha256, <path>.blake3 or <path>.md5)
+        #[arg(long)]
+        sidecar: Option<PathBuf>,
+        /// Checksum algorithm (detected from the sidecar extension when omitted)
+        #[arg(long)]
+        algorithm: Option<VerifyMode>,
+    },
+    /// Check an existing copy against its source without copying anything
+    VerifyJob {
+        /// Source file or directory
+        source: PathBuf,
+        /// Destination the source was copied to, as given to copy
+        destination: PathBuf,
+        /// Verification method
+        #[arg(long, default_value = "sha256")]
+        verify: VerifyMode,
+        /// Monitor job progress
+        #[arg(short, long)]
+        monitor: bool,
+        /// With --monitor: bar, or kv for one `job=<id> bytes=<n> total=<n>
+        /// pct=<f> mbps=<f> eta=<n>` line a second, for shell scripts
+        #[arg(long, default_value = "bar", value_parser = ["bar", "kv"], requires = "monitor")]
+        progress_format: String,
+    },
+    /// Change settings of the running daemon
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Change how many jobs may run at once, without restarting the daemon
+    SetConcurrency {
+        /// Maximum number of concurrent jobs
+        #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+        max: u32,
+    },
     /// TUI monitor mode
     Monitor,
     /// Navigator mode (dual-pane file browser)
     Navigator,
     /// Health check
     Health,
+    /// Stop the daemon: running jobs get the grace period to finish, the
+    /// rest resume from their checkpoints on the next start
+    Shutdown {
+        /// Seconds running jobs get to finish
+        #[arg(long, default_value = "30")]
+        grace: u32,
+    },
+    /// Stop the daemon as shutdown does, then start it again and wait
+    /// until it answers
+    Restart {
+        /// Seconds running jobs get to finish
+        #[arg(long, default_value = "30")]
+        grace: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Set hot-reloadable settings, e.g. max_concurrent_jobs=8
+    Set {
+        #[arg(required = true, value_name = "KEY=VALUE", value_parser = cli::parse_config_setting)]
+        settings: Vec<copyd_protocol::ConfigSetting>,
+    },
+    /// Re-read the daemon's configuration file (same as SIGHUP)
+    Reload,
+    /// Bring a daemon config file up to the current version, adding missing
+    /// settings with their defaults; comments are kept
+    Migrate {
+        path: std::path::PathBuf,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    cli::apply_color_choice(&cli.color);
 
     // Initialize tracing
     let filter = if cli.verbose {
@@ -166,8 +598,26 @@ async fn main() -> Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    // Schemas are static, and test data, sizes and config migrations are
+    // worked out locally, so none of these requires a running daemon
+    match &cli.command {
+        Commands::Schema { command } => return cli::handle_schema(command),
+        Commands::Gen { size, files, dir, sparse, seed } => {
+            let spec = gen::TreeSpec { total_bytes: *size, files: *files, sparse: *sparse, seed: *seed };
+            return cli::handle_gen(dir, &spec, &cli.format);
+        }
+        Commands::Size { sources, recursive, exclude } => {
+            return cli::handle_size(sources.clone(), *recursive, exclude.clone(), &cli.format).await;
+        }
+        Commands::Config { action: ConfigAction::Migrate { path } } => {
+            return cli::handle_config_migrate(path, &cli.format).await;
+        }
+        _ => {}
+    }
+
     // Create client
-    let client = CopyClient::new(cli.socket).await?;
+    let socket = config::ClientConfig::load()?.resolve_socket(cli.socket, cli.profile.as_deref())?;
+    let client = CopyClient::new(socket).await?;
 
     // Execute command
     match cli.command {
@@ -175,26 +625,113 @@ async fn main() -> Result<()> {
             cli::handle_copy(client, args, &cli.format).await?;
         }
         Commands::Move { args } => {
-            // For move, we'll copy then delete the originals
             cli::handle_move(client, args, &cli.format).await?;
         }
-        Commands::List { completed, json: _ } => {
-            cli::handle_list(client, completed, &cli.format).await?;
+        Commands::Mv { sources, destination } => {
+            cli::handle_mv(client, sources, destination, &cli.format).await?;
+        }
+        Commands::List { completed, tags, absolute, json: _ } => {
+            cli::handle_list(client, completed, tags, absolute, &cli.format).await?;
+        }
+        Commands::Status { job_ids, json: _, monitor, progress_format, absolute } => {
+            let monitor = monitor.then_some(progress_format.as_str());
+            cli::handle_status(client, job_ids, monitor, absolute, &cli.format).await?;
         }
-        Commands::Status { job_id, json: _, monitor } => {
-            cli::handle_status(client, job_id, monitor, &cli.format).await?;
+        Commands::Attach { job_id } => {
+            cli::handle_attach(client, job_id, &cli.format).await?;
         }
-        Commands::Cancel { job_id } => {
-            cli::handle_cancel(client, job_id, &cli.format).await?;
+        Commands::Wait { job_id, timeout } => {
+            let code = cli::handle_wait(client, job_id, timeout.map(std::time::Duration::from_secs), &cli.format).await?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Cancel { job_id, source_prefix } => {
+            match (job_id, source_prefix) {
+                (_, Some(prefix)) => cli::handle_cancel_matching(client, prefix, &cli.format).await?,
+                (Some(job_id), None) => cli::handle_cancel(client, job_id, &cli.format).await?,
+                (None, None) => unreachable!("clap requires a job ID or --source-prefix"),
+            }
         }
         Commands::Pause { job_id } => {
             cli::handle_pause(client, job_id, &cli.format).await?;
         }
-        Commands::Resume { job_id } => {
-            cli::handle_resume(client, job_id, &cli.format).await?;
+        Commands::Resume { job_id, source_root_remap } => {
+            cli::handle_resume(client, job_id, source_root_remap, &cli.format).await?;
+        }
+        Commands::PauseAll => {
+            cli::handle_pause_all(client, true, &cli.format).await?;
+        }
+        Commands::ResumeAll => {
+            cli::handle_pause_all(client, false, &cli.format).await?;
+        }
+        Commands::Stats { days, json } => {
+            let format = if json { "json" } else { cli.format.as_str() };
+            cli::handle_stats(client, days, format).await?;
+        }
+        Commands::EngineStats => {
+            cli::handle_engine_stats(client, &cli.format).await?;
+        }
+        Commands::Purge { older_than, status } => {
+            cli::handle_purge(client, older_than, status, &cli.format).await?;
+        }
+        Commands::History { since, until, status, path, tags, limit, page } => {
+            let query = QueryJobsRequest {
+                since: since.unwrap_or(0),
+                until: until.unwrap_or(0),
+                status: status.into_iter().map(|s| s as i32).collect(),
+                path_filter: path.unwrap_or_default(),
+                limit,
+                offset: (page - 1).saturating_mul(limit),
+                tags,
+                job_id: String::new(),
+            };
+            cli::handle_history(client, query, &cli.format).await?;
+        }
+        Commands::ShowErrors { job_id } => {
+            cli::handle_show_errors(client, job_id, &cli.format).await?;
+        }
+        Commands::Results { job_id } => {
+            cli::handle_results(client, job_id, &cli.format).await?;
+        }
+        Commands::Replay { job_id, overrides } => {
+            cli::handle_replay(client, job_id, overrides, &cli.format).await?;
+        }
+        Commands::Import { spec } => {
+            cli::handle_import(client, spec, &cli.format).await?;
+        }
+        Commands::TreeDiff { source, destination } => {
+            cli::handle_tree_diff(client, source, destination, &cli.format).await?;
+        }
+        Commands::DiffVerify { a, b, verify, details } => {
+            cli::handle_diff_verify(client, a, b, verify, details, &cli.format).await?;
+        }
+        Commands::Schema { .. } | Commands::Gen { .. } | Commands::Size { .. }
+            | Commands::Config { action: ConfigAction::Migrate { .. } } => unreachable!("handled before connecting"),
+        Commands::Estimate { sources, destination, engine } => {
+            cli::handle_estimate(client, sources, destination, engine, &cli.format).await?;
+        }
+        Commands::Recommend { sources, destination, run } => {
+            cli::handle_recommend(client, sources, destination, run, &cli.format).await?;
+        }
+        Commands::Snapshot { source, destination } => {
+            cli::handle_snapshot(client, source, destination, &cli.format).await?;
         }
-        Commands::Stats { days, json: _ } => {
-            cli::handle_stats(client, days, &cli.format).await?;
+        Commands::Verify { path, sidecar, algorithm } => {
+            cli::handle_verify(client, path, sidecar, algorithm, &cli.format).await?;
+        }
+        Commands::VerifyJob { source, destination, verify, monitor, progress_format } => {
+            let monitor = monitor.then_some(progress_format.as_str());
+            cli::handle_verify_job(client, source, destination, verify, monitor, &cli.format).await?;
+        }
+        Commands::Config { action: ConfigAction::Set { settings } } => {
+            cli::handle_config_set(client, settings, &cli.format).await?;
+        }
+        Commands::SetConcurrency { max } => {
+            cli::handle_set_concurrency(client, max, &cli.format).await?;
+        }
+        Commands::Config { action: ConfigAction::Reload } => {
+            cli::handle_config_reload(client, &cli.format).await?;
         }
         Commands::Monitor => {
             tui::run_monitor(client).await?;
@@ -205,7 +742,62 @@ async fn main() -> Result<()> {
         Commands::Health => {
             cli::handle_health(client, &cli.format).await?;
         }
+        Commands::Shutdown { grace } => {
+            cli::handle_shutdown(client, grace, false, &cli.format).await?;
+        }
+        Commands::Restart { grace } => {
+            cli::handle_shutdown(client, grace, true, &cli.format).await?;
+        }
     }
 
     Ok(())
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_defaults_yield_to_flags() {
+        // The only test touching these variables, so nothing races with it
+        let vars = [
+            ("COPYCTL_SOCKET", "/run/container/copyd.sock"),
+            ("COPYCTL_PROFILE", "staging"),
+            ("COPYCTL_FORMAT", "json"),
+            ("COPYCTL_ENGINE", "readwrite"),
+        ];
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+        let copy_engine = |cli: &Cli| match &cli.command {
+            Commands::Copy { args } => args.engine,
+            _ => unreachable!(),
+        };
+
+        let cli = Cli::try_parse_from(["copyctl", "copy", "/src", "/dst"]).unwrap();
+        assert_eq!(cli.socket, Some(PathBuf::from("/run/container/copyd.sock")));
+        assert_eq!(cli.profile.as_deref(), Some("staging"));
+        assert_eq!(cli.format, "json");
+        assert_eq!(copy_engine(&cli), CopyEngine::ReadWrite);
+
+        let cli = Cli::try_parse_from([
+            "copyctl", "--socket", "/tmp/copyd.sock", "--profile", "prod", "--format", "text",
+            "copy", "--engine", "sendfile", "/src", "/dst",
+        ]).unwrap();
+        assert_eq!(cli.socket, Some(PathBuf::from("/tmp/copyd.sock")));
+        assert_eq!(cli.profile.as_deref(), Some("prod"));
+        assert_eq!(cli.format, "text");
+        assert_eq!(copy_engine(&cli), CopyEngine::Sendfile);
+
+        // Invalid values are reported like invalid flags
+        std::env::set_var("COPYCTL_ENGINE", "warp");
+        assert!(Cli::try_parse_from(["copyctl", "copy", "/src", "/dst"]).is_err());
+
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+        let cli = Cli::try_parse_from(["copyctl", "copy", "/src", "/dst"]).unwrap();
+        assert_eq!(copy_engine(&cli), CopyEngine::Auto);
+        assert_eq!((cli.socket, cli.profile, cli.format.as_str()), (None, None, "text"));
+    }
+}
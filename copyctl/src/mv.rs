@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Where `mv source destination` puts `source`: inside `destination` when
+/// that is an existing directory, otherwise at `destination` itself.
+/// Rejects the moves mv(1) refuses, such as replacing a directory with a
+/// file or moving a directory into itself.
+pub fn move_target(source: &Path, destination: &Path) -> Result<PathBuf> {
+    let source_metadata = std::fs::symlink_metadata(source)
+        .with_context(|| format!("Cannot stat {:?}", source))?;
+
+    let target = if destination.is_dir() {
+        let name = source.file_name()
+            .with_context(|| format!("Cannot move {:?}: it has no file name", source))?;
+        destination.join(name)
+    } else {
+        destination.to_path_buf()
+    };
+
+    if let Ok(target_metadata) = std::fs::symlink_metadata(&target) {
+        if (target_metadata.dev(), target_metadata.ino()) == (source_metadata.dev(), source_metadata.ino()) {
+            anyhow::bail!("{:?} and {:?} are the same file", source, target);
+        }
+        match (source_metadata.is_dir(), target_metadata.is_dir()) {
+            (true, false) => anyhow::bail!("Cannot overwrite non-directory {:?} with directory {:?}", target, source),
+            (false, true) => anyhow::bail!("Cannot overwrite directory {:?} with non-directory {:?}", target, source),
+            _ => {}
+        }
+    }
+
+    if source_metadata.is_dir() {
+        let source = std::fs::canonicalize(source)?;
+        let parent = target.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        if std::fs::canonicalize(parent).is_ok_and(|parent| parent.starts_with(&source)) {
+            anyhow::bail!("Cannot move {:?} into a subdirectory of itself", source);
+        }
+    }
+
+    Ok(target)
+}
+
+/// Renames `source` to `target`, replacing a file or empty directory there.
+/// Returns false when they are on different filesystems and the data has
+/// to be copied instead.
+pub fn try_rename(source: &Path, target: &Path) -> Result<bool> {
+    match std::fs::rename(source, target) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("Failed to move {:?} to {:?}", source, target)),
+    }
+}
+
+/// Destination to hand the daemon when copying `target` across
+/// filesystems. An existing directory target is the source's namesake
+/// inside the destination, which the daemon creates itself when given the
+/// parent.
+pub fn copy_destination(target: &Path) -> PathBuf {
+    match target.parent() {
+        Some(parent) if target.is_dir() => parent.to_path_buf(),
+        _ => target.to_path_buf(),
+    }
+}
+
+/// Removes a source once its copy has completed.
+pub fn remove_source(source: &Path) -> Result<()> {
+    let metadata = std::fs::symlink_metadata(source)?;
+    if metadata.is_dir() {
+        std::fs::remove_dir_all(source)
+    } else {
+        std::fs::remove_file(source)
+    }
+    .with_context(|| format!("Copied {:?} but failed to remove it", source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("copyctl-mv-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn mv(source: &Path, destination: &Path) -> Result<PathBuf> {
+        let target = move_target(source, destination)?;
+        assert!(try_rename(source, &target)?);
+        Ok(target)
+    }
+
+    #[test]
+    fn test_file_into_directory() {
+        let scratch = Scratch::new();
+        let file = scratch.0.join("report.txt");
+        std::fs::write(&file, "data").unwrap();
+        let dir = scratch.0.join("archive");
+        std::fs::create_dir(&dir).unwrap();
+
+        assert_eq!(mv(&file, &dir).unwrap(), dir.join("report.txt"));
+        assert!(!file.exists());
+        assert_eq!(std::fs::read_to_string(dir.join("report.txt")).unwrap(), "data");
+    }
+
+    #[test]
+    fn test_file_over_file() {
+        let scratch = Scratch::new();
+        let file = scratch.0.join("new.txt");
+        std::fs::write(&file, "new").unwrap();
+        let existing = scratch.0.join("old.txt");
+        std::fs::write(&existing, "old").unwrap();
+
+        assert_eq!(mv(&file, &existing).unwrap(), existing);
+        assert!(!file.exists());
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "new");
+
+        // A directory in the way is never replaced by a file
+        let dir = scratch.0.join("dir");
+        std::fs::create_dir_all(dir.join("old.txt")).unwrap();
+        let err = move_target(&existing, &dir).unwrap_err();
+        assert!(err.to_string().contains("Cannot overwrite directory"), "{}", err);
+
+        let err = move_target(&existing, &existing).unwrap_err();
+        assert!(err.to_string().contains("are the same file"), "{}", err);
+    }
+
+    #[test]
+    fn test_directory_rename() {
+        let scratch = Scratch::new();
+        let dir = scratch.0.join("photos");
+        std::fs::create_dir_all(dir.join("2024")).unwrap();
+        std::fs::write(dir.join("2024/a.jpg"), "jpg").unwrap();
+
+        // A new name renames the directory itself
+        let renamed = scratch.0.join("pictures");
+        assert_eq!(mv(&dir, &renamed).unwrap(), renamed);
+        assert_eq!(std::fs::read_to_string(renamed.join("2024/a.jpg")).unwrap(), "jpg");
+        assert!(!dir.exists());
+
+        // An existing directory receives it
+        let backup = scratch.0.join("backup");
+        std::fs::create_dir(&backup).unwrap();
+        assert_eq!(mv(&renamed, &backup).unwrap(), backup.join("pictures"));
+        assert!(backup.join("pictures/2024/a.jpg").exists());
+        assert_eq!(copy_destination(&backup.join("pictures")), backup);
+
+        let err = move_target(&backup, &backup.join("pictures/2024")).unwrap_err();
+        assert!(err.to_string().contains("subdirectory of itself"), "{}", err);
+
+        let file = scratch.0.join("file");
+        std::fs::write(&file, "x").unwrap();
+        let err = move_target(&backup, &file).unwrap_err();
+        assert!(err.to_string().contains("Cannot overwrite non-directory"), "{}", err);
+    }
+}
@@ -0,0 +1,343 @@
+use anyhow::Result;
+use copyd_protocol::{AtimeMode, CopyEngine, CreateJobRequest, ErrorPolicy, ExistsAction, JobClass, JobOperation, VerifyMode, XattrNamespace};
+use serde::Deserialize;
+
+/// A batch of jobs read by `copyctl import`.
+///
+/// ```toml
+/// [[job]]
+/// sources = ["/data/photos"]
+/// destination = "/backup"
+/// recursive = true
+/// verify = "sha256"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobSpecFile {
+    #[serde(default, rename = "job")]
+    pub jobs: Vec<JobSpec>,
+}
+
+/// One entry of a spec file; option names match the `copyctl copy` flags.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobSpec {
+    #[serde(default = "default_operation")]
+    pub operation: String,
+    pub sources: Vec<String>,
+    pub destination: String,
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default)]
+    pub preserve: bool,
+    #[serde(default)]
+    pub preserve_links: bool,
+    #[serde(default)]
+    pub preserve_sparse: bool,
+    #[serde(default = "default_verify")]
+    pub verify: String,
+    #[serde(default = "default_exists")]
+    pub exists: String,
+    #[serde(default = "default_priority")]
+    pub priority: u32,
+    /// Maximum transfer rate in MB/s
+    pub max_rate: Option<u64>,
+    /// Maximum transfer rate as a percentage of the destination disk's bandwidth
+    pub max_rate_percent: Option<u32>,
+    #[serde(default = "default_engine")]
+    pub engine: String,
+    #[serde(default)]
+    pub dry_run: bool,
+    pub regex_rename_match: Option<String>,
+    pub regex_rename_replace: Option<String>,
+    pub block_size: Option<u64>,
+    #[serde(default)]
+    pub compress: bool,
+    #[serde(default)]
+    pub encrypt: bool,
+    /// Keyring entry of the daemon holding the encryption passphrase
+    pub key_id: Option<String>,
+    #[serde(default)]
+    pub delete: bool,
+    #[serde(default)]
+    pub delete_dry_run: bool,
+    #[serde(default)]
+    pub device: bool,
+    #[serde(default = "default_atime")]
+    pub atime: String,
+    #[serde(default = "default_class")]
+    pub class: String,
+    #[serde(default)]
+    pub xattrs: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub no_plan: bool,
+    #[serde(default)]
+    pub stream_fifo: bool,
+    #[serde(default)]
+    pub parents: bool,
+    /// Octal mode for destination files and directories, e.g. "640"
+    pub chmod: Option<String>,
+    #[serde(default)]
+    pub dirs_only: bool,
+    /// What to do when a file fails: "skip", "abort" or "retry"
+    #[serde(default = "default_on_error")]
+    pub on_error: String,
+    #[serde(default)]
+    pub packed: bool,
+    #[serde(default)]
+    pub best_effort_metadata: bool,
+    /// Files copied at once
+    pub copy_parallelism: Option<u32>,
+    #[serde(default)]
+    pub dedup: bool,
+    /// Times a file failing verification is copied again
+    #[serde(default)]
+    pub verify_retries: u32,
+    #[serde(default)]
+    pub abort_on_change: bool,
+    #[serde(default)]
+    pub restart_on_change: bool,
+    #[serde(default)]
+    pub atomic: bool,
+    /// Where an atomic copy stages its tree
+    #[serde(default)]
+    pub temp_dir: Option<String>,
+    /// Further destinations written from the same reads
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    /// Why the job exists
+    #[serde(default)]
+    pub note: String,
+}
+
+fn default_operation() -> String {
+    "copy".to_string()
+}
+
+fn default_verify() -> String {
+    "none".to_string()
+}
+
+fn default_exists() -> String {
+    "overwrite".to_string()
+}
+
+fn default_priority() -> u32 {
+    100
+}
+
+fn default_engine() -> String {
+    "auto".to_string()
+}
+
+fn default_atime() -> String {
+    "preserve".to_string()
+}
+
+fn default_class() -> String {
+    "foreground".to_string()
+}
+
+fn default_on_error() -> String {
+    "skip".to_string()
+}
+
+impl JobSpec {
+    fn to_request(&self) -> Result<CreateJobRequest> {
+        if !matches!(self.operation.as_str(), "copy" | "move") {
+            anyhow::bail!("unknown operation '{}', expected copy or move", self.operation);
+        }
+        if self.sources.is_empty() || self.sources.iter().any(|s| s.is_empty()) {
+            anyhow::bail!("sources must list at least one non-empty path");
+        }
+        if self.destination.is_empty() {
+            anyhow::bail!("destination must not be empty");
+        }
+        if (self.delete || self.delete_dry_run) && !self.recursive {
+            anyhow::bail!("delete requires recursive = true");
+        }
+        if self.dirs_only && !self.recursive {
+            anyhow::bail!("dirs_only requires recursive = true");
+        }
+        if self.regex_rename_match.is_some() != self.regex_rename_replace.is_some() {
+            anyhow::bail!("regex_rename_match and regex_rename_replace must be given together");
+        }
+        if self.abort_on_change && self.restart_on_change {
+            anyhow::bail!("abort_on_change and restart_on_change can't both be set");
+        }
+        if self.max_rate_percent.is_some_and(|percent| !(1..=100).contains(&percent)) {
+            anyhow::bail!("max_rate_percent must be between 1 and 100");
+        }
+
+        let verify: VerifyMode = self.verify.parse()?;
+        let exists: ExistsAction = self.exists.parse()?;
+        let engine: CopyEngine = self.engine.parse()?;
+        let atime: AtimeMode = self.atime.parse()?;
+        let class: JobClass = self.class.parse()?;
+        let on_error: ErrorPolicy = self.on_error.parse()?;
+        let xattrs = self.xattrs.iter()
+            .map(|namespace| namespace.parse::<XattrNamespace>())
+            .collect::<Result<Vec<_>>>()?;
+        let chmod = self.chmod.as_deref()
+            .map(crate::cli::parse_mode)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("chmod: {}", e))?;
+
+        Ok(CreateJobRequest {
+            sources: self.sources.clone(),
+            destination: self.destination.clone(),
+            recursive: self.recursive,
+            preserve_metadata: self.preserve,
+            preserve_links: self.preserve_links,
+            preserve_sparse: self.preserve_sparse,
+            verify: verify as i32,
+            exists_action: exists as i32,
+            priority: self.priority,
+            max_rate_bps: match self.max_rate {
+                Some(r) => r.checked_mul(1024 * 1024)
+                    .ok_or_else(|| anyhow::anyhow!("max_rate is too large"))?,
+                None => 0,
+            },
+            engine: engine as i32,
+            dry_run: self.dry_run,
+            regex_rename_match: self.regex_rename_match.clone().unwrap_or_default(),
+            regex_rename_replace: self.regex_rename_replace.clone().unwrap_or_default(),
+            block_size: self.block_size.unwrap_or(0),
+            compress: self.compress,
+            encrypt: self.encrypt,
+            delete_extraneous: self.delete,
+            delete_dry_run: self.delete_dry_run,
+            skip_destinations: Vec::new(),
+            device_source: self.device,
+            atime: atime as i32,
+            job_class: class as i32,
+            xattr_namespaces: xattrs.into_iter().map(|namespace| namespace as i32).collect(),
+            tags: self.tags.clone(),
+            skip_plan: self.no_plan,
+            stream_fifo: self.stream_fifo,
+            create_parents: self.parents,
+            max_rate_percent: self.max_rate_percent.unwrap_or(0),
+            chmod,
+            dirs_only: self.dirs_only,
+            error_policy: on_error as i32,
+            packed: self.packed,
+            description: self.note.clone(),
+            atomic: self.atomic,
+            temp_dir: self.temp_dir.clone().unwrap_or_default(),
+            destinations: self.mirrors.clone(),
+            on_source_change: crate::cli::source_change_action(self.abort_on_change, self.restart_on_change) as i32,
+            best_effort_metadata: self.best_effort_metadata,
+            copy_parallelism: self.copy_parallelism.unwrap_or(0),
+            dedup: self.dedup,
+            verify_retries: self.verify_retries,
+            operation_type: JobOperation::Copy as i32,
+            key_id: self.key_id.clone().unwrap_or_default(),
+        })
+    }
+}
+
+/// Parses a spec file into job requests. Every entry is validated first and
+/// all problems are reported together, so nothing is submitted from a spec
+/// that is only partly valid.
+pub fn parse_job_spec(content: &str) -> Result<Vec<CreateJobRequest>> {
+    let spec: JobSpecFile = toml::from_str(content)?;
+    if spec.jobs.is_empty() {
+        anyhow::bail!("spec file contains no [[job]] entries");
+    }
+
+    let mut requests = Vec::new();
+    let mut problems = Vec::new();
+    for (index, job) in spec.jobs.iter().enumerate() {
+        match job.to_request() {
+            Ok(request) => requests.push(request),
+            Err(e) => problems.push(format!("job {}: {}", index + 1, e)),
+        }
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!("invalid job spec:\n  {}", problems.join("\n  "));
+    }
+    Ok(requests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multi_job_spec() {
+        let requests = parse_job_spec(r#"
+            [[job]]
+            sources = ["/data/photos", "/data/videos"]
+            destination = "/backup/media"
+            recursive = true
+            preserve = true
+            verify = "sha256"
+            exists = "skip"
+            max_rate = 20
+            delete = true
+            xattrs = ["security", "trusted"]
+
+            [[job]]
+            operation = "move"
+            sources = ["/scratch/report.pdf"]
+            destination = "/archive/"
+            priority = 10
+            engine = "copyfilerange"
+        "#).unwrap();
+
+        assert_eq!(requests.len(), 2);
+
+        let media = &requests[0];
+        assert_eq!(media.sources, vec!["/data/photos", "/data/videos"]);
+        assert_eq!(media.destination, "/backup/media");
+        assert!(media.recursive && media.preserve_metadata && media.delete_extraneous);
+        assert_eq!(media.verify, VerifyMode::Sha256 as i32);
+        assert_eq!(media.exists_action, ExistsAction::Skip as i32);
+        assert_eq!(media.max_rate_bps, 20 * 1024 * 1024);
+        assert_eq!(media.priority, 100);
+        assert_eq!(media.xattr_namespaces, vec![XattrNamespace::Security as i32, XattrNamespace::Trusted as i32]);
+
+        let report = &requests[1];
+        assert_eq!(report.sources, vec!["/scratch/report.pdf"]);
+        assert_eq!(report.priority, 10);
+        assert_eq!(report.engine, CopyEngine::CopyFileRange as i32);
+        assert_eq!(report.verify, VerifyMode::None as i32);
+        assert!(!report.recursive);
+        assert!(report.xattr_namespaces.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_entries_reject_whole_spec() {
+        let err = parse_job_spec(r#"
+            [[job]]
+            sources = ["/ok"]
+            destination = "/dest"
+
+            [[job]]
+            sources = ["/tree"]
+            destination = "/mirror"
+            delete = true
+
+            [[job]]
+            sources = ["/x"]
+            destination = "/y"
+            verify = "crc32"
+
+            [[job]]
+            sources = ["/x"]
+            destination = "/y"
+            chmod = "0789"
+        "#).unwrap_err().to_string();
+
+        assert!(err.contains("job 2: delete requires recursive"));
+        assert!(err.contains("job 3: Invalid verify mode"));
+        assert!(err.contains("job 4: chmod: invalid mode '0789'"));
+        assert!(!err.contains("job 1"));
+
+        assert!(parse_job_spec("[[job]]\nsources = [\"/a\"]\ndestination = \"/b\"\nbogus = 1\n").is_err());
+        assert!(parse_job_spec("").is_err());
+    }
+}
@@ -183,10 +183,8 @@ impl App {
     pub async fn handle_key_event(&mut self, key: KeyEvent) -> Result<bool> {
         // Global key bindings
         match key.code {
-            KeyCode::Char('q') => {
-                if key.modifiers.contains(KeyModifiers::CONTROL) {
-                    return Ok(true); // Quit
-                }
+            KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(true); // Quit
             }
             KeyCode::Char('?') => {
                 self.current_screen = AppScreen::Help;
@@ -216,11 +214,9 @@ impl App {
                 self.current_screen = AppScreen::Help;
                 return Ok(false);
             }
-            KeyCode::Esc => {
-                if self.show_popup {
-                    self.show_popup = false;
-                    return Ok(false);
-                }
+            KeyCode::Esc if self.show_popup => {
+                self.show_popup = false;
+                return Ok(false);
             }
             _ => {}
         }
@@ -247,7 +243,7 @@ impl App {
                 self.job_monitor.handle_key_event(key, &mut self.client).await?;
             }
             AppScreen::Config => {
-                if self.config_editor.handle_key_event(key).await? {
+                if self.config_editor.handle_key_event(key, &mut self.client).await? {
                     self.set_status_message("Configuration saved", false);
                 }
             }
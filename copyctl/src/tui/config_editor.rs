@@ -1,27 +1,114 @@
 use anyhow::Result;
-use crossterm::event::KeyEvent;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
-    layout::Rect,
-    widgets::{Block, Borders, Paragraph},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
     Frame,
 };
 
-pub struct ConfigEditor;
+use crate::client::CopyClient;
+
+/// Sends `key=value` settings to the running daemon.
+pub struct ConfigEditor {
+    input: String,
+    results: Vec<(String, bool)>, // (message, is_error)
+}
 
 impl ConfigEditor {
     pub fn new() -> Result<Self> {
-        Ok(Self)
+        Ok(Self {
+            input: String::new(),
+            results: Vec::new(),
+        })
     }
 
     pub fn draw(&mut self, f: &mut Frame, area: Rect) {
-        let config_text = "Configuration editor coming soon...";
-        let paragraph = Paragraph::new(config_text)
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Input
+                Constraint::Min(0),    // Results
+            ])
+            .split(area);
+
+        let input = Paragraph::new(format!("> {}", self.input))
+            .block(Block::default()
+                .title("Set KEY=VALUE (Enter to apply, Ctrl+R to reload the config file)")
+                .borders(Borders::ALL));
+        f.render_widget(input, chunks[0]);
+
+        let mut items: Vec<ListItem> = vec![ListItem::new(Line::from(Span::styled(
+            format!("Live settings: {}", copyd_protocol::HOT_RELOADABLE_KEYS.join(", ")),
+            Style::default().fg(Color::DarkGray),
+        )))];
+        items.extend(self.results.iter().rev().map(|(message, is_error)| {
+            let color = if *is_error { Color::Red } else { Color::Green };
+            ListItem::new(Line::from(Span::styled(message.clone(), Style::default().fg(color))))
+        }));
+
+        let results = List::new(items)
             .block(Block::default().title("Configuration").borders(Borders::ALL));
-        f.render_widget(paragraph, area);
+        f.render_widget(results, chunks[1]);
+    }
+
+    /// Returns true when the daemon accepted a change.
+    pub async fn handle_key_event(&mut self, key: KeyEvent, client: &mut CopyClient) -> Result<bool> {
+        match key.code {
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let result = client.reload_config().await;
+                Ok(self.record(result))
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                Ok(false)
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                Ok(false)
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                Ok(false)
+            }
+            KeyCode::Enter => {
+                let setting = match crate::cli::parse_config_setting(&self.input) {
+                    Ok(setting) => setting,
+                    Err(e) => {
+                        self.results.push((e, true));
+                        return Ok(false);
+                    }
+                };
+                let result = client.set_config(vec![setting]).await;
+                let applied = self.record(result);
+                if applied {
+                    self.input.clear();
+                }
+                Ok(applied)
+            }
+            _ => Ok(false),
+        }
     }
 
-    pub async fn handle_key_event(&mut self, _key: KeyEvent) -> Result<bool> {
-        // TODO: Implement config editing
-        Ok(false)
+    fn record(&mut self, result: Result<copyd_protocol::ConfigUpdateResponse>) -> bool {
+        match result {
+            Ok(update) => {
+                for key in &update.applied {
+                    self.results.push((format!("Applied {}", key), false));
+                }
+                for key in &update.restart_required {
+                    self.results.push((format!("{} changes after the daemon restarts", key), true));
+                }
+                if update.applied.is_empty() && update.restart_required.is_empty() {
+                    self.results.push(("Configuration unchanged".to_string(), false));
+                }
+                true
+            }
+            Err(e) => {
+                self.results.push((e.to_string(), true));
+                false
+            }
+        }
     }
-} 
\ No newline at end of file
+}
@@ -220,7 +220,7 @@ impl FileBrowser {
         f.render_widget(path_paragraph, layout[0]);
 
         // Draw file list
-        let items: Vec<ListItem> = pane.entries.iter().enumerate().map(|(_, entry)| {
+        let items: Vec<ListItem> = pane.entries.iter().map(|entry| {
             let mut spans = Vec::new();
             
             // Icon based on file type
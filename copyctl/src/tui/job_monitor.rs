@@ -6,6 +6,7 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem},
     Frame,
 };
+use copyd_protocol::JobStatus;
 use crate::client::CopyClient;
 
 pub struct JobMonitor {
@@ -15,7 +16,7 @@ pub struct JobMonitor {
 impl JobMonitor {
     pub fn new() -> Self {
         Self {
-            jobs: vec!["Job monitoring coming soon...".to_string()],
+            jobs: vec!["Loading jobs...".to_string()],
         }
     }
 
@@ -37,8 +38,31 @@ impl JobMonitor {
         Ok(())
     }
 
-    pub async fn update(&mut self, _client: &mut CopyClient) -> Result<()> {
-        // TODO: Refresh job list from daemon
+    /// Lists the daemon's active jobs, each with its recent rate history.
+    pub async fn update(&mut self, client: &mut CopyClient) -> Result<()> {
+        let job_ids: Vec<String> = client.list_jobs(false, Vec::new()).await?
+            .into_iter()
+            .filter_map(|job| job.job_id.map(|id| id.uuid))
+            .collect();
+        if job_ids.is_empty() {
+            self.jobs = vec!["No active jobs".to_string()];
+            return Ok(());
+        }
+
+        self.jobs = client.get_job_statuses(&job_ids).await?
+            .into_iter()
+            .zip(&job_ids)
+            .map(|(status, job_id)| {
+                let progress = status.progress.unwrap_or_default();
+                let state = JobStatus::try_from(progress.status).unwrap_or(JobStatus::Pending);
+                let rates: Vec<f64> = status.rate_samples.iter().map(|s| s.throughput_mbps).collect();
+                format!(
+                    "{}  {:?}  {:.1}%  {:.1} MB/s  {}",
+                    job_id.get(..8).unwrap_or(job_id), state, progress.overall_percent,
+                    progress.throughput_mbps, crate::cli::sparkline(&rates)
+                )
+            })
+            .collect();
         Ok(())
     }
 } 
\ No newline at end of file
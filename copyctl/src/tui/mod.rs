@@ -61,11 +61,8 @@ async fn run_app<B: ratatui::backend::Backend>(
 
         if event::poll(std::time::Duration::from_millis(50))? {
             if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match app.handle_key_event(key).await? {
-                        true => break, // Quit requested
-                        false => {}, // Continue
-                    }
+                if key.kind == KeyEventKind::Press && app.handle_key_event(key).await? {
+                    break; // Quit requested
                 }
             }
         }
@@ -3,7 +3,7 @@ use prost_build::Config;
 fn main() {
     let mut config = Config::new();
 
-    config.type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]");
+    config.type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema)]");
 
     // Enable BTreeMap for maps if needed
 
@@ -95,6 +95,30 @@ where
     MessageFramer::receive_message(reader).await
 }
 
+/// Settings a running daemon applies without a restart, shared so clients
+/// can list them.
+pub const HOT_RELOADABLE_KEYS: &[&str] = &[
+    "max_concurrent_jobs",
+    "default_engine",
+    "allowed_engines",
+    "numa_aware_buffers",
+    "read_ahead_blocks",
+    "checkpoint_interval_secs",
+    "checkpoint_interval_bytes",
+    "idle_io_verification",
+    "low_space_threshold",
+    "collapse_progress_ms",
+    "verify_cost",
+    "max_copy_parallelism",
+];
+
+impl JobStatus {
+    /// Whether the job has finished and will not change state again.
+    pub fn is_terminal(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
 impl fmt::Display for VerifyMode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -110,6 +134,8 @@ impl FromStr for VerifyMode {
             "size" => Ok(VerifyMode::Size),
             "md5" => Ok(VerifyMode::Md5),
             "sha256" => Ok(VerifyMode::Sha256),
+            "blake3" => Ok(VerifyMode::Blake3),
+            "fingerprint" => Ok(VerifyMode::Fingerprint),
             _ => Err(anyhow::anyhow!("Invalid verify mode: {}", s)),
         }
     }
@@ -134,6 +160,109 @@ impl FromStr for ExistsAction {
     }
 }
 
+impl fmt::Display for AtimeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for AtimeMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "preserve" => Ok(AtimeMode::Preserve),
+            "now" => Ok(AtimeMode::Now),
+            "mtime" => Ok(AtimeMode::Mtime),
+            _ => Err(anyhow::anyhow!("Invalid atime mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for JobClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for JobClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "foreground" => Ok(JobClass::Foreground),
+            "background" => Ok(JobClass::Background),
+            _ => Err(anyhow::anyhow!("Invalid job class: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ErrorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(ErrorPolicy::SkipFile),
+            "abort" => Ok(ErrorPolicy::AbortJob),
+            "retry" => Ok(ErrorPolicy::Retry),
+            _ => Err(anyhow::anyhow!("Invalid error policy: {}", s)),
+        }
+    }
+}
+
+impl FromStr for SourceChangeAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ignore" => Ok(SourceChangeAction::IgnoreChange),
+            "abort" => Ok(SourceChangeAction::AbortCopy),
+            "restart" => Ok(SourceChangeAction::RestartCopy),
+            _ => Err(anyhow::anyhow!("Invalid source change action: {}", s)),
+        }
+    }
+}
+
+impl XattrNamespace {
+    /// Namespace of the extended attribute `name`, if it is one of the known ones.
+    pub fn of(name: &[u8]) -> Option<Self> {
+        let prefix = &name[..name.iter().position(|&b| b == b'.')?];
+        match prefix {
+            b"user" => Some(XattrNamespace::User),
+            b"system" => Some(XattrNamespace::System),
+            b"security" => Some(XattrNamespace::Security),
+            b"trusted" => Some(XattrNamespace::Trusted),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for XattrNamespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for XattrNamespace {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "user" => Ok(XattrNamespace::User),
+            "system" => Ok(XattrNamespace::System),
+            "security" => Ok(XattrNamespace::Security),
+            "trusted" => Ok(XattrNamespace::Trusted),
+            _ => Err(anyhow::anyhow!("Invalid xattr namespace: {}", s)),
+        }
+    }
+}
+
 impl fmt::Display for CopyEngine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -151,6 +280,7 @@ impl FromStr for CopyEngine {
             "sendfile" => Ok(CopyEngine::Sendfile),
             "reflink" => Ok(CopyEngine::Reflink),
             "readwrite" => Ok(CopyEngine::ReadWrite),
+            "splice" => Ok(CopyEngine::Splice),
             _ => Err(anyhow::anyhow!("Invalid copy engine: {}", s)),
         }
     }
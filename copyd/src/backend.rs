@@ -0,0 +1,165 @@
+use anyhow::Result;
+use copyd_protocol::CopyEngine;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::copy_engine::{CopyOptions, FileCopyEngine};
+
+pub use futures::future::BoxFuture;
+
+/// A way of moving file contents from one path to another.
+///
+/// Backends receive the [`FileCopyEngine`] that dispatched to them so they can
+/// share its inflight budget and buffer settings, and fall back to the other
+/// built-in methods when their own system call is not supported.
+pub trait CopyBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether the backend can run on this system at all.
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    /// Copies `source` to `destination`, returning the number of bytes written.
+    fn copy<'a>(
+        &'a self,
+        engine: &'a FileCopyEngine,
+        source: &'a Path,
+        destination: &'a Path,
+        options: &'a CopyOptions,
+    ) -> BoxFuture<'a, Result<u64>>;
+}
+
+pub struct AutoBackend;
+
+impl CopyBackend for AutoBackend {
+    fn name(&self) -> &'static str {
+        "auto"
+    }
+
+    fn copy<'a>(&'a self, engine: &'a FileCopyEngine, source: &'a Path, destination: &'a Path, options: &'a CopyOptions) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(engine.auto_copy(source, destination, options))
+    }
+}
+
+/// io_uring requests currently go through the automatic selection chain; the
+/// backend exists so they can be disabled where the kernel lacks io_uring.
+pub struct IoUringBackend;
+
+impl CopyBackend for IoUringBackend {
+    fn name(&self) -> &'static str {
+        "io_uring"
+    }
+
+    fn is_available(&self) -> bool {
+        crate::io_uring_engine::IoUringCopyEngine::is_io_uring_available()
+    }
+
+    fn copy<'a>(&'a self, engine: &'a FileCopyEngine, source: &'a Path, destination: &'a Path, options: &'a CopyOptions) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(engine.auto_copy(source, destination, options))
+    }
+}
+
+pub struct CopyFileRangeBackend;
+
+impl CopyBackend for CopyFileRangeBackend {
+    fn name(&self) -> &'static str {
+        "copy_file_range"
+    }
+
+    fn copy<'a>(&'a self, engine: &'a FileCopyEngine, source: &'a Path, destination: &'a Path, options: &'a CopyOptions) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(engine.copy_file_range_copy(source, destination, options))
+    }
+}
+
+pub struct SendfileBackend;
+
+impl CopyBackend for SendfileBackend {
+    fn name(&self) -> &'static str {
+        "sendfile"
+    }
+
+    fn copy<'a>(&'a self, engine: &'a FileCopyEngine, source: &'a Path, destination: &'a Path, options: &'a CopyOptions) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(engine.sendfile_copy(source, destination, options))
+    }
+}
+
+pub struct ReflinkBackend;
+
+impl CopyBackend for ReflinkBackend {
+    fn name(&self) -> &'static str {
+        "reflink"
+    }
+
+    fn copy<'a>(&'a self, engine: &'a FileCopyEngine, source: &'a Path, destination: &'a Path, options: &'a CopyOptions) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(engine.reflink_copy(source, destination, options))
+    }
+}
+
+pub struct SpliceBackend;
+
+impl CopyBackend for SpliceBackend {
+    fn name(&self) -> &'static str {
+        "splice"
+    }
+
+    fn copy<'a>(&'a self, engine: &'a FileCopyEngine, source: &'a Path, destination: &'a Path, options: &'a CopyOptions) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(engine.splice_copy(source, destination, options))
+    }
+}
+
+pub struct ReadWriteBackend;
+
+impl CopyBackend for ReadWriteBackend {
+    fn name(&self) -> &'static str {
+        "read_write"
+    }
+
+    fn copy<'a>(&'a self, engine: &'a FileCopyEngine, source: &'a Path, destination: &'a Path, options: &'a CopyOptions) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(engine.read_write_copy(source, destination, options))
+    }
+}
+
+/// Maps each [`CopyEngine`] to the backend that implements it.
+#[derive(Clone)]
+pub struct BackendRegistry {
+    backends: HashMap<CopyEngine, Arc<dyn CopyBackend>>,
+}
+
+impl Default for BackendRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(CopyEngine::Auto, Arc::new(AutoBackend));
+        registry.register(CopyEngine::IoUring, Arc::new(IoUringBackend));
+        registry.register(CopyEngine::CopyFileRange, Arc::new(CopyFileRangeBackend));
+        registry.register(CopyEngine::Sendfile, Arc::new(SendfileBackend));
+        registry.register(CopyEngine::Reflink, Arc::new(ReflinkBackend));
+        registry.register(CopyEngine::ReadWrite, Arc::new(ReadWriteBackend));
+        registry.register(CopyEngine::Splice, Arc::new(SpliceBackend));
+        registry
+    }
+}
+
+impl BackendRegistry {
+    pub fn empty() -> Self {
+        Self { backends: HashMap::new() }
+    }
+
+    /// Registers `backend` for `engine`, replacing any previous one.
+    pub fn register(&mut self, engine: CopyEngine, backend: Arc<dyn CopyBackend>) {
+        self.backends.insert(engine, backend);
+    }
+
+    pub fn get(&self, engine: CopyEngine) -> Option<Arc<dyn CopyBackend>> {
+        self.backends.get(&engine).cloned()
+    }
+
+    /// The backend for `engine` if it is registered and available, otherwise
+    /// the automatic backend.
+    pub fn resolve(&self, engine: CopyEngine) -> Option<Arc<dyn CopyBackend>> {
+        self.get(engine)
+            .filter(|backend| backend.is_available())
+            .or_else(|| self.get(CopyEngine::Auto))
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+
+/// Bytes written and read back to estimate a device's bandwidth.
+const CALIBRATION_BYTES: usize = 32 * 1024 * 1024;
+const CALIBRATION_BLOCK: usize = 1024 * 1024;
+
+/// Measures the sustained bandwidth, in bytes per second, of the device
+/// holding a directory.
+pub trait BandwidthProbe: Send + Sync {
+    fn measure(&self, dir: &Path) -> std::io::Result<u64>;
+}
+
+/// Writes a scratch file into the directory, syncs it, drops it from the
+/// page cache and reads it back; the slower direction is the estimate.
+pub struct CalibrationProbe;
+
+impl BandwidthProbe for CalibrationProbe {
+    fn measure(&self, dir: &Path) -> std::io::Result<u64> {
+        let path = dir.join(format!(".copyd-calibration-{}", uuid::Uuid::new_v4()));
+        let result = Self::write_and_read(&path);
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+}
+
+impl CalibrationProbe {
+    fn write_and_read(path: &Path) -> std::io::Result<u64> {
+        let block = vec![0xA5u8; CALIBRATION_BLOCK];
+        let mut file = std::fs::File::create(path)?;
+        let started = Instant::now();
+        for _ in 0..CALIBRATION_BYTES / CALIBRATION_BLOCK {
+            file.write_all(&block)?;
+        }
+        file.sync_all()?;
+        let write_rate = rate(CALIBRATION_BYTES, started);
+
+        // Without this the read back would only measure the page cache
+        unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+        let mut file = std::fs::File::open(path)?;
+        let mut buffer = vec![0u8; CALIBRATION_BLOCK];
+        let started = Instant::now();
+        while file.read(&mut buffer)? > 0 {}
+        let read_rate = rate(CALIBRATION_BYTES, started);
+
+        Ok(write_rate.min(read_rate))
+    }
+}
+
+fn rate(bytes: usize, started: Instant) -> u64 {
+    (bytes as f64 / started.elapsed().as_secs_f64().max(1e-6)) as u64
+}
+
+/// Turns "at most N% of this disk" into a byte rate, calibrating each
+/// device once and reusing the result for later jobs.
+pub struct BandwidthCalibrator {
+    probe: Arc<dyn BandwidthProbe>,
+    measured: parking_lot::Mutex<HashMap<u64, u64>>,
+}
+
+impl Default for BandwidthCalibrator {
+    fn default() -> Self {
+        Self::new(Arc::new(CalibrationProbe))
+    }
+}
+
+impl BandwidthCalibrator {
+    pub fn new(probe: Arc<dyn BandwidthProbe>) -> Self {
+        Self { probe, measured: parking_lot::Mutex::new(HashMap::new()) }
+    }
+
+    /// Bandwidth of the device `path` is, or would be created, on.
+    pub async fn device_bandwidth(&self, path: &Path) -> Result<u64> {
+        let dir = existing_directory(path)
+            .with_context(|| format!("No existing directory above {:?} to calibrate", path))?;
+        let device = std::fs::metadata(&dir)?.dev();
+        if let Some(&bandwidth) = self.measured.lock().get(&device) {
+            return Ok(bandwidth);
+        }
+
+        let probe = self.probe.clone();
+        let probe_dir = dir.clone();
+        let bandwidth = tokio::task::spawn_blocking(move || probe.measure(&probe_dir)).await?
+            .with_context(|| format!("Failed to calibrate the bandwidth of {:?}", dir))?;
+        info!("Measured {:.1} MB/s on the device holding {:?}", bandwidth as f64 / (1024.0 * 1024.0), dir);
+        self.measured.lock().insert(device, bandwidth);
+        Ok(bandwidth)
+    }
+
+    /// `percent` of the measured bandwidth of `path`'s device, in bytes per
+    /// second.
+    pub async fn rate_cap(&self, path: &Path, percent: u32) -> Result<u64> {
+        if !(1..=100).contains(&percent) {
+            anyhow::bail!("Rate percentage must be between 1 and 100, got {}", percent);
+        }
+        let bandwidth = self.device_bandwidth(path).await?;
+        Ok((bandwidth as u128 * percent as u128 / 100).max(1) as u64)
+    }
+}
+
+/// `path` itself when it is a directory, otherwise its closest existing
+/// ancestor directory.
+fn existing_directory(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .find(|ancestor| ancestor.is_dir())
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct FixedProbe {
+        bandwidth: u64,
+        calls: AtomicUsize,
+    }
+
+    impl BandwidthProbe for FixedProbe {
+        fn measure(&self, _dir: &Path) -> std::io::Result<u64> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(self.bandwidth)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_cap_is_fraction_of_calibrated_bandwidth() {
+        let dir = tempfile::tempdir().unwrap();
+        let probe = Arc::new(FixedProbe { bandwidth: 400 * 1024 * 1024, calls: AtomicUsize::new(0) });
+        let calibrator = BandwidthCalibrator::new(probe.clone());
+
+        assert_eq!(calibrator.rate_cap(dir.path(), 50).await.unwrap(), 200 * 1024 * 1024);
+        // A destination that doesn't exist yet is measured on its parent's
+        // device, which is already calibrated
+        let missing = dir.path().join("new/dir/file.bin");
+        assert_eq!(calibrator.rate_cap(&missing, 25).await.unwrap(), 100 * 1024 * 1024);
+        assert_eq!(calibrator.rate_cap(dir.path(), 100).await.unwrap(), 400 * 1024 * 1024);
+        assert_eq!(probe.calls.load(Ordering::SeqCst), 1);
+
+        assert!(calibrator.rate_cap(dir.path(), 0).await.is_err());
+        assert!(calibrator.rate_cap(dir.path(), 101).await.is_err());
+    }
+
+    #[test]
+    fn test_calibration_probe_measures_a_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(CalibrationProbe.measure(dir.path()).unwrap() > 0);
+        // The scratch file is cleaned up
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
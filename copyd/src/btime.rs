@@ -0,0 +1,95 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// statfs `f_type` of CIFS and SMB2 mounts, whose servers keep a creation
+/// time that the client exposes as `user.cifs.creationtime`.
+const CIFS_FILESYSTEMS: &[u64] = &[0xff53_4d42, 0xfe53_4d42];
+/// statfs `f_type` of FUSE mounts; ntfs-3g exposes `system.ntfs_crtime`.
+const FUSE_FILESYSTEM: u64 = 0x6573_5546;
+/// Seconds from 1601-01-01, where Windows times start, to the Unix epoch.
+const NT_EPOCH_OFFSET: u64 = 11_644_473_600;
+
+/// Birth time of `path`, read with statx `STATX_BTIME`; `None` where the
+/// filesystem doesn't record one.
+pub fn birth_time(path: &Path) -> io::Result<Option<SystemTime>> {
+    match std::fs::metadata(path)?.created() {
+        Ok(born) => Ok(Some(born)),
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// `time` in 100ns intervals since 1601, as Windows filesystems store it.
+pub fn nt_time(time: SystemTime) -> u64 {
+    let since_epoch = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    (since_epoch.as_secs() + NT_EPOCH_OFFSET) * 10_000_000 + u64::from(since_epoch.subsec_nanos()) / 100
+}
+
+/// Gives `path` the birth time `born` where its filesystem has a way to set
+/// one: the creation time attribute of CIFS/SMB mounts, or of NTFS through
+/// ntfs-3g. Linux has no call that sets a birth time in general, so
+/// everywhere else this changes nothing and returns false.
+pub fn set_birth_time(path: &Path, born: SystemTime) -> io::Result<bool> {
+    let name = match crate::device::filesystem_type(path)? {
+        magic if CIFS_FILESYSTEMS.contains(&magic) => "user.cifs.creationtime",
+        FUSE_FILESYSTEM => "system.ntfs_crtime",
+        _ => return Ok(false),
+    };
+    let path_cstr = CString::new(path.as_os_str().as_bytes())?;
+    let name_cstr = CString::new(name).expect("attribute names have no NUL");
+    let value = nt_time(born).to_le_bytes();
+    let result = unsafe {
+        libc::setxattr(path_cstr.as_ptr(), name_cstr.as_ptr(), value.as_ptr().cast(), value.len(), 0)
+    };
+    if result == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        // A FUSE filesystem other than ntfs-3g, or a server that refuses
+        Some(libc::ENOTSUP | libc::ENODATA) => Ok(false),
+        _ => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nt_time_counts_from_1601() {
+        assert_eq!(nt_time(SystemTime::UNIX_EPOCH), 116_444_736_000_000_000);
+        let later = SystemTime::UNIX_EPOCH + Duration::new(1, 250);
+        assert_eq!(nt_time(later), 116_444_736_010_000_002);
+    }
+
+    #[test]
+    fn test_birth_time_is_read_and_setting_it_is_a_no_op_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let before = SystemTime::now() - Duration::from_secs(1);
+        std::fs::write(&source, b"archive").unwrap();
+
+        // Filesystems without birth times (or kernels without statx) have
+        // nothing to read; skip the check there
+        let Some(born) = birth_time(&source).unwrap() else {
+            return;
+        };
+        assert!(born >= before && born <= SystemTime::now(), "{:?}", born);
+
+        // Local filesystems offer no way to set it, so nothing changes
+        let destination = dir.path().join("destination");
+        std::fs::write(&destination, b"archive").unwrap();
+        let own = birth_time(&destination).unwrap();
+        let earlier = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let magic = crate::device::filesystem_type(dir.path()).unwrap();
+        if !CIFS_FILESYSTEMS.contains(&magic) && magic != FUSE_FILESYSTEM {
+            assert!(!set_birth_time(&destination, earlier).unwrap());
+            assert_eq!(birth_time(&destination).unwrap(), own);
+        }
+        assert!(birth_time(&dir.path().join("missing")).is_err());
+    }
+}
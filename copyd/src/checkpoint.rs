@@ -6,6 +6,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{info, debug, warn};
+use crate::sparse::SparseFileHandler;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileCheckpoint {
@@ -14,18 +15,52 @@ pub struct FileCheckpoint {
     pub bytes_copied: u64,
     pub total_size: u64,
     pub last_modified: u64, // Unix timestamp
+    /// Nanoseconds past `last_modified`; 0 in older checkpoints
+    #[serde(default)]
+    pub last_modified_nanos: u32,
     pub checksum_partial: Option<String>, // Partial checksum for verification
     pub chunk_size: u64,
     pub created_at: u64,
     pub updated_at: u64,
 }
 
+/// Size and modification time of a source when it was copied, to tell on
+/// resume whether it has changed since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceStamp {
+    pub size: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+}
+
+impl SourceStamp {
+    pub fn of(metadata: &std::fs::Metadata) -> Option<Self> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+/// A top-level source of a job and whether everything under it was copied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceCheckpoint {
+    pub path: PathBuf,
+    pub completed: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JobCheckpoint {
     pub job_id: String,
     pub operation_type: String, // "copy" or "move"
     pub files: HashMap<String, FileCheckpoint>, // file_id -> checkpoint
     pub completed_files: Vec<String>,
+    /// What the sources of completed files looked like when they were
+    /// copied; empty in older checkpoints
+    #[serde(default)]
+    pub completed_stamps: HashMap<String, SourceStamp>,
     pub failed_files: Vec<String>,
     pub total_files: usize,
     pub total_bytes: u64,
@@ -33,6 +68,39 @@ pub struct JobCheckpoint {
     pub created_at: u64,
     pub updated_at: u64,
     pub resume_count: u32,
+    /// Top-level sources in job order; empty in checkpoints that predate
+    /// per-source tracking
+    #[serde(default)]
+    pub sources: Vec<SourceCheckpoint>,
+    #[serde(default)]
+    pub destination: PathBuf,
+    /// The job's description and tags, restored with it on resume
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether the sources go inside the destination, as decided when the
+    /// job first started; unknown in older checkpoints
+    #[serde(default)]
+    pub into_destination: Option<bool>,
+    /// Further destinations the job writes the same tree to
+    #[serde(default)]
+    pub mirrors: Vec<PathBuf>,
+    /// Whether the job encrypts its copies, and the keyring entry holding
+    /// their passphrase; the passphrase itself is never saved
+    #[serde(default)]
+    pub encrypt: bool,
+    #[serde(default)]
+    pub key_id: Option<String>,
+    /// Whether the job compresses its copies
+    #[serde(default)]
+    pub compress: bool,
+    /// Whether the job swaps the copied tree into place at the end, and
+    /// where it stages it when not beside the target
+    #[serde(default)]
+    pub atomic: bool,
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
 }
 
 impl JobCheckpoint {
@@ -44,6 +112,7 @@ impl JobCheckpoint {
             operation_type,
             files: HashMap::new(),
             completed_files: Vec::new(),
+            completed_stamps: HashMap::new(),
             failed_files: Vec::new(),
             total_files: 0,
             total_bytes: 0,
@@ -51,9 +120,45 @@ impl JobCheckpoint {
             created_at: now,
             updated_at: now,
             resume_count: 0,
+            sources: Vec::new(),
+            destination: PathBuf::new(),
+            description: String::new(),
+            tags: Vec::new(),
+            into_destination: None,
+            mirrors: Vec::new(),
+            encrypt: false,
+            key_id: None,
+            compress: false,
+            atomic: false,
+            temp_dir: None,
+        }
+    }
+
+    /// Records the job's sources and destination so a resume can skip the
+    /// sources that already finished.
+    pub fn set_sources(&mut self, sources: &[PathBuf], destination: &Path) {
+        self.sources = sources.iter()
+            .map(|path| SourceCheckpoint { path: path.clone(), completed: false })
+            .collect();
+        self.destination = destination.to_path_buf();
+        self.update_timestamp();
+    }
+
+    pub fn complete_source(&mut self, index: usize) {
+        if let Some(source) = self.sources.get_mut(index) {
+            source.completed = true;
+            self.update_timestamp();
         }
     }
 
+    /// Sources a resumed job still has to copy, in job order.
+    pub fn pending_sources(&self) -> Vec<PathBuf> {
+        self.sources.iter()
+            .filter(|source| !source.completed)
+            .map(|source| source.path.clone())
+            .collect()
+    }
+
     pub fn add_file(&mut self, file_id: String, checkpoint: FileCheckpoint) {
         self.total_bytes += checkpoint.total_size;
         self.total_files += 1;
@@ -76,6 +181,11 @@ impl JobCheckpoint {
 
     pub fn complete_file(&mut self, file_id: String) {
         if let Some(checkpoint) = self.files.remove(&file_id) {
+            self.completed_stamps.insert(file_id.clone(), SourceStamp {
+                size: checkpoint.total_size,
+                modified_secs: checkpoint.last_modified,
+                modified_nanos: checkpoint.last_modified_nanos,
+            });
             self.completed_files.push(file_id);
             // Ensure bytes_completed accounts for this file
             if checkpoint.bytes_copied < checkpoint.total_size {
@@ -86,7 +196,7 @@ impl JobCheckpoint {
     }
 
     pub fn fail_file(&mut self, file_id: String) {
-        if let Some(_) = self.files.remove(&file_id) {
+        if self.files.remove(&file_id).is_some() {
             self.failed_files.push(file_id);
             self.update_timestamp();
         }
@@ -103,6 +213,28 @@ impl JobCheckpoint {
         !self.files.is_empty() || !self.failed_files.is_empty()
     }
 
+    /// Moves every file whose source lives under `from` to the same relative
+    /// path under `to`, for source trees that were remounted elsewhere.
+    /// Returns the number of files rewritten.
+    pub fn remap_source_root(&mut self, from: &Path, to: &Path) -> usize {
+        let mut remapped = 0;
+        for checkpoint in self.files.values_mut() {
+            if let Ok(relative) = checkpoint.source_path.strip_prefix(from) {
+                checkpoint.source_path = to.join(relative);
+                remapped += 1;
+            }
+        }
+        for source in &mut self.sources {
+            if let Ok(relative) = source.path.strip_prefix(from) {
+                source.path = to.join(relative);
+            }
+        }
+        if remapped > 0 {
+            self.update_timestamp();
+        }
+        remapped
+    }
+
     pub fn increment_resume_count(&mut self) {
         self.resume_count += 1;
         self.update_timestamp();
@@ -113,8 +245,22 @@ impl JobCheckpoint {
     }
 }
 
+/// A job still waiting in the queue when the daemon stopped, saved with the
+/// request it was created from so the next daemon queues it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedJobCheckpoint {
+    pub job_id: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub request: copyd_protocol::CreateJobRequest,
+}
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const CHECKPOINT_ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone)]
 pub struct CheckpointManager {
     checkpoint_dir: PathBuf,
+    compress: bool,
 }
 
 impl CheckpointManager {
@@ -122,43 +268,138 @@ impl CheckpointManager {
         std::fs::create_dir_all(&checkpoint_dir)
             .with_context(|| format!("Failed to create checkpoint directory: {:?}", checkpoint_dir))?;
 
-        Ok(Self { checkpoint_dir })
+        Ok(Self { checkpoint_dir, compress: false })
+    }
+
+    /// Write checkpoints as zstd-compressed `<job>.json.zst` files. Loading
+    /// accepts both formats regardless of this setting.
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.compress = enabled;
+        self
+    }
+
+    fn plain_path(&self, job_id: &str) -> PathBuf {
+        self.checkpoint_dir.join(format!("{}.json", job_id))
+    }
+
+    fn compressed_path(&self, job_id: &str) -> PathBuf {
+        self.checkpoint_dir.join(format!("{}.json.zst", job_id))
+    }
+
+    /// Path of the checkpoint file currently on disk for `job_id`, preferring
+    /// the compressed one.
+    pub fn checkpoint_path(&self, job_id: &str) -> Option<PathBuf> {
+        [self.compressed_path(job_id), self.plain_path(job_id)]
+            .into_iter()
+            .find(|path| path.exists())
     }
 
     pub async fn save_checkpoint(&self, checkpoint: &JobCheckpoint) -> Result<()> {
-        let checkpoint_file = self.checkpoint_dir.join(format!("{}.json", checkpoint.job_id));
-        
-        let json_data = serde_json::to_string_pretty(checkpoint)
-            .with_context(|| "Failed to serialize checkpoint")?;
+        let (checkpoint_file, stale_file, data) = if self.compress {
+            let json_data = serde_json::to_vec(checkpoint)
+                .with_context(|| "Failed to serialize checkpoint")?;
+            let compressed = zstd::encode_all(json_data.as_slice(), CHECKPOINT_ZSTD_LEVEL)
+                .with_context(|| "Failed to compress checkpoint")?;
+            (self.compressed_path(&checkpoint.job_id), self.plain_path(&checkpoint.job_id), compressed)
+        } else {
+            let json_data = serde_json::to_string_pretty(checkpoint)
+                .with_context(|| "Failed to serialize checkpoint")?;
+            (self.plain_path(&checkpoint.job_id), self.compressed_path(&checkpoint.job_id), json_data.into_bytes())
+        };
 
         let mut file = fs::File::create(&checkpoint_file).await
             .with_context(|| format!("Failed to create checkpoint file: {:?}", checkpoint_file))?;
 
-        file.write_all(json_data.as_bytes()).await
+        file.write_all(&data).await
             .with_context(|| "Failed to write checkpoint data")?;
 
         file.sync_all().await
             .with_context(|| "Failed to sync checkpoint file")?;
 
-        debug!("Saved checkpoint for job {}", checkpoint.job_id);
+        // Don't leave an older checkpoint in the other format behind
+        if stale_file.exists() {
+            fs::remove_file(&stale_file).await
+                .with_context(|| format!("Failed to remove stale checkpoint file: {:?}", stale_file))?;
+        }
+
+        debug!("Saved checkpoint for job {} ({} bytes)", checkpoint.job_id, data.len());
         Ok(())
     }
 
-    pub async fn load_checkpoint(&self, job_id: &str) -> Result<Option<JobCheckpoint>> {
-        let checkpoint_file = self.checkpoint_dir.join(format!("{}.json", job_id));
-        
-        if !checkpoint_file.exists() {
-            return Ok(None);
+    /// Queued jobs live in a subdirectory so they are never mistaken for
+    /// job checkpoints.
+    fn queued_dir(&self) -> PathBuf {
+        self.checkpoint_dir.join("queued")
+    }
+
+    pub async fn save_queued_job(&self, queued: &QueuedJobCheckpoint) -> Result<()> {
+        let queued_dir = self.queued_dir();
+        fs::create_dir_all(&queued_dir).await
+            .with_context(|| format!("Failed to create queued job directory: {:?}", queued_dir))?;
+
+        let queued_file = queued_dir.join(format!("{}.json", queued.job_id));
+        let json_data = serde_json::to_string_pretty(queued)
+            .with_context(|| "Failed to serialize queued job")?;
+        fs::write(&queued_file, json_data).await
+            .with_context(|| format!("Failed to write queued job file: {:?}", queued_file))?;
+
+        debug!("Saved queued job {}", queued.job_id);
+        Ok(())
+    }
+
+    /// Reads and removes the saved queued jobs, oldest first. Unreadable
+    /// files are skipped with a warning and left in place.
+    pub async fn take_queued_jobs(&self) -> Result<Vec<QueuedJobCheckpoint>> {
+        let queued_dir = self.queued_dir();
+        let mut entries = match fs::read_dir(&queued_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read queued job directory: {:?}", queued_dir)),
+        };
+
+        let mut queued = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let parsed = fs::read(&path).await
+                .map_err(anyhow::Error::from)
+                .and_then(|data| serde_json::from_slice::<QueuedJobCheckpoint>(&data).map_err(anyhow::Error::from));
+            match parsed {
+                Ok(job) => {
+                    fs::remove_file(&path).await
+                        .with_context(|| format!("Failed to remove queued job file: {:?}", path))?;
+                    queued.push(job);
+                }
+                Err(e) => warn!("Skipping unreadable queued job {:?}: {}", path, e),
+            }
         }
 
+        queued.sort_by_key(|job| job.created_at);
+        Ok(queued)
+    }
+
+    pub async fn load_checkpoint(&self, job_id: &str) -> Result<Option<JobCheckpoint>> {
+        let checkpoint_file = match self.checkpoint_path(job_id) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
         let mut file = fs::File::open(&checkpoint_file).await
             .with_context(|| format!("Failed to open checkpoint file: {:?}", checkpoint_file))?;
 
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).await
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await
             .with_context(|| "Failed to read checkpoint file")?;
 
-        let checkpoint: JobCheckpoint = serde_json::from_str(&contents)
+        // Detect the format from the content so renamed files still load
+        if contents.starts_with(&ZSTD_MAGIC) {
+            contents = zstd::decode_all(contents.as_slice())
+                .with_context(|| format!("Failed to decompress checkpoint file: {:?}", checkpoint_file))?;
+        }
+
+        let checkpoint: JobCheckpoint = serde_json::from_slice(&contents)
             .with_context(|| "Failed to deserialize checkpoint")?;
 
         info!("Loaded checkpoint for job {} (resume count: {})", job_id, checkpoint.resume_count);
@@ -166,32 +407,47 @@ impl CheckpointManager {
     }
 
     pub async fn delete_checkpoint(&self, job_id: &str) -> Result<()> {
-        let checkpoint_file = self.checkpoint_dir.join(format!("{}.json", job_id));
-        
-        if checkpoint_file.exists() {
-            fs::remove_file(&checkpoint_file).await
-                .with_context(|| format!("Failed to delete checkpoint file: {:?}", checkpoint_file))?;
+        let mut deleted = false;
+        for checkpoint_file in [self.plain_path(job_id), self.compressed_path(job_id)] {
+            if checkpoint_file.exists() {
+                fs::remove_file(&checkpoint_file).await
+                    .with_context(|| format!("Failed to delete checkpoint file: {:?}", checkpoint_file))?;
+                deleted = true;
+            }
+        }
+
+        if deleted {
             info!("Deleted checkpoint for job {}", job_id);
         }
 
         Ok(())
     }
 
-    pub async fn list_resumable_jobs(&self) -> Result<Vec<String>> {
-        let mut resumable_jobs = Vec::new();
+    /// IDs of all jobs with a checkpoint file, in either format.
+    async fn checkpoint_job_ids(&self) -> Result<Vec<String>> {
+        let mut job_ids = std::collections::BTreeSet::new();
         let mut entries = fs::read_dir(&self.checkpoint_dir).await
             .with_context(|| format!("Failed to read checkpoint directory: {:?}", self.checkpoint_dir))?;
 
         while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    // Verify the checkpoint is actually resumable
-                    if let Ok(Some(checkpoint)) = self.load_checkpoint(stem).await {
-                        if checkpoint.is_resumable() {
-                            resumable_jobs.push(stem.to_string());
-                        }
-                    }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if let Some(job_id) = name.strip_suffix(".json.zst").or_else(|| name.strip_suffix(".json")) {
+                job_ids.insert(job_id.to_string());
+            }
+        }
+
+        Ok(job_ids.into_iter().collect())
+    }
+
+    pub async fn list_resumable_jobs(&self) -> Result<Vec<String>> {
+        let mut resumable_jobs = Vec::new();
+
+        for job_id in self.checkpoint_job_ids().await? {
+            // Verify the checkpoint is actually resumable
+            if let Ok(Some(checkpoint)) = self.load_checkpoint(&job_id).await {
+                if checkpoint.is_resumable() {
+                    resumable_jobs.push(job_id);
                 }
             }
         }
@@ -203,19 +459,12 @@ impl CheckpointManager {
         let cutoff_time = now_unix_secs().saturating_sub(max_age_days * 24 * 60 * 60);
 
         let mut cleaned_count = 0;
-        let mut entries = fs::read_dir(&self.checkpoint_dir).await
-            .with_context(|| format!("Failed to read checkpoint directory: {:?}", self.checkpoint_dir))?;
 
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    if let Ok(Some(checkpoint)) = self.load_checkpoint(stem).await {
-                        if checkpoint.updated_at < cutoff_time {
-                            self.delete_checkpoint(stem).await?;
-                            cleaned_count += 1;
-                        }
-                    }
+        for job_id in self.checkpoint_job_ids().await? {
+            if let Ok(Some(checkpoint)) = self.load_checkpoint(&job_id).await {
+                if checkpoint.updated_at < cutoff_time {
+                    self.delete_checkpoint(&job_id).await?;
+                    cleaned_count += 1;
                 }
             }
         }
@@ -229,26 +478,19 @@ impl CheckpointManager {
 
     pub async fn get_checkpoint_stats(&self) -> Result<CheckpointStats> {
         let mut stats = CheckpointStats::default();
-        let mut entries = fs::read_dir(&self.checkpoint_dir).await
-            .with_context(|| format!("Failed to read checkpoint directory: {:?}", self.checkpoint_dir))?;
 
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    if let Ok(Some(checkpoint)) = self.load_checkpoint(stem).await {
-                        stats.total_checkpoints += 1;
-                        stats.total_bytes += checkpoint.total_bytes;
-                        stats.completed_bytes += checkpoint.bytes_completed;
-                        
-                        if checkpoint.is_resumable() {
-                            stats.resumable_jobs += 1;
-                        }
-                        
-                        if checkpoint.resume_count > 0 {
-                            stats.resumed_jobs += 1;
-                        }
-                    }
+        for job_id in self.checkpoint_job_ids().await? {
+            if let Ok(Some(checkpoint)) = self.load_checkpoint(&job_id).await {
+                stats.total_checkpoints += 1;
+                stats.total_bytes += checkpoint.total_bytes;
+                stats.completed_bytes += checkpoint.bytes_completed;
+                
+                if checkpoint.is_resumable() {
+                    stats.resumable_jobs += 1;
+                }
+                
+                if checkpoint.resume_count > 0 {
+                    stats.resumed_jobs += 1;
                 }
             }
         }
@@ -305,6 +547,13 @@ pub async fn can_resume_file(checkpoint: &FileCheckpoint) -> Result<bool> {
         return Ok(false);
     }
 
+    // A crash can leave the size extended over unwritten holes, so confirm the
+    // prefix is physically allocated before trusting the byte count
+    if !SparseFileHandler::is_prefix_allocated(dest_path, checkpoint.bytes_copied).await? {
+        warn!("Destination prefix of {:?} contains holes, not resuming", dest_path);
+        return Ok(false);
+    }
+
     // Check if source file still exists and hasn't changed
     let source_metadata = fs::metadata(&checkpoint.source_path).await
         .with_context(|| format!("Source file not found: {:?}", checkpoint.source_path))?;
@@ -374,6 +623,151 @@ mod tests {
         assert!(deleted.is_none());
     }
 
+    #[tokio::test]
+    async fn test_compressed_checkpoint_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let plain = CheckpointManager::new(temp_dir.path().join("plain")).unwrap();
+        let compressed = CheckpointManager::new(temp_dir.path().join("zst")).unwrap().with_compression(true);
+
+        let mut checkpoint = JobCheckpoint::new("big-job".to_string(), "copy".to_string());
+        for i in 0..20_000u64 {
+            checkpoint.add_file(format!("file-{}", i), FileCheckpoint {
+                source_path: PathBuf::from(format!("/data/src/dir-{}/file-{}.dat", i % 100, i)),
+                destination_path: PathBuf::from(format!("/backup/dst/dir-{}/file-{}.dat", i % 100, i)),
+                bytes_copied: i * 512,
+                total_size: i * 1024,
+                last_modified: 1_700_000_000 + i,
+                last_modified_nanos: 0,
+                checksum_partial: None,
+                chunk_size: 1024 * 1024,
+                created_at: 1_700_000_000,
+                updated_at: 1_700_000_000 + i,
+            });
+        }
+
+        plain.save_checkpoint(&checkpoint).await.unwrap();
+        compressed.save_checkpoint(&checkpoint).await.unwrap();
+
+        let plain_path = plain.checkpoint_path("big-job").unwrap();
+        let compressed_path = compressed.checkpoint_path("big-job").unwrap();
+        assert!(compressed_path.to_string_lossy().ends_with(".json.zst"));
+        let plain_size = std::fs::metadata(&plain_path).unwrap().len();
+        let compressed_size = std::fs::metadata(&compressed_path).unwrap().len();
+        assert!(compressed_size * 5 < plain_size, "{} vs {}", compressed_size, plain_size);
+
+        let loaded = compressed.load_checkpoint("big-job").await.unwrap().unwrap();
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&checkpoint).unwrap());
+        assert_eq!(compressed.list_resumable_jobs().await.unwrap(), vec!["big-job".to_string()]);
+
+        // A manager with compression enabled still reads plain checkpoints and
+        // replaces them on the next save
+        let upgraded = CheckpointManager::new(temp_dir.path().join("plain")).unwrap().with_compression(true);
+        let loaded = upgraded.load_checkpoint("big-job").await.unwrap().unwrap();
+        assert_eq!(loaded.files.len(), 20_000);
+        upgraded.save_checkpoint(&loaded).await.unwrap();
+        assert!(!plain_path.exists());
+        assert_eq!(upgraded.checkpoint_path("big-job").unwrap().extension().unwrap(), "zst");
+
+        upgraded.delete_checkpoint("big-job").await.unwrap();
+        assert!(upgraded.checkpoint_path("big-job").is_none());
+    }
+
+    #[test]
+    fn test_source_completion_and_remap() {
+        let mut checkpoint = JobCheckpoint::new("multi".to_string(), "copy".to_string());
+        let sources = [PathBuf::from("/mnt/a"), PathBuf::from("/mnt/b"), PathBuf::from("/mnt/c")];
+        checkpoint.set_sources(&sources, Path::new("/backup"));
+        checkpoint.complete_source(1);
+        assert_eq!(checkpoint.pending_sources(), vec![PathBuf::from("/mnt/a"), PathBuf::from("/mnt/c")]);
+
+        checkpoint.remap_source_root(Path::new("/mnt"), Path::new("/media"));
+        assert_eq!(checkpoint.pending_sources(), vec![PathBuf::from("/media/a"), PathBuf::from("/media/c")]);
+
+        // Checkpoints written before sources were tracked still load
+        let mut legacy = serde_json::to_value(&checkpoint).unwrap();
+        legacy.as_object_mut().unwrap().retain(|key, _| key != "sources" && key != "destination");
+        let legacy: JobCheckpoint = serde_json::from_value(legacy).unwrap();
+        assert!(legacy.sources.is_empty());
+    }
+
+    fn resumable_pair(temp_dir: &TempDir) -> FileCheckpoint {
+        let source = temp_dir.path().join("source.bin");
+        let destination = temp_dir.path().join("dest.bin");
+        std::fs::write(&source, vec![0xAAu8; 256 * 1024]).unwrap();
+        std::fs::write(&destination, vec![0xAAu8; 128 * 1024]).unwrap();
+
+        let source_metadata = std::fs::metadata(&source).unwrap();
+        let last_modified = source_metadata.modified().unwrap()
+            .duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        FileCheckpoint {
+            source_path: source,
+            destination_path: destination,
+            bytes_copied: 128 * 1024,
+            total_size: source_metadata.len(),
+            last_modified,
+            last_modified_nanos: 0,
+            checksum_partial: None,
+            chunk_size: 4096,
+            created_at: last_modified,
+            updated_at: last_modified,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_can_resume_allocated_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let checkpoint = resumable_pair(&temp_dir);
+        assert!(can_resume_file(&checkpoint).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_punched_hole_prefix_not_resumed() {
+        use std::os::unix::io::AsRawFd;
+
+        let temp_dir = TempDir::new().unwrap();
+        let checkpoint = resumable_pair(&temp_dir);
+
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&checkpoint.destination_path)
+            .unwrap();
+        let result = unsafe {
+            libc::fallocate(
+                file.as_raw_fd(),
+                libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                32 * 1024,
+                64 * 1024,
+            )
+        };
+        if result != 0 {
+            // Filesystem cannot punch holes; nothing to test
+            return;
+        }
+        file.sync_all().unwrap();
+        drop(file);
+
+        assert_eq!(std::fs::metadata(&checkpoint.destination_path).unwrap().len(), 128 * 1024);
+        assert!(!can_resume_file(&checkpoint).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_remap_source_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut file_checkpoint = resumable_pair(&temp_dir);
+        file_checkpoint.source_path = PathBuf::from("/mnt/old/data/source.bin");
+
+        let mut checkpoint = JobCheckpoint::new("remap-job".to_string(), "copy".to_string());
+        checkpoint.add_file("a".to_string(), file_checkpoint.clone());
+        file_checkpoint.source_path = PathBuf::from("/srv/other/source.bin");
+        checkpoint.add_file("b".to_string(), file_checkpoint);
+
+        let remapped = checkpoint.remap_source_root(Path::new("/mnt/old"), Path::new("/media/new"));
+        assert_eq!(remapped, 1);
+        assert_eq!(checkpoint.files["a"].source_path, PathBuf::from("/media/new/data/source.bin"));
+        assert_eq!(checkpoint.files["b"].source_path, PathBuf::from("/srv/other/source.bin"));
+    }
+
     #[test]
     fn test_file_id_creation() {
         let source = Path::new("/tmp/source.txt");
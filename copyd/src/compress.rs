@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use crate::verify::{FileVerifier, StreamHasher, VerifyMode};
+
+/// zstd level of compressed copies: fast enough to keep up with most
+/// links between filesystems while still shrinking text and logs well.
+pub const ZSTD_LEVEL: i32 = 3;
+
+/// Longest zstd frame header, which holds the original size
+const ZSTD_FRAME_HEADER_MAX: usize = 18;
+
+/// Codec of compressed copies. Only zstd for now; the extension each codec
+/// appends tells which one a copy was written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    Zstd,
+}
+
+impl Codec {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Codec::Zstd => "zst",
+        }
+    }
+
+    /// Where the compressed copy of `destination` is written: the same
+    /// path with the codec's extension appended.
+    pub fn compressed_path(self, destination: &Path) -> PathBuf {
+        let mut path = destination.as_os_str().to_owned();
+        path.push(".");
+        path.push(self.extension());
+        PathBuf::from(path)
+    }
+
+    /// Compressor writing to `output`, recording `size`, the length of
+    /// the data to come, for verification.
+    pub fn encoder<W: std::io::Write>(self, output: W, size: u64) -> Result<zstd::stream::write::Encoder<'static, W>> {
+        match self {
+            Codec::Zstd => {
+                let mut encoder = zstd::stream::write::Encoder::new(output, ZSTD_LEVEL)?;
+                encoder.include_contentsize(true)?;
+                encoder.set_pledged_src_size(Some(size))?;
+                Ok(encoder)
+            }
+        }
+    }
+
+    /// Decompressed contents of the file at `path`, as a reader.
+    pub fn decoder(self, path: &Path) -> Result<impl Read> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open compressed file: {:?}", path))?;
+        match self {
+            Codec::Zstd => Ok(zstd::stream::read::Decoder::new(file)?),
+        }
+    }
+
+    /// Original size recorded when the file at `path` was compressed.
+    pub fn recorded_size(self, path: &Path) -> Result<Option<u64>> {
+        let mut header = Vec::with_capacity(ZSTD_FRAME_HEADER_MAX);
+        std::fs::File::open(path)
+            .with_context(|| format!("Failed to open compressed file: {:?}", path))?
+            .take(ZSTD_FRAME_HEADER_MAX as u64)
+            .read_to_end(&mut header)?;
+        match self {
+            Codec::Zstd => zstd::zstd_safe::get_frame_content_size(&header)
+                .map_err(|_| anyhow::anyhow!("{:?} is not a zstd file", path)),
+        }
+    }
+
+    /// Whether the compressed file at `compressed` decompresses to the
+    /// content of `source`, as `mode` compares them. The size recorded in
+    /// its header is checked first, which is all a size check needs.
+    pub async fn verify_decompressed(self, source: &Path, compressed: &Path, mode: VerifyMode) -> Result<bool> {
+        let source_size = tokio::fs::metadata(source).await?.len();
+        if self.recorded_size(compressed)? != Some(source_size) {
+            return Ok(false);
+        }
+        if let VerifyMode::None | VerifyMode::Size = mode {
+            return Ok(true);
+        }
+
+        let expected = FileVerifier::calculate_checksum(source, mode).await?;
+        let compressed = compressed.to_path_buf();
+        let actual = tokio::task::spawn_blocking(move || -> Result<String> {
+            let mut decoder = self.decoder(&compressed)?;
+            let mut hasher = StreamHasher::new(mode);
+            let mut buffer = vec![0u8; 1024 * 1024];
+            loop {
+                let read = decoder.read(&mut buffer)
+                    .with_context(|| format!("Failed to decompress {:?}", compressed))?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+            }
+            Ok(hasher.finish())
+        }).await??;
+        Ok(actual == expected)
+    }
+}
@@ -1,11 +1,28 @@
-use anyhow::{Result};
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
 use tracing::warn;
+use copyd_protocol::CopyEngine;
+use crate::space::SpaceThreshold;
+/// Settings a running daemon applies without a restart.
+pub use copyd_protocol::HOT_RELOADABLE_KEYS;
+
+/// Schema version stamped into config files by `copyctl config migrate`.
+/// Version 2 added `max_copy_parallelism`.
+pub const CONFIG_VERSION: u32 = 2;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Schema the file was written for; files from before versioning are 0
+    #[serde(default)]
+    pub version: u32,
     pub socket_path: PathBuf,
+    /// Permission bits for the socket, e.g. `0o660`; unset keeps the umask default
+    #[serde(default)]
+    pub socket_mode: Option<u32>,
+    /// Group, by name or number, that owns the socket
+    #[serde(default)]
+    pub socket_group: Option<String>,
     pub max_concurrent_jobs: usize,
     pub max_job_queue_size: usize,
     pub default_block_size: u64,
@@ -13,19 +30,97 @@ pub struct Config {
     pub metrics_bind_addr: Option<String>,
     pub log_level: String,
     pub job_history_days: u32,
+    /// Seconds between checkpoint saves while a job copies
     pub checkpoint_interval_secs: u64,
+    /// Bytes copied between checkpoint saves; zero saves on time alone
+    #[serde(default = "default_checkpoint_interval_bytes")]
+    pub checkpoint_interval_bytes: u64,
     pub temp_dir: PathBuf,
     pub enable_compression: bool,
     pub enable_encryption: bool,
     pub io_uring_entries: u32,
     pub watchdog_enabled: bool,
     pub checkpoint_dir: PathBuf,
+    /// Finished jobs, kept for `job_history_days` and searched by `copyctl history`
+    #[serde(default = "default_history_path")]
+    pub history_path: PathBuf,
+    /// Per-file results of each job, shown by `copyctl results`; unset keeps
+    /// them in a `results` directory beside the history
+    #[serde(default)]
+    pub results_dir: Option<PathBuf>,
+    /// Engine substituted for requests that ask for `Auto`
+    #[serde(default = "default_engine")]
+    pub default_engine: CopyEngine,
+    /// Engines requests may use; empty allows every engine
+    #[serde(default)]
+    pub allowed_engines: Vec<CopyEngine>,
+    /// Bytes of copy buffers that may be allocated at once across all jobs;
+    /// zero disables the limit
+    #[serde(default)]
+    pub max_inflight_bytes: u64,
+    /// Allocate copy buffers on the NUMA node of the copying CPU; ignored on
+    /// single-node systems
+    #[serde(default)]
+    pub numa_aware_buffers: bool,
+    /// Write job checkpoints as zstd-compressed `.json.zst` files
+    #[serde(default)]
+    pub compress_checkpoints: bool,
+    /// Blocks the read/write engine reads ahead of the writer in a background
+    /// task; zero reads and writes in turn
+    #[serde(default)]
+    pub read_ahead_blocks: usize,
+    /// Run post-copy verification reads at idle I/O priority so they yield
+    /// to running copies
+    #[serde(default)]
+    pub idle_io_verification: bool,
+    /// Alert when a running job's destination device has less free space
+    /// than this, e.g. "10%" or "50G"; unset disables the check
+    #[serde(default)]
+    pub low_space_threshold: Option<SpaceThreshold>,
+    /// Milliseconds between two progress events of a job; zero sends every
+    /// update
+    #[serde(default = "default_collapse_progress_ms")]
+    pub collapse_progress_ms: u64,
+    /// Work of verifying a byte relative to copying it, weighting the
+    /// verification phase in jobs' overall progress
+    #[serde(default = "default_verify_cost")]
+    pub verify_cost: f64,
+    /// Most files a single job may ask to copy at once
+    #[serde(default = "default_max_copy_parallelism")]
+    pub max_copy_parallelism: usize,
+}
+
+fn default_engine() -> CopyEngine {
+    CopyEngine::Auto
+}
+
+fn default_checkpoint_interval_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_collapse_progress_ms() -> u64 {
+    100
+}
+
+fn default_max_copy_parallelism() -> usize {
+    crate::job::DEFAULT_MAX_COPY_PARALLELISM
+}
+
+fn default_verify_cost() -> f64 {
+    crate::job::DEFAULT_VERIFY_COST
+}
+
+fn default_history_path() -> PathBuf {
+    PathBuf::from("/var/lib/copyd/history.jsonl")
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             socket_path: PathBuf::from("/run/copyd/copyd.sock"),
+            socket_mode: None,
+            socket_group: None,
             max_concurrent_jobs: num_cpus::get(),
             max_job_queue_size: 1000,
             default_block_size: 1024 * 1024, // 1MB
@@ -34,40 +129,209 @@ impl Default for Config {
             log_level: "info".to_string(),
             job_history_days: 30,
             checkpoint_interval_secs: 5,
+            checkpoint_interval_bytes: default_checkpoint_interval_bytes(),
             temp_dir: PathBuf::from("/tmp/copyd"),
             enable_compression: false,
             enable_encryption: false,
             io_uring_entries: 256,
             watchdog_enabled: true,
             checkpoint_dir: PathBuf::from("/var/lib/copyd/checkpoints"),
+            history_path: default_history_path(),
+            results_dir: None,
+            default_engine: default_engine(),
+            allowed_engines: Vec::new(),
+            max_inflight_bytes: 0,
+            numa_aware_buffers: false,
+            compress_checkpoints: false,
+            read_ahead_blocks: 0,
+            idle_io_verification: false,
+            low_space_threshold: None,
+            collapse_progress_ms: default_collapse_progress_ms(),
+            verify_cost: default_verify_cost(),
+            max_copy_parallelism: default_max_copy_parallelism(),
         }
     }
 }
 
 impl Config {
+    pub fn config_path() -> PathBuf {
+        std::env::var_os("COPYD_CONFIG_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/etc/copyd/config.toml"))
+    }
+
     pub async fn load() -> Result<Self> {
-        let config_path = std::env::var("COPYD_CONFIG_PATH")
-            .unwrap_or_else(|_| "/etc/copyd/config.toml".to_string());
+        let config_path = Self::config_path();
         
         match tokio::fs::read_to_string(&config_path).await {
             Ok(content) => {
                 let config: Config = toml::from_str(&content)?;
+                if config.version < CONFIG_VERSION {
+                    warn!("Configuration file {:?} is version {}; `copyctl config migrate` brings it to version {}",
+                        config_path, config.version, CONFIG_VERSION);
+                }
                 Ok(config)
             }
             Err(_) => {
                 // If config file doesn't exist or fails to load, use defaults
-                warn!("Configuration file not found at {}. Using default settings.", config_path);
+                warn!("Configuration file not found at {:?}. Using default settings.", config_path);
                 Ok(Config::default())
             }
         }
     }
 
+    /// Rejects settings the daemon could not run with.
+    pub fn validate(&self) -> Result<()> {
+        if self.max_concurrent_jobs == 0 {
+            anyhow::bail!("max_concurrent_jobs must be at least 1");
+        }
+        if self.max_job_queue_size == 0 {
+            anyhow::bail!("max_job_queue_size must be at least 1");
+        }
+        if self.socket_mode.is_some_and(|mode| mode > 0o777) {
+            anyhow::bail!("socket_mode must be a permission mode no larger than 0o777");
+        }
+        if self.checkpoint_interval_secs == 0 {
+            anyhow::bail!("checkpoint_interval_secs must be at least 1");
+        }
+        if self.max_copy_parallelism == 0 {
+            anyhow::bail!("max_copy_parallelism must be at least 1");
+        }
+        if !self.verify_cost.is_finite() || self.verify_cost < 0.0 {
+            anyhow::bail!("verify_cost must be a number no smaller than 0");
+        }
+        if !self.allowed_engines.is_empty()
+            && self.default_engine != CopyEngine::Auto
+            && !self.allowed_engines.contains(&self.default_engine)
+        {
+            anyhow::bail!("default_engine {} is not in allowed_engines", self.default_engine);
+        }
+        Ok(())
+    }
+
+    /// Sets `key` from its TOML representation, e.g. `4`, `true` or
+    /// `["ReadWrite"]`; bare words are taken as strings.
+    pub fn set_value(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut table = toml::Value::try_from(&*self)?;
+        let fields = table.as_table_mut().expect("config serializes to a table");
+
+        let parsed = toml::from_str::<toml::Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut wrapper| wrapper.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+        fields.insert(key.to_string(), parsed);
+
+        let updated: Config = table.try_into()
+            .map_err(|e| anyhow::anyhow!("Invalid value for '{}': {}", key, e))?;
+        // Unknown keys are dropped when deserializing, so they don't round-trip
+        if toml::Value::try_from(&updated)?.get(key).is_none() {
+            anyhow::bail!("Unknown configuration key '{}'", key);
+        }
+
+        *self = updated;
+        Ok(())
+    }
+
+    /// Names of the settings that differ between `self` and `other`.
+    pub fn changed_keys(&self, other: &Config) -> Result<Vec<String>> {
+        let ours = toml::Value::try_from(self)?;
+        let theirs = toml::Value::try_from(other)?;
+        let (Some(ours), Some(theirs)) = (ours.as_table(), theirs.as_table()) else {
+            return Ok(Vec::new());
+        };
+
+        let mut keys: Vec<String> = ours.keys().chain(theirs.keys())
+            .filter(|key| ours.get(*key) != theirs.get(*key))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// Brings the config file at `path` up to [`CONFIG_VERSION`]: settings
+    /// it lacks are added with their defaults and the version is stamped.
+    /// Comments and sections copyd doesn't read are kept; a file that's
+    /// already current is left untouched.
+    pub async fn migrate_file(path: &Path) -> Result<ConfigMigration> {
+        let content = tokio::fs::read_to_string(path).await
+            .with_context(|| format!("Failed to read configuration file {:?}", path))?;
+        let mut document: toml_edit::DocumentMut = content.parse()
+            .with_context(|| format!("Failed to parse configuration file {:?}", path))?;
+
+        let from_version = match document.get("version") {
+            Some(version) => version.as_integer()
+                .and_then(|version| u32::try_from(version).ok())
+                .context("version must be a whole number")?,
+            None => 0,
+        };
+        if from_version > CONFIG_VERSION {
+            anyhow::bail!("{:?} is version {}, newer than the supported version {}", path, from_version, CONFIG_VERSION);
+        }
+
+        let defaults = toml::Value::try_from(Config::default())?;
+        let defaults = defaults.as_table().expect("config serializes to a table");
+        let mut added = Vec::new();
+        for (key, value) in defaults {
+            if key == "version" || document.contains_key(key) {
+                continue;
+            }
+            let value = value.to_string();
+            document[key.as_str()] = toml_edit::value(value.parse::<toml_edit::Value>()
+                .with_context(|| format!("Failed to write the default of {}", key))?);
+            added.push((key.clone(), value));
+        }
+
+        let migration = ConfigMigration { from_version, to_version: CONFIG_VERSION, added };
+        if !migration.changed() {
+            return Ok(migration);
+        }
+        document["version"] = toml_edit::value(i64::from(CONFIG_VERSION));
+
+        let migrated = document.to_string();
+        let config: Config = toml::from_str(&migrated)
+            .with_context(|| format!("Migrated configuration {:?} doesn't parse", path))?;
+        config.validate()?;
+
+        let mut staging = path.as_os_str().to_owned();
+        staging.push(".migrating");
+        tokio::fs::write(&staging, migrated).await
+            .with_context(|| format!("Failed to write {:?}", staging))?;
+        let permissions = tokio::fs::metadata(path).await?.permissions();
+        tokio::fs::set_permissions(&staging, permissions).await?;
+        tokio::fs::rename(&staging, path).await
+            .with_context(|| format!("Failed to replace configuration file {:?}", path))?;
+        Ok(migration)
+    }
+
+    /// Where per-file job results are kept.
+    pub fn results_dir(&self) -> PathBuf {
+        self.results_dir.clone().unwrap_or_else(|| self.history_path.with_file_name("results"))
+    }
+
     pub async fn ensure_directories(&self) -> Result<()> {
         if let Some(parent) = self.socket_path.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
         tokio::fs::create_dir_all(&self.temp_dir).await?;
         tokio::fs::create_dir_all(&self.checkpoint_dir).await?;
+        if let Some(parent) = self.history_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
         Ok(())
     }
-} 
\ No newline at end of file
+} 
+/// What [`Config::migrate_file`] changed.
+#[derive(Debug)]
+pub struct ConfigMigration {
+    pub from_version: u32,
+    pub to_version: u32,
+    /// Settings added with their default, as TOML
+    pub added: Vec<(String, String)>,
+}
+
+impl ConfigMigration {
+    pub fn changed(&self) -> bool {
+        self.from_version != self.to_version || !self.added.is_empty()
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::{Result, Context};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
@@ -12,12 +13,65 @@ use nix::sys::sendfile::sendfile;
 #[cfg(unix)]
 use nix::unistd;
 use std::time::SystemTime;
-use crate::verify::{FileVerifier};
+use crate::verify::{FileVerifier, StreamHasher};
 use copyd_protocol::VerifyMode;
 use crate::sparse::SparseFileHandler;
-use copyd_protocol::{CopyEngine, ExistsAction};
+use crate::inflight::{allocate_buffer, InflightBudget};
+use crate::numa::CopyBuffer;
+use crate::backend::{BackendRegistry, CopyBackend};
+use crate::profiler::PerformanceProfiler;
+use crate::source_watch::{SourceWatch, MAX_SOURCE_CHANGE_RESTARTS};
+use crate::privileges::{MetadataPrivileges, SkippedMetadata};
+use crate::monitor::EnhancedMonitor;
+use crate::dedup::{DedupIndex, DedupLink, DedupStats, DEDUP_MIN_SIZE};
+use crate::compress::Codec;
+use crate::encrypt::{EncryptingWriter, EncryptionKey};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use copyd_protocol::{AtimeMode, CopyEngine, ExistsAction, SourceChangeAction, VerifyResult, XattrNamespace};
 
-#[derive(Debug, Clone)]
+tokio::task_local! {
+    /// Engine of the file copied within [`FileCopyEngine::track_engine`]
+    static FILE_ENGINE: Cell<Option<CopyEngine>>;
+    /// Bytes written of the file copied within [`FileCopyEngine::count_written`]
+    static FILE_WRITTEN: WrittenBytes;
+}
+
+/// Bytes written so far of a file being copied, also counted in a total
+/// shared with the other copies of a job, so its progress moves within
+/// large files. What is still counted when the copy is dropped unfinished
+/// is taken back off the total.
+struct WrittenBytes {
+    file: Cell<u64>,
+    total: Arc<AtomicU64>,
+}
+
+impl Drop for WrittenBytes {
+    fn drop(&mut self) {
+        self.total.fetch_sub(self.file.get(), Ordering::SeqCst);
+    }
+}
+
+/// Counts `bytes` more written of the file being copied.
+pub(crate) fn report_written(bytes: u64) {
+    let _ = FILE_WRITTEN.try_with(|written| {
+        written.file.set(written.file.get() + bytes);
+        written.total.fetch_add(bytes, Ordering::SeqCst);
+    });
+}
+
+/// Counts the file being copied from zero again, as an engine starting
+/// over writes it again from the beginning.
+fn restart_written() {
+    let _ = FILE_WRITTEN.try_with(|written| {
+        written.total.fetch_sub(written.file.replace(0), Ordering::SeqCst);
+    });
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct CopyOptions {
     pub preserve_metadata: bool,
     pub preserve_links: bool,
@@ -29,15 +83,348 @@ pub struct CopyOptions {
     pub dry_run: bool,
     pub compress: bool,
     pub encrypt: bool,
+    pub atime: AtimeMode,
+    /// Extended attribute namespaces preserved besides `user.*`
+    pub xattr_namespaces: Vec<XattrNamespace>,
+    /// Read FIFO sources to EOF into regular files
+    pub stream_fifo: bool,
+    /// Permission bits set on the destination file
+    pub chmod: Option<u32>,
+    /// What to do when the source is written to while it is copied
+    pub on_source_change: SourceChangeAction,
+    /// Times a file that fails verification is copied again before it fails
+    pub verify_retries: u32,
+}
+
+/// Most re-copies of a file that fails verification a job may ask for.
+pub const MAX_VERIFY_RETRIES: u32 = 10;
+
+/// Whether `path` is a regular file with more than one name, which a copy
+/// must not rewrite in place.
+fn shares_inode(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.nlink() > 1)
+}
+
+const AUTO_BLOCK_MIN: u64 = 64 * 1024;
+const AUTO_BLOCK_MAX: u64 = 8 * 1024 * 1024;
+
+/// Picks a block size for a file of `file_size` bytes on a device whose
+/// preferred I/O size is `device_io_size`: small files get a buffer about
+/// their own size, larger ones about 1/64th of the file within 64KiB..8MiB.
+/// The result is always a multiple of the device I/O size.
+pub fn choose_block_size(file_size: u64, device_io_size: u64) -> u64 {
+    let io_size = if device_io_size == 0 { 4096 } else { device_io_size.clamp(512, AUTO_BLOCK_MAX) };
+
+    let target = if file_size <= AUTO_BLOCK_MIN {
+        file_size.max(1).next_power_of_two()
+    } else {
+        (file_size / 64).next_power_of_two().clamp(AUTO_BLOCK_MIN, AUTO_BLOCK_MAX)
+    };
+
+    target.max(io_size).div_ceil(io_size) * io_size
 }
 
 pub struct FileCopyEngine {
     engine_type: CopyEngine,
+    inflight_budget: InflightBudget,
+    numa_buffers: bool,
+    read_ahead_blocks: usize,
+    idle_io_verification: bool,
+    backends: BackendRegistry,
+    failures: parking_lot::Mutex<Vec<EngineFailure>>,
+    profiler: Option<PerformanceProfiler>,
+    /// Whether each destination device, by `st_dev`, supports reflinks
+    reflink_devices: parking_lot::Mutex<HashMap<u64, bool>>,
+    /// Destination devices already reported as unable to take birth times
+    btime_unsupported_devices: parking_lot::Mutex<HashSet<u64>>,
+    /// Engine that wrote the data of the last file copied
+    last_engine: parking_lot::Mutex<Option<CopyEngine>>,
+    /// Skip metadata the daemon lacks the privileges to set, counting it in
+    /// `skipped_metadata` rather than logging each failure
+    best_effort_metadata: bool,
+    metadata_privileges: MetadataPrivileges,
+    skipped_metadata: parking_lot::Mutex<SkippedMetadata>,
+    /// Content already on the destination device, linked to instead of
+    /// copied again; `deduplicated` counts the files that were
+    dedup: Option<Arc<DedupIndex>>,
+    deduplicated: parking_lot::Mutex<DedupStats>,
+    /// Counts re-copies after failed verifications in `retry_operations`
+    monitor: Option<Arc<EnhancedMonitor>>,
+    verify_recopies: AtomicU64,
+    /// Key of copies made with `encrypt`
+    encryption_key: Option<EncryptionKey>,
+}
+
+/// An engine that failed partway through a file and is skipped for every
+/// later file copied by the same [`FileCopyEngine`].
+#[derive(Debug, Clone)]
+pub struct EngineFailure {
+    pub engine: CopyEngine,
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// How one destination of a copy fared: the bytes written to it or why it
+/// failed, and what verification found.
+#[derive(Debug)]
+pub struct CopyOutcome {
+    pub destination: PathBuf,
+    pub result: Result<u64>,
+    pub verify_result: VerifyResult,
+}
+
+/// Engines tried, in order, after the selected one fails. Read/write only
+/// needs plain read and write calls, so it is always the last resort.
+const FALLBACK_ENGINES: [CopyEngine; 2] = [CopyEngine::CopyFileRange, CopyEngine::Sendfile];
+
+/// OS errors any engine would hit for the same files; everything else is
+/// put down to the copy method and retried with another one.
+const FILE_ERRNOS: &[i32] = &[
+    libc::ENOENT, libc::EACCES, libc::EPERM, libc::ENOSPC, libc::EDQUOT, libc::EROFS,
+    libc::EISDIR, libc::ENOTDIR, libc::ENAMETOOLONG, libc::EFBIG, libc::ELOOP,
+];
+
+fn is_engine_failure(error: &anyhow::Error) -> bool {
+    error.chain()
+        .find_map(|cause| {
+            cause.downcast_ref::<std::io::Error>().and_then(|e| e.raw_os_error())
+                .or_else(|| cause.downcast_ref::<nix::errno::Errno>().map(|&errno| errno as i32))
+        })
+        .is_some_and(|errno| !FILE_ERRNOS.contains(&errno))
+}
+
+/// Supplies filled blocks to the read/write engine. With read-ahead a
+/// background task keeps reading into idle buffers while the caller writes,
+/// so read and write latency overlap instead of adding up.
+enum BlockReader {
+    Sequential {
+        file: tokio::fs::File,
+        idle: Vec<CopyBuffer>,
+    },
+    ReadAhead {
+        filled: mpsc::Receiver<std::io::Result<(CopyBuffer, usize)>>,
+        idle: mpsc::Sender<CopyBuffer>,
+        task: tokio::task::JoinHandle<()>,
+    },
+}
+
+impl BlockReader {
+    fn new(file: tokio::fs::File, buffers: Vec<CopyBuffer>, read_ahead: bool) -> Self {
+        if !read_ahead || buffers.len() < 2 {
+            return BlockReader::Sequential { file, idle: buffers };
+        }
+
+        let (filled_tx, filled) = mpsc::channel(buffers.len());
+        let (idle, mut idle_rx) = mpsc::channel(buffers.len());
+        for buffer in buffers {
+            idle.try_send(buffer).expect("idle channel holds every buffer");
+        }
+
+        let task = tokio::spawn(async move {
+            let mut file = file;
+            while let Some(mut buffer) = idle_rx.recv().await {
+                let result = tokio::io::AsyncReadExt::read(&mut file, &mut buffer).await;
+                let done = !matches!(result, Ok(n) if n > 0);
+                if filled_tx.send(result.map(|n| (buffer, n))).await.is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        BlockReader::ReadAhead { filled, idle, task }
+    }
+
+    /// Next block and its length, or `None` at end of file.
+    async fn next(&mut self) -> std::io::Result<Option<(CopyBuffer, usize)>> {
+        match self {
+            BlockReader::Sequential { file, idle } => {
+                let mut buffer = idle.pop().expect("a buffer is idle between blocks");
+                let bytes_read = tokio::io::AsyncReadExt::read(file, &mut buffer).await?;
+                if bytes_read == 0 {
+                    idle.push(buffer);
+                    return Ok(None);
+                }
+                Ok(Some((buffer, bytes_read)))
+            }
+            BlockReader::ReadAhead { filled, .. } => match filled.recv().await {
+                Some(Ok((_, 0))) | None => Ok(None),
+                Some(Ok(block)) => Ok(Some(block)),
+                Some(Err(e)) => Err(e),
+            },
+        }
+    }
+
+    /// Returns a written buffer so it can be filled again.
+    fn recycle(&mut self, buffer: CopyBuffer) {
+        match self {
+            BlockReader::Sequential { idle, .. } => idle.insert(0, buffer),
+            // The channel has room for every buffer, and a closed channel
+            // means the reader already hit end of file
+            BlockReader::ReadAhead { idle, .. } => {
+                let _ = idle.try_send(buffer);
+            }
+        }
+    }
+}
+
+impl Drop for BlockReader {
+    fn drop(&mut self) {
+        if let BlockReader::ReadAhead { task, .. } = self {
+            task.abort();
+        }
+    }
 }
 
 impl FileCopyEngine {
     pub fn new(engine_type: CopyEngine) -> Self {
-        Self { engine_type }
+        Self {
+            engine_type,
+            inflight_budget: InflightBudget::unlimited(),
+            numa_buffers: false,
+            read_ahead_blocks: 0,
+            idle_io_verification: false,
+            backends: BackendRegistry::default(),
+            failures: parking_lot::Mutex::new(Vec::new()),
+            profiler: None,
+            reflink_devices: parking_lot::Mutex::new(HashMap::new()),
+            btime_unsupported_devices: parking_lot::Mutex::new(HashSet::new()),
+            last_engine: parking_lot::Mutex::new(None),
+            best_effort_metadata: !nix::unistd::geteuid().is_root(),
+            metadata_privileges: MetadataPrivileges::detect(),
+            skipped_metadata: parking_lot::Mutex::new(SkippedMetadata::default()),
+            dedup: None,
+            deduplicated: parking_lot::Mutex::new(DedupStats::default()),
+            monitor: None,
+            verify_recopies: AtomicU64::new(0),
+            encryption_key: None,
+        }
+    }
+
+    /// Files linked to identical content instead of copied so far.
+    pub fn deduplicated(&self) -> DedupStats {
+        *self.deduplicated.lock()
+    }
+
+    /// Copies made again so far because the previous one failed verification.
+    pub fn verify_recopies(&self) -> u64 {
+        self.verify_recopies.load(Ordering::Relaxed)
+    }
+
+    /// Metadata left out so far for lack of privileges.
+    pub fn skipped_metadata(&self) -> SkippedMetadata {
+        *self.skipped_metadata.lock()
+    }
+
+    /// Engines that have failed so far, oldest first.
+    pub fn engine_failures(&self) -> Vec<EngineFailure> {
+        self.failures.lock().clone()
+    }
+
+    /// Engine that wrote the data of the file copied last, after automatic
+    /// selection and any fallback; `None` when no data was written, as in a
+    /// dry run. Within [`Self::track_engine`], that of the file copied there.
+    pub fn last_engine(&self) -> Option<CopyEngine> {
+        FILE_ENGINE.try_with(Cell::get).unwrap_or_else(|_| *self.last_engine.lock())
+    }
+
+    pub(crate) fn note_engine(&self, engine: CopyEngine) {
+        self.update_last_engine(|_| Some(engine));
+    }
+
+    fn update_last_engine(&self, update: impl Fn(Option<CopyEngine>) -> Option<CopyEngine>) {
+        let mut last_engine = self.last_engine.lock();
+        *last_engine = update(*last_engine);
+        let _ = FILE_ENGINE.try_with(|engine| engine.set(update(engine.get())));
+    }
+
+    /// Runs `copy` with a [`Self::last_engine`] of its own, so copies that
+    /// run side by side on one engine each see the method that wrote their
+    /// own file.
+    pub async fn track_engine<F: Future>(copy: F) -> F::Output {
+        FILE_ENGINE.scope(Cell::new(None), copy).await
+    }
+
+    /// Runs `copy`, adding the bytes it writes to `total` as they are
+    /// written. Returns them along with its output, still counted in
+    /// `total`, for the caller to take off once it counts them otherwise.
+    pub async fn count_written<F: Future>(total: Arc<AtomicU64>, copy: F) -> (F::Output, u64) {
+        let written = WrittenBytes { file: Cell::new(0), total };
+        FILE_WRITTEN.scope(written, async {
+            let output = copy.await;
+            (output, FILE_WRITTEN.with(|written| written.file.replace(0)))
+        }).await
+    }
+
+    /// Uses `backend` whenever `engine` is selected, e.g. to plug in a new
+    /// copy method or a test double.
+    pub fn with_backend(mut self, engine: CopyEngine, backend: Arc<dyn CopyBackend>) -> Self {
+        self.backends.register(engine, backend);
+        self
+    }
+
+    /// Preserves only the metadata the daemon has the privileges to set, as
+    /// it always does when not running as root.
+    pub fn with_best_effort_metadata(mut self, enabled: bool) -> Self {
+        self.best_effort_metadata |= enabled;
+        self
+    }
+
+    /// Before copying a file to the device `index` covers, looks its content
+    /// up there and reflinks, or failing that hard-links, a file with the
+    /// same content instead; files copied are added to the index.
+    pub fn with_dedup(mut self, index: Arc<DedupIndex>) -> Self {
+        self.dedup = Some(index);
+        self
+    }
+
+    /// Encrypts the copies of jobs that ask for it with `key`.
+    pub fn with_encryption_key(mut self, key: EncryptionKey) -> Self {
+        self.encryption_key = Some(key);
+        self
+    }
+
+    /// Counts the re-copies of files that failed verification in the
+    /// monitor's retry operations.
+    pub fn with_monitor(mut self, monitor: Arc<EnhancedMonitor>) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    /// Takes these privileges as the daemon's instead of detecting them.
+    pub fn with_metadata_privileges(mut self, privileges: MetadataPrivileges) -> Self {
+        self.metadata_privileges = privileges;
+        self
+    }
+
+    /// Allocate copy buffers on the NUMA node of the CPU running the copy.
+    pub fn with_numa_buffers(mut self, enabled: bool) -> Self {
+        self.numa_buffers = enabled;
+        self
+    }
+
+    /// Let the read/write engine read up to `blocks` blocks ahead in a
+    /// background task while the current block is written; 0 disables it.
+    pub fn with_read_ahead(mut self, blocks: usize) -> Self {
+        self.read_ahead_blocks = blocks;
+        self
+    }
+
+    /// Read copies back for verification at idle I/O priority.
+    pub fn with_idle_io_verification(mut self, enabled: bool) -> Self {
+        self.idle_io_verification = enabled;
+        self
+    }
+
+    /// Records the throughput and outcome of every engine attempt in `profiler`.
+    pub fn with_profiler(mut self, profiler: PerformanceProfiler) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Shares a daemon-wide budget for the buffers this engine allocates.
+    pub fn with_inflight_budget(mut self, budget: InflightBudget) -> Self {
+        self.inflight_budget = budget;
+        self
     }
 
     pub async fn copy_file(
@@ -45,13 +432,220 @@ impl FileCopyEngine {
         source: &Path,
         destination: &Path,
         options: &CopyOptions,
+    ) -> Result<u64> {
+        let written = Self::written_path(destination, options);
+        if options.dry_run || !shares_inode(&written) {
+            return self.copy_file_in_place(source, destination, options).await;
+        }
+
+        // Rewriting a file in place would change every other name of its
+        // inode too, such as an earlier job's copy that deduplication
+        // hard-linked to it, so it is copied beside and renamed over instead
+        debug!("{:?} shares its inode, replacing it rather than rewriting it", written);
+        let name = destination.file_name().unwrap_or_default().to_string_lossy();
+        let staging = destination.with_file_name(format!(".{}.copyd-unshare", name));
+        let staged = Self::written_path(&staging, options);
+        let copied = match self.copy_file_in_place(source, &staging, options).await {
+            Ok(bytes_copied) => tokio::fs::rename(&staged, &written).await
+                .with_context(|| format!("Failed to rename {:?} to {:?}", staged, written))
+                .map(|()| bytes_copied),
+            Err(e) => Err(e),
+        };
+        if copied.is_err() {
+            let _ = tokio::fs::remove_file(&staged).await;
+        }
+        copied
+    }
+
+    /// Where the copy of a file to `destination` is written: a compressed
+    /// or encrypted copy goes beside where a plain one would be.
+    pub(crate) fn written_path(destination: &Path, options: &CopyOptions) -> PathBuf {
+        if options.compress {
+            Codec::default().compressed_path(destination)
+        } else if options.encrypt {
+            crate::encrypt::encrypted_path(destination)
+        } else {
+            destination.to_path_buf()
+        }
+    }
+
+    async fn copy_file_in_place(
+        &self,
+        source: &Path,
+        destination: &Path,
+        options: &CopyOptions,
     ) -> Result<u64> {
         info!("Copying {:?} to {:?} with engine {:?}", source, destination, self.engine_type);
+        self.update_last_engine(|_| None);
+
+        // Verification is given the plain path, as callers know it
+        let plain_destination = destination;
+        let written_destination = Self::written_path(destination, options);
+        let destination = written_destination.as_path();
 
         if options.dry_run {
             return self.perform_dry_run(source, destination, options).await;
         }
 
+        if options.stream_fifo && std::fs::metadata(source).is_ok_and(|m| crate::device::is_fifo(&m)) {
+            let bytes_copied = self.stream_fifo(source, destination, options).await?;
+            self.note_engine(CopyEngine::ReadWrite);
+            if options.preserve_metadata {
+                self.copy_metadata(source, destination, options).await?;
+            }
+            Self::apply_chmod(destination, options).await?;
+            if options.verify != VerifyMode::None {
+                // The data is gone from the pipe once read
+                warn!("Skipping verification of {:?}, streamed from a FIFO", destination);
+            }
+            return Ok(bytes_copied);
+        }
+
+        // An explicit block size is authoritative; otherwise size the blocks
+        // to the file and the destination device
+        let auto_options;
+        let options = if options.block_size.is_none() {
+            let block_size = Self::auto_block_size(source, destination).await;
+            debug!("Auto-selected block size {} for {:?}", block_size, source);
+            auto_options = CopyOptions { block_size: Some(block_size), ..options.clone() };
+            &auto_options
+        } else {
+            options
+        };
+
+        // Identical content already on the destination device is linked
+        // rather than copied
+        let dedup = match self.dedup.as_ref().filter(|index| !options.compress && !options.encrypt && index.covers(destination)) {
+            Some(index) => {
+                let source_metadata = tokio::fs::metadata(source).await?;
+                if source_metadata.len() >= DEDUP_MIN_SIZE {
+                    let hash = FileVerifier::calculate_checksum(source, crate::verify::VerifyMode::Blake3).await?;
+                    if let Some(bytes_linked) = self.link_duplicate(index, &hash, source, &source_metadata, destination, options).await? {
+                        return Ok(bytes_linked);
+                    }
+                    Some((index, hash, source_metadata))
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+
+        // Perform the actual copy, again from fresh reads while the copy
+        // fails verification and retries remain
+        let mut recopies = 0;
+        let bytes_copied = loop {
+            let bytes_copied = match options.on_source_change {
+                SourceChangeAction::IgnoreChange => self.copy_contents(source, destination, options).await?,
+                action => self.copy_watching_source(source, destination, options, action).await?,
+            };
+
+            // Copy metadata if requested (but only after the file content is copied)
+            if options.preserve_metadata {
+                self.copy_metadata(source, destination, options).await?;
+            }
+            Self::apply_chmod(destination, options).await?;
+
+            if self.verify_matches(source, plain_destination, options).await? {
+                break bytes_copied;
+            }
+            if recopies == options.verify_retries {
+                anyhow::bail!("File verification failed for {:?}", destination);
+            }
+            recopies += 1;
+            self.start_recopy(source, destination, recopies, options.verify_retries);
+        };
+
+        // Indexed only if the source didn't change since it was hashed
+        if let Some((index, hash, hashed)) = dedup {
+            let unchanged = tokio::fs::metadata(source).await
+                .is_ok_and(|now| now.len() == hashed.len() && crate::dedup::mtime_ns(&now) == crate::dedup::mtime_ns(&hashed));
+            if unchanged {
+                index.insert(hash, destination);
+            }
+        }
+
+        Ok(bytes_copied)
+    }
+
+    /// Links `destination` to a file `index` holds with the content hashed
+    /// as `hash`, returning its size, or `None` when there is no such file
+    /// or it can't be linked. A reflink gets the metadata a copy would; a
+    /// hard link shares its inode, so it is only made when the job preserves
+    /// metadata and the file's already match the source's.
+    async fn link_duplicate(
+        &self,
+        index: &DedupIndex,
+        hash: &str,
+        source: &Path,
+        source_metadata: &std::fs::Metadata,
+        destination: &Path,
+        options: &CopyOptions,
+    ) -> Result<Option<u64>> {
+        let Some(original) = index.lookup(hash) else {
+            return Ok(None);
+        };
+        let original_metadata = std::fs::metadata(&original)?;
+        // Writing a file over itself would lose the content being linked
+        if std::fs::metadata(destination).is_ok_and(|existing| existing.ino() == original_metadata.ino()) {
+            return Ok(None);
+        }
+
+        // Linked beside the destination and renamed over it, so it is never
+        // missing, and a destination sharing its inode with another file is
+        // replaced rather than emptied
+        let name = destination.file_name().unwrap_or_default().to_string_lossy();
+        let staging = destination.with_file_name(format!(".{}.copyd-dedup", name));
+        let _ = tokio::fs::remove_file(&staging).await;
+
+        let reflinked = {
+            let original_file = std::fs::File::open(&original)
+                .with_context(|| format!("Failed to open {:?}", original))?;
+            let staged_file = std::fs::File::create(&staging)
+                .with_context(|| format!("Failed to create destination file: {:?}", staging))?;
+            clone_file(&original_file, &staged_file).is_ok()
+        };
+        let link = if reflinked {
+            tokio::fs::rename(&staging, destination).await
+                .with_context(|| format!("Failed to rename {:?} to {:?}", staging, destination))?;
+            if options.preserve_metadata {
+                self.copy_metadata(source, destination, options).await?;
+            }
+            Self::apply_chmod(destination, options).await?;
+            DedupLink::Reflink
+        } else {
+            let same_metadata = original_metadata.mode() == source_metadata.mode()
+                && original_metadata.uid() == source_metadata.uid()
+                && original_metadata.gid() == source_metadata.gid()
+                && crate::dedup::mtime_ns(&original_metadata) == crate::dedup::mtime_ns(source_metadata);
+            let _ = tokio::fs::remove_file(&staging).await;
+            if !(options.preserve_metadata && options.chmod.is_none() && same_metadata) {
+                return Ok(None);
+            }
+            tokio::fs::hard_link(&original, &staging).await
+                .with_context(|| format!("Failed to hard-link {:?} to {:?}", original, staging))?;
+            tokio::fs::rename(&staging, destination).await
+                .with_context(|| format!("Failed to rename {:?} to {:?}", staging, destination))?;
+            DedupLink::HardLink
+        };
+        self.verify_written(source, destination, options).await?;
+
+        debug!("Linked {:?} to identical {:?} ({:?})", destination, original, link);
+        if link == DedupLink::Reflink {
+            self.note_engine(CopyEngine::Reflink);
+        }
+        self.deduplicated.lock().add(link, original_metadata.len());
+        Ok(Some(original_metadata.len()))
+    }
+
+    async fn copy_contents(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        if options.compress {
+            return self.compressed_copy(source, destination, options, Codec::default()).await;
+        }
+        if options.encrypt {
+            return self.encrypted_copy(source, destination, options).await;
+        }
+
         // Check if this is a sparse file and we should preserve sparse regions
         let is_sparse = if options.preserve_sparse {
             SparseFileHandler::is_sparse_file(source).await.unwrap_or(false)
@@ -59,76 +653,259 @@ impl FileCopyEngine {
             false
         };
 
-        // Perform the actual copy
-        let bytes_copied = if is_sparse && options.preserve_sparse {
+        if is_sparse {
             info!("Detected sparse file, using sparse-aware copy");
-            SparseFileHandler::copy_sparse_file(source, destination, options.block_size).await?
+            let _permit = self.inflight_budget.acquire(options.block_size.unwrap_or(64 * 1024)).await?;
+            let bytes_copied = SparseFileHandler::copy_sparse_file(source, destination, options.block_size).await?;
+            self.note_engine(CopyEngine::ReadWrite);
+            Ok(bytes_copied)
         } else {
-            match self.engine_type {
-                CopyEngine::Auto => self.auto_copy(source, destination, options).await?,
-                CopyEngine::IoUring => self.auto_copy(source, destination, options).await?,
-                CopyEngine::CopyFileRange => self.copy_file_range_copy(source, destination, options).await?,
-                CopyEngine::Sendfile => self.sendfile_copy(source, destination, options).await?,
-                CopyEngine::Reflink => self.reflink_copy(source, destination, options).await?,
-                CopyEngine::ReadWrite => self.read_write_copy(source, destination, options).await?,
+            self.copy_with_fallback(source, destination, options).await
+        }
+    }
+
+    /// Copies the contents of `source` while inotify watches it for writes.
+    /// A write stops the copy at once, as does one that lands between the
+    /// last read and the end, since either may have torn the copy. The
+    /// partial destination is removed, then the copy fails, or with
+    /// `RestartCopy` starts over up to [`MAX_SOURCE_CHANGE_RESTARTS`] times.
+    async fn copy_watching_source(
+        &self,
+        source: &Path,
+        destination: &Path,
+        options: &CopyOptions,
+        action: SourceChangeAction,
+    ) -> Result<u64> {
+        let mut restarts = 0;
+        loop {
+            let watch = SourceWatch::new(source)?;
+            tokio::select! {
+                result = self.copy_contents(source, destination, options) => {
+                    let bytes_copied = result?;
+                    if !watch.changed()? {
+                        return Ok(bytes_copied);
+                    }
+                }
+                changed = watch.wait() => changed?,
             }
-        };
 
-        // Copy metadata if requested (but only after the file content is copied)
-        if options.preserve_metadata {
-            self.copy_metadata(source, destination).await?;
+            // A fresh file, so a write still in flight from the dropped copy
+            // can't land in the next one
+            match tokio::fs::remove_file(destination).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    warn!("Failed to remove torn copy {:?}: {}", destination, e);
+                }
+                _ => {}
+            }
+            if action != SourceChangeAction::RestartCopy || restarts == MAX_SOURCE_CHANGE_RESTARTS {
+                anyhow::bail!("Source {:?} changed while it was being copied", source);
+            }
+            restarts += 1;
+            warn!("Source {:?} changed while it was being copied; starting over ({}/{})",
+                  source, restarts, MAX_SOURCE_CHANGE_RESTARTS);
         }
+    }
 
-        // Verify the copy if requested
-        if matches!(options.verify, VerifyMode::Size | VerifyMode::Md5 | VerifyMode::Sha256) {
-            info!("Verifying copied file with {:?}", options.verify);
-            let verification_start = std::time::Instant::now();
-            
-            let verify_mode_local = crate::verify::VerifyMode::from(options.verify);
+    /// Checks a freshly written `destination` against `source` as
+    /// `options.verify` asks; does nothing without verification.
+    pub(crate) async fn verify_written(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<()> {
+        match self.verify_matches(source, destination, options).await? {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!("File verification failed for {:?}", destination)),
+        }
+    }
 
-            match FileVerifier::verify_copy(source, destination, verify_mode_local).await {
-                Ok(true) => {
-                    let verification_time = verification_start.elapsed();
-                    info!("Verification completed successfully in {:.2}s", verification_time.as_secs_f64());
-                }
-                Ok(false) => {
-                    return Err(anyhow::anyhow!("File verification failed for {:?}", destination));
-                }
+    /// Whether the encrypted file at `encrypted` decrypts to the content of
+    /// `source`, as `mode` compares them. Decrypting checks every chunk's
+    /// authentication tag and the plaintext checksum stored in the file, so
+    /// a copy altered on its way to the disk fails even a size check.
+    async fn verify_decrypted(&self, source: &Path, encrypted: &Path, mode: crate::verify::VerifyMode) -> Result<bool> {
+        let key = self.encryption_key.clone()
+            .ok_or_else(|| anyhow::anyhow!("No encryption key to verify {:?}", encrypted))?;
+        let expected = FileVerifier::calculate_checksum(source, mode).await?;
+        let encrypted = encrypted.to_path_buf();
+        let actual = tokio::task::spawn_blocking(move || -> Result<Option<String>> {
+            let file = std::fs::File::open(&encrypted)
+                .with_context(|| format!("Failed to open encrypted file: {:?}", encrypted))?;
+            let mut hasher = StreamHasher::new(mode);
+            match crate::encrypt::decrypt(std::io::BufReader::new(file), &key, &mut hasher) {
+                Ok(_) => Ok(Some(hasher.finish())),
+                // A copy that fails to decrypt doesn't match, like one whose checksum differs
                 Err(e) => {
-                    return Err(e).with_context(|| format!("Verification error for {:?}", destination));
+                    warn!("Failed to decrypt {:?}: {:#}", encrypted, e);
+                    Ok(None)
                 }
             }
+        }).await??;
+        Ok(actual.as_deref() == Some(expected.as_str()))
+    }
+
+    /// Gets ready to copy `source` again after its copy failed verification:
+    /// both files are dropped from the page cache, so the copy and its
+    /// verification read what is on the devices rather than cached pages.
+    pub(crate) fn start_recopy(&self, source: &Path, destination: &Path, recopy: u32, retries: u32) {
+        warn!("Verification of {:?} failed; copying it again ({}/{})", destination, recopy, retries);
+        for path in [source, destination] {
+            if let Ok(file) = std::fs::File::open(path) {
+                unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+            }
+        }
+        self.verify_recopies.fetch_add(1, Ordering::Relaxed);
+        if let Some(monitor) = &self.monitor {
+            monitor.retry_operation();
         }
+    }
 
-        Ok(bytes_copied)
+    /// Whether a freshly written `destination` matches `source` as
+    /// `options.verify` asks; always without verification.
+    pub(crate) async fn verify_matches(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<bool> {
+        if options.verify == VerifyMode::None {
+            return Ok(true);
+        }
+        info!("Verifying copied file with {:?}", options.verify);
+        let verification_start = std::time::Instant::now();
+
+        let verification = match crate::verify::VerifyMode::from(options.verify) {
+            mode if options.compress => {
+                let codec = Codec::default();
+                codec.verify_decompressed(source, &codec.compressed_path(destination), mode).await
+            }
+            mode if options.encrypt => self.verify_decrypted(source, &crate::encrypt::encrypted_path(destination), mode).await,
+            // A fresh copy only carries the source's mtime when metadata is preserved
+            crate::verify::VerifyMode::Fingerprint => {
+                FileVerifier::verify_fingerprint(source, destination, options.preserve_metadata).await
+            }
+            mode if self.idle_io_verification => FileVerifier::verify_copy_at_idle(source, destination, mode).await,
+            mode => FileVerifier::verify_copy(source, destination, mode).await,
+        };
+        match verification {
+            Ok(true) => {
+                let verification_time = verification_start.elapsed();
+                info!("Verification completed successfully in {:.2}s", verification_time.as_secs_f64());
+                Ok(true)
+            }
+            Ok(false) => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("Verification error for {:?}", destination)),
+        }
     }
 
-    async fn auto_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
-        // Auto mode: intelligently choose the best copy method
-        debug!("Auto-selecting best copy engine for {:?} -> {:?}", source, destination);
-        
-        // Check if source and destination are on the same filesystem
+    /// Copies with the selected engine, moving on to the next one in
+    /// [`FALLBACK_ENGINES`] when an engine fails partway. A failed engine is
+    /// not used again by this `FileCopyEngine`, i.e. for the rest of the job.
+    /// Engines truncate the destination when they start, so the retry copies
+    /// the file again from the beginning.
+    async fn copy_with_fallback(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        let candidates = std::iter::once(self.engine_type)
+            .chain(FALLBACK_ENGINES.into_iter().filter(|&engine| engine != self.engine_type))
+            .take_while(|&engine| engine != CopyEngine::ReadWrite);
+
+        for engine in candidates {
+            if self.failures.lock().iter().any(|failure| failure.engine == engine) {
+                continue;
+            }
+            match self.dispatch(engine, source, destination, options).await {
+                Err(e) if is_engine_failure(&e) => {
+                    warn!("Copy engine {} failed on {:?}: {:#}; not using it for the rest of the job", engine, source, e);
+                    self.failures.lock().push(EngineFailure {
+                        engine,
+                        path: source.to_path_buf(),
+                        error: format!("{:#}", e),
+                    });
+                }
+                result => return result,
+            }
+        }
+
+        self.dispatch(CopyEngine::ReadWrite, source, destination, options).await
+    }
+
+    async fn dispatch(&self, engine: CopyEngine, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        let backend = self.backends.resolve(engine)
+            .ok_or_else(|| anyhow::anyhow!("No copy backend registered for {:?}", engine))?;
+        if self.backends.get(engine).is_some_and(|selected| !selected.is_available()) {
+            warn!("Copy engine {:?} is not available, using {}", engine, backend.name());
+        }
+        let started = std::time::Instant::now();
+        restart_written();
+        let result = backend.copy(self, source, destination, options).await;
+        if result.is_ok() {
+            // Automatic selection notes the method it settled on
+            self.update_last_engine(|last| last.or(Some(engine)));
+        }
+        if let Some(profiler) = &self.profiler {
+            let bytes_copied = result.as_ref().map_or(0, |&bytes| bytes);
+            let elapsed = started.elapsed();
+            profiler.record_engine_performance(backend.name(), bytes_copied, elapsed, result.is_ok());
+            if result.is_ok() {
+                profiler.record_transfer(backend.name(), &crate::device::device_label(destination), bytes_copied, elapsed);
+            }
+        }
+        result
+    }
+
+    async fn auto_block_size(source: &Path, destination: &Path) -> u64 {
+        let file_size = crate::device::path_length(source).unwrap_or(0);
+
+        // The destination may not exist yet; its directory lives on the same device
+        let device_io_size = match tokio::fs::metadata(destination).await {
+            Ok(metadata) => metadata.blksize(),
+            Err(_) => tokio::fs::metadata(destination.parent().unwrap_or(destination)).await
+                .map(|m| m.blksize())
+                .unwrap_or(0),
+        };
+
+        choose_block_size(file_size, device_io_size)
+    }
+
+    /// Method auto mode tries first for copying `source` to `destination`:
+    /// a reflink within a filesystem that supports them, otherwise
+    /// copy_file_range.
+    pub async fn auto_method(&self, source: &Path, destination: &Path) -> Result<CopyEngine> {
         let source_metadata = tokio::fs::metadata(source).await?;
         let dest_parent = destination.parent().unwrap_or(destination);
-        
-        // Try to get destination filesystem info
-        let same_filesystem = if let Ok(dest_metadata) = tokio::fs::metadata(dest_parent).await {
-            source_metadata.dev() == dest_metadata.dev()
-        } else {
-            false
+        let Ok(dest_metadata) = tokio::fs::metadata(dest_parent).await else {
+            return Ok(CopyEngine::CopyFileRange);
         };
-        
+        if source_metadata.dev() != dest_metadata.dev() {
+            return Ok(CopyEngine::CopyFileRange);
+        }
+
+        let device = dest_metadata.dev();
+        let cached = self.reflink_devices.lock().get(&device).copied();
+        let reflink = match cached {
+            Some(supported) => supported,
+            None => {
+                let supported = crate::device::supports_reflink(dest_parent).unwrap_or(false);
+                debug!("Device {} {} reflinks", device, if supported { "supports" } else { "does not support" });
+                self.reflink_devices.lock().insert(device, supported);
+                supported
+            }
+        };
+        Ok(if reflink { CopyEngine::Reflink } else { CopyEngine::CopyFileRange })
+    }
+
+    pub(crate) async fn auto_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        // Auto mode: intelligently choose the best copy method
+        debug!("Auto-selecting best copy engine for {:?} -> {:?}", source, destination);
+        let method = self.auto_method(source, destination).await?;
+
         // Decision tree for best copy method:
-        if same_filesystem {
-            // Same filesystem - try reflink first (instant COW copy)
-            info!("Same filesystem detected, trying reflink (COW) first");
+        if method == CopyEngine::Reflink {
+            // Same CoW filesystem - try reflink first (instant COW copy)
+            info!("Same reflink-capable filesystem detected, trying reflink (COW) first");
             match self.reflink_copy(source, destination, options).await {
-                Ok(bytes) => return Ok(bytes),
+                Ok(bytes) => {
+                    self.note_engine(CopyEngine::Reflink);
+                    return Ok(bytes);
+                }
                 Err(e) => {
                     debug!("Reflink failed: {}, trying copy_file_range", e);
                     // Reflink failed, try copy_file_range
                     match self.copy_file_range_copy(source, destination, options).await {
-                        Ok(bytes) => return Ok(bytes),
+                        Ok(bytes) => {
+                            self.note_engine(CopyEngine::CopyFileRange);
+                            return Ok(bytes);
+                        }
                         Err(e) => {
                             debug!("copy_file_range failed: {}, falling back to read/write", e);
                         }
@@ -136,14 +913,20 @@ impl FileCopyEngine {
                 }
             }
         } else {
-            // Cross-filesystem - use copy_file_range or sendfile
-            info!("Cross-filesystem copy detected, using copy_file_range");
+            // Cross-filesystem or no reflink support - use copy_file_range or sendfile
+            info!("Using copy_file_range");
             match self.copy_file_range_copy(source, destination, options).await {
-                Ok(bytes) => return Ok(bytes),
+                Ok(bytes) => {
+                    self.note_engine(CopyEngine::CopyFileRange);
+                    return Ok(bytes);
+                }
                 Err(e) => {
                     debug!("copy_file_range failed: {}, trying sendfile", e);
                     match self.sendfile_copy(source, destination, options).await {
-                        Ok(bytes) => return Ok(bytes),
+                        Ok(bytes) => {
+                            self.note_engine(CopyEngine::Sendfile);
+                            return Ok(bytes);
+                        }
                         Err(e) => {
                             debug!("sendfile failed: {}, falling back to read/write", e);
                         }
@@ -154,11 +937,13 @@ impl FileCopyEngine {
         
         // Final fallback to simple read/write
         info!("Using read/write fallback");
-        self.read_write_copy(source, destination, options).await
+        let bytes = self.read_write_copy(source, destination, options).await?;
+        self.note_engine(CopyEngine::ReadWrite);
+        Ok(bytes)
     }
 
     #[cfg(unix)]
-    async fn copy_file_range_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+    pub(crate) async fn copy_file_range_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
         info!("Using copy_file_range for high-performance copying");
         
         let source_file = std::fs::File::open(source)
@@ -167,26 +952,27 @@ impl FileCopyEngine {
         let dest_file = std::fs::File::create(destination)
             .with_context(|| format!("Failed to create destination file: {:?}", destination))?;
         
-        // Get source file size
-        let source_metadata = source_file.metadata()?;
-        let file_size = source_metadata.len();
+        // Get source file size; block devices report theirs via ioctl
+        let file_size = crate::device::source_length(&source_file)?;
         
         let mut total_copied = 0u64;
         let chunk_size = options.block_size.unwrap_or(4 * 1024 * 1024) as usize; // Default 4MB chunks
+        // Each side keeps its own offset, which the kernel advances by what
+        // every call copies, so the destination needn't start where the
+        // source does
+        let mut src_off: i64 = 0;
+        let mut dst_off: i64 = 0;
         
         while total_copied < file_size {
             let remaining = file_size - total_copied;
             let copy_size = std::cmp::min(remaining, chunk_size as u64) as usize;
-            
-            let mut source_offset = total_copied as i64;
-            let mut dest_offset = total_copied as i64;
 
             // Use copy_file_range system call
             match copy_file_range(
                 &source_file,
-                Some(&mut source_offset),
+                Some(&mut src_off),
                 &dest_file, 
-                Some(&mut dest_offset),
+                Some(&mut dst_off),
                 copy_size
             ) {
                 Ok(bytes_copied) => {
@@ -194,6 +980,7 @@ impl FileCopyEngine {
                         break; // EOF reached
                     }
                     total_copied += bytes_copied as u64;
+                    report_written(bytes_copied as u64);
                     
                     // Apply rate limiting if specified
                     if let Some(max_rate) = options.max_rate_bps {
@@ -219,13 +1006,13 @@ impl FileCopyEngine {
     }
 
     #[cfg(not(unix))]
-    async fn copy_file_range_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+    pub(crate) async fn copy_file_range_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
         warn!("copy_file_range is not supported on this platform, falling back to read/write");
         self.read_write_copy(source, destination, options).await
     }
 
     #[cfg(unix)]
-    async fn sendfile_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+    pub(crate) async fn sendfile_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
         info!("Using sendfile for zero-copy transfer");
         
         let source_file = std::fs::File::open(source)
@@ -234,9 +1021,8 @@ impl FileCopyEngine {
         let dest_file = std::fs::File::create(destination)
             .with_context(|| format!("Failed to create destination file: {:?}", destination))?;
         
-        // Get source file size
-        let source_metadata = source_file.metadata()?;
-        let file_size = source_metadata.len();
+        // Get source file size; block devices report theirs via ioctl
+        let file_size = crate::device::source_length(&source_file)?;
         
         let mut total_copied = 0u64;
         let mut offset = 0i64;
@@ -253,6 +1039,7 @@ impl FileCopyEngine {
                         break; // EOF reached
                     }
                     total_copied += bytes_copied as u64;
+                    report_written(bytes_copied as u64);
                     
                     // Apply rate limiting if specified
                     if let Some(max_rate) = options.max_rate_bps {
@@ -278,106 +1065,165 @@ impl FileCopyEngine {
     }
 
     #[cfg(not(unix))]
-    async fn sendfile_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+    pub(crate) async fn sendfile_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
         warn!("sendfile is not supported on this platform, falling back to read/write");
         self.read_write_copy(source, destination, options).await
     }
 
-    #[cfg(unix)]
-    async fn reflink_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
-        info!("Attempting reflink (COW) copy");
-        
+    /// Moves the data source -> pipe -> destination with splice(2), so it
+    /// never passes through a user-space buffer. Works for descriptors that
+    /// copy_file_range and sendfile reject, as long as one side of each
+    /// splice is the pipe.
+    #[cfg(target_os = "linux")]
+    pub(crate) async fn splice_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        use nix::fcntl::{splice, OFlag, SpliceFFlags};
+        use std::os::unix::io::{FromRawFd, OwnedFd};
+
+        info!("Using splice for zero-copy transfer");
+
         let source_file = std::fs::File::open(source)
             .with_context(|| format!("Failed to open source file: {:?}", source))?;
-        
+
         let dest_file = std::fs::File::create(destination)
             .with_context(|| format!("Failed to create destination file: {:?}", destination))?;
 
-        let source_fd = source_file.as_raw_fd();
-        let dest_fd = dest_file.as_raw_fd();
-        
-        // Try to use FICLONE ioctl for reflink (COW) copy
-        // This is supported on Btrfs, XFS, and OCFS2
-        const FICLONE: libc::c_ulong = 0x40049409;
-        
-        let result = unsafe {
-            libc::ioctl(dest_fd, FICLONE, source_fd)
-        };
-        
-        if result == 0 {
-            // Reflink succeeded - instant copy!
-            let source_metadata = source_file.metadata()?;
-            let file_size = source_metadata.len();
-            
-            info!("Reflink completed successfully: {} bytes (instant COW copy)", file_size);
-            Ok(file_size)
-        } else {
-            let errno = unsafe { *libc::__errno_location() };
-            match errno {
-                libc::EOPNOTSUPP => {
-                    info!("Reflink not supported on this filesystem, falling back to copy_file_range");
+        // Get source file size; block devices report theirs via ioctl
+        let file_size = crate::device::source_length(&source_file)?;
+
+        let (pipe_read, pipe_write) = unistd::pipe2(OFlag::O_CLOEXEC)?;
+        let (pipe_read, pipe_write) = unsafe { (OwnedFd::from_raw_fd(pipe_read), OwnedFd::from_raw_fd(pipe_write)) };
+
+        let mut total_copied = 0u64;
+        let mut offset: libc::loff_t = 0;
+        let chunk_size = options.block_size.unwrap_or(1024 * 1024) as usize; // Default 1MB chunks
+        let flags = SpliceFFlags::SPLICE_F_MOVE;
+
+        while total_copied < file_size {
+            let remaining = file_size - total_copied;
+            let copy_size = std::cmp::min(remaining, chunk_size as u64) as usize;
+
+            // Fill the pipe from the source, then drain it into the destination
+            let bytes_in = match splice(source_file.as_raw_fd(), Some(&mut offset), pipe_write.as_raw_fd(), None, copy_size, flags) {
+                Ok(bytes_in) => bytes_in,
+                Err(nix::errno::Errno::EINVAL) => {
+                    warn!("splice is not supported for {:?}, falling back to read/write", source);
                     drop(source_file);
                     drop(dest_file);
-                    self.copy_file_range_copy(source, destination, options).await
+                    return self.read_write_copy(source, destination, options).await;
                 }
-                libc::EXDEV => {
-                    info!("Cross-device reflink not supported, falling back to copy_file_range");
-                    drop(source_file);
-                    drop(dest_file);
-                    self.copy_file_range_copy(source, destination, options).await
+                Err(e) => return Err(e).with_context(|| format!("splice failed reading {:?}", source)),
+            };
+            if bytes_in == 0 {
+                break; // EOF reached
+            }
+
+            let mut pending = bytes_in;
+            while pending > 0 {
+                match splice(pipe_read.as_raw_fd(), None, dest_file.as_raw_fd(), None, pending, flags) {
+                    Ok(bytes_out) => pending -= bytes_out,
+                    Err(nix::errno::Errno::EINVAL) => {
+                        warn!("splice is not supported for {:?}, falling back to read/write", destination);
+                        drop(source_file);
+                        drop(dest_file);
+                        return self.read_write_copy(source, destination, options).await;
+                    }
+                    Err(e) => return Err(e).with_context(|| format!("splice failed writing {:?}", destination)),
                 }
-                libc::EINVAL => {
-                    warn!("Invalid reflink operation, falling back to copy_file_range");
-                    drop(source_file);
-                    drop(dest_file);
-                    self.copy_file_range_copy(source, destination, options).await
+            }
+            total_copied += bytes_in as u64;
+            report_written(bytes_in as u64);
+
+            // Apply rate limiting if specified
+            if let Some(max_rate) = options.max_rate_bps {
+                let elapsed = std::time::Duration::from_nanos(
+                    (bytes_in as f64 / max_rate as f64 * 1_000_000_000.0) as u64
+                );
+                if elapsed > std::time::Duration::from_millis(1) {
+                    tokio::time::sleep(elapsed).await;
                 }
-                _ => {
-                    warn!("Reflink failed with errno {}, falling back to copy_file_range", errno);
-                    drop(source_file);
-                    drop(dest_file);
-                    self.copy_file_range_copy(source, destination, options).await
+            }
+        }
+
+        info!("splice completed: {} bytes", total_copied);
+        Ok(total_copied)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) async fn splice_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        warn!("splice is not supported on this platform, falling back to read/write");
+        self.read_write_copy(source, destination, options).await
+    }
+
+    #[cfg(unix)]
+    pub(crate) async fn reflink_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        info!("Attempting reflink (COW) copy");
+        
+        let source_file = std::fs::File::open(source)
+            .with_context(|| format!("Failed to open source file: {:?}", source))?;
+        
+        let dest_file = std::fs::File::create(destination)
+            .with_context(|| format!("Failed to create destination file: {:?}", destination))?;
+
+        match clone_file(&source_file, &dest_file) {
+            Ok(()) => {
+                // Reflink succeeded - instant copy!
+                let source_metadata = source_file.metadata()?;
+                let file_size = source_metadata.len();
+                
+                info!("Reflink completed successfully: {} bytes (instant COW copy)", file_size);
+                Ok(file_size)
+            }
+            Err(e) => {
+                drop(source_file);
+                drop(dest_file);
+                match e.raw_os_error().unwrap_or(0) {
+                    libc::EOPNOTSUPP => info!("Reflink not supported on this filesystem, falling back to copy_file_range"),
+                    libc::EXDEV => info!("Cross-device reflink not supported, falling back to copy_file_range"),
+                    libc::EINVAL => warn!("Invalid reflink operation, falling back to copy_file_range"),
+                    errno => warn!("Reflink failed with errno {}, falling back to copy_file_range", errno),
                 }
+                self.copy_file_range_copy(source, destination, options).await
             }
         }
     }
 
     #[cfg(not(unix))]
-    async fn reflink_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+    pub(crate) async fn reflink_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
         warn!("reflink is not supported on this platform, falling back to read/write");
         self.read_write_copy(source, destination, options).await
     }
 
-    async fn read_write_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+    pub(crate) async fn read_write_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
         info!("Using read/write copy with optimized buffering");
+        // Engines fall back to this one partway through a file
+        restart_written();
         
         let block_size = options.block_size.unwrap_or(1024 * 1024) as usize; // Default 1MB for better performance
         
-        let mut source_file = tokio::fs::File::open(source).await
+        // Use multiple buffers for better I/O parallelism, as far as the
+        // inflight budget allows; read-ahead needs one more than it prefetches
+        let wanted = (self.read_ahead_blocks + 1).max(2);
+        let (_permit, buffer_count) = self.inflight_budget.acquire_buffers(wanted, block_size).await?;
+        let buffers = (0..buffer_count)
+            .map(|_| allocate_buffer(block_size, self.numa_buffers))
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        let source_file = tokio::fs::File::open(source).await
             .with_context(|| format!("Failed to open source file: {:?}", source))?;
+        let mut reader = BlockReader::new(source_file, buffers, self.read_ahead_blocks > 0);
         
         let mut dest_file = tokio::fs::File::create(destination).await
             .with_context(|| format!("Failed to create destination file: {:?}", destination))?;
 
-        // Use multiple buffers for better I/O parallelism
-        let mut buffer1 = vec![0u8; block_size];
-        let mut buffer2 = vec![0u8; block_size];
-        let mut use_buffer1 = true;
-        
         let mut total_bytes = 0u64;
         let start_time = std::time::Instant::now();
         let mut last_report = start_time;
 
-        loop {
-            let buffer = if use_buffer1 { &mut buffer1 } else { &mut buffer2 };
-            
-            let bytes_read = tokio::io::AsyncReadExt::read(&mut source_file, buffer).await?;
-            if bytes_read == 0 {
-                break;
-            }
-
+        while let Some((buffer, bytes_read)) = reader.next().await? {
             tokio::io::AsyncWriteExt::write_all(&mut dest_file, &buffer[..bytes_read]).await?;
+            reader.recycle(buffer);
             total_bytes += bytes_read as u64;
+            report_written(bytes_read as u64);
             
             // Apply rate limiting if specified
             if let Some(max_rate) = options.max_rate_bps {
@@ -395,8 +1241,6 @@ impl FileCopyEngine {
                 debug!("Copy progress: {} bytes, {:.2} MB/s", total_bytes, throughput);
                 last_report = now;
             }
-            
-            use_buffer1 = !use_buffer1;
         }
 
         tokio::io::AsyncWriteExt::flush(&mut dest_file).await?;
@@ -409,8 +1253,301 @@ impl FileCopyEngine {
         Ok(total_bytes)
     }
 
+    /// Writes `source` compressed with `codec` to `destination`, the path of
+    /// the compressed copy, recording the original size in it. Returns the
+    /// original's size.
+    pub(crate) async fn compressed_copy(&self, source: &Path, destination: &Path, options: &CopyOptions, codec: Codec) -> Result<u64> {
+        info!("Compressing {:?} to {:?} with {:?}", source, destination, codec);
+        let size = tokio::fs::metadata(source).await
+            .with_context(|| format!("Failed to read source: {:?}", source))?.len();
+        let dest_file = std::fs::File::create(destination)
+            .with_context(|| format!("Failed to create destination file: {:?}", destination))?;
+        let mut encoder = codec.encoder(dest_file, size)?;
+        let total_bytes = self.transform_copy(source, destination, &mut encoder, options).await
+            .with_context(|| format!("Failed to compress {:?} to {:?}", source, destination))?;
+        // A source that changed size on the way fails here, as the size
+        // recorded up front no longer matches
+        encoder.finish()
+            .with_context(|| format!("Failed to finish compressing {:?} to {:?}", source, destination))?;
+
+        let compressed_size = std::fs::metadata(destination)?.len();
+        info!("Compressed {} bytes to {} ({:.1}%)", total_bytes, compressed_size,
+              compressed_size as f64 * 100.0 / total_bytes.max(1) as f64);
+        Ok(total_bytes)
+    }
+
+    /// Writes `source` encrypted with the engine's key to `destination`,
+    /// the path of the encrypted copy. Returns the plaintext's size.
+    pub(crate) async fn encrypted_copy(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        let key = self.encryption_key.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No encryption key for encrypted copy of {:?}", source))?;
+        info!("Encrypting {:?} to {:?}", source, destination);
+        let dest_file = std::fs::File::create(destination)
+            .with_context(|| format!("Failed to create destination file: {:?}", destination))?;
+        let mut writer = EncryptingWriter::new(dest_file, key)?;
+        let total_bytes = self.transform_copy(source, destination, &mut writer, options).await
+            .with_context(|| format!("Failed to encrypt {:?} to {:?}", source, destination))?;
+        writer.finish()
+            .with_context(|| format!("Failed to finish encrypting {:?} to {:?}", source, destination))?;
+        Ok(total_bytes)
+    }
+
+    /// Reads `source` through a buffer into `output`, which transforms the
+    /// data on its way to `destination`. Transforming needs the data in
+    /// user space, so it reads and writes like [`Self::read_write_copy`]
+    /// and never takes the reflink, copy_file_range or sendfile fast paths;
+    /// compressing and encrypting pay off most between filesystems, where
+    /// those don't apply anyway. Returns the bytes read.
+    async fn transform_copy(&self, source: &Path, destination: &Path, output: &mut impl std::io::Write, options: &CopyOptions) -> Result<u64> {
+        restart_written();
+        let block_size = options.block_size.unwrap_or(1024 * 1024) as usize;
+        let _permit = self.inflight_budget.acquire(block_size as u64).await?;
+        let mut buffer = allocate_buffer(block_size, self.numa_buffers)?;
+        let mut source_file = tokio::fs::File::open(source).await
+            .with_context(|| format!("Failed to open source file: {:?}", source))?;
+
+        let mut total_bytes = 0u64;
+        let start_time = std::time::Instant::now();
+        loop {
+            let bytes_read = tokio::io::AsyncReadExt::read(&mut source_file, &mut buffer).await
+                .with_context(|| format!("Failed to read source file: {:?}", source))?;
+            if bytes_read == 0 {
+                break;
+            }
+            output.write_all(&buffer[..bytes_read])
+                .with_context(|| format!("Failed to write to destination file: {:?}", destination))?;
+            total_bytes += bytes_read as u64;
+            report_written(bytes_read as u64);
+
+            if let Some(max_rate) = options.max_rate_bps {
+                let expected_time = std::time::Duration::from_secs_f64(total_bytes as f64 / max_rate as f64);
+                if let Some(wait) = expected_time.checked_sub(start_time.elapsed()) {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+        self.note_engine(CopyEngine::ReadWrite);
+        Ok(total_bytes)
+    }
+
+    /// Reads the FIFO at `source` until its last writer closes it, writing
+    /// the stream to a regular file at `destination`. The pipe is opened
+    /// non-blocking, so cancelling the job while it waits for a writer or for
+    /// data drops the read instead of leaving a thread blocked in the kernel.
+    pub(crate) async fn stream_fifo(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<u64> {
+        info!("Streaming FIFO {:?} to {:?}", source, destination);
+
+        let block_size = options.block_size.unwrap_or(64 * 1024) as usize;
+        let _permit = self.inflight_budget.acquire(block_size as u64).await?;
+        let mut buffer = allocate_buffer(block_size, self.numa_buffers)?;
+
+        let mut pipe = tokio::net::unix::pipe::OpenOptions::new()
+            .open_receiver(source)
+            .with_context(|| format!("Failed to open FIFO: {:?}", source))?;
+        let mut dest_file = tokio::fs::File::create(destination).await
+            .with_context(|| format!("Failed to create destination file: {:?}", destination))?;
+
+        let mut total_bytes = 0u64;
+        let start_time = std::time::Instant::now();
+        loop {
+            let bytes_read = tokio::io::AsyncReadExt::read(&mut pipe, &mut buffer).await
+                .with_context(|| format!("Failed to read FIFO: {:?}", source))?;
+            if bytes_read == 0 {
+                break;
+            }
+            tokio::io::AsyncWriteExt::write_all(&mut dest_file, &buffer[..bytes_read]).await?;
+            total_bytes += bytes_read as u64;
+            report_written(bytes_read as u64);
+
+            if let Some(max_rate) = options.max_rate_bps {
+                let expected_time = std::time::Duration::from_secs_f64(total_bytes as f64 / max_rate as f64);
+                if let Some(wait) = expected_time.checked_sub(start_time.elapsed()) {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+        tokio::io::AsyncWriteExt::flush(&mut dest_file).await?;
+
+        info!("Captured {} bytes from FIFO {:?}", total_bytes, source);
+        Ok(total_bytes)
+    }
+
+    /// Copies `source` to every one of `destinations` with a single read:
+    /// each block is written to all of them at once. A destination that
+    /// fails is dropped while the others carry on. Each is then given the
+    /// source's metadata and verified against a checksum of the data as it
+    /// was read, so the source is never read back, and FIFO sources can be
+    /// verified too. Fails as a whole only when the source can't be read.
+    pub async fn tee_copy(&self, source: &Path, destinations: &[PathBuf], options: &CopyOptions) -> Result<Vec<CopyOutcome>> {
+        info!("Copying {:?} to {} destinations from one read", source, destinations.len());
+        self.update_last_engine(|_| None);
+
+        if options.dry_run {
+            let mut outcomes = Vec::with_capacity(destinations.len());
+            for destination in destinations {
+                outcomes.push(CopyOutcome {
+                    destination: destination.clone(),
+                    result: self.perform_dry_run(source, destination, options).await,
+                    verify_result: VerifyResult::NotVerified,
+                });
+            }
+            return Ok(outcomes);
+        }
+
+        let block_size = options.block_size.unwrap_or(1024 * 1024) as usize;
+        let _permit = self.inflight_budget.acquire(block_size as u64).await?;
+        let mut buffer = allocate_buffer(block_size, self.numa_buffers)?;
+
+        let mut reader: Box<dyn tokio::io::AsyncRead + Unpin + Send> =
+            if options.stream_fifo && std::fs::metadata(source).is_ok_and(|m| crate::device::is_fifo(&m)) {
+                Box::new(tokio::net::unix::pipe::OpenOptions::new()
+                    .open_receiver(source)
+                    .with_context(|| format!("Failed to open FIFO: {:?}", source))?)
+            } else {
+                Box::new(tokio::fs::File::open(source).await
+                    .with_context(|| format!("Failed to open source file: {:?}", source))?)
+            };
+
+        // A destination sharing its inode is written beside and renamed over,
+        // as in copy_file
+        let targets: Vec<PathBuf> = destinations.iter()
+            .map(|destination| match shares_inode(destination) {
+                true => {
+                    let name = destination.file_name().unwrap_or_default().to_string_lossy();
+                    destination.with_file_name(format!(".{}.copyd-unshare", name))
+                }
+                false => destination.clone(),
+            })
+            .collect();
+        let mut writers = Vec::with_capacity(destinations.len());
+        let mut results = Vec::with_capacity(destinations.len());
+        for (destination, target) in destinations.iter().zip(&targets) {
+            match tokio::fs::File::create(target).await {
+                Ok(file) => {
+                    writers.push(Some(file));
+                    results.push(Ok(0));
+                }
+                Err(e) => {
+                    writers.push(None);
+                    results.push(Err(anyhow::Error::new(e)
+                        .context(format!("Failed to create destination file: {:?}", destination))));
+                }
+            }
+        }
+
+        let mut hasher = StreamHasher::new(options.verify.into());
+        let mut total_bytes = 0u64;
+        let start_time = std::time::Instant::now();
+        while writers.iter().any(Option::is_some) {
+            let bytes_read = tokio::io::AsyncReadExt::read(&mut reader, &mut buffer).await
+                .with_context(|| format!("Failed to read source: {:?}", source))?;
+            if bytes_read == 0 {
+                break;
+            }
+            let block = &buffer[..bytes_read];
+            hasher.update(block);
+
+            let writes = writers.iter_mut().enumerate().filter_map(|(index, writer)| {
+                writer.as_mut().map(|file| async move {
+                    (index, tokio::io::AsyncWriteExt::write_all(file, block).await)
+                })
+            });
+            for (index, written) in futures::future::join_all(writes).await {
+                if let Err(e) = written {
+                    warn!("Dropping destination {:?} of {:?}: {}", destinations[index], source, e);
+                    writers[index] = None;
+                    results[index] = Err(anyhow::Error::new(e)
+                        .context(format!("Failed to write destination file: {:?}", destinations[index])));
+                }
+            }
+            total_bytes += bytes_read as u64;
+
+            if let Some(max_rate) = options.max_rate_bps {
+                let expected_time = std::time::Duration::from_secs_f64(total_bytes as f64 / max_rate as f64);
+                if let Some(wait) = expected_time.checked_sub(start_time.elapsed()) {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+        self.note_engine(CopyEngine::ReadWrite);
+
+        let digest = hasher.finish();
+        let mut outcomes = Vec::with_capacity(destinations.len());
+        for (((destination, target), writer), result) in destinations.iter().zip(&targets).zip(writers).zip(results) {
+            let mut result = match writer {
+                Some(file) => self.finish_tee_destination(file, source, target, options).await
+                    .map(|()| total_bytes),
+                None => result,
+            };
+            if target != destination {
+                if result.is_ok() {
+                    result = tokio::fs::rename(target, destination).await
+                        .with_context(|| format!("Failed to rename {:?} to {:?}", target, destination))
+                        .and(result);
+                }
+                if result.is_err() {
+                    let _ = tokio::fs::remove_file(target).await;
+                }
+            }
+            let (result, verify_result) = match result {
+                Ok(bytes) if options.verify != VerifyMode::None => {
+                    match self.matches_digest(destination, options.verify, &digest).await {
+                        Ok(true) => (Ok(bytes), VerifyResult::Verified),
+                        Ok(false) => (
+                            Err(anyhow::anyhow!("File verification failed for {:?}", destination)),
+                            VerifyResult::Mismatch,
+                        ),
+                        Err(e) => (
+                            Err(e.context(format!("Verification error for {:?}", destination))),
+                            VerifyResult::NotVerified,
+                        ),
+                    }
+                }
+                result => (result, VerifyResult::NotVerified),
+            };
+            outcomes.push(CopyOutcome { destination: destination.clone(), result, verify_result });
+        }
+
+        info!("Copied {} bytes from {:?} to {} of {} destinations in {:.2}s",
+              total_bytes, source,
+              outcomes.iter().filter(|outcome| outcome.result.is_ok()).count(),
+              destinations.len(), start_time.elapsed().as_secs_f64());
+        Ok(outcomes)
+    }
+
+    async fn finish_tee_destination(
+        &self,
+        mut file: tokio::fs::File,
+        source: &Path,
+        destination: &Path,
+        options: &CopyOptions,
+    ) -> Result<()> {
+        tokio::io::AsyncWriteExt::flush(&mut file).await
+            .with_context(|| format!("Failed to write destination file: {:?}", destination))?;
+        drop(file);
+        if options.preserve_metadata {
+            self.copy_metadata(source, destination, options).await?;
+        }
+        Self::apply_chmod(destination, options).await
+    }
+
+    /// Whether `destination` has the checksum `digest` under `mode`.
+    async fn matches_digest(&self, destination: &Path, mode: VerifyMode, digest: &str) -> Result<bool> {
+        let mode = crate::verify::VerifyMode::from(mode);
+        let checksum = if self.idle_io_verification {
+            let destination = destination.to_path_buf();
+            crate::priority::idle_io_runtime()
+                .spawn(async move { FileVerifier::calculate_checksum(&destination, mode).await })
+                .await
+                .context("Verification task failed")??
+        } else {
+            FileVerifier::calculate_checksum(destination, mode).await?
+        };
+        Ok(checksum == digest)
+    }
+
     #[cfg(unix)]
-    async fn copy_metadata(&self, source: &Path, destination: &Path) -> Result<()> {
+    async fn copy_metadata(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<()> {
         let metadata = tokio::fs::metadata(source).await?;
         
         // Copy permissions
@@ -421,23 +1558,21 @@ impl FileCopyEngine {
         }
 
         // Copy ownership (requires appropriate privileges)
-        {
-            let uid = metadata.uid();
-            let gid = metadata.gid();
-            
-            if let Err(e) = unistd::chown(destination, Some(unistd::Uid::from_raw(uid)), Some(unistd::Gid::from_raw(gid))) {
-                // Don't fail if we can't change ownership (common when not root)
-                debug!("Could not change ownership of {:?}: {}", destination, e);
-            }
-        }
+        self.preserve_owner(destination, metadata.uid(), metadata.gid(), |uid, gid| {
+            unistd::chown(destination, Some(uid), Some(gid))
+        });
 
         // Copy timestamps using utimensat system call
         {
             use nix::sys::stat::{utimensat, UtimensatFlags};
             use nix::sys::time::{TimeSpec};
             
-            let atime = metadata.accessed().unwrap_or_else(|_| metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH));
             let mtime = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let atime = match options.atime {
+                AtimeMode::Preserve => metadata.accessed().unwrap_or(mtime),
+                AtimeMode::Now => SystemTime::now(),
+                AtimeMode::Mtime => mtime,
+            };
             
             let atime_spec = TimeSpec::from(atime.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default());
             let mtime_spec = TimeSpec::from(mtime.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default());
@@ -446,10 +1581,11 @@ impl FileCopyEngine {
                 warn!("Could not set timestamps for {:?}: {}", destination, e);
             }
         }
+        self.copy_birth_time(source, destination);
 
         // Copy extended attributes (xattrs)
         {
-            if let Err(e) = self.copy_xattrs(source, destination).await {
+            if let Err(e) = self.copy_xattrs(source, destination, &options.xattr_namespaces).await {
                 debug!("Could not copy extended attributes: {}", e);
             }
         }
@@ -458,7 +1594,7 @@ impl FileCopyEngine {
     }
 
     #[cfg(not(unix))]
-    async fn copy_metadata(&self, source: &Path, destination: &Path) -> Result<()> {
+    async fn copy_metadata(&self, source: &Path, destination: &Path, _options: &CopyOptions) -> Result<()> {
         warn!("Metadata preservation is not fully supported on this platform");
         let source_metadata = tokio::fs::metadata(source).await?;
         let dest_file = tokio::fs::File::open(destination).await?;
@@ -466,88 +1602,146 @@ impl FileCopyEngine {
         Ok(())
     }
 
+    /// Gives an existing destination directory the permissions, ownership,
+    /// timestamps and extended attributes of `source`.
+    pub async fn copy_directory_metadata(&self, source: &Path, destination: &Path, options: &CopyOptions) -> Result<()> {
+        self.copy_metadata(source, destination, options).await
+            .with_context(|| format!("Failed to copy metadata of {:?} to {:?}", source, destination))
+    }
+
+    /// Gives `destination` the birth time of `source` where the destination
+    /// filesystem can take one, and says once per device when it can't.
+    pub(crate) fn copy_birth_time(&self, source: &Path, destination: &Path) {
+        let born = match crate::btime::birth_time(source) {
+            Ok(Some(born)) => born,
+            // Nothing recorded at the source, so nothing to carry over
+            Ok(None) => return,
+            Err(e) => {
+                debug!("Could not read birth time of {:?}: {}", source, e);
+                return;
+            }
+        };
+        match crate::btime::set_birth_time(destination, born) {
+            Ok(true) => {}
+            Ok(false) => {
+                let device = std::fs::metadata(destination).map(|metadata| metadata.dev()).unwrap_or(0);
+                if self.btime_unsupported_devices.lock().insert(device) {
+                    info!("Birth times can't be set on the filesystem of {:?}; copies there get their own", destination);
+                }
+            }
+            Err(e) => debug!("Could not set birth time of {:?}: {}", destination, e),
+        }
+    }
+
+    /// Gives `destination` the owner `uid`:`gid` through `chown`, which
+    /// doesn't fail the copy if it can't. In best-effort mode an owner the
+    /// daemon may not give away isn't tried, and ownership left out is
+    /// counted for the job rather than logged.
+    pub(crate) fn preserve_owner(
+        &self,
+        destination: &Path,
+        uid: u32,
+        gid: u32,
+        chown: impl FnOnce(unistd::Uid, unistd::Gid) -> nix::Result<()>,
+    ) {
+        if self.best_effort_metadata && !self.metadata_privileges.chown && uid != unistd::geteuid().as_raw() {
+            self.skipped_metadata.lock().ownership += 1;
+            return;
+        }
+        if let Err(e) = chown(unistd::Uid::from_raw(uid), unistd::Gid::from_raw(gid)) {
+            if self.best_effort_metadata {
+                self.skipped_metadata.lock().ownership += 1;
+            } else {
+                // Don't fail if we can't change ownership (common when not root)
+                debug!("Could not change ownership of {:?}: {}", destination, e);
+            }
+        }
+    }
+
+    /// Sets the explicitly requested mode, after any preserved one so it wins.
+    async fn apply_chmod(destination: &Path, options: &CopyOptions) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(mode) = options.chmod {
+            tokio::fs::set_permissions(destination, std::fs::Permissions::from_mode(mode)).await
+                .with_context(|| format!("Failed to set mode {:o} on {:?}", mode, destination))?;
+        }
+        Ok(())
+    }
+
+    /// Copies the `user.*` extended attributes of `source` and those in
+    /// `namespaces`. Attributes that can't be set on the destination are
+    /// logged and skipped; in best-effort mode privileged ones the daemon
+    /// can't set aren't tried and are counted for the job instead.
     #[cfg(unix)]
-    async fn copy_xattrs(&self, source: &Path, destination: &Path) -> Result<()> {
+    pub(crate) async fn copy_xattrs(&self, source: &Path, destination: &Path, namespaces: &[XattrNamespace]) -> Result<()> {
         use std::ffi::CString;
         
-        // Get list of extended attributes
         let source_cstr = CString::new(source.to_string_lossy().as_bytes())?;
         let dest_cstr = CString::new(destination.to_string_lossy().as_bytes())?;
         
-        // Buffer to hold attribute names
-        let mut names_buf = vec![0u8; 1024];
-        let names_len = unsafe {
-            libc::listxattr(
-                source_cstr.as_ptr(),
-                names_buf.as_mut_ptr() as *mut libc::c_char,
-                names_buf.len(),
-            )
-        };
-        
-        if names_len < 0 {
-            let errno = unsafe { *libc::__errno_location() };
-            if errno == libc::ENOTSUP || errno == libc::EOPNOTSUPP {
+        let names_buf = match read_xattr_names(&source_cstr) {
+            Ok(names) => names,
+            Err(e) if matches!(e.raw_os_error(), Some(libc::ENOTSUP)) => {
                 debug!("Extended attributes not supported");
                 return Ok(());
             }
-            return Err(anyhow::anyhow!("Failed to list xattrs: errno {}", errno));
-        }
-        
-        if names_len == 0 {
-            return Ok(()); // No extended attributes
-        }
-        
-        names_buf.truncate(names_len as usize);
+            Err(e) => return Err(anyhow::anyhow!("Failed to list xattrs: {}", e)),
+        };
         
-        // Parse attribute names (null-separated)
-        let mut pos = 0;
-        while pos < names_buf.len() {
-            let name_end = names_buf[pos..].iter().position(|&b| b == 0).unwrap_or(names_buf.len() - pos);
-            if name_end == 0 {
-                break;
+        // Attribute names are null-separated
+        let mut skipped_privileged = false;
+        for name in names_buf.split(|&b| b == 0).filter(|name| !name.is_empty()) {
+            let namespace = match XattrNamespace::of(name) {
+                Some(XattrNamespace::User) => XattrNamespace::User,
+                Some(namespace) if namespaces.contains(&namespace) => namespace,
+                _ => {
+                    debug!("Skipping xattr {}", String::from_utf8_lossy(name));
+                    continue;
+                }
+            };
+            let privileged = namespace != XattrNamespace::User;
+            if self.best_effort_metadata && !self.metadata_privileges.can_set_xattrs(namespace) {
+                skipped_privileged = true;
+                continue;
             }
+
+            let name_cstr = CString::new(name)?;
+            let value = match read_xattr_value(&source_cstr, &name_cstr) {
+                Ok(value) => value,
+                Err(e) => {
+                    debug!("Failed to read xattr {:?}: {}", name_cstr, e);
+                    continue;
+                }
+            };
+
+            let result = unsafe {
+                libc::setxattr(
+                    dest_cstr.as_ptr(),
+                    name_cstr.as_ptr(),
+                    value.as_ptr() as *const libc::c_void,
+                    value.len(),
+                    0,
+                )
+            };
             
-            let name = &names_buf[pos..pos + name_end];
-            if let Ok(name_cstr) = CString::new(name) {
-                // Get attribute value
-                let mut value_buf = vec![0u8; 4096];
-                let value_len = unsafe {
-                    libc::getxattr(
-                        source_cstr.as_ptr(),
-                        name_cstr.as_ptr(),
-                        value_buf.as_mut_ptr() as *mut libc::c_void,
-                        value_buf.len(),
-                    )
-                };
-                
-                if value_len >= 0 {
-                    value_buf.truncate(value_len as usize);
-                    
-                    // Set attribute on destination
-                    let result = unsafe {
-                        libc::setxattr(
-                            dest_cstr.as_ptr(),
-                            name_cstr.as_ptr(),
-                            value_buf.as_ptr() as *const libc::c_void,
-                            value_buf.len(),
-                            0,
-                        )
-                    };
-                    
-                    if result < 0 {
-                        debug!("Failed to set xattr {:?}", name_cstr);
-                    }
+            if result < 0 {
+                let error = std::io::Error::last_os_error();
+                if self.best_effort_metadata && privileged && error.raw_os_error() == Some(libc::EPERM) {
+                    skipped_privileged = true;
+                } else {
+                    debug!("Failed to set xattr {:?}: {}", name_cstr, error);
                 }
             }
-            
-            pos += name_end + 1;
+        }
+        if skipped_privileged {
+            self.skipped_metadata.lock().xattrs += 1;
         }
         
         Ok(())
     }
 
     #[cfg(not(unix))]
-    async fn copy_xattrs(&self, source: &Path, destination: &Path) -> Result<()> {
+    pub(crate) async fn copy_xattrs(&self, source: &Path, destination: &Path, _namespaces: &[XattrNamespace]) -> Result<()> {
         warn!("Extended attributes are not supported on this platform");
         Ok(())
     }
@@ -619,6 +1813,8 @@ impl FileCopyEngine {
                 VerifyMode::Size => "size check",
                 VerifyMode::Md5 => "MD5 checksum",
                 VerifyMode::Sha256 => "SHA256 checksum",
+                VerifyMode::Blake3 => "BLAKE3 checksum",
+                VerifyMode::Fingerprint => "size and sampled fingerprint",
                 _ => "size check (default)",
             };
             info!("Would verify integrity with: {}", verify_type);
@@ -702,4 +1898,50 @@ impl FileCopyEngine {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Makes `destination` share the data blocks of `source` (a reflink) with
+/// the FICLONE ioctl, supported on Btrfs, XFS and OCFS2. Fails with
+/// EOPNOTSUPP or EXDEV where the files can't share blocks.
+#[cfg(unix)]
+pub(crate) fn clone_file(source: &std::fs::File, destination: &std::fs::File) -> std::io::Result<()> {
+    const FICLONE: libc::c_ulong = 0x40049409;
+
+    if unsafe { libc::ioctl(destination.as_raw_fd(), FICLONE, source.as_raw_fd()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Calls `read` with a buffer sized by asking it for the length first (a
+/// zero-length call), retrying if the data grows in between.
+#[cfg(unix)]
+fn read_sized(read: impl Fn(*mut libc::c_void, usize) -> libc::ssize_t) -> std::io::Result<Vec<u8>> {
+    loop {
+        let size = read(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u8; size as usize];
+        let len = read(buf.as_mut_ptr() as *mut libc::c_void, buf.len());
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::ERANGE) {
+            return Err(error);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_xattr_names(path: &std::ffi::CStr) -> std::io::Result<Vec<u8>> {
+    read_sized(|buf, size| unsafe { libc::listxattr(path.as_ptr(), buf as *mut libc::c_char, size) })
+}
+
+#[cfg(unix)]
+fn read_xattr_value(path: &std::ffi::CStr, name: &std::ffi::CStr) -> std::io::Result<Vec<u8>> {
+    read_sized(|buf, size| unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size) })
+}
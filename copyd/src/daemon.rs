@@ -1,17 +1,29 @@
-use crate::config::Config;
+use crate::config::{Config, HOT_RELOADABLE_KEYS};
+use crate::directory::DirectoryHandler;
+use crate::history::JobHistory;
+use crate::results::JobResults;
 use crate::job::{JobManager};
+use crate::inflight::InflightBudget;
 use crate::metrics::Metrics;
+use crate::monitor::EnhancedMonitor;
+use crate::space::{LowSpaceWatch, StatvfsSource, LOW_SPACE_CHECK_INTERVAL};
+use crate::verify::FileVerifier;
 use copyd_protocol::*;
 use anyhow::{Result, Context};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::RwLock;
 use tracing::{info, error, debug, warn};
 
 pub struct Daemon {
-    config: Config,
+    config: Arc<RwLock<Config>>,
     job_manager: JobManager,
     metrics: Metrics,
+    monitor: Arc<EnhancedMonitor>,
     start_time: Instant,
+    /// The accepted shutdown request, once one arrives
+    shutdown: Arc<tokio::sync::watch::Sender<Option<ShutdownRequest>>>,
 }
 
 impl Daemon {
@@ -19,36 +31,67 @@ impl Daemon {
         // Ensure required directories exist
         config.ensure_directories().await?;
 
+        let monitor = Arc::new(EnhancedMonitor::new()?);
+
         // Initialize job manager
         let (job_manager, _event_receiver) = JobManager::new_with_checkpoint_dir(
             config.max_concurrent_jobs,
             config.checkpoint_dir.clone()
         );
+        let job_manager = job_manager.with_engine_policy(
+            config.default_engine,
+            config.allowed_engines.clone(),
+        )
+        .with_inflight_budget(InflightBudget::new(config.max_inflight_bytes))
+        .with_numa_buffers(config.numa_aware_buffers)
+        .with_read_ahead(config.read_ahead_blocks)
+        .with_idle_io_verification(config.idle_io_verification)
+        .with_verify_cost(config.verify_cost)
+        .with_max_copy_parallelism(config.max_copy_parallelism)
+        .with_checkpoint_interval(config.checkpoint_interval_bytes, config.checkpoint_interval_secs)
+        .with_checkpoint_compression(config.compress_checkpoints)
+        .with_progress_interval(Duration::from_millis(config.collapse_progress_ms))
+        .with_history(JobHistory::new(config.history_path.clone()))
+        .with_results(JobResults::new(config.results_dir()))
+        .with_monitor(monitor.clone());
+
+        if config.job_history_days > 0 {
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(config.job_history_days as i64);
+            if let Err(e) = job_manager.prune_history(cutoff).await {
+                warn!("Failed to prune the job history: {}", e);
+            }
+        }
         
         // Initialize metrics
         let metrics = Metrics::new()?;
 
         Ok(Self {
-            config,
+            config: Arc::new(RwLock::new(config)),
             job_manager,
             metrics,
+            monitor,
             start_time: Instant::now(),
+            shutdown: Arc::new(tokio::sync::watch::channel(None).0),
         })
     }
 
     pub async fn run(&self) -> Result<()> {
-        info!("Starting copyd daemon on socket: {:?}", self.config.socket_path);
+        // Listener settings only take effect at startup
+        let startup_config = self.config.read().await.clone();
+        info!("Starting copyd daemon on socket: {:?}", startup_config.socket_path);
 
         // Remove existing socket if it exists
-        if self.config.socket_path.exists() {
-            tokio::fs::remove_file(&self.config.socket_path).await?;
+        if startup_config.socket_path.exists() {
+            tokio::fs::remove_file(&startup_config.socket_path).await?;
         }
 
         // Create Unix domain socket listener
-        let listener = UnixListener::bind(&self.config.socket_path)
-            .with_context(|| format!("Failed to bind to socket: {:?}", self.config.socket_path))?;
+        let listener = UnixListener::bind(&startup_config.socket_path)
+            .with_context(|| format!("Failed to bind to socket: {:?}", startup_config.socket_path))?;
 
-        info!("Daemon listening on socket: {:?}", self.config.socket_path);
+        apply_socket_permissions(&startup_config)?;
+
+        info!("Daemon listening on socket: {:?}", startup_config.socket_path);
 
         // Resume jobs from checkpoints
         match self.job_manager.resume_jobs_from_checkpoints().await {
@@ -65,33 +108,113 @@ impl Daemon {
         // Start job queue processor
         self.job_manager.start_queue_processor().await;
 
+        // Reload the configuration file on SIGHUP
+        let daemon = self.clone();
+        tokio::spawn(async move {
+            let mut hangups = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+                Ok(hangups) => hangups,
+                Err(e) => {
+                    warn!("Failed to install SIGHUP handler: {}", e);
+                    return;
+                }
+            };
+            while hangups.recv().await.is_some() {
+                match daemon.reload_config().await {
+                    Ok((applied, restart_required)) => info!(
+                        "Reloaded configuration: applied {:?}, restart required for {:?}",
+                        applied, restart_required
+                    ),
+                    Err(e) => error!("Failed to reload configuration: {}", e),
+                }
+            }
+        });
+
+        // Log the state of every job on SIGUSR1
+        let job_manager = self.job_manager.clone();
+        tokio::spawn(async move {
+            let mut dumps = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1()) {
+                Ok(dumps) => dumps,
+                Err(e) => {
+                    warn!("Failed to install SIGUSR1 handler: {}", e);
+                    return;
+                }
+            };
+            while dumps.recv().await.is_some() {
+                for line in job_manager.dump_state().await.lines() {
+                    info!("{}", line);
+                }
+            }
+        });
+
+        // Warn before the devices running jobs write to fill up
+        let daemon = self.clone();
+        tokio::spawn(async move {
+            let watch = LowSpaceWatch::new(daemon.monitor.clone(), Arc::new(StatvfsSource));
+            let mut ticks = tokio::time::interval(LOW_SPACE_CHECK_INTERVAL);
+            loop {
+                ticks.tick().await;
+                let Some(threshold) = daemon.config.read().await.low_space_threshold else { continue };
+                watch.check(&daemon.job_manager.running_destinations().await, threshold).await;
+            }
+        });
+
         // Start metrics server if configured
-        if let Some(metrics_addr) = &self.config.metrics_bind_addr {
+        if let Some(metrics_addr) = &startup_config.metrics_bind_addr {
             let metrics = self.metrics.clone();
+            let monitor = self.monitor.clone();
             let addr = metrics_addr.clone();
             tokio::spawn(async move {
-                if let Err(e) = Self::run_metrics_server(metrics, addr).await {
+                if let Err(e) = Self::run_metrics_server(metrics, monitor, addr).await {
                     error!("Metrics server error: {}", e);
                 }
             });
         }
 
-        // Accept connections
-        loop {
-            match listener.accept().await {
-                Ok((stream, _)) => {
-                    let daemon = self.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = daemon.handle_client(stream).await {
-                            error!("Client handler error: {}", e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    error!("Failed to accept connection: {}", e);
+        // Accept connections until a shutdown is requested
+        let mut shutdown = self.shutdown.subscribe();
+        let request = loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let daemon = self.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = daemon.handle_client(stream).await {
+                                error!("Client handler error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                    }
+                },
+                _ = shutdown.changed() => {
+                    let requested = shutdown.borrow().clone();
+                    if let Some(request) = requested {
+                        break request;
+                    }
                 }
             }
+        };
+
+        drop(listener);
+        if let Err(e) = tokio::fs::remove_file(&startup_config.socket_path).await {
+            warn!("Failed to remove socket {:?}: {}", startup_config.socket_path, e);
         }
+        info!("Shutting down; giving running jobs {}s to finish", request.grace_secs);
+        let stopped = self.job_manager.drain(Duration::from_secs(request.grace_secs.into())).await;
+        info!("Daemon stopped; {} jobs resume from their checkpoints on the next start", stopped.len());
+        Ok(())
+    }
+
+    /// Whether the daemon stopped to be started again, so the process
+    /// should replace itself with a new one.
+    pub fn restart_requested(&self) -> bool {
+        self.shutdown.borrow().as_ref().is_some_and(|request| request.restart)
+    }
+
+    /// Engine history behind `copyctl engine-stats` and `copyctl estimate`.
+    pub fn profiler(&self) -> &crate::profiler::PerformanceProfiler {
+        self.job_manager.profiler()
     }
 
     async fn handle_client(&self, mut stream: UnixStream) -> Result<()> {
@@ -109,6 +232,15 @@ impl Daemon {
 
             debug!("Received request: {:?}", request);
 
+            // An event subscription takes over the connection until the job ends
+            if let Some(copyd_protocol::request::RequestType::SubscribeEvents(req)) = request.request_type {
+                return self.stream_job_events(req, &mut stream).await;
+            }
+            // A shutdown is answered before it starts, so the client hears back
+            if let Some(copyd_protocol::request::RequestType::Shutdown(req)) = request.request_type {
+                return self.handle_shutdown(req, &mut stream).await;
+            }
+
             // Process request and send response
             let response = self.process_request(request).await;
             
@@ -129,9 +261,15 @@ impl Daemon {
             Some(RequestType::CreateJob(req)) => {
                 ResponseType::CreateJob(self.handle_create_job(req).await)
             }
+            Some(RequestType::VerifyJob(req)) => {
+                ResponseType::VerifyJob(self.handle_verify_job(req).await)
+            }
             Some(RequestType::JobStatus(req)) => {
                 ResponseType::JobStatus(self.handle_job_status(req).await)
             }
+            Some(RequestType::BatchJobStatus(req)) => {
+                ResponseType::BatchJobStatus(self.handle_batch_job_status(req).await)
+            }
             Some(RequestType::ListJobs(req)) => {
                 ResponseType::ListJobs(self.handle_list_jobs(req).await)
             }
@@ -144,16 +282,80 @@ impl Daemon {
             Some(RequestType::ResumeJob(req)) => {
                 ResponseType::ResumeJob(self.handle_resume_job(req).await)
             }
+            Some(RequestType::GetJobResults(req)) => {
+                ResponseType::GetJobResults(self.handle_get_job_results(req).await)
+            }
+            Some(RequestType::PauseAll(_)) => {
+                ResponseType::PauseAll(self.handle_pause_all().await)
+            }
+            Some(RequestType::ResumeAll(_)) => {
+                ResponseType::ResumeAll(self.handle_resume_all().await)
+            }
             Some(RequestType::GetStats(req)) => {
                 ResponseType::GetStats(self.handle_get_stats(req).await)
             }
             Some(RequestType::HealthCheck(req)) => {
                 ResponseType::HealthCheck(self.handle_health_check(req).await)
             }
+            Some(RequestType::PurgeJobs(req)) => {
+                ResponseType::PurgeJobs(self.handle_purge_jobs(req).await)
+            }
+            Some(RequestType::QueryJobs(req)) => {
+                ResponseType::QueryJobs(self.handle_query_jobs(req).await)
+            }
+            Some(RequestType::Snapshot(req)) => {
+                ResponseType::Snapshot(self.handle_snapshot(req).await)
+            }
+            Some(RequestType::VerifyFile(req)) => {
+                ResponseType::VerifyFile(self.handle_verify_file(req).await)
+            }
+            Some(RequestType::AnalyzeJob(req)) => {
+                ResponseType::AnalyzeJob(self.handle_analyze_job(req).await)
+            }
+            Some(RequestType::TreeDiff(req)) => {
+                ResponseType::TreeDiff(self.handle_tree_diff(req).await)
+            }
+            Some(RequestType::DiffVerify(req)) => {
+                ResponseType::DiffVerify(self.handle_diff_verify(req).await)
+            }
+            Some(RequestType::Estimate(req)) => {
+                ResponseType::Estimate(self.handle_estimate(req).await)
+            }
+            Some(RequestType::Recommend(req)) => {
+                ResponseType::Recommend(self.handle_recommend(req).await)
+            }
+            Some(RequestType::GetEngineStats(_)) => {
+                ResponseType::GetEngineStats(self.handle_get_engine_stats())
+            }
+            Some(RequestType::CancelMatching(req)) => {
+                ResponseType::CancelMatching(self.handle_cancel_matching(req).await)
+            }
+            Some(RequestType::SetConfig(req)) => {
+                ResponseType::SetConfig(self.handle_set_config(req).await)
+            }
+            Some(RequestType::SetConcurrency(req)) => {
+                ResponseType::SetConcurrency(self.handle_set_concurrency(req).await)
+            }
+            Some(RequestType::ReloadConfig(_)) => {
+                ResponseType::ReloadConfig(config_update_response(self.reload_config().await))
+            }
+            Some(RequestType::SubscribeEvents(_)) => {
+                ResponseType::JobEvent(JobEventResponse {
+                    event: None,
+                    error: "Event subscriptions are only served on their own connection".to_string(),
+                })
+            }
+            Some(RequestType::Shutdown(_)) => {
+                ResponseType::Shutdown(ShutdownResponse {
+                    error: "Shutdown requests are only served on their own connection".to_string(),
+                    ..Default::default()
+                })
+            }
             None => {
                 ResponseType::CreateJob(CreateJobResponse {
                     job_id: None,
                     error: "Invalid request".to_string(),
+                    plan: None,
                 })
             }
         };
@@ -163,18 +365,214 @@ impl Daemon {
         }
     }
 
+    /// Accepts a shutdown from root or the daemon's own user, as the peer
+    /// credentials of `stream` tell, and starts it once the client has the
+    /// answer.
+    async fn handle_shutdown(&self, request: ShutdownRequest, stream: &mut UnixStream) -> Result<()> {
+        let daemon_uid = nix::unistd::geteuid().as_raw();
+        let peer_uid = stream.peer_cred().ok().map(|peer| peer.uid());
+        let response = if peer_uid.is_some_and(|uid| uid == 0 || uid == daemon_uid) {
+            ShutdownResponse {
+                running_jobs: self.job_manager.running_jobs().await as u32,
+                error: String::new(),
+            }
+        } else {
+            warn!("Refused shutdown requested by uid {:?}", peer_uid);
+            ShutdownResponse {
+                error: "Only root or the daemon's own user may shut it down".to_string(),
+                ..Default::default()
+            }
+        };
+
+        let accepted = response.error.is_empty();
+        let response = Response { response_type: Some(copyd_protocol::response::ResponseType::Shutdown(response)) };
+        send_response(stream, &response).await?;
+        if accepted {
+            info!("Shutdown requested by uid {:?} (restart: {})", peer_uid, request.restart);
+            self.shutdown.send_replace(Some(request));
+        }
+        Ok(())
+    }
+
+    async fn handle_set_concurrency(&self, request: SetConcurrencyRequest) -> SetConcurrencyResponse {
+        let previous_max = self.job_manager.max_concurrent() as u32;
+        let mut new_config = self.config.read().await.clone();
+        new_config.max_concurrent_jobs = request.max_concurrent_jobs as usize;
+
+        match self.apply_config(new_config).await {
+            Ok(_) => SetConcurrencyResponse {
+                previous_max,
+                max_concurrent_jobs: request.max_concurrent_jobs,
+                running_jobs: self.job_manager.running_jobs().await as u32,
+                error: String::new(),
+            },
+            Err(e) => SetConcurrencyResponse {
+                previous_max,
+                max_concurrent_jobs: previous_max,
+                error: format!("Failed to change concurrency: {}", e),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Applies the hot-reloadable settings of `new_config` to the running
+    /// daemon and stores it as the current configuration. Returns the changed
+    /// settings that took effect and those that need a restart.
+    pub async fn apply_config(&self, new_config: Config) -> Result<(Vec<String>, Vec<String>)> {
+        new_config.validate()?;
+
+        let mut config = self.config.write().await;
+        let (applied, restart_required): (Vec<String>, Vec<String>) = config.changed_keys(&new_config)?
+            .into_iter()
+            .partition(|key| HOT_RELOADABLE_KEYS.contains(&key.as_str()));
+
+        if new_config.max_concurrent_jobs != config.max_concurrent_jobs {
+            self.job_manager.set_max_concurrent(new_config.max_concurrent_jobs).await?;
+        }
+        self.job_manager.set_engine_policy(new_config.default_engine, new_config.allowed_engines.clone());
+        self.job_manager.set_numa_buffers(new_config.numa_aware_buffers);
+        self.job_manager.set_read_ahead(new_config.read_ahead_blocks);
+        self.job_manager.set_idle_io_verification(new_config.idle_io_verification);
+        self.job_manager.set_verify_cost(new_config.verify_cost);
+        self.job_manager.set_max_copy_parallelism(new_config.max_copy_parallelism);
+        self.job_manager.set_checkpoint_interval(new_config.checkpoint_interval_bytes, new_config.checkpoint_interval_secs);
+        self.job_manager.set_progress_interval(Duration::from_millis(new_config.collapse_progress_ms));
+
+        *config = new_config;
+        Ok((applied, restart_required))
+    }
+
+    /// Re-reads the configuration file and applies it.
+    pub async fn reload_config(&self) -> Result<(Vec<String>, Vec<String>)> {
+        let path = Config::config_path();
+        let content = tokio::fs::read_to_string(&path).await
+            .with_context(|| format!("Failed to read configuration file {:?}", path))?;
+        let new_config: Config = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse configuration file {:?}", path))?;
+        self.apply_config(new_config).await
+    }
+
+    async fn handle_set_config(&self, request: SetConfigRequest) -> ConfigUpdateResponse {
+        let mut new_config = self.config.read().await.clone();
+        for setting in &request.settings {
+            if !HOT_RELOADABLE_KEYS.contains(&setting.key.as_str()) {
+                return ConfigUpdateResponse {
+                    error: format!("'{}' cannot be changed while the daemon runs", setting.key),
+                    ..Default::default()
+                };
+            }
+            if let Err(e) = new_config.set_value(&setting.key, &setting.value) {
+                return ConfigUpdateResponse {
+                    error: e.to_string(),
+                    ..Default::default()
+                };
+            }
+        }
+
+        config_update_response(self.apply_config(new_config).await)
+    }
+
+    async fn stream_job_events(&self, request: SubscribeEventsRequest, stream: &mut UnixStream) -> Result<()> {
+        use copyd_protocol::response::ResponseType;
+
+        let job_id = request.job_id.map(|id| id.uuid).unwrap_or_default();
+        let event_response = |event: Option<JobEvent>, error: String| Response {
+            response_type: Some(ResponseType::JobEvent(JobEventResponse { event, error })),
+        };
+
+        let (progress, mut events) = match self.job_manager.subscribe_events(&job_id).await {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                send_response(stream, &event_response(None, format!("Failed to attach: {}", e))).await?;
+                return Ok(());
+            }
+        };
+
+        debug!("Client attached to job {}", job_id);
+        let finished = JobStatus::try_from(progress.status).is_ok_and(JobStatus::is_terminal);
+        let snapshot = JobEvent {
+            job_id: Some(JobId { uuid: job_id.clone() }),
+            event_type: Some(job_event::EventType::ProgressUpdate(progress)),
+        };
+        send_response(stream, &event_response(Some(snapshot), String::new())).await?;
+        if finished {
+            return Ok(());
+        }
+
+        loop {
+            let Some(event) = events.recv().await else {
+                break;
+            };
+            if event.job_id.as_ref().map(|id| id.uuid.as_str()) != Some(job_id.as_str()) {
+                continue;
+            }
+
+            let finished = matches!(
+                event.event_type,
+                Some(job_event::EventType::StatusChange(status))
+                    if JobStatus::try_from(status).is_ok_and(JobStatus::is_terminal)
+            );
+            if let Err(e) = send_response(stream, &event_response(Some(event), String::new())).await {
+                debug!("Attached client for job {} went away: {}", job_id, e);
+                break;
+            }
+            if finished {
+                break;
+            }
+        }
+
+        if events.dropped() > 0 {
+            debug!("Attached client for job {} fell behind; {} events were dropped", job_id, events.dropped());
+        }
+        Ok(())
+    }
+
     async fn handle_create_job(&self, request: CreateJobRequest) -> CreateJobResponse {
+        // Planned before the job is queued so conflicts reflect the
+        // destination as it was, not files the job has already written
+        let plan = if request.skip_plan {
+            None
+        } else {
+            match self.job_manager.plan_job(&request).await {
+                Ok(plan) => Some(plan),
+                Err(e) => {
+                    warn!("Failed to plan job for {:?}: {}", request.sources, e);
+                    None
+                }
+            }
+        };
+
         match self.job_manager.create_job(request).await {
             Ok(job_id) => {
                 self.metrics.record_job_created();
                 CreateJobResponse {
                     job_id: Some(JobId { uuid: job_id }),
                     error: String::new(),
+                    plan,
                 }
             }
             Err(e) => CreateJobResponse {
                 job_id: None,
                 error: format!("Failed to create job: {}", e),
+                plan: None,
+            },
+        }
+    }
+
+    async fn handle_verify_job(&self, request: VerifyJobRequest) -> CreateJobResponse {
+        match self.job_manager.create_verify_job(request).await {
+            Ok(job_id) => {
+                self.metrics.record_job_created();
+                CreateJobResponse {
+                    job_id: Some(JobId { uuid: job_id }),
+                    error: String::new(),
+                    plan: None,
+                }
+            }
+            Err(e) => CreateJobResponse {
+                job_id: None,
+                error: format!("Failed to create verify job: {}", e),
+                plan: None,
             },
         }
     }
@@ -185,9 +583,8 @@ impl Daemon {
             None => {
                 return JobStatusResponse {
                     job_id: None,
-                    progress: None,
                     error: "Missing job_id".to_string(),
-                    log_entries: vec![],
+                    ..Default::default()
                 }
             }
         };
@@ -195,32 +592,38 @@ impl Daemon {
         match self.job_manager.get_job(&job_id).await {
             Some(job) => JobStatusResponse {
                 job_id: Some(JobId { uuid: job_id }),
-                progress: Some(job.progress),
+                progress: Some(job.progress_snapshot()),
                 error: String::new(),
+                rate_samples: job.rate_sampler.samples(),
                 log_entries: job.log_entries,
+                error_summary: Some(job.error_summary),
+                description: job.description,
+                created_at: job.created_at.timestamp(),
+                started_at: job.started_at.map(|t| t.timestamp()).unwrap_or(0),
+                completed_at: job.completed_at.map(|t| t.timestamp()).unwrap_or(0),
+                not_found: false,
             },
             None => JobStatusResponse {
                 job_id: Some(JobId { uuid: job_id }),
-                progress: None,
                 error: "Job not found".to_string(),
-                log_entries: vec![],
+                not_found: true,
+                ..Default::default()
             },
         }
     }
 
+    async fn handle_batch_job_status(&self, request: BatchJobStatusRequest) -> BatchJobStatusResponse {
+        let mut statuses = Vec::with_capacity(request.job_ids.len());
+        for job_id in request.job_ids {
+            statuses.push(self.handle_job_status(JobStatusRequest { job_id: Some(job_id) }).await);
+        }
+        BatchJobStatusResponse { statuses }
+    }
+
     async fn handle_list_jobs(&self, request: ListJobsRequest) -> ListJobsResponse {
-        let jobs = self.job_manager.list_jobs(request.include_completed).await;
+        let jobs = self.job_manager.list_jobs(request.include_completed, &request.tags).await;
         
-        let job_infos = jobs.into_iter().map(|job| JobInfo {
-            job_id: Some(JobId { uuid: job.id }),
-            sources: job.sources.into_iter().map(|p| p.to_string_lossy().to_string()).collect(),
-            destination: job.destination.to_string_lossy().to_string(),
-            progress: Some(job.progress),
-            created_at: job.created_at.timestamp(),
-            started_at: job.started_at.map(|t| t.timestamp()).unwrap_or(0),
-            completed_at: job.completed_at.map(|t| t.timestamp()).unwrap_or(0),
-            priority: job.priority,
-        }).collect();
+        let job_infos = jobs.iter().map(|job| job.to_info()).collect();
 
         ListJobsResponse { jobs: job_infos }
     }
@@ -240,6 +643,21 @@ impl Daemon {
         }
     }
 
+    async fn handle_cancel_matching(&self, request: CancelMatchingRequest) -> CancelMatchingResponse {
+        let prefix = std::path::PathBuf::from(request.path_prefix);
+
+        match self.job_manager.cancel_matching(&prefix).await {
+            Ok(cancelled) => CancelMatchingResponse {
+                cancelled_job_ids: cancelled.into_iter().map(|uuid| JobId { uuid }).collect(),
+                error: String::new(),
+            },
+            Err(e) => CancelMatchingResponse {
+                cancelled_job_ids: vec![],
+                error: format!("Failed to cancel jobs: {}", e),
+            },
+        }
+    }
+
     async fn handle_pause_job(&self, request: PauseJobRequest) -> PauseJobResponse {
         let job_id = request.job_id.map(|id| id.uuid).unwrap_or_default();
         
@@ -257,8 +675,16 @@ impl Daemon {
 
     async fn handle_resume_job(&self, request: ResumeJobRequest) -> ResumeJobResponse {
         let job_id = request.job_id.map(|id| id.uuid).unwrap_or_default();
+        let result = if request.source_root_remaps.is_empty() {
+            self.job_manager.resume_job(&job_id).await
+        } else {
+            let remaps: Vec<_> = request.source_root_remaps.into_iter()
+                .map(|r| (std::path::PathBuf::from(r.from), std::path::PathBuf::from(r.to)))
+                .collect();
+            self.job_manager.resume_checkpointed_job(&job_id, &remaps).await
+        };
         
-        match self.job_manager.resume_job(&job_id).await {
+        match result {
             Ok(()) => ResumeJobResponse {
                 success: true,
                 error: String::new(),
@@ -270,14 +696,271 @@ impl Daemon {
         }
     }
 
-    async fn handle_get_stats(&self, _request: GetStatsRequest) -> StatsResponse {
-        // TODO: Implement proper statistics gathering
-        StatsResponse {
-            total_bytes_copied: 0,
-            total_files_copied: 0,
-            total_jobs: 0,
-            daily_stats: vec![],
-            slow_paths: vec![],
+    async fn handle_get_job_results(&self, request: GetJobResultsRequest) -> GetJobResultsResponse {
+        let job_id = request.job_id.map(|id| id.uuid).unwrap_or_default();
+
+        match self.job_manager.job_results(&job_id).await {
+            Ok(results) => GetJobResultsResponse { results, error: String::new() },
+            Err(e) => GetJobResultsResponse {
+                results: vec![],
+                error: format!("Failed to read results of job {}: {}", job_id, e),
+            },
+        }
+    }
+
+    async fn handle_pause_all(&self) -> PauseAllResponse {
+        match self.job_manager.pause_all().await {
+            Ok(paused) => PauseAllResponse {
+                paused_job_ids: paused.into_iter().map(|uuid| JobId { uuid }).collect(),
+                error: String::new(),
+            },
+            Err(e) => PauseAllResponse {
+                paused_job_ids: vec![],
+                error: format!("Failed to pause jobs: {}", e),
+            },
+        }
+    }
+
+    async fn handle_resume_all(&self) -> ResumeAllResponse {
+        match self.job_manager.resume_all().await {
+            Ok(resumed) => ResumeAllResponse {
+                resumed_job_ids: resumed.into_iter().map(|uuid| JobId { uuid }).collect(),
+                error: String::new(),
+            },
+            Err(e) => ResumeAllResponse {
+                resumed_job_ids: vec![],
+                error: format!("Failed to resume jobs: {}", e),
+            },
+        }
+    }
+
+    async fn handle_get_stats(&self, request: GetStatsRequest) -> StatsResponse {
+        self.job_manager.collect_stats(request.days_back).await
+    }
+
+    fn handle_get_engine_stats(&self) -> EngineStatsResponse {
+        let profiler = self.job_manager.profiler();
+        let mut engines: Vec<EngineStats> = profiler.get_performance_report().engine_reports.into_iter()
+            .map(|report| EngineStats {
+                engine: report.name,
+                throughput_mbps: report.throughput_mbps,
+                operations: report.operations,
+                error_rate: report.error_rate,
+                total_bytes: report.total_bytes,
+            })
+            .collect();
+        engines.sort_by(|a, b| a.engine.cmp(&b.engine));
+
+        let recommendations = profiler.analyze_performance().into_iter()
+            .map(|recommendation| EngineRecommendation {
+                category: recommendation.category,
+                issue: recommendation.issue,
+                suggestion: recommendation.suggestion,
+                severity: format!("{:?}", recommendation.severity).to_lowercase(),
+            })
+            .collect();
+
+        EngineStatsResponse { engines, recommendations }
+    }
+
+    async fn handle_purge_jobs(&self, request: PurgeJobsRequest) -> PurgeJobsResponse {
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(request.older_than_days as i64);
+        let statuses: Vec<JobStatus> = request.status_filter.iter()
+            .filter_map(|s| JobStatus::try_from(*s).ok())
+            .collect();
+
+        match self.job_manager.purge_jobs(cutoff, &statuses).await {
+            Ok(purged) => PurgeJobsResponse {
+                purged_count: purged.len() as u32,
+                purged_job_ids: purged.into_iter().map(|uuid| JobId { uuid }).collect(),
+                error: String::new(),
+            },
+            Err(e) => PurgeJobsResponse {
+                purged_count: 0,
+                purged_job_ids: vec![],
+                error: format!("Failed to purge jobs: {}", e),
+            },
+        }
+    }
+
+    async fn handle_query_jobs(&self, request: QueryJobsRequest) -> QueryJobsResponse {
+        match self.job_manager.query_history(&request).await {
+            Ok((jobs, total_matches)) => QueryJobsResponse {
+                jobs,
+                total_matches,
+                error: String::new(),
+            },
+            Err(e) => QueryJobsResponse {
+                error: format!("Failed to query job history: {}", e),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn handle_analyze_job(&self, request: AnalyzeJobRequest) -> AnalyzeJobResponse {
+        let job = request.job.unwrap_or_default();
+        match self.job_manager.analyze_job(&job).await {
+            Ok(response) => response,
+            Err(e) => AnalyzeJobResponse {
+                error: format!("Failed to analyze job: {}", e),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn handle_tree_diff(&self, request: TreeDiffRequest) -> TreeDiffResponse {
+        let sources = [std::path::PathBuf::from(request.source)];
+        let destination = std::path::PathBuf::from(request.destination);
+        let diff = match DirectoryHandler::analyze_sources(&sources, &destination, true, true).await {
+            Ok(traversal) => DirectoryHandler::diff_tree(&traversal).await,
+            Err(e) => Err(e),
+        };
+
+        let to_strings = |paths: Vec<std::path::PathBuf>| -> Vec<String> {
+            paths.into_iter().map(|p| p.to_string_lossy().to_string()).collect()
+        };
+
+        match diff {
+            Ok(diff) => TreeDiffResponse {
+                new_files: to_strings(diff.new_files),
+                changed_files: to_strings(diff.changed_files),
+                extraneous_files: to_strings(diff.extraneous),
+                unchanged_count: diff.unchanged,
+                error: String::new(),
+            },
+            Err(e) => TreeDiffResponse {
+                error: format!("Failed to diff trees: {}", e),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn handle_diff_verify(&self, request: DiffVerifyRequest) -> DiffVerifyResponse {
+        let left = std::path::PathBuf::from(request.left);
+        let right = std::path::PathBuf::from(request.right);
+        let mode = crate::verify::VerifyMode::from(request.mode);
+
+        let to_strings = |paths: Vec<std::path::PathBuf>| -> Vec<String> {
+            paths.into_iter().map(|p| p.to_string_lossy().to_string()).collect()
+        };
+
+        match FileVerifier::compare_trees(&left, &right, mode).await {
+            Ok(comparison) => DiffVerifyResponse {
+                matching: to_strings(comparison.matching),
+                differing: to_strings(comparison.differing),
+                only_left: to_strings(comparison.only_left),
+                only_right: to_strings(comparison.only_right),
+                error: String::new(),
+            },
+            Err(e) => DiffVerifyResponse {
+                error: format!("Failed to compare trees: {:#}", e),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn handle_estimate(&self, request: EstimateRequest) -> EstimateResponse {
+        let engine = match CopyEngine::try_from(request.engine).unwrap_or(CopyEngine::Auto) {
+            CopyEngine::Auto => None,
+            engine => crate::backend::BackendRegistry::default().get(engine).map(|backend| backend.name()),
+        };
+        let Some(estimate) = self.job_manager.profiler().estimate_throughput(engine, &request.device) else {
+            return EstimateResponse {
+                total_files: request.total_files,
+                total_bytes: request.total_bytes,
+                error: format!("No copies by {} have run since the daemon started", engine.unwrap_or("any engine")),
+                ..Default::default()
+            };
+        };
+
+        let (seconds, min_seconds, max_seconds) = estimate.seconds(request.total_bytes);
+        EstimateResponse {
+            total_files: request.total_files,
+            total_bytes: request.total_bytes,
+            throughput_mbps: estimate.mbps,
+            low_throughput_mbps: estimate.low_mbps,
+            high_throughput_mbps: estimate.high_mbps,
+            samples: estimate.samples,
+            other_devices: estimate.other_devices,
+            seconds,
+            min_seconds,
+            max_seconds,
+            verify_cost: self.job_manager.verify_cost(),
+            error: String::new(),
+        }
+    }
+
+    async fn handle_recommend(&self, request: RecommendRequest) -> RecommendResponse {
+        let sources: Vec<std::path::PathBuf> = request.sources.iter().map(std::path::PathBuf::from).collect();
+        let destination = std::path::PathBuf::from(request.destination);
+
+        match crate::recommend::inspect(&sources, &destination).await {
+            Ok(profile) => {
+                let recommendation = crate::recommend::recommend(&profile);
+                RecommendResponse {
+                    engine: recommendation.engine.into(),
+                    block_size: recommendation.block_size.unwrap_or(0),
+                    packed: recommendation.packed,
+                    reasons: recommendation.reasons,
+                    total_files: profile.total_files,
+                    total_bytes: profile.total_bytes,
+                    median_file_size: profile.median_file_size,
+                    small_files: profile.small_files,
+                    same_filesystem: profile.same_filesystem,
+                    reflink: profile.reflink,
+                    io_uring: profile.io_uring,
+                    error: String::new(),
+                }
+            }
+            Err(e) => RecommendResponse {
+                error: format!("Failed to inspect the copy: {:#}", e),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn handle_snapshot(&self, request: SnapshotRequest) -> SnapshotResponse {
+        let source = std::path::PathBuf::from(request.source);
+        let destination = std::path::PathBuf::from(request.destination);
+
+        match crate::snapshot::create_snapshot(&source, &destination).await {
+            Ok(stats) => SnapshotResponse {
+                files: stats.files,
+                hard_links: stats.hard_links,
+                directories: stats.directories,
+                symlinks: stats.symlinks,
+                bytes: stats.bytes,
+                error: String::new(),
+            },
+            Err(e) => SnapshotResponse {
+                error: format!("Failed to create snapshot: {:#}", e),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn handle_verify_file(&self, request: VerifyFileRequest) -> VerifyFileResponse {
+        let path = std::path::PathBuf::from(&request.path);
+        let sidecar = (!request.sidecar_path.is_empty())
+            .then(|| std::path::PathBuf::from(&request.sidecar_path));
+        let mode = match VerifyMode::try_from(request.algorithm).unwrap_or(VerifyMode::None) {
+            VerifyMode::None => None,
+            mode => Some(crate::verify::VerifyMode::from(mode)),
+        };
+
+        match FileVerifier::verify_against_sidecar(&path, sidecar.as_deref(), mode).await {
+            Ok(result) => VerifyFileResponse {
+                verified: result.verified,
+                sidecar_path: result.sidecar.to_string_lossy().to_string(),
+                algorithm: result.mode as i32,
+                expected_checksum: result.expected_checksum,
+                calculated_checksum: result.calculated_checksum,
+                error: String::new(),
+            },
+            Err(e) => VerifyFileResponse {
+                error: format!("Failed to verify file: {:#}", e),
+                ..Default::default()
+            },
         }
     }
 
@@ -294,17 +977,23 @@ impl Daemon {
         }
     }
 
-    async fn run_metrics_server(metrics: Metrics, addr: String) -> Result<()> {
+    /// Serves `/metrics`, and the scheduling metrics of the monitor under
+    /// `/metrics/monitor`, whose names overlap with the former.
+    async fn run_metrics_server(metrics: Metrics, monitor: Arc<EnhancedMonitor>, addr: String) -> Result<()> {
         use std::convert::Infallible;
         use std::net::SocketAddr;
 
         let make_svc = hyper::service::make_service_fn(move |_conn| {
             let metrics = metrics.clone();
+            let monitor = monitor.clone();
             async move {
                 Ok::<_, Infallible>(hyper::service::service_fn(move |req| {
                     let metrics = metrics.clone();
+                    let monitor = monitor.clone();
                     async move {
-                        if req.uri().path() == "/metrics" {
+                        if req.uri().path() == "/metrics/monitor" {
+                            Ok(hyper::Response::new(hyper::Body::from(monitor.export_metrics())))
+                        } else if req.uri().path() == "/metrics" {
                             match metrics.export() {
                                 Ok(body) => Ok::<_, Infallible>(hyper::Response::new(hyper::Body::from(body))),
                                 Err(e) => {
@@ -344,13 +1033,52 @@ impl Daemon {
     }
 }
 
+/// Sets the configured mode and group on the freshly bound socket, so
+/// access can be granted to a group instead of depending on directory modes.
+fn apply_socket_permissions(config: &Config) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = &config.socket_path;
+    if let Some(group) = &config.socket_group {
+        let gid = match group.parse::<u32>() {
+            Ok(gid) => nix::unistd::Gid::from_raw(gid),
+            Err(_) => nix::unistd::Group::from_name(group)?
+                .with_context(|| format!("Unknown socket group '{}'", group))?
+                .gid,
+        };
+        nix::unistd::chown(path, None, Some(gid))
+            .with_context(|| format!("Failed to give socket {:?} to group '{}'", path, group))?;
+    }
+    if let Some(mode) = config.socket_mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set mode {:o} on socket {:?}", mode, path))?;
+    }
+    Ok(())
+}
+
+fn config_update_response(result: Result<(Vec<String>, Vec<String>)>) -> ConfigUpdateResponse {
+    match result {
+        Ok((applied, restart_required)) => ConfigUpdateResponse {
+            applied,
+            restart_required,
+            error: String::new(),
+        },
+        Err(e) => ConfigUpdateResponse {
+            error: format!("Failed to update configuration: {}", e),
+            ..Default::default()
+        },
+    }
+}
+
 impl Clone for Daemon {
     fn clone(&self) -> Self {
         Self {
             config: self.config.clone(),
             job_manager: self.job_manager.clone(),
             metrics: self.metrics.clone(),
+            monitor: self.monitor.clone(),
             start_time: self.start_time,
+            shutdown: self.shutdown.clone(),
         }
     }
 } 
\ No newline at end of file
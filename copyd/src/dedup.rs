@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// Files smaller than this are always copied; linking them saves next to
+/// nothing and costs a hash of the source.
+pub const DEDUP_MIN_SIZE: u64 = 4096;
+
+/// A destination file as it was when indexed, so one changed or replaced
+/// since is noticed before anything is linked to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct IndexedFile {
+    path: PathBuf,
+    size: u64,
+    inode: u64,
+    mtime_ns: i64,
+}
+
+impl IndexedFile {
+    fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            inode: metadata.ino(),
+            mtime_ns: mtime_ns(&metadata),
+        })
+    }
+}
+
+pub(crate) fn mtime_ns(metadata: &std::fs::Metadata) -> i64 {
+    metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec()
+}
+
+/// Content hashes of files already written to one destination device, with
+/// a path to each, kept across jobs so a later copy of the same content can
+/// reflink or hard-link it instead of writing it again.
+pub struct DedupIndex {
+    device: u64,
+    path: PathBuf,
+    files: parking_lot::Mutex<HashMap<String, IndexedFile>>,
+    dirty: AtomicBool,
+}
+
+impl DedupIndex {
+    async fn load(path: PathBuf, device: u64) -> Result<Self> {
+        let files = match tokio::fs::read(&path).await {
+            Ok(data) => serde_json::from_slice(&data)
+                .with_context(|| format!("Failed to parse deduplication index {:?}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read deduplication index {:?}", path)),
+        };
+        Ok(Self { device, path, files: parking_lot::Mutex::new(files), dirty: AtomicBool::new(false) })
+    }
+
+    /// Whether `destination` is written to the device this index covers.
+    pub fn covers(&self, destination: &Path) -> bool {
+        destination.parent()
+            .and_then(|parent| std::fs::metadata(parent).ok())
+            .is_some_and(|metadata| metadata.dev() == self.device)
+    }
+
+    /// A file holding the content hashed as `hash`, if one was indexed and
+    /// is unchanged since; a changed or missing one is forgotten.
+    pub fn lookup(&self, hash: &str) -> Option<PathBuf> {
+        let mut files = self.files.lock();
+        let indexed = files.get(hash)?;
+        if IndexedFile::of(&indexed.path).is_ok_and(|current| current == *indexed) {
+            return Some(indexed.path.clone());
+        }
+        debug!("Forgetting {:?}, changed since it was indexed", indexed.path);
+        files.remove(hash);
+        self.dirty.store(true, Ordering::Relaxed);
+        None
+    }
+
+    /// Indexes `path` as holding the content hashed as `hash`, unless a
+    /// file with that content is indexed already.
+    pub fn insert(&self, hash: String, path: &Path) {
+        let mut files = self.files.lock();
+        if files.contains_key(&hash) {
+            return;
+        }
+        if let Ok(file) = IndexedFile::of(path) {
+            files.insert(hash, file);
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the index out if it changed since it was loaded or last saved.
+    pub async fn save(&self) -> Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let data = serde_json::to_vec(&*self.files.lock())?;
+        let staging = self.path.with_extension("json.tmp");
+        tokio::fs::write(&staging, data).await
+            .with_context(|| format!("Failed to write deduplication index {:?}", staging))?;
+        tokio::fs::rename(&staging, &self.path).await
+            .with_context(|| format!("Failed to replace deduplication index {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+/// The [`DedupIndex`] of each destination device, stored as
+/// `<major>_<minor>.json` in a directory of the checkpoint directory and
+/// shared by the jobs writing to that device.
+pub struct DedupIndexes {
+    dir: PathBuf,
+    indexes: tokio::sync::Mutex<HashMap<u64, Arc<DedupIndex>>>,
+}
+
+impl DedupIndexes {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, indexes: tokio::sync::Mutex::new(HashMap::new()) }
+    }
+
+    /// The index of the device `destination` is on, or will be once its
+    /// closest existing ancestor has it created.
+    pub async fn open(&self, destination: &Path) -> Result<Arc<DedupIndex>> {
+        let metadata = destination.ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .find_map(|ancestor| std::fs::metadata(ancestor).ok())
+            .with_context(|| format!("No existing directory holds {:?}", destination))?;
+        let device = metadata.dev();
+
+        let mut indexes = self.indexes.lock().await;
+        if let Some(index) = indexes.get(&device) {
+            return Ok(index.clone());
+        }
+        tokio::fs::create_dir_all(&self.dir).await
+            .with_context(|| format!("Failed to create deduplication directory {:?}", self.dir))?;
+        let path = self.dir.join(format!("{}_{}.json", libc::major(device), libc::minor(device)));
+        let index = Arc::new(DedupIndex::load(path, device).await?);
+        info!("Deduplication index of device {}:{} holds {} files", libc::major(device), libc::minor(device), index.len());
+        indexes.insert(device, index.clone());
+        Ok(index)
+    }
+}
+
+/// How a file was linked to identical content already on its device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupLink {
+    Reflink,
+    HardLink,
+}
+
+/// Files a job linked to earlier copies instead of copying.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DedupStats {
+    pub reflinked: u64,
+    pub hard_linked: u64,
+    pub bytes: u64,
+}
+
+impl DedupStats {
+    pub fn add(&mut self, link: DedupLink, bytes: u64) {
+        match link {
+            DedupLink::Reflink => self.reflinked += 1,
+            DedupLink::HardLink => self.hard_linked += 1,
+        }
+        self.bytes += bytes;
+    }
+
+    /// One line for the job log, or `None` when nothing was linked.
+    pub fn summary(&self) -> Option<String> {
+        let files = self.reflinked + self.hard_linked;
+        (files > 0).then(|| format!(
+            "Deduplicated {} files ({} bytes) against earlier copies: {} reflinked, {} hard-linked",
+            files, self.bytes, self.reflinked, self.hard_linked
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_index_forgets_changed_files_and_persists() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("a.bin");
+        std::fs::write(&file, b"contents")?;
+
+        let indexes = DedupIndexes::new(temp_dir.path().join("dedup"));
+        let index = indexes.open(&temp_dir.path().join("new/dir")).await?;
+        assert!(index.covers(&file));
+        index.insert("h1".to_string(), &file);
+        assert_eq!(index.lookup("h1"), Some(file.clone()));
+        index.save().await?;
+
+        // A second daemon finds it on disk
+        let reloaded = DedupIndexes::new(temp_dir.path().join("dedup")).open(temp_dir.path()).await?;
+        assert_eq!(reloaded.lookup("h1"), Some(file.clone()));
+
+        std::fs::write(&file, b"changed!")?;
+        let filetime = std::fs::File::options().write(true).open(&file)?;
+        filetime.set_modified(std::time::SystemTime::UNIX_EPOCH)?;
+        assert_eq!(reloaded.lookup("h1"), None);
+        assert!(reloaded.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_stats_summary() {
+        let mut stats = DedupStats::default();
+        assert_eq!(stats.summary(), None);
+        stats.add(DedupLink::HardLink, 4096);
+        stats.add(DedupLink::HardLink, 8192);
+        stats.add(DedupLink::Reflink, 100);
+        assert_eq!(
+            stats.summary().unwrap(),
+            "Deduplicated 3 files (12388 bytes) against earlier copies: 1 reflinked, 2 hard-linked"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs::{File, Metadata};
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// `_IOR(0x12, 114, size_t)`: size of a block device in bytes.
+const BLKGETSIZE64: libc::c_ulong = 0x8008_1272;
+
+/// statfs `f_type` of the filesystems whose files can share data blocks
+/// through FICLONE: Btrfs, XFS and OCFS2.
+const REFLINK_FILESYSTEMS: &[u64] = &[0x9123_683e, 0x5846_5342, 0x7461_636f];
+
+pub fn is_block_device(metadata: &Metadata) -> bool {
+    metadata.file_type().is_block_device()
+}
+
+pub fn is_char_device(metadata: &Metadata) -> bool {
+    metadata.file_type().is_char_device()
+}
+
+pub fn is_fifo(metadata: &Metadata) -> bool {
+    metadata.file_type().is_fifo()
+}
+
+/// Size in bytes of the block device open as `file`.
+pub fn block_device_size(file: &File) -> io::Result<u64> {
+    let mut size: u64 = 0;
+    let result = unsafe { libc::ioctl(file.as_raw_fd(), BLKGETSIZE64, &mut size as *mut u64) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(size)
+}
+
+/// Number of bytes a copy of `file` should read. Block devices report a
+/// length of 0 in their metadata, so their size is queried from the kernel.
+pub fn source_length(file: &File) -> io::Result<u64> {
+    let metadata = file.metadata()?;
+    if is_block_device(&metadata) {
+        block_device_size(file)
+    } else {
+        Ok(metadata.len())
+    }
+}
+
+/// [`source_length`] for a path; only opens the file when it is a block device.
+pub fn path_length(path: &Path) -> io::Result<u64> {
+    let metadata = std::fs::metadata(path)?;
+    if is_block_device(&metadata) {
+        block_device_size(&File::open(path)?)
+    } else {
+        Ok(metadata.len())
+    }
+}
+
+/// Whether `path` is on a filesystem mounted read-only. A path that
+/// doesn't exist yet is judged by the closest existing directory above it.
+pub fn is_read_only(path: &Path) -> io::Result<bool> {
+    let Some(existing) = path.ancestors().find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists()) else {
+        return Ok(false);
+    };
+    let stat = nix::sys::statvfs::statvfs(existing)?;
+    Ok(stat.flags().contains(nix::sys::statvfs::FsFlags::ST_RDONLY))
+}
+
+/// statfs `f_type` magic number of the filesystem holding `path`.
+pub fn filesystem_type(path: &Path) -> io::Result<u64> {
+    let stat = nix::sys::statfs::statfs(path)?;
+    Ok(stat.filesystem_type().0 as u64)
+}
+
+/// Whether the filesystem holding `path` supports reflinks, so a copy
+/// within it is worth trying with FICLONE first.
+pub fn supports_reflink(path: &Path) -> io::Result<bool> {
+    filesystem_type(path).map(|magic| REFLINK_FILESYSTEMS.contains(&magic))
+}
+
+/// `major:minor` of the device holding `path`, or of its closest existing
+/// ancestor when it doesn't exist yet.
+pub fn device_label(path: &Path) -> String {
+    use std::os::unix::fs::MetadataExt;
+    path.ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .find_map(|ancestor| std::fs::metadata(ancestor).ok())
+        .map(|metadata| format!("{}:{}", libc::major(metadata.dev()), libc::minor(metadata.dev())))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_regular_file_length() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&[1u8; 12345]).unwrap();
+
+        assert_eq!(source_length(file.as_file()).unwrap(), 12345);
+        assert_eq!(path_length(file.path()).unwrap(), 12345);
+        assert!(block_device_size(file.as_file()).is_err());
+    }
+
+    #[test]
+    fn test_reflink_support_follows_filesystem_type() {
+        // procfs and sysfs never hold reflinkable files
+        assert!(!supports_reflink(Path::new("/proc")).unwrap());
+        assert!(!supports_reflink(Path::new("/sys")).unwrap());
+        assert_eq!(filesystem_type(Path::new("/proc")).unwrap(), 0x9fa0);
+        assert!(filesystem_type(Path::new("/no/such/path")).is_err());
+    }
+
+    /// Set COPYD_TEST_BLOCK_DEVICE to a readable block device, e.g. a loop
+    /// device attached with `losetup -f --show image.bin`, to run this test.
+    #[test]
+    fn test_block_device_length() {
+        let device = match std::env::var_os("COPYD_TEST_BLOCK_DEVICE") {
+            Some(device) => std::path::PathBuf::from(device),
+            None => return,
+        };
+
+        let metadata = std::fs::metadata(&device).unwrap();
+        assert!(is_block_device(&metadata));
+        assert_eq!(metadata.len(), 0);
+
+        let size = path_length(&device).unwrap();
+        assert!(size > 0);
+        assert_eq!(size % 512, 0);
+    }
+}
@@ -2,8 +2,10 @@ use anyhow::{Result, Context};
 use std::path::{Path, PathBuf};
 use std::collections::{HashMap, HashSet};
 use std::os::unix::fs::MetadataExt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::fs;
 use tracing::{info, debug, warn};
+use crate::error::CopydError;
 
 #[derive(Debug, Clone)]
 pub struct FileEntry {
@@ -16,14 +18,177 @@ pub struct FileEntry {
     pub hard_links: Option<(u64, u64)>, // (device, inode) for hard link detection
 }
 
+/// Destination changes a sync of a traversal would make.
+#[derive(Debug, Clone, Default)]
+pub struct TreeDiff {
+    pub new_files: Vec<PathBuf>,
+    pub changed_files: Vec<PathBuf>,
+    pub extraneous: Vec<PathBuf>,
+    pub unchanged: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct DirectoryTraversal {
     pub files: Vec<FileEntry>,
     pub total_size: u64,
     pub total_files: u64,
     pub directories: Vec<PathBuf>,
+    /// Source of each of `directories`, in the same order
+    pub directory_sources: Vec<PathBuf>,
     pub symlinks: Vec<FileEntry>,
     pub hard_link_map: HashMap<(u64, u64), PathBuf>, // Track hard links
+    /// Sources left out because another source already covers them, each
+    /// with the reason
+    pub redundant_sources: Vec<String>,
+    /// Whether the sources were placed inside the destination, rather than
+    /// each copied as the destination itself
+    pub into_destination: bool,
+}
+
+impl DirectoryTraversal {
+    /// The traversal with only its directories left, for copies of the
+    /// directory structure alone.
+    pub fn without_files(mut self) -> Self {
+        self.files.clear();
+        self.symlinks.clear();
+        self.hard_link_map.clear();
+        self.total_size = 0;
+        self.total_files = 0;
+        self
+    }
+
+    /// The traversal written under `mirror` instead of `destination`, laid
+    /// out the same way, for jobs that copy to several destinations.
+    pub fn mirrored(&self, destination: &Path, mirror: &Path) -> Self {
+        let relocate = |path: &Path| match path.strip_prefix(destination) {
+            Ok(relative) if relative.as_os_str().is_empty() => mirror.to_path_buf(),
+            Ok(relative) => mirror.join(relative),
+            Err(_) => path.to_path_buf(),
+        };
+        let relocate_entry = |entry: &FileEntry| FileEntry { dest_path: relocate(&entry.dest_path), ..entry.clone() };
+
+        Self {
+            files: self.files.iter().map(relocate_entry).collect(),
+            total_size: self.total_size,
+            total_files: self.total_files,
+            directories: self.directories.iter().map(|dir| relocate(dir)).collect(),
+            directory_sources: self.directory_sources.clone(),
+            symlinks: self.symlinks.iter().map(relocate_entry).collect(),
+            hard_link_map: self.hard_link_map.clone(),
+            redundant_sources: self.redundant_sources.clone(),
+            into_destination: self.into_destination,
+        }
+    }
+}
+
+/// Entries a traversal leaves out, as glob patterns. A pattern without a
+/// slash matches file and directory names anywhere in the tree; one with a
+/// slash, leading or inside, matches the path relative to the source being
+/// walked. A trailing slash limits a pattern to directories. Excluding a
+/// directory skips everything below it.
+#[derive(Debug, Clone, Default)]
+pub struct ExcludeFilter {
+    patterns: Vec<ExcludePattern>,
+}
+
+#[derive(Debug, Clone)]
+struct ExcludePattern {
+    glob: glob::Pattern,
+    /// Matched against the relative path rather than the name
+    anchored: bool,
+    directories_only: bool,
+}
+
+impl ExcludeFilter {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns.iter()
+            .map(|pattern| {
+                let directories_only = pattern.ends_with('/');
+                let trimmed = pattern.trim_end_matches('/');
+                let glob = glob::Pattern::new(trimmed.trim_start_matches('/'))
+                    .with_context(|| format!("Invalid exclude pattern '{}'", pattern))?;
+                Ok(ExcludePattern { glob, anchored: trimmed.contains('/'), directories_only })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Whether the entry at `relative`, below a source root, is excluded.
+    pub fn excludes(&self, relative: &Path, is_dir: bool) -> bool {
+        let name = relative.file_name().map(|name| name.to_string_lossy());
+        // `*` stays within one path component, as in a shell
+        let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+        self.patterns.iter()
+            .filter(|pattern| is_dir || !pattern.directories_only)
+            .any(|pattern| if pattern.anchored {
+                pattern.glob.matches_path_with(relative, options)
+            } else {
+                name.as_deref().is_some_and(|name| pattern.glob.matches(name))
+            })
+    }
+}
+
+/// Over-long destination paths named in the error; the rest are counted.
+const MAX_LISTED_LONG_PATHS: usize = 10;
+
+/// Longest file name and path, in bytes, that a filesystem accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathLimits {
+    pub name_max: usize,
+    /// Including the terminating NUL, as `pathconf` reports it
+    pub path_max: usize,
+}
+
+impl PathLimits {
+    /// Limits of the filesystem that will hold `path`, asked of its closest
+    /// existing ancestor. Falls back to the usual Linux limits when the
+    /// filesystem doesn't say.
+    pub fn of(path: &Path) -> Self {
+        let existing = path.ancestors()
+            .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+            .unwrap_or(Path::new("."));
+        let query = |variable, default| match nix::unistd::pathconf(existing, variable) {
+            Ok(Some(limit)) if limit > 0 => limit as usize,
+            _ => default,
+        };
+        Self {
+            name_max: query(nix::unistd::PathconfVar::NAME_MAX, 255),
+            path_max: query(nix::unistd::PathconfVar::PATH_MAX, 4096),
+        }
+    }
+
+    /// Why `path` could not be created under these limits, if it couldn't.
+    pub fn violation(&self, path: &Path) -> Option<String> {
+        let long_name = path.components()
+            .map(|component| component.as_os_str().len())
+            .find(|&length| length > self.name_max);
+        if let Some(length) = long_name {
+            return Some(format!("a name of {} bytes exceeds the limit of {}", length, self.name_max));
+        }
+        let length = path.as_os_str().len();
+        (length >= self.path_max)
+            .then(|| format!("the path of {} bytes exceeds the limit of {}", length, self.path_max - 1))
+    }
+}
+
+/// Files and bytes an analysis has found so far, readable while it is still
+/// walking the sources.
+#[derive(Debug, Default)]
+pub struct AnalysisCounter {
+    files: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl AnalysisCounter {
+    fn add(&self, bytes: u64) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Files and bytes found so far.
+    pub fn get(&self) -> (u64, u64) {
+        (self.files.load(Ordering::Relaxed), self.bytes.load(Ordering::Relaxed))
+    }
 }
 
 pub struct DirectoryHandler;
@@ -34,83 +199,216 @@ impl DirectoryHandler {
         destination: &Path, 
         recursive: bool,
         preserve_links: bool,
+    ) -> Result<DirectoryTraversal> {
+        Self::analyze_sources_excluding(sources, destination, recursive, preserve_links, &ExcludeFilter::default()).await
+    }
+
+    /// [`Self::analyze_sources`] with the layout fixed rather than taken from
+    /// whether the destination is a directory, for a job started again
+    /// after its first run may have created the destination.
+    pub async fn analyze_sources_into(
+        sources: &[PathBuf],
+        destination: &Path,
+        into_destination: bool,
+        recursive: bool,
+        preserve_links: bool,
+    ) -> Result<DirectoryTraversal> {
+        Self::analyze(sources, destination, Some(into_destination), recursive, preserve_links, &ExcludeFilter::default(), &AnalysisCounter::default()).await
+    }
+
+    /// [`Self::analyze_sources`], or [`Self::analyze_sources_into`] when
+    /// `into_destination` is given, counting each file in `counter` as it is
+    /// found so progress can be reported before the analysis ends.
+    pub async fn analyze_sources_counting(
+        sources: &[PathBuf],
+        destination: &Path,
+        into_destination: Option<bool>,
+        recursive: bool,
+        preserve_links: bool,
+        counter: &AnalysisCounter,
+    ) -> Result<DirectoryTraversal> {
+        Self::analyze(sources, destination, into_destination, recursive, preserve_links, &ExcludeFilter::default(), counter).await
+    }
+
+    /// [`Self::analyze_sources`], leaving out entries below the sources that
+    /// `exclude` matches. The sources themselves are always included.
+    pub async fn analyze_sources_excluding(
+        sources: &[PathBuf],
+        destination: &Path,
+        recursive: bool,
+        preserve_links: bool,
+        exclude: &ExcludeFilter,
+    ) -> Result<DirectoryTraversal> {
+        Self::analyze(sources, destination, None, recursive, preserve_links, exclude, &AnalysisCounter::default()).await
+    }
+
+    async fn analyze(
+        sources: &[PathBuf],
+        destination: &Path,
+        into_destination: Option<bool>,
+        recursive: bool,
+        preserve_links: bool,
+        exclude: &ExcludeFilter,
+        counter: &AnalysisCounter,
     ) -> Result<DirectoryTraversal> {
         let mut traversal = DirectoryTraversal {
             files: Vec::new(),
             total_size: 0,
             total_files: 0,
             directories: Vec::new(),
+            directory_sources: Vec::new(),
             symlinks: Vec::new(),
             hard_link_map: HashMap::new(),
+            redundant_sources: Vec::new(),
+            into_destination: false,
         };
+        let (sources, redundant) = Self::distinct_sources(sources, recursive);
+        for reason in &redundant {
+            warn!("{}", reason);
+        }
+        traversal.redundant_sources = redundant;
 
         // Determine if destination is a directory
-        let dest_is_dir = if let Ok(metadata) = fs::metadata(destination).await {
+        let dest_is_dir = if let Some(into_destination) = into_destination {
+            into_destination
+        } else if let Ok(metadata) = fs::metadata(destination).await {
             metadata.is_dir()
         } else {
             // If destination doesn't exist, assume it's a directory if multiple sources
             sources.len() > 1
         };
+        traversal.into_destination = dest_is_dir;
 
-        for source in sources {
-            if let Ok(metadata) = fs::metadata(source).await {
-                if metadata.is_dir() {
-                    if recursive {
-                        let dest_dir = if dest_is_dir {
-                            destination.join(source.file_name().unwrap_or_default())
-                        } else {
-                            destination.to_path_buf()
-                        };
-                        
-                        Self::traverse_directory(
-                            source, 
-                            &dest_dir, 
-                            &mut traversal,
-                            preserve_links
-                        ).await?;
-                    } else {
-                        warn!("Skipping directory {:?} (recursive not enabled)", source);
-                    }
-                } else {
-                    // Single file
-                    let dest_path = if dest_is_dir {
+        for source in &sources {
+            let metadata = match fs::metadata(source).await {
+                Ok(metadata) => metadata,
+                Err(e) => return Err(Self::source_error(source, e).await.into()),
+            };
+            if metadata.is_dir() {
+                if recursive {
+                    let dest_dir = if dest_is_dir {
                         destination.join(source.file_name().unwrap_or_default())
                     } else {
                         destination.to_path_buf()
                     };
-
-                    let entry = Self::create_file_entry(
+                        
+                    Self::traverse_directory(
                         source, 
-                        &dest_path, 
-                        &metadata, 
-                        &mut traversal.hard_link_map,
-                        preserve_links
+                        &dest_dir, 
+                        &mut traversal,
+                        preserve_links,
+                        source,
+                        exclude,
+                        counter,
                     ).await?;
-
-                    if entry.is_symlink {
-                        traversal.symlinks.push(entry);
-                    } else {
-                        traversal.total_size += entry.size;
-                        traversal.total_files += 1;
-                        traversal.files.push(entry);
-                    }
+                } else {
+                    warn!("Skipping directory {:?} (recursive not enabled)", source);
                 }
             } else {
-                return Err(anyhow::anyhow!("Source not found: {:?}", source));
+                // Single file
+                let dest_path = if dest_is_dir {
+                    destination.join(source.file_name().unwrap_or_default())
+                } else {
+                    destination.to_path_buf()
+                };
+
+                let entry = Self::create_file_entry(
+                    source, 
+                    &dest_path, 
+                    &metadata, 
+                    &mut traversal.hard_link_map,
+                    preserve_links
+                ).await?;
+
+                if entry.is_symlink {
+                    traversal.symlinks.push(entry);
+                } else {
+                    traversal.total_size += entry.size;
+                    traversal.total_files += 1;
+                    counter.add(entry.size);
+                    traversal.files.push(entry);
+                }
             }
         }
 
+        if !destination.as_os_str().is_empty() {
+            Self::check_path_lengths(&traversal, PathLimits::of(destination))?;
+        }
+
         info!("Directory analysis complete: {} files, {} bytes, {} directories", 
               traversal.total_files, traversal.total_size, traversal.directories.len());
 
         Ok(traversal)
     }
 
+    /// `sources` without repeats and, in a recursive copy, without paths
+    /// inside another source, whose copy already includes them. The first
+    /// of several equal paths is kept, and the order is otherwise
+    /// unchanged. Paths are compared as written, without following
+    /// symlinks. Also returns why each left-out source was dropped.
+    pub fn distinct_sources(sources: &[PathBuf], recursive: bool) -> (Vec<PathBuf>, Vec<String>) {
+        // Components ignore repeated separators, `.` parts and trailing slashes
+        let normalized: Vec<PathBuf> = sources.iter().map(|source| source.components().collect()).collect();
+        let mut kept = Vec::new();
+        let mut redundant = Vec::new();
+        for (index, source) in sources.iter().enumerate() {
+            let path = &normalized[index];
+            if normalized[..index].contains(path) {
+                redundant.push(format!("Source {:?} is listed more than once; copying it once", source));
+            } else if let Some(parent) = normalized.iter()
+                .position(|other| recursive && other != path && path.starts_with(other))
+            {
+                redundant.push(format!(
+                    "Source {:?} is inside source {:?}, whose copy already includes it",
+                    source, sources[parent],
+                ));
+            } else {
+                kept.push(source.clone());
+            }
+        }
+        (kept, redundant)
+    }
+
+    /// Fails with every destination path of `traversal` that `limits`
+    /// rule out, so an over-long name is reported before anything is
+    /// copied rather than as a failure partway through.
+    pub fn check_path_lengths(traversal: &DirectoryTraversal, limits: PathLimits) -> Result<()> {
+        let destinations = traversal.directories.iter()
+            .chain(traversal.files.iter().map(|entry| &entry.dest_path))
+            .chain(traversal.symlinks.iter().map(|entry| &entry.dest_path));
+        let mut offenders: Vec<String> = destinations
+            .filter_map(|path| limits.violation(path).map(|reason| format!("{:?}: {}", path, reason)))
+            .collect();
+        if offenders.is_empty() {
+            return Ok(());
+        }
+        if offenders.len() > MAX_LISTED_LONG_PATHS {
+            let more = offenders.len() - MAX_LISTED_LONG_PATHS;
+            offenders.truncate(MAX_LISTED_LONG_PATHS);
+            offenders.push(format!("and {} more", more));
+        }
+        Err(CopydError::PathTooLong { offenders }.into())
+    }
+
+    /// Classifies a source that could not be stat'ed, telling a dangling
+    /// symlink apart from a path that is simply missing.
+    async fn source_error(source: &Path, error: std::io::Error) -> CopydError {
+        if error.kind() == std::io::ErrorKind::NotFound
+            && fs::symlink_metadata(source).await.is_ok_and(|metadata| metadata.is_symlink())
+        {
+            return CopydError::InvalidPath { path: source.to_path_buf() };
+        }
+        CopydError::from_path_error(source, error)
+    }
+
     fn traverse_directory<'a>(
         source_dir: &'a Path,
         dest_dir: &'a Path,
         traversal: &'a mut DirectoryTraversal,
         preserve_links: bool,
+        root: &'a Path,
+        exclude: &'a ExcludeFilter,
+        counter: &'a AnalysisCounter,
     ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let mut entries = fs::read_dir(source_dir).await
@@ -118,12 +416,16 @@ impl DirectoryHandler {
 
             // Add directory to create list
             traversal.directories.push(dest_dir.to_path_buf());
+            traversal.directory_sources.push(source_dir.to_path_buf());
 
             while let Some(entry) = entries.next_entry().await? {
                 let source_path = entry.path();
                 let dest_path = dest_dir.join(entry.file_name());
-                
                 let metadata = entry.metadata().await?;
+                if exclude.excludes(source_path.strip_prefix(root).unwrap_or(&source_path), metadata.is_dir()) {
+                    debug!("Excluded {:?}", source_path);
+                    continue;
+                }
 
                 if metadata.is_dir() {
                     // Recursively traverse subdirectory
@@ -131,7 +433,10 @@ impl DirectoryHandler {
                         &source_path, 
                         &dest_path, 
                         traversal,
-                        preserve_links
+                        preserve_links,
+                        root,
+                        exclude,
+                        counter,
                     ).await?;
                 } else {
                     let file_entry = Self::create_file_entry(
@@ -147,6 +452,7 @@ impl DirectoryHandler {
                     } else {
                         traversal.total_size += file_entry.size;
                         traversal.total_files += 1;
+                        counter.add(file_entry.size);
                         traversal.files.push(file_entry);
                     }
                 }
@@ -163,10 +469,17 @@ impl DirectoryHandler {
         preserve_links: bool,
     ) -> Result<FileEntry> {
         let is_symlink = metadata.file_type().is_symlink();
-        let size = if is_symlink { 0 } else { metadata.len() };
+        let size = if is_symlink {
+            0
+        } else if crate::device::is_block_device(metadata) {
+            crate::device::path_length(source_path)
+                .with_context(|| format!("Failed to get block device size: {:?}", source_path))?
+        } else {
+            metadata.len()
+        };
         
         // Check for sparse files (simplified detection)
-        let is_sparse = if !is_symlink && size > 0 {
+        let is_sparse = if metadata.is_file() && size > 0 {
             Self::is_sparse_file(source_path, metadata).await.unwrap_or(false)
         } else {
             false
@@ -281,6 +594,96 @@ impl DirectoryHandler {
         Ok(())
     }
 
+    /// Lists destination entries that have no counterpart in the traversal.
+    ///
+    /// Only directories produced by the traversal are scanned, so paths outside
+    /// the copied tree are never considered. Extraneous directories are reported
+    /// once rather than descended into.
+    pub async fn find_extraneous(traversal: &DirectoryTraversal) -> Result<Vec<PathBuf>> {
+        let expected: HashSet<&Path> = traversal.directories.iter()
+            .map(|p| p.as_path())
+            .chain(traversal.files.iter().map(|f| f.dest_path.as_path()))
+            .chain(traversal.symlinks.iter().map(|f| f.dest_path.as_path()))
+            .collect();
+
+        let mut extraneous = Vec::new();
+        for dir_path in &traversal.directories {
+            let mut entries = match fs::read_dir(dir_path).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).with_context(|| format!("Failed to read directory: {:?}", dir_path)),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if !expected.contains(path.as_path()) {
+                    extraneous.push(path);
+                }
+            }
+        }
+
+        extraneous.sort();
+        Ok(extraneous)
+    }
+
+    /// Classifies every traversal entry against the destination. A file is
+    /// changed when its size differs or the source is newer than the copy.
+    pub async fn diff_tree(traversal: &DirectoryTraversal) -> Result<TreeDiff> {
+        let mut diff = TreeDiff {
+            extraneous: Self::find_extraneous(traversal).await?,
+            ..Default::default()
+        };
+
+        for entry in &traversal.files {
+            match fs::metadata(&entry.dest_path).await {
+                Ok(dest_metadata) => {
+                    let source_metadata = fs::metadata(&entry.source_path).await
+                        .with_context(|| format!("Failed to stat source: {:?}", entry.source_path))?;
+                    if source_metadata.len() != dest_metadata.len()
+                        || source_metadata.mtime() > dest_metadata.mtime()
+                    {
+                        diff.changed_files.push(entry.dest_path.clone());
+                    } else {
+                        diff.unchanged += 1;
+                    }
+                }
+                Err(_) => diff.new_files.push(entry.dest_path.clone()),
+            }
+        }
+
+        for entry in &traversal.symlinks {
+            if fs::symlink_metadata(&entry.dest_path).await.is_ok() {
+                diff.unchanged += 1;
+            } else {
+                diff.new_files.push(entry.dest_path.clone());
+            }
+        }
+
+        diff.new_files.sort();
+        diff.changed_files.sort();
+        Ok(diff)
+    }
+
+    pub async fn remove_extraneous(paths: &[PathBuf], dry_run: bool) -> Result<()> {
+        for path in paths {
+            if dry_run {
+                info!("Would delete extraneous destination entry: {:?}", path);
+                continue;
+            }
+
+            let metadata = fs::symlink_metadata(path).await
+                .with_context(|| format!("Failed to stat extraneous entry: {:?}", path))?;
+            if metadata.is_dir() {
+                fs::remove_dir_all(path).await
+            } else {
+                fs::remove_file(path).await
+            }
+            .with_context(|| format!("Failed to delete extraneous entry: {:?}", path))?;
+            debug!("Deleted extraneous destination entry: {:?}", path);
+        }
+        Ok(())
+    }
+
     pub fn estimate_completion_time(
         transferred_bytes: u64,
         total_bytes: u64,
@@ -0,0 +1,350 @@
+use anyhow::{Context, Result};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Daemon environment variable holding the passphrase of encrypted copies
+/// whose jobs name no keyring entry.
+pub const PASSPHRASE_ENV: &str = "COPYD_ENCRYPTION_PASSPHRASE";
+
+/// Plaintext bytes sealed together; each chunk carries its own nonce and
+/// authentication tag.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+const MAGIC: &[u8; 8] = b"COPYDENC";
+const FORMAT_VERSION: u8 = 1;
+const CIPHER_CHACHA20_POLY1305: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 2 + SALT_LEN + 4;
+/// Marks the last chunk, which holds the SHA-256 of the plaintext, so a
+/// file cut short is told apart from a complete one
+const FINAL_CHUNK: u8 = 1;
+
+/// Where the encrypted copy of `destination` is written.
+pub fn encrypted_path(destination: &Path) -> PathBuf {
+    let mut path = destination.as_os_str().to_owned();
+    path.push(".enc");
+    PathBuf::from(path)
+}
+
+/// Length of the encrypted copy of `plaintext_len` bytes: the header, then
+/// each chunk and the final one with their framing and tags.
+pub fn encrypted_len(plaintext_len: u64) -> u64 {
+    let chunks = plaintext_len.div_ceil(CHUNK_SIZE as u64) + 1;
+    let digest_len = 32;
+    HEADER_LEN as u64 + chunks * (1 + NONCE_LEN + 4 + TAG_LEN) as u64 + plaintext_len + digest_len
+}
+
+/// Passphrase of an encrypted copy: the `user` key named `key_id` in the
+/// daemon's keyrings, or else [`PASSPHRASE_ENV`].
+pub fn passphrase(key_id: Option<&str>) -> Result<Vec<u8>> {
+    match key_id {
+        Some(key_id) => keyring_passphrase(key_id),
+        None => std::env::var_os(PASSPHRASE_ENV)
+            .filter(|passphrase| !passphrase.is_empty())
+            .map(std::os::unix::ffi::OsStringExt::into_vec)
+            .ok_or_else(|| anyhow::anyhow!("Encrypted copies need a key ID or {} set for the daemon", PASSPHRASE_ENV)),
+    }
+}
+
+fn keyring_passphrase(key_id: &str) -> Result<Vec<u8>> {
+    let key_type = std::ffi::CString::new("user")?;
+    let description = std::ffi::CString::new(key_id)?;
+    let serial = unsafe {
+        libc::syscall(libc::SYS_request_key, key_type.as_ptr(), description.as_ptr(), std::ptr::null::<libc::c_char>(), 0)
+    };
+    if serial < 0 {
+        anyhow::bail!("No user key {:?} in the daemon's keyrings: {}", key_id, std::io::Error::last_os_error());
+    }
+
+    let mut buffer = vec![0u8; 256];
+    loop {
+        let length = unsafe {
+            libc::syscall(libc::SYS_keyctl, libc::KEYCTL_READ, serial, buffer.as_mut_ptr(), buffer.len())
+        };
+        if length < 0 {
+            anyhow::bail!("Failed to read key {:?}: {}", key_id, std::io::Error::last_os_error());
+        }
+        // The call gives the key's full length when the buffer is too short
+        if length as usize <= buffer.len() {
+            buffer.truncate(length as usize);
+            return Ok(buffer);
+        }
+        buffer.resize(length as usize, 0);
+    }
+}
+
+/// Key of encrypted copies, derived with Argon2id from a passphrase and a
+/// salt that each file's header records.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    salt: [u8; SALT_LEN],
+    cipher: ChaCha20Poly1305,
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptionKey").field("salt", &self.salt).finish_non_exhaustive()
+    }
+}
+
+impl EncryptionKey {
+    /// A key for new copies, with a fresh random salt.
+    pub fn generate(passphrase: &[u8]) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(passphrase, salt)
+    }
+
+    /// The key the file at `path` was encrypted with, given its passphrase.
+    pub fn for_file(path: &Path, passphrase: &[u8]) -> Result<Self> {
+        let mut file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open encrypted file: {:?}", path))?;
+        let header = read_header(&mut file)?;
+        Self::derive(passphrase, header_salt(&header))
+    }
+
+    fn derive(passphrase: &[u8], salt: [u8; SALT_LEN]) -> Result<Self> {
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase, &salt, &mut key)
+            .map_err(|e| anyhow::anyhow!("Failed to derive the encryption key: {}", e))?;
+        Ok(Self { salt, cipher: ChaCha20Poly1305::new(&key.into()) })
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&[FORMAT_VERSION, CIPHER_CHACHA20_POLY1305]);
+        header.extend_from_slice(&self.salt);
+        header.extend_from_slice(&(CHUNK_SIZE as u32).to_le_bytes());
+        header
+    }
+}
+
+/// Chunks are bound to the file's header, their position and whether they
+/// are the last, so they can't be swapped, reordered or dropped unnoticed.
+fn chunk_aad(header: &[u8], index: u64, flags: u8) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(header.len() + 9);
+    aad.extend_from_slice(header);
+    aad.extend_from_slice(&index.to_le_bytes());
+    aad.push(flags);
+    aad
+}
+
+fn read_header(input: &mut impl Read) -> Result<Vec<u8>> {
+    let mut header = vec![0u8; HEADER_LEN];
+    input.read_exact(&mut header).context("Encrypted file is shorter than its header")?;
+    if &header[..MAGIC.len()] != MAGIC {
+        anyhow::bail!("Not a copyd encrypted file");
+    }
+    let (version, cipher) = (header[MAGIC.len()], header[MAGIC.len() + 1]);
+    if version != FORMAT_VERSION || cipher != CIPHER_CHACHA20_POLY1305 {
+        anyhow::bail!("Unsupported encrypted file format {} with cipher {}", version, cipher);
+    }
+    Ok(header)
+}
+
+fn header_salt(header: &[u8]) -> [u8; SALT_LEN] {
+    let start = MAGIC.len() + 2;
+    header[start..start + SALT_LEN].try_into().expect("header holds a salt")
+}
+
+/// Encrypts what is written to it into `inner`, a chunk at a time;
+/// [`Self::finish`] seals the rest along with the plaintext's SHA-256.
+pub struct EncryptingWriter<W: Write> {
+    inner: W,
+    cipher: ChaCha20Poly1305,
+    header: Vec<u8>,
+    pending: Vec<u8>,
+    chunk_index: u64,
+    plaintext_hash: Sha256,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    pub fn new(mut inner: W, key: &EncryptionKey) -> Result<Self> {
+        let header = key.header();
+        inner.write_all(&header)?;
+        Ok(Self {
+            inner,
+            cipher: key.cipher.clone(),
+            header,
+            pending: Vec::with_capacity(CHUNK_SIZE),
+            chunk_index: 0,
+            plaintext_hash: Sha256::new(),
+        })
+    }
+
+    fn seal(&mut self, plaintext: &[u8], flags: u8) -> std::io::Result<()> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = chunk_aad(&self.header, self.chunk_index, flags);
+        let sealed = self.cipher.encrypt(&nonce, Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| std::io::Error::other("Failed to encrypt a chunk"))?;
+        self.inner.write_all(&[flags])?;
+        self.inner.write_all(&nonce)?;
+        self.inner.write_all(&(sealed.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&sealed)?;
+        self.chunk_index += 1;
+        Ok(())
+    }
+
+    /// Seals what is still pending and the final chunk, returning `inner`.
+    pub fn finish(mut self) -> Result<W> {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.seal(&pending, 0)?;
+        }
+        let digest = self.plaintext_hash.clone().finalize();
+        self.seal(&digest, FINAL_CHUNK)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.plaintext_hash.update(data);
+        let mut rest = data;
+        while !rest.is_empty() {
+            let taken = (CHUNK_SIZE - self.pending.len()).min(rest.len());
+            self.pending.extend_from_slice(&rest[..taken]);
+            rest = &rest[taken..];
+            if self.pending.len() == CHUNK_SIZE {
+                let chunk = std::mem::replace(&mut self.pending, Vec::with_capacity(CHUNK_SIZE));
+                self.seal(&chunk, 0)?;
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts the encrypted file read from `input` into `output`, returning
+/// the plaintext's length. Fails when any chunk was altered, reordered or
+/// dropped, when the file was cut short, or when `key` is not the one it
+/// was encrypted with.
+pub fn decrypt(mut input: impl Read, key: &EncryptionKey, mut output: impl Write) -> Result<u64> {
+    let header = read_header(&mut input)?;
+    if header_salt(&header) != key.salt {
+        anyhow::bail!("File was encrypted with a different key");
+    }
+
+    let mut plaintext_hash = Sha256::new();
+    let mut length = 0u64;
+    let mut index = 0u64;
+    loop {
+        let mut frame = [0u8; 1 + NONCE_LEN + 4];
+        input.read_exact(&mut frame).context("Encrypted file is truncated")?;
+        let flags = frame[0];
+        let nonce = Nonce::from_slice(&frame[1..1 + NONCE_LEN]);
+        let sealed_len = u32::from_le_bytes(frame[1 + NONCE_LEN..].try_into().expect("four length bytes")) as usize;
+        if sealed_len > CHUNK_SIZE + TAG_LEN {
+            anyhow::bail!("Chunk {} of the encrypted file is corrupt", index);
+        }
+        let mut sealed = vec![0u8; sealed_len];
+        input.read_exact(&mut sealed).context("Encrypted file is truncated")?;
+
+        let aad = chunk_aad(&header, index, flags);
+        let plaintext = key.cipher.decrypt(nonce, Payload { msg: &sealed, aad: &aad })
+            .map_err(|_| anyhow::anyhow!("Chunk {} of the encrypted file failed authentication", index))?;
+        if flags == FINAL_CHUNK {
+            if plaintext != plaintext_hash.finalize().as_slice() {
+                anyhow::bail!("Decrypted content doesn't match the checksum stored with it");
+            }
+            if input.read(&mut [0u8; 1])? != 0 {
+                anyhow::bail!("Encrypted file has data after its final chunk");
+            }
+            return Ok(length);
+        }
+        plaintext_hash.update(&plaintext);
+        output.write_all(&plaintext)?;
+        length += plaintext.len() as u64;
+        index += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypt(data: &[u8], key: &EncryptionKey) -> Vec<u8> {
+        let mut writer = EncryptingWriter::new(Vec::new(), key).unwrap();
+        // Odd-sized writes, so chunks are filled across them
+        for piece in data.chunks(10_007) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        let key = EncryptionKey::generate(b"correct horse").unwrap();
+        for length in [0, 1, CHUNK_SIZE, 3 * CHUNK_SIZE + 17] {
+            let data: Vec<u8> = (0..length).map(|i| (i % 251) as u8).collect();
+            let encrypted = encrypt(&data, &key);
+            assert_eq!(encrypted.len() as u64, encrypted_len(length as u64));
+
+            let mut decrypted = Vec::new();
+            assert_eq!(decrypt(encrypted.as_slice(), &key, &mut decrypted).unwrap(), length as u64);
+            assert_eq!(decrypted, data);
+        }
+    }
+
+    #[test]
+    fn test_key_for_file_reads_its_salt() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("file.enc");
+        let key = EncryptionKey::generate(b"correct horse").unwrap();
+        std::fs::write(&path, encrypt(b"secret", &key)).unwrap();
+
+        let mut decrypted = Vec::new();
+        let key = EncryptionKey::for_file(&path, b"correct horse").unwrap();
+        decrypt(std::fs::File::open(&path).unwrap(), &key, &mut decrypted).unwrap();
+        assert_eq!(decrypted, b"secret");
+
+        let wrong = EncryptionKey::for_file(&path, b"wrong horse").unwrap();
+        assert!(decrypt(std::fs::File::open(&path).unwrap(), &wrong, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let key = EncryptionKey::generate(b"correct horse").unwrap();
+        let data = vec![7u8; 2 * CHUNK_SIZE + 100];
+        let encrypted = encrypt(&data, &key);
+        let frame_len = 1 + NONCE_LEN + 4;
+        let chunk_len = frame_len + CHUNK_SIZE + TAG_LEN;
+
+        // A flipped bit in the ciphertext
+        let mut flipped = encrypted.clone();
+        flipped[HEADER_LEN + chunk_len + frame_len + 5] ^= 1;
+        let err = decrypt(flipped.as_slice(), &key, &mut Vec::new()).unwrap_err();
+        assert!(err.to_string().contains("Chunk 1"), "{}", err);
+
+        // Chunks swapped
+        let mut swapped = encrypted.clone();
+        let (first, rest) = swapped[HEADER_LEN..].split_at_mut(chunk_len);
+        first.swap_with_slice(&mut rest[..chunk_len]);
+        assert!(decrypt(swapped.as_slice(), &key, &mut Vec::new()).is_err());
+
+        // Cut short, at a chunk boundary or within one
+        let without_final = &encrypted[..HEADER_LEN + 3 * chunk_len - CHUNK_SIZE + 100];
+        assert!(decrypt(without_final, &key, &mut Vec::new()).is_err());
+        assert!(decrypt(&without_final[..without_final.len() - 1], &key, &mut Vec::new()).is_err());
+        assert!(decrypt(&encrypted[..HEADER_LEN + 2 * chunk_len], &key, &mut Vec::new()).is_err());
+
+        // Trailing data
+        let mut extended = encrypted.clone();
+        extended.push(0);
+        assert!(decrypt(extended.as_slice(), &key, &mut Vec::new()).is_err());
+
+        assert!(decrypt(encrypted.as_slice(), &key, &mut Vec::new()).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// Comprehensive error types for copyd operations
@@ -14,6 +14,9 @@ pub enum CopydError {
     #[error("Invalid file path: {path}")]
     InvalidPath { path: PathBuf },
 
+    #[error("Destination paths too long for the filesystem: {}", .offenders.join("; "))]
+    PathTooLong { offenders: Vec<String> },
+
     #[error("Destination already exists: {path}")]
     DestinationExists { path: PathBuf },
 
@@ -191,6 +194,21 @@ impl From<prometheus::Error> for CopydError {
 }
 
 impl CopydError {
+    /// Maps a failed operation on `path` to the variant for its cause, so
+    /// missing files, permission problems and malformed paths each get
+    /// their own suggested action.
+    pub fn from_path_error(path: &Path, error: std::io::Error) -> Self {
+        let path = path.to_path_buf();
+        match error.kind() {
+            std::io::ErrorKind::NotFound => CopydError::FileNotFound { path },
+            std::io::ErrorKind::PermissionDenied => CopydError::PermissionDenied { path },
+            _ if matches!(error.raw_os_error(), Some(libc::ENOTDIR | libc::ELOOP | libc::ENAMETOOLONG | libc::EINVAL)) => {
+                CopydError::InvalidPath { path }
+            }
+            _ => CopydError::Filesystem { path, source: error },
+        }
+    }
+
     /// Check if this error is retryable
     pub fn is_retryable(&self) -> bool {
         matches!(
@@ -233,6 +251,12 @@ impl CopydError {
             CopydError::PermissionDenied { .. } => {
                 "Check file permissions or run with appropriate privileges"
             }
+            CopydError::InvalidPath { .. } => {
+                "Check the path for dangling symlinks or components that are not directories"
+            }
+            CopydError::PathTooLong { .. } => {
+                "Shorten the listed names, or copy to a shallower destination or a filesystem with longer limits"
+            }
             CopydError::DestinationExists { .. } => {
                 "Use --overwrite, --skip, or --serial to handle existing files"
             }
@@ -376,6 +400,26 @@ mod tests {
         assert!(error.exit_code() == 1);
     }
 
+    #[test]
+    fn test_path_errors_map_to_their_cause() {
+        let path = Path::new("/data/source");
+        let from_kind = |kind| CopydError::from_path_error(path, std::io::Error::from(kind));
+        let from_errno = |errno| CopydError::from_path_error(path, std::io::Error::from_raw_os_error(errno));
+
+        assert!(matches!(from_kind(std::io::ErrorKind::NotFound), CopydError::FileNotFound { path } if path == Path::new("/data/source")));
+        assert!(matches!(from_errno(libc::EACCES), CopydError::PermissionDenied { .. }));
+        assert!(matches!(from_errno(libc::ENOTDIR), CopydError::InvalidPath { .. }));
+        assert!(matches!(from_errno(libc::ELOOP), CopydError::InvalidPath { .. }));
+        assert!(matches!(from_errno(libc::EIO), CopydError::Filesystem { .. }));
+
+        let actions = [
+            from_kind(std::io::ErrorKind::NotFound).suggested_action(),
+            from_errno(libc::EACCES).suggested_action(),
+            from_errno(libc::ENOTDIR).suggested_action(),
+        ];
+        assert!(actions[0] != actions[1] && actions[1] != actions[2] && actions[0] != actions[2]);
+    }
+
     #[test]
     fn test_retryable_errors() {
         let retryable = CopydError::TemporaryFailure {
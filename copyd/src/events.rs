@@ -0,0 +1,373 @@
+use crate::job::Job;
+use copyd_protocol::{job_event, JobEvent, JobId, JobStatus};
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Events held for each consumer before progress updates start being
+/// dropped in favour of newer ones.
+pub const EVENT_QUEUE_CAPACITY: usize = 1024;
+
+/// Default shortest time between two progress updates of the same job.
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether a later event carries the same information, so this one can be
+/// dropped for a consumer that is falling behind. Status changes never are:
+/// a client must always learn how its job ended.
+fn is_coalescible(event: &JobEvent) -> bool {
+    matches!(
+        event.event_type,
+        Some(job_event::EventType::ProgressUpdate(_)) | Some(job_event::EventType::DirectoryProgress(_))
+    )
+}
+
+fn is_essential(event: &JobEvent) -> bool {
+    matches!(event.event_type, Some(job_event::EventType::StatusChange(_)))
+}
+
+struct QueueState {
+    events: VecDeque<JobEvent>,
+    dropped: u64,
+    closed: bool,
+}
+
+/// One consumer's pending events, bounded so a slow reader costs a fixed
+/// amount of memory rather than everything published since it fell behind.
+struct EventQueue {
+    state: Mutex<QueueState>,
+    ready: Notify,
+    capacity: usize,
+}
+
+impl EventQueue {
+    fn push(&self, event: JobEvent) {
+        let mut state = self.state.lock();
+        if state.events.len() >= self.capacity {
+            // Make room by dropping the oldest progress update, then the
+            // oldest log line or file error; status changes always stay
+            let victim = state.events.iter().position(is_coalescible)
+                .or_else(|| state.events.iter().position(|queued| !is_essential(queued)));
+            match victim {
+                Some(index) => {
+                    state.events.remove(index);
+                    state.dropped += 1;
+                }
+                None if !is_essential(&event) => {
+                    state.dropped += 1;
+                    return;
+                }
+                None => {}
+            }
+        }
+        state.events.push_back(event);
+        drop(state);
+        self.ready.notify_one();
+    }
+
+    fn close(&self) {
+        self.state.lock().closed = true;
+        self.ready.notify_one();
+    }
+}
+
+struct Subscribers {
+    queues: Mutex<Vec<Weak<EventQueue>>>,
+    capacity: usize,
+}
+
+impl Drop for Subscribers {
+    fn drop(&mut self) {
+        for queue in self.queues.get_mut().iter().filter_map(Weak::upgrade) {
+            queue.close();
+        }
+    }
+}
+
+/// Collapses each job's progress updates to at most one per interval.
+struct ProgressCoalescer {
+    interval: Duration,
+    last_sent: HashMap<String, Instant>,
+}
+
+impl ProgressCoalescer {
+    /// Whether a progress update of `job_id` may go out at `now`.
+    fn admit(&mut self, job_id: &str, now: Instant) -> bool {
+        match self.last_sent.get_mut(job_id) {
+            Some(last) if now.duration_since(*last) < self.interval => false,
+            Some(last) => {
+                *last = now;
+                true
+            }
+            None => {
+                self.last_sent.insert(job_id.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+/// Fans job events out to every [`EventReceiver`], each with its own
+/// bounded queue. Publishing never waits on a consumer.
+#[derive(Clone)]
+pub struct EventPublisher {
+    subscribers: Arc<Subscribers>,
+    progress: Arc<Mutex<ProgressCoalescer>>,
+}
+
+impl EventPublisher {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            subscribers: Arc::new(Subscribers { queues: Mutex::new(Vec::new()), capacity }),
+            progress: Arc::new(Mutex::new(ProgressCoalescer {
+                interval: DEFAULT_PROGRESS_INTERVAL,
+                last_sent: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Shortest time between two progress updates of the same job; zero
+    /// sends every update.
+    pub fn set_progress_interval(&self, interval: Duration) {
+        self.progress.lock().interval = interval;
+    }
+
+    /// Attaches a consumer that receives every event published from now on.
+    pub fn subscribe(&self) -> EventReceiver {
+        let queue = Arc::new(EventQueue {
+            state: Mutex::new(QueueState { events: VecDeque::new(), dropped: 0, closed: false }),
+            ready: Notify::new(),
+            capacity: self.subscribers.capacity,
+        });
+        self.subscribers.queues.lock().push(Arc::downgrade(&queue));
+        EventReceiver { queue }
+    }
+
+    pub fn send(&self, event: JobEvent) {
+        let mut queues = self.subscribers.queues.lock();
+        // Receivers that went away are forgotten on the next event
+        queues.retain(|queue| match queue.upgrade() {
+            Some(queue) => {
+                queue.push(event.clone());
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Sends `job`'s progress unless an update for it went out less than
+    /// the progress interval ago; the next one after that carries the
+    /// accumulated change.
+    pub fn send_progress(&self, job: &Job) {
+        if self.progress.lock().admit(&job.id, Instant::now()) {
+            self.send_progress_now(job);
+        }
+    }
+
+    /// Sends `job`'s progress regardless of the interval, for the final
+    /// update before a job ends.
+    pub fn send_progress_now(&self, job: &Job) {
+        self.send(JobEvent {
+            job_id: Some(JobId { uuid: job.id.clone() }),
+            event_type: Some(job_event::EventType::ProgressUpdate(job.progress_snapshot())),
+        });
+    }
+
+    pub fn send_source_removed(&self, job_id: &str, path: &Path) {
+        self.send(JobEvent {
+            job_id: Some(JobId { uuid: job_id.to_string() }),
+            event_type: Some(job_event::EventType::SourceRemoved(path.to_string_lossy().to_string())),
+        });
+    }
+
+    pub fn send_file_completed(&self, job_id: &str, path: &Path) {
+        self.send(JobEvent {
+            job_id: Some(JobId { uuid: job_id.to_string() }),
+            event_type: Some(job_event::EventType::FileCompleted(path.to_string_lossy().to_string())),
+        });
+    }
+
+    pub fn send_status(&self, job_id: &str, status: JobStatus) {
+        if status.is_terminal() {
+            self.progress.lock().last_sent.remove(job_id);
+        }
+        self.send(JobEvent {
+            job_id: Some(JobId { uuid: job_id.to_string() }),
+            event_type: Some(job_event::EventType::StatusChange(status.into())),
+        });
+    }
+}
+
+/// A consumer's end of an [`EventPublisher`].
+pub struct EventReceiver {
+    queue: Arc<EventQueue>,
+}
+
+impl EventReceiver {
+    /// Waits for the next event; `None` once every publisher is gone and
+    /// the queue is drained.
+    pub async fn recv(&mut self) -> Option<JobEvent> {
+        loop {
+            {
+                let mut state = self.queue.state.lock();
+                if let Some(event) = state.events.pop_front() {
+                    return Some(event);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            self.queue.ready.notified().await;
+        }
+    }
+
+    /// Events waiting to be received.
+    pub fn len(&self) -> usize {
+        self.queue.state.lock().events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Events dropped so far because this receiver fell behind.
+    pub fn dropped(&self) -> u64 {
+        self.queue.state.lock().dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copyd_protocol::Progress;
+    use std::time::Duration;
+
+    fn event(event_type: job_event::EventType) -> JobEvent {
+        JobEvent { job_id: Some(JobId { uuid: "job".to_string() }), event_type: Some(event_type) }
+    }
+
+    fn progress(bytes_copied: u64) -> JobEvent {
+        event(job_event::EventType::ProgressUpdate(Progress { bytes_copied, ..Default::default() }))
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_stays_bounded_and_keeps_status_changes() {
+        let publisher = EventPublisher::new(16);
+        let mut receiver = publisher.subscribe();
+
+        let producer = {
+            let publisher = publisher.clone();
+            tokio::spawn(async move {
+                publisher.send(event(job_event::EventType::StatusChange(JobStatus::Running.into())));
+                for bytes in 1..=10_000 {
+                    publisher.send(progress(bytes));
+                    if bytes % 1000 == 0 {
+                        tokio::task::yield_now().await;
+                    }
+                }
+                publisher.send(event(job_event::EventType::StatusChange(JobStatus::Completed.into())));
+            })
+        };
+
+        let mut statuses = Vec::new();
+        let mut last_bytes = 0;
+        let mut max_pending = 0;
+        while statuses.last() != Some(&i32::from(JobStatus::Completed)) {
+            let next = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+            max_pending = max_pending.max(receiver.len());
+            match next.event_type {
+                Some(job_event::EventType::StatusChange(status)) => statuses.push(status),
+                Some(job_event::EventType::ProgressUpdate(progress)) => {
+                    // Dropping never reorders what is kept
+                    assert!(progress.bytes_copied > last_bytes);
+                    last_bytes = progress.bytes_copied;
+                }
+                _ => unreachable!(),
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        producer.await.unwrap();
+
+        assert_eq!(statuses, vec![i32::from(JobStatus::Running), i32::from(JobStatus::Completed)]);
+        assert!(max_pending <= 16, "{} events pending", max_pending);
+        assert_eq!(last_bytes, 10_000);
+        assert!(receiver.dropped() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_progress_bursts_are_collapsed() {
+        let publisher = EventPublisher::new(EVENT_QUEUE_CAPACITY);
+        publisher.set_progress_interval(Duration::from_millis(20));
+        let mut receiver = publisher.subscribe();
+        let mut job = Job::new(copyd_protocol::CreateJobRequest {
+            sources: vec!["/src/file".to_string()],
+            destination: "/dst/file".to_string(),
+            ..Default::default()
+        });
+
+        // A burst of small byte deltas over about 100ms
+        let started = Instant::now();
+        for _ in 0..2000 {
+            job.progress.bytes_copied += 4096;
+            publisher.send_progress(&job);
+            if job.progress.bytes_copied.is_multiple_of(4096 * 100) {
+                publisher.send_file_completed(&job.id, Path::new("/src/file"));
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+        let elapsed = started.elapsed();
+        publisher.send_progress_now(&job);
+        publisher.send_status(&job.id, JobStatus::Completed);
+
+        let (mut progress_updates, mut completions, mut last) = (0, 0, None);
+        while let Some(next) = receiver.recv().await {
+            last = next.event_type.clone();
+            match next.event_type {
+                Some(job_event::EventType::ProgressUpdate(_)) => progress_updates += 1,
+                Some(job_event::EventType::FileCompleted(_)) => completions += 1,
+                _ => {}
+            }
+            if receiver.is_empty() {
+                break;
+            }
+        }
+        // One per elapsed interval, the first, and the final update
+        let bound = (elapsed.as_millis() / 20) as usize + 2;
+        assert!(progress_updates <= bound, "{} progress events for {:?}", progress_updates, elapsed);
+        assert!(progress_updates >= 2);
+        assert_eq!(completions, 20);
+        assert_eq!(last, Some(job_event::EventType::StatusChange(JobStatus::Completed.into())));
+    }
+
+    #[tokio::test]
+    async fn test_status_changes_survive_a_full_queue() {
+        let publisher = EventPublisher::new(2);
+        let mut receiver = publisher.subscribe();
+        publisher.send(event(job_event::EventType::StatusChange(JobStatus::Running.into())));
+        publisher.send(event(job_event::EventType::StatusChange(JobStatus::Paused.into())));
+        publisher.send(progress(1));
+        publisher.send(event(job_event::EventType::StatusChange(JobStatus::Cancelled.into())));
+        assert_eq!(receiver.dropped(), 1);
+
+        let mut statuses = Vec::new();
+        drop(publisher);
+        while let Some(next) = receiver.recv().await {
+            if let Some(job_event::EventType::StatusChange(status)) = next.event_type {
+                statuses.push(status);
+            }
+        }
+        assert_eq!(statuses, vec![
+            i32::from(JobStatus::Running),
+            i32::from(JobStatus::Paused),
+            i32::from(JobStatus::Cancelled),
+        ]);
+
+        // A receiver that went away stops costing the publisher anything
+        let publisher = EventPublisher::new(2);
+        drop(publisher.subscribe());
+        publisher.send(progress(1));
+        assert!(publisher.subscribers.queues.lock().is_empty());
+    }
+}
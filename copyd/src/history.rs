@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use copyd_protocol::{JobInfo, QueryJobsRequest};
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Record of finished jobs that outlives the daemon, kept as one JSON
+/// [`JobInfo`] per line so finishing a job only appends to the file.
+#[derive(Debug)]
+pub struct JobHistory {
+    path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl JobHistory {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, write_lock: Mutex::new(()) }
+    }
+
+    /// Appends a finished job to the history.
+    pub async fn record(&self, job: &JobInfo) -> Result<()> {
+        let mut line = serde_json::to_string(job)?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().await;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open job history {:?}", self.path))?;
+        file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Jobs matching `query`, most recently finished first, along with the
+    /// number of matches before `limit` and `offset` were applied.
+    pub async fn query(&self, query: &QueryJobsRequest) -> Result<(Vec<JobInfo>, u64)> {
+        let mut matches: Vec<JobInfo> = self.load().await?
+            .into_iter()
+            .filter(|job| query.job_id.is_empty() || job.job_id.as_ref().is_some_and(|id| id.uuid.starts_with(&query.job_id)))
+            .filter(|job| query.since == 0 || job.completed_at >= query.since)
+            .filter(|job| query.until == 0 || job.completed_at <= query.until)
+            .filter(|job| query.status.is_empty() || job.progress.as_ref().is_some_and(|p| query.status.contains(&p.status)))
+            .filter(|job| query.tags.iter().all(|tag| job.tags.contains(tag)))
+            .filter(|job| {
+                query.path_filter.is_empty()
+                    || job.destination.contains(&query.path_filter)
+                    || job.sources.iter().any(|source| source.contains(&query.path_filter))
+            })
+            .collect();
+        matches.sort_by_key(|job| std::cmp::Reverse(job.completed_at));
+
+        let total = matches.len() as u64;
+        let limit = if query.limit == 0 { usize::MAX } else { query.limit as usize };
+        let page = matches.into_iter().skip(query.offset as usize).take(limit).collect();
+        Ok((page, total))
+    }
+
+    /// Drops jobs that finished before `cutoff`, returning how many were removed.
+    pub async fn prune(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let removed = self.remove_where(|job| job.completed_at < cutoff.timestamp()).await?.len();
+        if removed > 0 {
+            info!("Pruned {} jobs finished before {} from the job history", removed, cutoff);
+        }
+        Ok(removed)
+    }
+
+    /// Drops the jobs `matches` picks, returning their IDs.
+    pub async fn remove_where(&self, matches: impl Fn(&JobInfo) -> bool) -> Result<Vec<String>> {
+        let _guard = self.write_lock.lock().await;
+        let (removed, kept): (Vec<JobInfo>, Vec<JobInfo>) = self.load().await?
+            .into_iter()
+            .partition(|job| matches(job));
+        if removed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut content = String::new();
+        for job in &kept {
+            content.push_str(&serde_json::to_string(job)?);
+            content.push('\n');
+        }
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, content).await?;
+        fs::rename(&temp_path, &self.path).await?;
+
+        Ok(removed.into_iter().filter_map(|job| job.job_id.map(|id| id.uuid)).collect())
+    }
+
+    async fn load(&self) -> Result<Vec<JobInfo>> {
+        let content = match fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read job history {:?}", self.path)),
+        };
+
+        let mut jobs = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A crash mid-append leaves at most one partial line behind
+            match serde_json::from_str::<JobInfo>(line) {
+                Ok(job) => jobs.push(job),
+                Err(e) => warn!("Skipping line {} of job history {:?}: {}", index + 1, self.path, e),
+            }
+        }
+        Ok(jobs)
+    }
+}
@@ -0,0 +1,140 @@
+use crate::verify::StreamHasher;
+use anyhow::{Context, Result};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Times a dropped download is picked up again with a Range request before
+/// the job gives up on it.
+const MAX_RESUMES: u32 = 5;
+/// Wait before each resume, growing with every further one.
+const RESUME_DELAY: Duration = Duration::from_millis(200);
+
+/// Whether a job source names a file to download rather than a local path.
+pub fn is_url(source: &str) -> bool {
+    let lower = source.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// Name a download gets inside a destination directory: the last segment
+/// of the URL's path.
+pub fn file_name(url: &str) -> Result<String> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("Invalid URL {}", url))?;
+    parsed.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .map(str::to_string)
+        .with_context(|| format!("URL {} does not end in a file name", url))
+}
+
+/// A file streamed from an HTTP(S) server. When the connection drops
+/// partway, the rest is requested with a Range from the bytes already
+/// received, so a large download doesn't start over.
+pub struct HttpDownload {
+    client: reqwest::Client,
+    url: String,
+    response: reqwest::Response,
+    total: Option<u64>,
+    received: u64,
+    resumes: u32,
+    hasher: StreamHasher,
+}
+
+impl HttpDownload {
+    /// Requests `url`, checksumming what arrives with `hasher`.
+    pub async fn start(client: &reqwest::Client, url: &str, hasher: StreamHasher) -> Result<Self> {
+        let response = client.get(url).send().await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to download {}", url))?;
+        Ok(Self {
+            client: client.clone(),
+            url: url.to_string(),
+            total: response.content_length(),
+            response,
+            received: 0,
+            resumes: 0,
+            hasher,
+        })
+    }
+
+    /// Length the server announced, when it did.
+    pub fn total_bytes(&self) -> Option<u64> {
+        self.total
+    }
+
+    pub fn resumes(&self) -> u32 {
+        self.resumes
+    }
+
+    /// Writes the next chunk of the body to `file` and returns its length;
+    /// 0 once the whole file has arrived.
+    pub async fn write_next(&mut self, file: &mut tokio::fs::File) -> Result<usize> {
+        loop {
+            let interruption = match self.response.chunk().await {
+                Ok(Some(chunk)) => {
+                    file.write_all(&chunk).await?;
+                    self.hasher.update(&chunk);
+                    self.received += chunk.len() as u64;
+                    return Ok(chunk.len());
+                }
+                Ok(None) => match self.total {
+                    Some(total) if self.received < total => {
+                        format!("connection closed after {} of {} bytes", self.received, total)
+                    }
+                    _ => return Ok(0),
+                },
+                Err(e) => format!("{:#}", e),
+            };
+            self.resume(&interruption).await?;
+        }
+    }
+
+    async fn resume(&mut self, interruption: &str) -> Result<()> {
+        if self.resumes >= MAX_RESUMES {
+            anyhow::bail!("Download of {} failed after {} resumes: {}", self.url, self.resumes, interruption);
+        }
+        self.resumes += 1;
+        warn!("Download of {} interrupted ({}); resuming at byte {}", self.url, interruption, self.received);
+        tokio::time::sleep(RESUME_DELAY * self.resumes).await;
+
+        let response = self.client.get(&self.url)
+            .header(RANGE, format!("bytes={}-", self.received))
+            .send().await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to resume download of {}", self.url))?;
+        let expected = format!("bytes {}-", self.received);
+        let resumes_here = response.status() == StatusCode::PARTIAL_CONTENT
+            && response.headers().get(CONTENT_RANGE)
+                .and_then(|range| range.to_str().ok())
+                .is_some_and(|range| range.starts_with(&expected));
+        if !resumes_here {
+            anyhow::bail!("{} cannot resume: the server ignored the Range request ({})", self.url, interruption);
+        }
+        self.response = response;
+        Ok(())
+    }
+
+    /// Bytes received and their checksum, once `write_next` has returned 0.
+    pub fn finish(self) -> (u64, String) {
+        (self.received, self.hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_sources() {
+        assert!(is_url("https://example.com/file.iso"));
+        assert!(is_url("HTTP://example.com/file.iso"));
+        assert!(!is_url("/srv/https://file"));
+        assert!(!is_url("ftp://example.com/file"));
+
+        assert_eq!(file_name("https://example.com/releases/v1/app.tar.gz?token=1").unwrap(), "app.tar.gz");
+        assert!(file_name("https://example.com/").is_err());
+        assert!(file_name("not a url").is_err());
+    }
+}
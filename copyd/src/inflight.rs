@@ -0,0 +1,142 @@
+use crate::error::CopydError;
+use crate::numa::CopyBuffer;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// Daemon-wide budget for copy buffers that are allocated at the same time.
+///
+/// Each copy reserves the bytes for its buffers before allocating them and
+/// waits while the budget is exhausted, so many parallel copies with large
+/// block sizes queue up instead of exhausting memory.
+#[derive(Debug, Clone)]
+pub struct InflightBudget {
+    inner: Option<Arc<BudgetInner>>,
+}
+
+#[derive(Debug)]
+struct BudgetInner {
+    semaphore: Arc<Semaphore>,
+    capacity: u64,
+    in_use: AtomicU64,
+    peak: AtomicU64,
+}
+
+/// Bytes reserved from an [`InflightBudget`], released on drop.
+#[derive(Debug)]
+pub struct InflightPermit {
+    bytes: u64,
+    inner: Option<Arc<BudgetInner>>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl InflightPermit {
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for InflightPermit {
+    fn drop(&mut self) {
+        if let Some(inner) = &self.inner {
+            inner.in_use.fetch_sub(self.bytes, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Default for InflightBudget {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+impl InflightBudget {
+    /// Creates a budget of `max_bytes`; zero means unlimited.
+    pub fn new(max_bytes: u64) -> Self {
+        if max_bytes == 0 {
+            return Self::unlimited();
+        }
+
+        let permits = max_bytes.min(Semaphore::MAX_PERMITS as u64);
+        Self {
+            inner: Some(Arc::new(BudgetInner {
+                semaphore: Arc::new(Semaphore::new(permits as usize)),
+                capacity: permits,
+                in_use: AtomicU64::new(0),
+                peak: AtomicU64::new(0),
+            })),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self { inner: None }
+    }
+
+    pub fn capacity(&self) -> Option<u64> {
+        self.inner.as_ref().map(|inner| inner.capacity)
+    }
+
+    /// Bytes currently reserved by running copies.
+    pub fn in_use(&self) -> u64 {
+        self.inner.as_ref().map_or(0, |inner| inner.in_use.load(Ordering::SeqCst))
+    }
+
+    /// Highest number of bytes reserved at once since the budget was created.
+    pub fn peak(&self) -> u64 {
+        self.inner.as_ref().map_or(0, |inner| inner.peak.load(Ordering::SeqCst))
+    }
+
+    /// Reserves `bytes`, waiting for other copies to release theirs if needed.
+    pub async fn acquire(&self, bytes: u64) -> Result<InflightPermit, CopydError> {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return Ok(InflightPermit { bytes, inner: None, _permit: None }),
+        };
+
+        if bytes > inner.capacity || bytes > u32::MAX as u64 {
+            return Err(CopydError::ResourceLimitExceeded {
+                resource: "max_inflight_bytes".to_string(),
+                limit: inner.capacity,
+                current: bytes,
+            });
+        }
+
+        let permit = inner.semaphore.clone()
+            .acquire_many_owned(bytes as u32)
+            .await
+            .map_err(|_| CopydError::MemoryAllocationFailed { size: bytes as usize })?;
+
+        let in_use = inner.in_use.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        inner.peak.fetch_max(in_use, Ordering::SeqCst);
+        debug!("Reserved {} inflight bytes ({} in use)", bytes, in_use);
+
+        Ok(InflightPermit {
+            bytes,
+            inner: Some(inner.clone()),
+            _permit: Some(permit),
+        })
+    }
+
+    /// Reserves room for up to `count` buffers of `buffer_size` bytes, using
+    /// fewer buffers when the whole set would never fit in the budget.
+    /// Returns the permit and the number of buffers it covers.
+    pub async fn acquire_buffers(&self, count: usize, buffer_size: usize) -> Result<(InflightPermit, usize), CopydError> {
+        let count = count.max(1);
+        let fitting = match self.capacity() {
+            Some(capacity) => (capacity / buffer_size.max(1) as u64).min(count as u64) as usize,
+            None => count,
+        };
+
+        // Even a single buffer is larger than the whole budget
+        let fitting = fitting.max(1);
+        let permit = self.acquire((fitting * buffer_size) as u64).await?;
+        Ok((permit, fitting))
+    }
+}
+
+/// Allocates a zeroed buffer, reporting allocation failure instead of aborting.
+pub fn allocate_buffer(size: usize, numa_aware: bool) -> Result<CopyBuffer, CopydError> {
+    CopyBuffer::new(size, numa_aware)
+        .ok_or(CopydError::MemoryAllocationFailed { size })
+}
@@ -3,15 +3,19 @@ use std::path::Path;
 use std::os::unix::io::AsRawFd;
 use std::time::Instant;
 use tracing::{info, debug};
-use io_uring::{IoUring, opcode, types};
+use io_uring::{IoUring, opcode, squeue, types};
 use std::io::{IoSlice, IoSliceMut};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use crate::inflight::{allocate_buffer, InflightBudget};
+use crate::numa::CopyBuffer;
 
 pub struct IoUringCopyEngine {
     ring: IoUring,
     max_concurrent_ops: usize,
     buffer_size: usize,
+    inflight_budget: InflightBudget,
+    numa_buffers: bool,
 }
 
 #[derive(Debug)]
@@ -41,14 +45,55 @@ impl IoUringCopyEngine {
             ring,
             max_concurrent_ops: queue_depth as usize,
             buffer_size: buffer_size.unwrap_or(1024 * 1024), // 1MB default
+            inflight_budget: InflightBudget::unlimited(),
+            numa_buffers: false,
         })
     }
 
+    /// Allocate ring buffers on the NUMA node of the CPU running the copy.
+    pub fn with_numa_buffers(mut self, enabled: bool) -> Self {
+        self.numa_buffers = enabled;
+        self
+    }
+
+    /// Keeps up to `ops` reads and writes in flight, even more than the ring
+    /// has submission entries; pushes then wait for the queue to drain.
+    pub fn with_max_concurrent_ops(mut self, ops: usize) -> Self {
+        self.max_concurrent_ops = ops.max(1);
+        self
+    }
+
+    /// Shares a daemon-wide budget for the ring's read buffers.
+    pub fn with_inflight_budget(mut self, budget: InflightBudget) -> Self {
+        self.inflight_budget = budget;
+        self
+    }
+
     pub fn is_io_uring_available() -> bool {
         // Try to create a minimal io_uring to test availability
         IoUring::new(1).is_ok()
     }
 
+    /// Pushes `entry`, submitting what's already queued whenever the
+    /// submission queue is full rather than failing the copy.
+    ///
+    /// # Safety
+    ///
+    /// The buffers `entry` points at must stay valid until it completes.
+    unsafe fn push_entry(&mut self, entry: &squeue::Entry, operation: &str) -> Result<()> {
+        loop {
+            if unsafe { self.ring.submission().push(entry) }.is_ok() {
+                return Ok(());
+            }
+            let submitted = self.ring.submit()
+                .with_context(|| format!("Failed to submit queued operations to make room for {}", operation))?;
+            if submitted == 0 {
+                return Err(anyhow::anyhow!("io_uring submission queue stayed full while pushing {}", operation));
+            }
+            debug!("Submission queue full, submitted {} operations before pushing {}", submitted, operation);
+        }
+    }
+
     pub async fn copy_file_async(
         &mut self,
         source: &Path,
@@ -82,20 +127,24 @@ impl IoUringCopyEngine {
 
         // Use multiple buffers for better parallelism
         let num_buffers = std::cmp::min(self.max_concurrent_ops, 8);
-        let mut buffers: Vec<Vec<u8>> = (0..num_buffers)
-            .map(|_| vec![0u8; self.buffer_size])
-            .collect();
+        let (_permit, num_buffers) = self.inflight_budget.acquire_buffers(num_buffers, self.buffer_size).await?;
+        let mut buffers: Vec<CopyBuffer> = (0..num_buffers)
+            .map(|_| allocate_buffer(self.buffer_size, self.numa_buffers))
+            .collect::<Result<_, _>>()?;
+        // Completions arrive out of order, so a buffer is reused only once
+        // its write has finished
+        let mut free_buffers: Vec<usize> = (0..buffers.len()).rev().collect();
 
         let mut offset = 0u64;
         let mut pending_ops = 0;
-        let mut buffer_index = 0;
         
         let total_read_latency = Arc::new(AtomicU64::new(0));
         let total_write_latency = Arc::new(AtomicU64::new(0));
 
         while offset < file_size || pending_ops > 0 {
             // Submit read operations
-            while offset < file_size && pending_ops < self.max_concurrent_ops && buffer_index < buffers.len() {
+            while offset < file_size && pending_ops < self.max_concurrent_ops && !free_buffers.is_empty() {
+                let buffer_index = free_buffers.pop().unwrap_or_default();
                 let read_size = std::cmp::min(self.buffer_size as u64, file_size - offset);
                 
                 let read_entry = opcode::Read::new(
@@ -108,13 +157,10 @@ impl IoUringCopyEngine {
                 .user_data(Self::encode_user_data(true, buffer_index as u64, offset));
 
                 unsafe {
-                    self.ring.submission()
-                        .push(&read_entry)
-                        .with_context(|| "Failed to push read operation")?;
+                    self.push_entry(&read_entry, "read operation")?;
                 }
 
                 offset += read_size;
-                buffer_index = (buffer_index + 1) % buffers.len();
                 pending_ops += 1;
             }
 
@@ -154,14 +200,18 @@ impl IoUringCopyEngine {
                         .user_data(Self::encode_user_data(false, buf_idx, file_offset));
 
                         unsafe {
-                            self.ring.submission()
-                                .push(&write_entry)
-                                .with_context(|| "Failed to push write operation")?;
+                            self.push_entry(&write_entry, "write operation")?;
                         }
+                    } else {
+                        // The file shrank under us: nothing left to write
+                        free_buffers.push(buf_idx as usize);
+                        pending_ops -= 1;
                     }
                 } else {
                     stats.bytes_written += bytes_transferred;
+                    crate::copy_engine::report_written(bytes_transferred);
                     stats.write_ops += 1;
+                    free_buffers.push(buf_idx as usize);
                     pending_ops -= 1;
                 }
             }
@@ -185,9 +235,7 @@ impl IoUringCopyEngine {
             .user_data(u64::MAX); // Special marker for fsync
 
         unsafe {
-            self.ring.submission()
-                .push(&fsync_entry)
-                .with_context(|| "Failed to push fsync operation")?;
+            self.push_entry(&fsync_entry, "fsync operation")?;
         }
         
         self.ring.submit_and_wait(1)?;
@@ -246,6 +294,7 @@ impl IoUringCopyEngine {
         };
 
         // Create vectored buffers
+        let _permit = self.inflight_budget.acquire((2 * vector_size * chunk_size) as u64).await?;
         let mut buffer_vecs: Vec<Vec<Vec<u8>>> = (0..2)
             .map(|_| (0..vector_size).map(|_| vec![0u8; chunk_size]).collect())
             .collect();
@@ -275,7 +324,7 @@ impl IoUringCopyEngine {
             .user_data(1); // Mark as read operation
 
             unsafe {
-                self.ring.submission().push(&readv_entry)?;
+                self.push_entry(&readv_entry, "vectored read")?;
             }
             self.ring.submit_and_wait(1)?;
 
@@ -307,7 +356,7 @@ impl IoUringCopyEngine {
                 .user_data(2); // Mark as write operation
 
                 unsafe {
-                    self.ring.submission().push(&writev_entry)?;
+                    self.push_entry(&writev_entry, "vectored write")?;
                 }
                 self.ring.submit_and_wait(1)?;
 
@@ -318,6 +367,7 @@ impl IoUringCopyEngine {
                         return Err(anyhow::anyhow!("Vectored write failed: {}", write_cqe.result()));
                     }
                     stats.bytes_written += write_cqe.result() as u64;
+                    crate::copy_engine::report_written(write_cqe.result() as u64);
                     stats.write_ops += 1;
                 }
             }
@@ -415,4 +465,27 @@ mod tests {
         assert!(stats.read_ops > 0);
         assert!(stats.write_ops > 0);
     }
+
+    #[tokio::test]
+    async fn test_io_uring_copy_with_more_ops_in_flight_than_queue_entries() {
+        if !IoUringCopyEngine::is_io_uring_available() {
+            return; // Skip test if io_uring not available
+        }
+
+        // Eight reads in flight through a two-entry submission queue
+        let mut engine = IoUringCopyEngine::new(2, Some(4 * 1024)).unwrap()
+            .with_max_concurrent_ops(8);
+        assert_eq!(engine.get_ring_stats().0, 2);
+
+        let mut source_file = NamedTempFile::new().unwrap();
+        let test_data: Vec<u8> = (0..200 * 1024u32).map(|i| (i % 251) as u8).collect();
+        source_file.write_all(&test_data).unwrap();
+        let dest_file = NamedTempFile::new().unwrap();
+
+        let stats = engine.copy_file_async(source_file.path(), dest_file.path(), None).await.unwrap();
+
+        assert_eq!(stats.bytes_written, test_data.len() as u64);
+        assert_eq!(stats.read_ops, 50);
+        assert_eq!(std::fs::read(dest_file.path()).unwrap(), test_data);
+    }
 } 
\ No newline at end of file
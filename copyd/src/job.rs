@@ -1,15 +1,32 @@
 use copyd_protocol::*;
-use crate::copy_engine::{CopyOptions, FileCopyEngine};
-use crate::directory::DirectoryHandler;
-use crate::checkpoint::{CheckpointManager, JobCheckpoint};
+use crate::bandwidth::{BandwidthCalibrator, BandwidthProbe};
+use crate::compress::Codec;
+use crate::copy_engine::{CopyOptions, CopyOutcome, FileCopyEngine, MAX_VERIFY_RETRIES};
+use crate::encrypt::EncryptionKey;
+use crate::directory::{AnalysisCounter, DirectoryHandler, DirectoryTraversal, FileEntry};
+use crate::checkpoint::{can_resume_file, create_file_id, CheckpointManager, FileCheckpoint, JobCheckpoint, QueuedJobCheckpoint, SourceStamp};
+use crate::history::JobHistory;
+use crate::results::JobResults;
+use crate::http_source::{self, HttpDownload};
+use crate::inflight::InflightBudget;
+use crate::packed::PackedWriter;
+use crate::monitor::EnhancedMonitor;
+use crate::profiler::PerformanceProfiler;
+use crate::dedup::DedupIndexes;
+use crate::error::CopydError;
+use crate::events::{EventPublisher, EventReceiver, EVENT_QUEUE_CAPACITY};
+use crate::verify::{FileVerifier, StreamHasher};
 use anyhow::{Result, Context};
-use std::collections::{HashMap, VecDeque};
+use futures::StreamExt;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use crate::queue::{JobQueue, RESUMED_PRIORITY};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Instant;
-use tokio::sync::{RwLock, mpsc, Semaphore};
+use tokio::sync::{RwLock, Semaphore};
 use tokio::time::{interval, Duration};
-use tracing::{info, error};
+use tracing::{debug, info, warn, error};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
@@ -17,6 +34,8 @@ use chrono::{DateTime, Utc};
 pub struct Job {
     pub id: String,
     pub sources: Vec<PathBuf>,
+    /// HTTP(S) sources, downloaded instead of copied
+    pub urls: Vec<String>,
     pub destination: PathBuf,
     pub options: JobOptions,
     pub progress: Progress,
@@ -24,9 +43,34 @@ pub struct Job {
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub priority: u32,
+    pub tags: Vec<String>,
+    /// Why the job exists, in its creator's words; empty when not given
+    pub description: String,
     pub log_entries: Vec<String>,
+    pub rate_sampler: RateSampler,
+    pub error_summary: ErrorSummary,
+    /// Bytes written of files still being copied, shown as copied before
+    /// the files are done
+    pub copying: Arc<AtomicU64>,
+    /// The request the job was created from, kept so it can be replayed
+    pub request: Option<CreateJobRequest>,
 }
 
+/// Trims tags and drops empty and repeated ones, keeping their order.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !normalized.iter().any(|t| t == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+/// Failing paths kept per job; later failures are only counted.
+pub const MAX_REPORTED_ERRORS: usize = 20;
+
 #[derive(Debug, Clone)]
 pub struct JobOptions {
     pub recursive: bool,
@@ -36,6 +80,8 @@ pub struct JobOptions {
     pub verify: VerifyMode,
     pub exists_action: ExistsAction,
     pub max_rate_bps: Option<u64>,
+    /// Percentage of the destination device's bandwidth to use at most
+    pub max_rate_percent: Option<u32>,
     pub engine: CopyEngine,
     pub dry_run: bool,
     pub regex_rename_match: Option<String>,
@@ -43,12 +89,136 @@ pub struct JobOptions {
     pub block_size: Option<u64>,
     pub compress: bool,
     pub encrypt: bool,
+    /// Keyring entry holding the passphrase of encrypted copies
+    pub key_id: Option<String>,
+    pub delete_extraneous: bool,
+    pub delete_dry_run: bool,
+    pub skip_destinations: HashSet<PathBuf>,
+    pub device_source: bool,
+    pub atime: AtimeMode,
+    /// Compare existing destination files with their sources instead of copying
+    pub verify_only: bool,
+    pub class: JobClass,
+    pub xattr_namespaces: Vec<XattrNamespace>,
+    /// Read FIFO sources to EOF into regular files
+    pub stream_fifo: bool,
+    /// Create missing parent directories of destination files
+    pub create_parents: bool,
+    /// Permission bits for every destination file and directory
+    pub chmod: Option<u32>,
+    /// Create the directory tree only, copying no files
+    pub dirs_only: bool,
+    /// Whether a failed file is skipped, retried or fails the job
+    pub error_policy: ErrorPolicy,
+    /// Copy small files through a [`PackedWriter`]
+    pub packed: bool,
+    /// Stage the whole tree and swap it into place only on success
+    pub atomic: bool,
+    /// Where an atomic copy stages its tree, instead of beside the target
+    pub temp_dir: Option<PathBuf>,
+    /// Further destinations written from the same reads as the job's own
+    pub mirrors: Vec<PathBuf>,
+    /// What to do when a source file is written to while it is copied
+    pub on_source_change: SourceChangeAction,
+    /// Skip metadata the daemon lacks the privileges to set
+    pub best_effort_metadata: bool,
+    /// Files copied at once
+    pub copy_parallelism: usize,
+    /// Link files to identical content written by earlier jobs
+    pub dedup: bool,
+    /// Times a file that fails verification is copied again before it fails
+    pub verify_retries: u32,
+    /// Whether the sources are removed once copied
+    pub operation: JobOperation,
+    /// Bytes copied between checkpoint saves; zero saves on time alone
+    pub checkpoint_interval_bytes: u64,
+    /// Seconds between checkpoint saves; zero saves on bytes alone
+    pub checkpoint_interval_secs: u64,
+    /// Verify copies at idle I/O priority
+    pub idle_io_verification: bool,
+    /// Work of verifying a byte relative to copying it, for overall progress
+    pub verify_cost: f64,
+}
+
+const DEFAULT_CHECKPOINT_INTERVAL_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_CHECKPOINT_INTERVAL_SECS: u64 = 5;
+/// Verifying a byte rereads both copies, about the work of copying it.
+pub const DEFAULT_VERIFY_COST: f64 = 1.0;
+/// Files a single job may copy at once unless the daemon says otherwise.
+pub const DEFAULT_MAX_COPY_PARALLELISM: usize = 16;
+
+/// Number of rate samples retained per job (one minute at the default interval).
+const RATE_SAMPLE_CAPACITY: usize = 60;
+const RATE_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the files and bytes found by a running analysis are published.
+const ANALYSIS_REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often a shutdown checks whether the running jobs have finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Fixed-size history of per-job transfer rates.
+///
+/// Ops are counted as completed file operations, so a stalled job shows up as
+/// a run of zero samples even while a single large file is in flight.
+#[derive(Debug, Clone)]
+pub struct RateSampler {
+    samples: VecDeque<RateSample>,
+    capacity: usize,
+    last_bytes: u64,
+    last_ops: u64,
+}
+
+impl Default for RateSampler {
+    fn default() -> Self {
+        Self::with_capacity(RATE_SAMPLE_CAPACITY)
+    }
+}
+
+impl RateSampler {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            last_bytes: 0,
+            last_ops: 0,
+        }
+    }
+
+    /// Converts the deltas observed over `elapsed` into a sample, evicting the
+    /// oldest sample once the buffer is full.
+    pub fn record(&mut self, bytes_delta: u64, ops_delta: u64, elapsed: Duration) -> Option<RateSample> {
+        let secs = elapsed.as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+
+        let sample = RateSample {
+            timestamp: Utc::now().timestamp(),
+            throughput_mbps: bytes_delta as f64 / secs / 1024.0 / 1024.0,
+            ops_per_second: ops_delta as f64 / secs,
+        };
+
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample.clone());
+        Some(sample)
+    }
+
+    /// Samples from oldest to newest.
+    pub fn samples(&self) -> Vec<RateSample> {
+        self.samples.iter().cloned().collect()
+    }
 }
 
 impl Job {
     pub fn new(request: CreateJobRequest) -> Self {
         let id = Uuid::new_v4().to_string();
-        let sources = request.sources.into_iter().map(PathBuf::from).collect();
+        let submitted = request.clone();
+        let (urls, sources): (Vec<String>, Vec<String>) = request.sources.into_iter()
+            .partition(|source| http_source::is_url(source));
+        let sources = sources.into_iter().map(PathBuf::from).collect();
         let destination = PathBuf::from(request.destination);
         
         let options = JobOptions {
@@ -59,6 +229,7 @@ impl Job {
             verify: VerifyMode::try_from(request.verify).unwrap_or(VerifyMode::None),
             exists_action: ExistsAction::try_from(request.exists_action).unwrap_or(ExistsAction::Overwrite),
             max_rate_bps: if request.max_rate_bps > 0 { Some(request.max_rate_bps) } else { None },
+            max_rate_percent: (request.max_rate_percent > 0).then_some(request.max_rate_percent),
             engine: CopyEngine::try_from(request.engine).unwrap_or(CopyEngine::Auto),
             dry_run: request.dry_run,
             regex_rename_match: if request.regex_rename_match.is_empty() { None } else { Some(request.regex_rename_match) },
@@ -66,11 +237,42 @@ impl Job {
             block_size: if request.block_size > 0 { Some(request.block_size) } else { None },
             compress: request.compress,
             encrypt: request.encrypt,
+            key_id: Some(request.key_id.clone()).filter(|key_id| !key_id.is_empty()),
+            delete_extraneous: request.delete_extraneous,
+            delete_dry_run: request.delete_dry_run,
+            skip_destinations: request.skip_destinations.into_iter().map(PathBuf::from).collect(),
+            device_source: request.device_source,
+            atime: AtimeMode::try_from(request.atime).unwrap_or(AtimeMode::Preserve),
+            verify_only: false,
+            class: JobClass::try_from(request.job_class).unwrap_or(JobClass::Foreground),
+            xattr_namespaces: request.xattr_namespaces.into_iter()
+                .filter_map(|namespace| XattrNamespace::try_from(namespace).ok())
+                .collect(),
+            stream_fifo: request.stream_fifo,
+            create_parents: request.create_parents,
+            chmod: request.chmod,
+            dirs_only: request.dirs_only,
+            error_policy: ErrorPolicy::try_from(request.error_policy).unwrap_or(ErrorPolicy::SkipFile),
+            packed: request.packed,
+            atomic: request.atomic,
+            temp_dir: (!request.temp_dir.is_empty()).then(|| PathBuf::from(&request.temp_dir)),
+            mirrors: request.destinations.into_iter().map(PathBuf::from).collect(),
+            on_source_change: SourceChangeAction::try_from(request.on_source_change).unwrap_or(SourceChangeAction::IgnoreChange),
+            best_effort_metadata: request.best_effort_metadata,
+            copy_parallelism: (request.copy_parallelism as usize).max(1),
+            dedup: request.dedup,
+            verify_retries: request.verify_retries,
+            operation: JobOperation::try_from(request.operation_type).unwrap_or(JobOperation::Copy),
+            checkpoint_interval_bytes: DEFAULT_CHECKPOINT_INTERVAL_BYTES,
+            checkpoint_interval_secs: DEFAULT_CHECKPOINT_INTERVAL_SECS,
+            idle_io_verification: false,
+            verify_cost: DEFAULT_VERIFY_COST,
         };
 
         Self {
             id,
             sources,
+            urls,
             destination,
             options,
             progress: Progress {
@@ -81,12 +283,25 @@ impl Job {
                 throughput_mbps: 0.0,
                 eta_seconds: 0,
                 status: JobStatus::Pending.into(),
+                directories_created: 0,
+                total_directories: 0,
+                bytes_verified: 0,
+                overall_percent: 0.0,
+                bytes_analyzed: 0,
+                files_analyzed: 0,
+                files_verified: 0,
             },
             created_at: Utc::now(),
             started_at: None,
             completed_at: None,
             priority: request.priority,
+            tags: normalize_tags(request.tags),
+            description: request.description.trim().to_string(),
             log_entries: Vec::new(),
+            rate_sampler: RateSampler::default(),
+            error_summary: ErrorSummary::default(),
+            copying: Arc::default(),
+            request: Some(submitted),
         }
     }
 
@@ -99,36 +314,440 @@ impl Job {
         }
     }
 
+    /// Counts a failed file, keeping its path and reason for the first
+    /// [`MAX_REPORTED_ERRORS`] failures.
+    pub fn record_file_error(&mut self, error: FileError) {
+        self.error_summary.error_count += 1;
+        if self.error_summary.errors.len() < MAX_REPORTED_ERRORS {
+            self.error_summary.errors.push(error);
+        }
+    }
+
+    /// Records a rate sample from the progress made since the previous sample.
+    pub fn sample_rates(&mut self, elapsed: Duration) {
+        let bytes_copied = self.bytes_copied();
+        let bytes_delta = bytes_copied.saturating_sub(self.rate_sampler.last_bytes);
+        let ops_delta = self.progress.files_copied.saturating_sub(self.rate_sampler.last_ops);
+        self.rate_sampler.last_bytes = bytes_copied;
+        self.rate_sampler.last_ops = self.progress.files_copied;
+
+        if let Some(sample) = self.rate_sampler.record(bytes_delta, ops_delta, elapsed) {
+            self.progress.throughput_mbps = sample.throughput_mbps;
+        }
+
+        // At the average rate since the job started, over the same work as
+        // the overall percentage, so verification is included
+        let (done, work) = self.work_done();
+        let running_for = self.started_at.and_then(|started| (Utc::now() - started).to_std().ok());
+        self.progress.eta_seconds = running_for
+            .and_then(|running_for| DirectoryHandler::estimate_completion_time(done as u64, work as u64, running_for))
+            .map_or(0, |eta| eta.as_secs().max(1) as i64);
+    }
+
+    /// Work done and to do in bytes, with each verified byte counting
+    /// `verify_cost` times for jobs that verify.
+    fn work_done(&self) -> (f64, f64) {
+        let verify_cost = if self.options.verify != VerifyMode::None && !self.options.verify_only {
+            self.options.verify_cost
+        } else {
+            0.0
+        };
+        let work = self.progress.total_bytes as f64 * (1.0 + verify_cost);
+        let done = self.bytes_copied() as f64 + self.progress.bytes_verified as f64 * verify_cost;
+        (done, work)
+    }
+
+    /// Bytes of the files copied and of those being copied.
+    fn bytes_copied(&self) -> u64 {
+        self.progress.bytes_copied + self.copying.load(Ordering::SeqCst)
+    }
+
+    /// Progress with `overall_percent` filled in: each byte counts once
+    /// copied and, for jobs that verify, `verify_cost` times more once
+    /// verified, so the total only reaches 100% after verification.
+    pub fn progress_snapshot(&self) -> Progress {
+        let (done, work) = self.work_done();
+        let overall_percent = if work > 0.0 { (done / work * 100.0).min(100.0) } else { 0.0 };
+        Progress { overall_percent, bytes_copied: self.bytes_copied(), ..self.progress.clone() }
+    }
+
     pub fn get_status(&self) -> JobStatus {
         JobStatus::try_from(self.progress.status).unwrap_or(JobStatus::Pending)
     }
 
+    /// Summary of the job as reported by `ListJobs` and the job history.
+    pub fn to_info(&self) -> JobInfo {
+        JobInfo {
+            job_id: Some(JobId { uuid: self.id.clone() }),
+            sources: self.sources.iter().map(|p| p.to_string_lossy().to_string())
+                .chain(self.urls.iter().cloned())
+                .collect(),
+            destination: self.destination.to_string_lossy().to_string(),
+            progress: Some(self.progress_snapshot()),
+            created_at: self.created_at.timestamp(),
+            started_at: self.started_at.map(|t| t.timestamp()).unwrap_or(0),
+            completed_at: self.completed_at.map(|t| t.timestamp()).unwrap_or(0),
+            priority: self.priority,
+            tags: self.tags.clone(),
+            request: self.request.clone(),
+            description: self.description.clone(),
+            error_summary: Some(self.error_summary.clone()),
+        }
+    }
+
+    /// Paths the job writes into: its destination and mirrors, or none for
+    /// a dry run or verification.
+    pub fn written_destinations(&self) -> Vec<&Path> {
+        if self.options.dry_run || self.options.verify_only {
+            return Vec::new();
+        }
+        std::iter::once(self.destination.as_path())
+            .chain(self.options.mirrors.iter().map(PathBuf::as_path))
+            .collect()
+    }
+
+    /// Whether the job writes into a path another job writing `others` does,
+    /// or into one holding or held by such a path.
+    pub fn writes_overlapping(&self, others: &[&Path]) -> bool {
+        self.written_destinations().iter()
+            .any(|path| others.iter().any(|other| path.starts_with(other) || other.starts_with(path)))
+    }
+
+    /// Whether the job carries every one of `tags`.
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
     pub fn set_status(&mut self, status: JobStatus) {
         self.progress.status = status.into();
         match status {
-            JobStatus::Running => {
-                if self.started_at.is_none() {
-                    self.started_at = Some(Utc::now());
-                }
+            JobStatus::Running if self.started_at.is_none() => {
+                self.started_at = Some(Utc::now());
             }
-            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled => {
-                if self.completed_at.is_none() {
-                    self.completed_at = Some(Utc::now());
-                }
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled if self.completed_at.is_none() => {
+                self.completed_at = Some(Utc::now());
+                self.progress.eta_seconds = 0;
             }
             _ => {}
         }
     }
 }
 
+/// What an earlier run of a job, paused or stopped before it finished, left
+/// in its checkpoint.
+struct EarlierRun {
+    /// IDs of the files it copied, with what their sources looked like then
+    copied: HashMap<String, SourceStamp>,
+    into_destination: Option<bool>,
+}
+
+impl EarlierRun {
+    async fn load(manager: &CheckpointManager, job_id: &str) -> Option<Self> {
+        let saved = manager.load_checkpoint(job_id).await.ok()??;
+        Some(Self {
+            copied: saved.completed_stamps,
+            into_destination: saved.into_destination,
+        })
+    }
+
+    /// Whether `entry` was copied then, with its source unchanged since and
+    /// its copy, like the `mirrored` copies of it, still of the length a
+    /// copy made with `options` has.
+    fn still_copied(&self, entry: &FileEntry, mirrored: &[&FileEntry], options: &CopyOptions) -> bool {
+        let Some(copied) = self.copied.get(&create_file_id(&entry.source_path, &entry.dest_path)) else {
+            return false;
+        };
+        let Ok(source) = std::fs::metadata(&entry.source_path) else {
+            return false;
+        };
+        SourceStamp::of(&source).as_ref() == Some(copied)
+            && std::iter::once(entry).chain(mirrored.iter().copied()).all(|copy| {
+                let written = FileCopyEngine::written_path(&copy.dest_path, options);
+                if options.compress {
+                    // Compressed copies record the length they hold
+                    return Codec::default().recorded_size(&written).is_ok_and(|size| size == Some(source.len()));
+                }
+                let expected_len = match options.encrypt {
+                    true => crate::encrypt::encrypted_len(source.len()),
+                    false => source.len(),
+                };
+                std::fs::metadata(&written).is_ok_and(|dest| dest.is_file() && dest.len() == expected_len)
+            })
+    }
+}
+
+/// Aborts a helper task of a job when the job's own task ends, including
+/// when that task is aborted to pause, cancel or stop the job.
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// The staging tree of an atomic copy, removed when dropped, including when
+/// the job's task is aborted to pause, cancel or stop the job, so no partial
+/// tree stays behind.
+struct StagingTree(Option<PathBuf>);
+
+impl StagingTree {
+    fn new(path: PathBuf) -> Self {
+        Self(Some(path))
+    }
+
+    fn path(&self) -> &Path {
+        self.0.as_deref().expect("staging path is kept until dropped")
+    }
+
+    /// Removes the tree, if any, and waits for it to be gone.
+    async fn remove_now(&self) {
+        let path = self.path().to_path_buf();
+        let _ = tokio::task::spawn_blocking(move || Self::remove(&path)).await;
+    }
+
+    fn remove(path: &Path) {
+        if let Err(e) = std::fs::remove_dir_all(path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove staging directory {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+impl Drop for StagingTree {
+    fn drop(&mut self) {
+        let Some(path) = self.0.take() else {
+            return;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(move || Self::remove(&path));
+            }
+            Err(_) => Self::remove(&path),
+        }
+    }
+}
+
+/// Copies of a file attempted under [`ErrorPolicy::Retry`]; the wait before
+/// each retry grows by [`FILE_RETRY_DELAY`].
+const FILE_RETRY_ATTEMPTS: u32 = 3;
+const FILE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait for a download server to accept the connection.
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Directories created between directory progress events.
+pub const DIRECTORY_PROGRESS_BATCH: usize = 100;
+
+/// Conflicting destinations listed in a job plan; the rest are only counted.
+pub const PLAN_CONFLICT_LIMIT: usize = 10;
+
+/// Keeps a running copy's checkpoint on disk, saving it after each file once
+/// the job's byte or time interval has passed since the previous save. A
+/// source is marked complete once all of its files and links are written
+/// without error.
+struct CheckpointWriter<'a> {
+    manager: &'a CheckpointManager,
+    checkpoint: JobCheckpoint,
+    /// Entries still to be written under each source
+    pending: Vec<usize>,
+    /// Whether any entry under each source failed
+    failed: Vec<bool>,
+    interval_bytes: u64,
+    interval: Option<Duration>,
+    bytes_since_save: u64,
+    last_save: Instant,
+}
+
+impl<'a> CheckpointWriter<'a> {
+    /// Records every file of the job and saves the initial checkpoint.
+    /// `links` are the symlinks the job will create after its files.
+    #[allow(clippy::too_many_arguments)]
+    async fn new(
+        manager: &'a CheckpointManager,
+        job_id: &str,
+        description: &str,
+        tags: &[String],
+        options: &JobOptions,
+        sources: &[PathBuf],
+        destination: &Path,
+        into_destination: bool,
+        files: &[FileEntry],
+        links: &[FileEntry],
+    ) -> Self {
+        let operation_type = match options.operation {
+            JobOperation::Copy => "copy",
+            JobOperation::Move => "move",
+        };
+        let mut checkpoint = JobCheckpoint::new(job_id.to_string(), operation_type.to_string());
+        checkpoint.set_sources(sources, destination);
+        checkpoint.into_destination = Some(into_destination);
+        checkpoint.mirrors = options.mirrors.clone();
+        checkpoint.encrypt = options.encrypt;
+        checkpoint.key_id = options.key_id.clone();
+        checkpoint.compress = options.compress;
+        checkpoint.description = description.to_string();
+        checkpoint.tags = tags.to_vec();
+        checkpoint.atomic = options.atomic;
+        checkpoint.temp_dir = options.temp_dir.clone();
+        for entry in files {
+            // Stamped before the file is read, so a change during its copy
+            // shows on resume
+            let modified = std::fs::metadata(&entry.source_path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            checkpoint.add_file(create_file_id(&entry.source_path, &entry.dest_path), FileCheckpoint {
+                source_path: entry.source_path.clone(),
+                destination_path: entry.dest_path.clone(),
+                bytes_copied: 0,
+                total_size: entry.size,
+                last_modified: modified.as_secs(),
+                last_modified_nanos: modified.subsec_nanos(),
+                checksum_partial: None,
+                chunk_size: options.block_size.unwrap_or(0),
+                created_at: checkpoint.created_at,
+                updated_at: checkpoint.created_at,
+            });
+        }
+
+        let mut writer = Self {
+            manager,
+            checkpoint,
+            pending: vec![0; sources.len()],
+            failed: vec![false; sources.len()],
+            interval_bytes: options.checkpoint_interval_bytes,
+            interval: (options.checkpoint_interval_secs > 0).then(|| Duration::from_secs(options.checkpoint_interval_secs)),
+            bytes_since_save: 0,
+            last_save: Instant::now(),
+        };
+        for entry in files.iter().chain(links) {
+            if let Some(index) = writer.source_index(entry) {
+                writer.pending[index] += 1;
+            }
+        }
+        // Sources with nothing to copy, such as empty directories, are done
+        for index in 0..sources.len() {
+            if writer.pending[index] == 0 {
+                writer.checkpoint.complete_source(index);
+            }
+        }
+        writer.save().await;
+        writer
+    }
+
+    fn source_index(&self, entry: &FileEntry) -> Option<usize> {
+        self.checkpoint.sources.iter().position(|source| entry.source_path.starts_with(&source.path))
+    }
+
+    /// Counts `entry` as written, or failed, towards its source's completion.
+    fn entry_done(&mut self, entry: &FileEntry, succeeded: bool) {
+        if let Some(index) = self.source_index(entry) {
+            self.pending[index] = self.pending[index].saturating_sub(1);
+            self.failed[index] |= !succeeded;
+            if self.pending[index] == 0 && !self.failed[index] {
+                self.checkpoint.complete_source(index);
+            }
+        }
+    }
+
+    /// Marks `entry` copied with `bytes_copied` bytes, or failed on `None`,
+    /// and saves when an interval has passed.
+    async fn file_done(&mut self, entry: &FileEntry, bytes_copied: Option<u64>) {
+        let file_id = create_file_id(&entry.source_path, &entry.dest_path);
+        self.entry_done(entry, bytes_copied.is_some());
+        match bytes_copied {
+            Some(bytes) => {
+                self.checkpoint.update_file_progress(&file_id, bytes, None);
+                self.checkpoint.complete_file(file_id);
+                self.bytes_since_save += bytes;
+            }
+            None => self.checkpoint.fail_file(file_id),
+        }
+
+        let bytes_due = self.interval_bytes > 0 && self.bytes_since_save >= self.interval_bytes;
+        let time_due = self.interval.is_some_and(|interval| self.last_save.elapsed() >= interval);
+        if bytes_due || time_due {
+            self.save().await;
+        }
+    }
+
+    /// Marks the job's symlinks created and saves the checkpoint.
+    async fn links_done(&mut self, links: &[FileEntry]) {
+        for entry in links {
+            self.entry_done(entry, true);
+        }
+        self.save().await;
+    }
+
+    async fn save(&mut self) {
+        if let Err(e) = self.manager.save_checkpoint(&self.checkpoint).await {
+            warn!("Failed to save checkpoint of job {}: {}", self.checkpoint.job_id, e);
+        }
+        self.bytes_since_save = 0;
+        self.last_save = Instant::now();
+    }
+}
+
+/// Settings that can change while the daemon runs, shared by every clone of
+/// a [`JobManager`]. Jobs pick them up when they are created or started.
+#[derive(Debug, Clone)]
+struct RuntimeSettings {
+    max_concurrent: usize,
+    default_engine: CopyEngine,
+    allowed_engines: Vec<CopyEngine>,
+    numa_buffers: bool,
+    read_ahead_blocks: usize,
+    checkpoint_interval_bytes: u64,
+    checkpoint_interval_secs: u64,
+    idle_io_verification: bool,
+    verify_cost: f64,
+    max_copy_parallelism: usize,
+}
+
 pub struct JobManager {
     jobs: Arc<RwLock<HashMap<String, Job>>>,
-    job_queue: Arc<RwLock<VecDeque<String>>>,
+    job_queue: Arc<RwLock<JobQueue>>,
     active_jobs: Arc<RwLock<HashMap<String, tokio::task::JoinHandle<()>>>>,
-    max_concurrent: usize,
     semaphore: Arc<Semaphore>,
-    event_sender: mpsc::UnboundedSender<JobEvent>,
+    event_sender: EventPublisher,
     checkpoint_manager: Arc<CheckpointManager>,
+    inflight_budget: InflightBudget,
+    settings: Arc<parking_lot::RwLock<RuntimeSettings>>,
+    history: Option<Arc<JobHistory>>,
+    results: Option<Arc<JobResults>>,
+    bandwidth: Arc<BandwidthCalibrator>,
+    monitor: Option<Arc<EnhancedMonitor>>,
+    profiler: PerformanceProfiler,
+    dedup_indexes: Arc<DedupIndexes>,
+    /// Held while a queued job is chosen and started, so two jobs writing to
+    /// overlapping destinations can't both start
+    starting: Arc<tokio::sync::Mutex<()>>,
+    /// Set once the daemon is shutting down; no job starts or is queued after
+    draining: Arc<AtomicBool>,
+}
+
+/// Counts a running job against its destination device in the monitor
+/// until the job's task ends or is aborted.
+struct DeviceSlot {
+    monitor: Arc<EnhancedMonitor>,
+    device: String,
+}
+
+impl DeviceSlot {
+    fn new(monitor: Arc<EnhancedMonitor>, destination: &Path) -> Self {
+        let device = crate::device::device_label(destination);
+        monitor.device_job_started(&device);
+        Self { monitor, device }
+    }
+}
+
+impl Drop for DeviceSlot {
+    fn drop(&mut self) {
+        self.monitor.device_job_finished(&self.device);
+    }
 }
 
 impl JobManager {
@@ -136,9 +755,11 @@ impl JobManager {
     /// This retains the original behaviour but the method name is made
     /// explicit so we can also provide a convenience constructor that
     /// matches the test-suite signature.
-    pub fn new_with_checkpoint_dir(max_concurrent: usize, checkpoint_dir: PathBuf) -> (Self, mpsc::UnboundedReceiver<JobEvent>) {
-        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+    pub fn new_with_checkpoint_dir(max_concurrent: usize, checkpoint_dir: PathBuf) -> (Self, EventReceiver) {
+        let event_sender = EventPublisher::new(EVENT_QUEUE_CAPACITY);
+        let event_receiver = event_sender.subscribe();
         
+        let dedup_indexes = Arc::new(DedupIndexes::new(checkpoint_dir.join("dedup")));
         let checkpoint_manager = Arc::new(
             CheckpointManager::new(checkpoint_dir)
                 .expect("Failed to create checkpoint manager")
@@ -146,241 +767,1907 @@ impl JobManager {
         
         let manager = Self {
             jobs: Arc::new(RwLock::new(HashMap::new())),
-            job_queue: Arc::new(RwLock::new(VecDeque::new())),
+            job_queue: Arc::new(RwLock::new(JobQueue::new())),
             active_jobs: Arc::new(RwLock::new(HashMap::new())),
-            max_concurrent,
             semaphore: Arc::new(Semaphore::new(max_concurrent)),
             event_sender,
             checkpoint_manager,
+            inflight_budget: InflightBudget::unlimited(),
+            settings: Arc::new(parking_lot::RwLock::new(RuntimeSettings {
+                max_concurrent,
+                default_engine: CopyEngine::Auto,
+                allowed_engines: Vec::new(),
+                numa_buffers: false,
+                read_ahead_blocks: 0,
+                checkpoint_interval_bytes: DEFAULT_CHECKPOINT_INTERVAL_BYTES,
+                checkpoint_interval_secs: DEFAULT_CHECKPOINT_INTERVAL_SECS,
+                idle_io_verification: false,
+                verify_cost: DEFAULT_VERIFY_COST,
+                max_copy_parallelism: DEFAULT_MAX_COPY_PARALLELISM,
+            })),
+            history: None,
+            results: None,
+            monitor: None,
+            profiler: PerformanceProfiler::new(),
+            dedup_indexes,
+            starting: Arc::new(tokio::sync::Mutex::new(())),
+            bandwidth: Arc::new(BandwidthCalibrator::default()),
+            draining: Arc::new(AtomicBool::new(false)),
         };
 
         (manager, event_receiver)
     }
 
-    /// Convenience constructor used by integration tests – stores checkpoints in the system temp directory.
-    pub fn new(max_concurrent: usize) -> (Self, mpsc::UnboundedReceiver<JobEvent>) {
-        let checkpoint_dir = std::env::temp_dir().join("copyd_checkpoints");
-        Self::new_with_checkpoint_dir(max_concurrent, checkpoint_dir)
+    /// Apply the daemon's engine policy: `default_engine` replaces `Auto` in
+    /// incoming requests and a non-empty `allowed_engines` rejects the rest.
+    pub fn with_engine_policy(self, default_engine: CopyEngine, allowed_engines: Vec<CopyEngine>) -> Self {
+        self.set_engine_policy(default_engine, allowed_engines);
+        self
     }
 
-    pub async fn create_job(&self, request: CreateJobRequest) -> Result<String> {
-        let job = Job::new(request);
-        let job_id = job.id.clone();
-        
-        info!("Created job {}: {:?} -> {:?}", job_id, job.sources, job.destination);
-        
-        // Add to jobs map
-        {
-            let mut jobs = self.jobs.write().await;
-            jobs.insert(job_id.clone(), job);
+    /// Cap the bytes of copy buffers allocated across all running jobs.
+    pub fn with_inflight_budget(mut self, budget: InflightBudget) -> Self {
+        self.inflight_budget = budget;
+        self
+    }
+
+    /// Pin copy buffers to the NUMA node of the CPU running each copy.
+    pub fn with_numa_buffers(self, enabled: bool) -> Self {
+        self.set_numa_buffers(enabled);
+        self
+    }
+
+    /// Read up to `blocks` blocks ahead of the writer in read/write copies.
+    pub fn with_read_ahead(self, blocks: usize) -> Self {
+        self.set_read_ahead(blocks);
+        self
+    }
+
+    /// Save a running job's checkpoint once it has copied `bytes` more bytes
+    /// or `secs` more seconds have passed, whichever comes first; zero
+    /// disables either trigger.
+    pub fn with_checkpoint_interval(self, bytes: u64, secs: u64) -> Self {
+        self.set_checkpoint_interval(bytes, secs);
+        self
+    }
+
+    /// Read files back for verification at idle I/O priority, so it yields
+    /// the disk to running copies.
+    pub fn with_idle_io_verification(self, enabled: bool) -> Self {
+        self.set_idle_io_verification(enabled);
+        self
+    }
+
+    /// Count verifying a byte as `cost` times the work of copying it when
+    /// reporting the overall progress of verified jobs.
+    pub fn with_verify_cost(self, cost: f64) -> Self {
+        self.set_verify_cost(cost);
+        self
+    }
+
+    /// Let a job copy at most `max` of its files at once.
+    pub fn with_max_copy_parallelism(self, max: usize) -> Self {
+        self.set_max_copy_parallelism(max);
+        self
+    }
+
+    /// Emit at most one progress update per job every `interval`; file
+    /// completions and status changes are always sent at once.
+    pub fn with_progress_interval(self, interval: Duration) -> Self {
+        self.set_progress_interval(interval);
+        self
+    }
+
+    /// Progress coalescing interval from now on; see [`Self::with_progress_interval`].
+    pub fn set_progress_interval(&self, interval: Duration) {
+        self.event_sender.set_progress_interval(interval);
+    }
+
+    /// Engine policy for jobs created from now on; see [`Self::with_engine_policy`].
+    pub fn set_engine_policy(&self, default_engine: CopyEngine, allowed_engines: Vec<CopyEngine>) {
+        let mut settings = self.settings.write();
+        settings.default_engine = default_engine;
+        settings.allowed_engines = allowed_engines;
+    }
+
+    /// NUMA buffer placement for jobs started from now on.
+    pub fn set_numa_buffers(&self, enabled: bool) {
+        self.settings.write().numa_buffers = enabled;
+    }
+
+    /// Read-ahead depth for jobs started from now on.
+    pub fn set_read_ahead(&self, blocks: usize) {
+        self.settings.write().read_ahead_blocks = blocks;
+    }
+
+    /// Checkpoint interval for jobs started from now on; see
+    /// [`Self::with_checkpoint_interval`].
+    pub fn set_checkpoint_interval(&self, bytes: u64, secs: u64) {
+        let mut settings = self.settings.write();
+        settings.checkpoint_interval_bytes = bytes;
+        settings.checkpoint_interval_secs = secs;
+    }
+
+    /// Verification I/O priority for jobs started from now on.
+    pub fn set_idle_io_verification(&self, enabled: bool) {
+        self.settings.write().idle_io_verification = enabled;
+    }
+
+    /// Weight of verification in the overall progress of jobs started from
+    /// now on; see [`Self::with_verify_cost`].
+    pub fn set_verify_cost(&self, cost: f64) {
+        self.settings.write().verify_cost = cost.max(0.0);
+    }
+
+    /// How many times the work of copying a byte verifying it takes.
+    pub fn verify_cost(&self) -> f64 {
+        self.settings.read().verify_cost
+    }
+
+    /// Per-job file parallelism limit for jobs created or started from now
+    /// on; running jobs keep theirs.
+    pub fn set_max_copy_parallelism(&self, max: usize) {
+        self.settings.write().max_copy_parallelism = max.max(1);
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.settings.read().max_concurrent
+    }
+
+    /// Number of jobs currently holding a concurrency slot.
+    pub async fn running_jobs(&self) -> usize {
+        self.active_jobs.read().await.len()
+    }
+
+    /// Changes how many jobs may run at once. Extra slots start queued jobs
+    /// right away; when shrinking, running jobs finish and their slots are
+    /// retired instead of being handed to the next job.
+    pub async fn set_max_concurrent(&self, max_concurrent: usize) -> Result<()> {
+        if max_concurrent == 0 {
+            anyhow::bail!("max_concurrent_jobs must be at least 1");
         }
 
-        // Add to queue based on priority
-        {
-            let mut queue = self.job_queue.write().await;
-            queue.push_back(job_id.clone());
+        let previous = std::mem::replace(&mut self.settings.write().max_concurrent, max_concurrent);
+        if max_concurrent > previous {
+            self.semaphore.add_permits(max_concurrent - previous);
+            for _ in previous..max_concurrent {
+                self.try_start_next_job().await;
+            }
+        } else if max_concurrent < previous {
+            let semaphore = self.semaphore.clone();
+            let retired = (previous - max_concurrent) as u32;
+            tokio::spawn(async move {
+                if let Ok(permits) = semaphore.acquire_many_owned(retired).await {
+                    permits.forget();
+                }
+            });
         }
 
-        // Try to start the job immediately if capacity allows
-        self.try_start_next_job().await;
+        info!("Concurrent job limit changed from {} to {}", previous, max_concurrent);
+        Ok(())
+    }
 
-        Ok(job_id)
+    /// Record every job that completes, fails or is cancelled in `history`.
+    pub fn with_history(mut self, history: JobHistory) -> Self {
+        self.history = Some(Arc::new(history));
+        self
     }
 
-    pub async fn get_job(&self, job_id: &str) -> Option<Job> {
-        let jobs = self.jobs.read().await;
-        jobs.get(job_id).cloned()
+    /// Keep a result for every file each job copies, or fails to, in
+    /// `results`.
+    pub fn with_results(mut self, results: JobResults) -> Self {
+        self.results = Some(Arc::new(results));
+        self
     }
 
-    pub async fn list_jobs(&self, include_completed: bool) -> Vec<Job> {
-        let jobs = self.jobs.read().await;
-        jobs.values()
-            .filter(|job| include_completed || matches!(job.get_status(), JobStatus::Pending | JobStatus::Running | JobStatus::Paused))
-            .cloned()
-            .collect()
+    /// Report queue depth, time spent queued and running jobs per
+    /// destination device to `monitor`.
+    pub fn with_monitor(mut self, monitor: Arc<EnhancedMonitor>) -> Self {
+        self.monitor = Some(monitor);
+        self
     }
 
-    pub async fn cancel_job(&self, job_id: &str) -> Result<()> {
-        // Remove from queue
+    fn record_queue_depth(&self, queue: &JobQueue) {
+        if let Some(monitor) = &self.monitor {
+            monitor.set_queue_depth(queue.len());
+        }
+    }
+
+    /// Searches the job history; empty when no history is configured.
+    pub async fn query_history(&self, query: &QueryJobsRequest) -> Result<(Vec<JobInfo>, u64)> {
+        match &self.history {
+            Some(history) => history.query(query).await,
+            None => Ok((Vec::new(), 0)),
+        }
+    }
+
+    /// Drops history entries for jobs that finished before `cutoff`, and
+    /// the file results of jobs that ended before it.
+    pub async fn prune_history(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        if let Some(results) = &self.results {
+            results.prune(cutoff).await?;
+        }
+        match &self.history {
+            Some(history) => history.prune(cutoff).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Results of every file `job_id` has finished so far; empty when no
+    /// results are kept.
+    pub async fn job_results(&self, job_id: &str) -> Result<Vec<FileResult>> {
+        match &self.results {
+            Some(results) => results.load(job_id).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn record_history(history: Option<&JobHistory>, job: &Job) {
+        if let Some(history) = history {
+            if let Err(e) = history.record(&job.to_info()).await {
+                warn!("Failed to record job {} in the job history: {}", job.id, e);
+            }
+        }
+    }
+
+    /// Per-engine throughput and error rates of every copy run so far.
+    pub fn profiler(&self) -> &PerformanceProfiler {
+        &self.profiler
+    }
+
+    /// Measure device bandwidth for percentage rate caps with `probe`
+    /// instead of a calibration read and write.
+    pub fn with_bandwidth_probe(mut self, probe: Arc<dyn BandwidthProbe>) -> Self {
+        self.bandwidth = Arc::new(BandwidthCalibrator::new(probe));
+        self
+    }
+
+    /// Store checkpoints zstd-compressed; existing plain ones still load.
+    pub fn with_checkpoint_compression(mut self, enabled: bool) -> Self {
+        self.checkpoint_manager = Arc::new((*self.checkpoint_manager).clone().with_compression(enabled));
+        self
+    }
+
+    /// Convenience constructor used by integration tests – stores checkpoints in the system temp directory.
+    pub fn new(max_concurrent: usize) -> (Self, EventReceiver) {
+        let checkpoint_dir = std::env::temp_dir().join("copyd_checkpoints");
+        Self::new_with_checkpoint_dir(max_concurrent, checkpoint_dir)
+    }
+
+    pub async fn create_job(&self, request: CreateJobRequest) -> Result<String> {
+        let job = Job::new(request);
+        self.enqueue_job(job).await
+    }
+
+    /// Queues a job that checks an existing copy of `source` at
+    /// `destination` with `mode`, reporting each mismatch as a file error.
+    pub async fn create_verify_job(&self, request: VerifyJobRequest) -> Result<String> {
+        let mode = VerifyMode::try_from(request.mode).unwrap_or(VerifyMode::None);
+        if mode == VerifyMode::None {
+            anyhow::bail!("Verify-only jobs need a verification mode");
+        }
+
+        let mut job = Job::new(CreateJobRequest {
+            sources: vec![request.source],
+            destination: request.destination,
+            recursive: true,
+            verify: mode.into(),
+            ..Default::default()
+        });
+        job.options.verify_only = true;
+        job.request = None;
+        self.enqueue_job(job).await
+    }
+
+    async fn enqueue_job(&self, mut job: Job) -> Result<String> {
+        if self.draining.load(Ordering::SeqCst) {
+            anyhow::bail!("The daemon is shutting down and takes no new jobs");
+        }
+        let job_id = job.id.clone();
+
         {
-            let mut queue = self.job_queue.write().await;
-            queue.retain(|id| id != job_id);
+            let settings = self.settings.read();
+            if job.options.engine == CopyEngine::Auto {
+                job.options.engine = settings.default_engine;
+            }
+            if !settings.allowed_engines.is_empty() && !settings.allowed_engines.contains(&job.options.engine) {
+                anyhow::bail!("Copy engine {} is not allowed by daemon configuration", job.options.engine);
+            }
+            if job.options.copy_parallelism > settings.max_copy_parallelism {
+                anyhow::bail!("Copy parallelism {} is above the daemon's max_copy_parallelism of {}",
+                    job.options.copy_parallelism, settings.max_copy_parallelism);
+            }
+        }
+        if job.options.packed && job.options.copy_parallelism > 1 {
+            anyhow::bail!("Packed copies write their files one at a time");
+        }
+        if job.options.dedup && (job.options.packed || !job.options.mirrors.is_empty()) {
+            anyhow::bail!("Deduplication needs a single destination and no packed copies");
+        }
+        if job.options.verify_retries > MAX_VERIFY_RETRIES {
+            anyhow::bail!("At most {} verification retries are allowed", MAX_VERIFY_RETRIES);
+        }
+        if job.options.verify_retries > 0 && !job.options.mirrors.is_empty() {
+            anyhow::bail!("Verification retries need a single destination; --on-error retry covers mirrored copies");
+        }
+        if let Some(mode) = job.options.chmod.filter(|mode| *mode > 0o7777) {
+            anyhow::bail!("Mode {:o} has bits outside 7777", mode);
+        }
+        if job.options.max_rate_percent.is_some_and(|percent| percent > 100) {
+            anyhow::bail!("Rate percentage must be between 1 and 100");
+        }
+        if (job.options.delete_extraneous || job.options.delete_dry_run) && !job.options.recursive {
+            anyhow::bail!("Deleting extraneous destination files requires a recursive copy");
+        }
+        if job.options.dirs_only && !job.options.recursive {
+            anyhow::bail!("Copying only directories requires a recursive copy");
+        }
+        if job.options.compress {
+            let options = &job.options;
+            if !job.urls.is_empty() || options.verify_only || options.stream_fifo {
+                anyhow::bail!("Only copies of local files can be compressed");
+            }
+            if options.atomic || options.packed || options.delete_extraneous || options.delete_dry_run {
+                anyhow::bail!("Compressed copies can't be atomic, packed or delete extraneous files");
+            }
+        }
+        if job.options.encrypt {
+            let options = &job.options;
+            if !job.urls.is_empty() || options.verify_only || options.stream_fifo {
+                anyhow::bail!("Only copies of local files can be encrypted");
+            }
+            if options.atomic || options.packed || options.delete_extraneous || options.delete_dry_run || options.compress {
+                anyhow::bail!("Encrypted copies can't be atomic, packed, compressed or delete extraneous files");
+            }
+            crate::encrypt::passphrase(options.key_id.as_deref())?;
+        }
+        if job.options.operation == JobOperation::Move {
+            if !job.urls.is_empty() || job.options.verify_only || job.options.dirs_only {
+                anyhow::bail!("Only copies of local files can be moves");
+            }
+            if !job.options.recursive && job.sources.iter().any(|source| source.is_dir()) {
+                anyhow::bail!("Moving a directory requires a recursive job");
+            }
+        }
+        if job.options.dirs_only && (job.options.delete_extraneous || job.options.delete_dry_run) {
+            anyhow::bail!("Deleting extraneous files can't be combined with copying only directories");
+        }
+        if !job.urls.is_empty() && (!job.sources.is_empty() || job.options.verify_only) {
+            anyhow::bail!("URL sources can't be mixed with local ones in one job");
+        }
+        if !job.urls.is_empty() && (job.options.dirs_only || job.options.delete_extraneous || job.options.delete_dry_run) {
+            anyhow::bail!("URL sources are single files; directory options don't apply to them");
+        }
+        for url in &job.urls {
+            http_source::file_name(url)?;
+        }
+        if !job.options.mirrors.is_empty() {
+            let options = &job.options;
+            if !job.urls.is_empty() || options.device_source {
+                anyhow::bail!("Only files and directories can be copied to several destinations");
+            }
+            if options.atomic || options.packed || options.compress || options.encrypt
+                || options.delete_extraneous || options.delete_dry_run
+                || options.on_source_change != SourceChangeAction::IgnoreChange
+            {
+                anyhow::bail!("Atomic, packed, compressed, encrypted, deleting and source-watching copies go to a single destination");
+            }
+            let mut destinations = HashSet::from([job.destination.as_path()]);
+            if let Some(repeated) = options.mirrors.iter().find(|mirror| !destinations.insert(mirror.as_path())) {
+                anyhow::bail!("Destination {:?} is given more than once", repeated);
+            }
+        }
+        if job.options.packed && job.options.on_source_change != SourceChangeAction::IgnoreChange {
+            anyhow::bail!("Packed copies don't watch their sources for changes");
+        }
+        for source in &job.sources {
+            let Ok(metadata) = std::fs::metadata(source) else { continue };
+            if crate::device::is_char_device(&metadata) {
+                anyhow::bail!("Character device {:?} has no fixed length and cannot be copied", source);
+            }
+            if crate::device::is_block_device(&metadata) && !job.options.device_source {
+                anyhow::bail!("{:?} is a block device; enable device mode to image it", source);
+            }
+            if crate::device::is_fifo(&metadata) && !job.options.stream_fifo {
+                anyhow::bail!("{:?} is a named pipe; enable FIFO streaming to capture its data", source);
+            }
+        }
+        if let Some(parent) = Self::missing_destination_parent(&job) {
+            anyhow::bail!("Destination directory {:?} does not exist; enable parent creation to create it", parent);
+        }
+        if let Some(temp_dir) = &job.options.temp_dir {
+            if !job.options.atomic {
+                anyhow::bail!("A temporary directory only applies to atomic copies");
+            }
+            let target_parent = if job.destination.is_dir() {
+                job.destination.as_path()
+            } else {
+                job.destination.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."))
+            };
+            Self::check_staging_dir(temp_dir, target_parent)?;
+        }
+        let destinations = std::iter::once(&job.destination).chain(&job.options.mirrors);
+        for destination in destinations.filter(|_| !job.options.dry_run && !job.options.verify_only) {
+            if crate::device::is_read_only(destination).unwrap_or(false) {
+                return Err(anyhow::Error::new(CopydError::PermissionDenied { path: destination.clone() })
+                    .context(format!(
+                        "Destination {:?} is on a read-only filesystem; remount it read-write or choose another destination",
+                        destination
+                    )));
+            }
+        }
+        
+        info!("Created job {}: {:?} -> {:?}", job_id, job.sources, job.destination);
+        
+        if let Some(blocking) = self.job_writing_overlapping(&job).await {
+            job.add_log(format!("Waiting for job {}, which writes to an overlapping destination", blocking));
+        }
+
+        // Add to jobs map
+        let (priority, created_at) = (job.priority, job.created_at);
+        {
+            let mut jobs = self.jobs.write().await;
+            jobs.insert(job_id.clone(), job);
+        }
+
+        // Add to queue based on priority
+        {
+            let mut queue = self.job_queue.write().await;
+            queue.push(job_id.clone(), priority, created_at);
+            self.record_queue_depth(&queue);
+        }
+
+        // Try to start the job immediately if capacity allows
+        self.try_start_next_job().await;
+
+        Ok(job_id)
+    }
+
+    /// Directory a single-file copy would write into when it doesn't exist
+    /// and the job won't create it. Mirrors are laid out like the job's own
+    /// destination, so they take the file inside when that is a directory.
+    fn missing_destination_parent(job: &Job) -> Option<PathBuf> {
+        if job.options.create_parents || job.options.verify_only || job.sources.len() + job.urls.len() != 1 {
+            return None;
+        }
+        let source_is_file = !job.urls.is_empty()
+            || std::fs::metadata(&job.sources[0]).is_ok_and(|metadata| !metadata.is_dir());
+        if !source_is_file {
+            return None;
+        }
+        let into_directory = job.destination.is_dir();
+        std::iter::once(&job.destination).chain(&job.options.mirrors).find_map(|destination| {
+            let parent = if into_directory {
+                destination.as_path()
+            } else {
+                destination.parent().filter(|parent| !parent.as_os_str().is_empty())?
+            };
+            (!parent.exists()).then(|| parent.to_path_buf())
+        })
+    }
+
+    /// Checks that an atomic copy can stage its tree in `temp_dir` and rename
+    /// it into `target_parent`. Where the parent exists, a probe directory is
+    /// renamed across, which is the only sure test; otherwise the devices of
+    /// the closest existing ancestors are compared.
+    fn check_staging_dir(temp_dir: &Path, target_parent: &Path) -> Result<()> {
+        if !temp_dir.is_dir() {
+            anyhow::bail!("Temporary directory {:?} does not exist or is not a directory", temp_dir);
+        }
+        let other_filesystem = || anyhow::anyhow!(
+            "Temporary directory {:?} is on another filesystem than the destination {:?}; an atomic copy \
+             renames its staged tree into place, which only works within one filesystem",
+            temp_dir, target_parent,
+        );
+        if !target_parent.is_dir() {
+            let existing = target_parent.ancestors()
+                .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+                .unwrap_or(Path::new("."));
+            if crate::device::device_label(temp_dir) != crate::device::device_label(existing) {
+                return Err(other_filesystem());
+            }
+            return Ok(());
+        }
+
+        let probe_name = format!(".copyd-probe-{}", Uuid::new_v4());
+        let probe = temp_dir.join(&probe_name);
+        std::fs::create_dir(&probe)
+            .with_context(|| format!("Failed to write to temporary directory {:?}", temp_dir))?;
+        let moved = target_parent.join(&probe_name);
+        match std::fs::rename(&probe, &moved) {
+            Ok(()) => {
+                let _ = std::fs::remove_dir(&moved);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = std::fs::remove_dir(&probe);
+                if e.raw_os_error() == Some(libc::EXDEV) {
+                    Err(other_filesystem())
+                } else {
+                    Err(anyhow::Error::new(e).context(format!(
+                        "Failed to move a staged directory from {:?} to {:?}", temp_dir, target_parent
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Runs the traversal for a prospective job and reports the destination
+    /// files it would overwrite, without creating the job.
+    pub async fn analyze_job(&self, request: &CreateJobRequest) -> Result<AnalyzeJobResponse> {
+        let (traversal, conflicts) = Self::traverse_request(request).await?;
+
+        Ok(AnalyzeJobResponse {
+            conflicts,
+            total_files: traversal.total_files,
+            total_bytes: traversal.total_size,
+            error: String::new(),
+        })
+    }
+
+    /// Totals for a job about to be accepted, listing only the first
+    /// `PLAN_CONFLICT_LIMIT` destinations it would overwrite.
+    pub async fn plan_job(&self, request: &CreateJobRequest) -> Result<JobPlan> {
+        let (traversal, mut conflicts) = Self::traverse_request(request).await?;
+        let conflict_count = conflicts.len() as u64;
+        conflicts.truncate(PLAN_CONFLICT_LIMIT);
+
+        Ok(JobPlan {
+            total_files: traversal.total_files,
+            total_bytes: traversal.total_size,
+            total_directories: traversal.directories.len() as u64,
+            conflicts,
+            conflict_count,
+        })
+    }
+
+    async fn traverse_request(request: &CreateJobRequest) -> Result<(DirectoryTraversal, Vec<String>)> {
+        let sources: Vec<PathBuf> = request.sources.iter().map(PathBuf::from).collect();
+        let destination = PathBuf::from(&request.destination);
+        let mut traversal = DirectoryHandler::analyze_sources(&sources, &destination, request.recursive, request.preserve_links).await?;
+        if request.dirs_only {
+            traversal = traversal.without_files();
+        }
+
+        // Symlinks never replace what is at their destination, so only files
+        // can conflict
+        let mut conflicts = Vec::new();
+        for entry in &traversal.files {
+            if tokio::fs::symlink_metadata(&entry.dest_path).await.is_ok() {
+                conflicts.push(entry.dest_path.to_string_lossy().to_string());
+            }
+        }
+        Ok((traversal, conflicts))
+    }
+
+    /// Human-readable snapshot of every job and the queue, oldest job first,
+    /// for debugging a running daemon.
+    pub async fn dump_state(&self) -> String {
+        let mut jobs: Vec<Job> = self.jobs.read().await.values().cloned().collect();
+        jobs.sort_by_key(|job| job.created_at);
+        let queue: Vec<String> = self.job_queue.read().await.iter().cloned().collect();
+        let running = self.running_jobs().await;
+
+        let mut dump = format!(
+            "Job state: {} jobs, {} running, {} queued, at most {} concurrent\n",
+            jobs.len(), running, queue.len(), self.max_concurrent()
+        );
+        for job in &jobs {
+            let progress = &job.progress;
+            dump.push_str(&format!(
+                "  {} {:?} priority={} class={:?} bytes={}/{} files={}/{} rate={:.1}MB/s errors={} {:?} -> {:?}",
+                job.id,
+                job.get_status(),
+                job.priority,
+                job.options.class,
+                progress.bytes_copied,
+                progress.total_bytes,
+                progress.files_copied,
+                progress.total_files,
+                progress.throughput_mbps,
+                job.error_summary.error_count,
+                job.sources,
+                job.destination,
+            ));
+            if !job.tags.is_empty() {
+                dump.push_str(&format!(" tags={}", job.tags.join(",")));
+            }
+            dump.push('\n');
+        }
+        dump.push_str(&format!("Queue: [{}]", queue.join(", ")));
+        dump
+    }
+
+    pub async fn get_job(&self, job_id: &str) -> Option<Job> {
+        let jobs = self.jobs.read().await;
+        jobs.get(job_id).cloned()
+    }
+
+    /// Destinations of the jobs currently copying.
+    pub async fn running_destinations(&self) -> Vec<PathBuf> {
+        let jobs = self.jobs.read().await;
+        jobs.values()
+            .filter(|job| job.get_status() == JobStatus::Running)
+            .flat_map(|job| std::iter::once(job.destination.clone()).chain(job.options.mirrors.iter().cloned()))
+            .collect()
+    }
+
+    /// Jobs known to the manager that carry all of `tags`, finished ones
+    /// only if `include_completed` is set.
+    pub async fn list_jobs(&self, include_completed: bool, tags: &[String]) -> Vec<Job> {
+        let jobs = self.jobs.read().await;
+        jobs.values()
+            .filter(|job| include_completed || matches!(job.get_status(), JobStatus::Pending | JobStatus::Running | JobStatus::Paused))
+            .filter(|job| job.has_tags(tags))
+            .cloned()
+            .collect()
+    }
+
+    /// Aggregate statistics over jobs completed within the last `days_back`
+    /// days (all completed jobs when `days_back` is not positive).
+    pub async fn collect_stats(&self, days_back: i32) -> StatsResponse {
+        let cutoff = if days_back > 0 {
+            Some(Utc::now() - chrono::Duration::days(days_back as i64))
+        } else {
+            None
+        };
+
+        let mut stats = StatsResponse::default();
+        let mut daily: BTreeMap<String, DailyStats> = BTreeMap::new();
+        let mut path_throughput: HashMap<String, (f64, u32)> = HashMap::new();
+
+        let jobs = self.jobs.read().await;
+        for job in jobs.values() {
+            if job.get_status() != JobStatus::Completed {
+                continue;
+            }
+            let completed_at = match job.completed_at {
+                Some(t) if cutoff.is_none_or(|c| t >= c) => t,
+                _ => continue,
+            };
+
+            stats.total_bytes_copied += job.progress.bytes_copied;
+            stats.total_files_copied += job.progress.files_copied;
+            stats.total_jobs += 1;
+
+            // ISO-8601 calendar date
+            let date = completed_at.format("%Y-%m-%d").to_string();
+            let day = daily.entry(date.clone()).or_insert_with(|| DailyStats {
+                date,
+                ..Default::default()
+            });
+            day.bytes_copied += job.progress.bytes_copied;
+            day.files_copied += job.progress.files_copied;
+            day.jobs_completed += 1;
+
+            let elapsed = job.started_at
+                .map(|s| (completed_at - s).num_milliseconds() as f64 / 1000.0)
+                .unwrap_or(0.0);
+            if elapsed > 0.0 {
+                let mbps = job.progress.bytes_copied as f64 / elapsed / 1024.0 / 1024.0;
+                for source in &job.sources {
+                    let entry = path_throughput.entry(source.to_string_lossy().to_string()).or_insert((0.0, 0));
+                    entry.0 += mbps;
+                    entry.1 += 1;
+                }
+            }
+        }
+
+        stats.daily_stats = daily.into_values().collect();
+
+        let mut slow_paths: Vec<SlowPath> = path_throughput.into_iter()
+            .map(|(path, (sum, count))| SlowPath {
+                path,
+                avg_throughput_mbps: sum / count as f64,
+                copy_count: count,
+            })
+            .collect();
+        slow_paths.sort_by(|a, b| a.avg_throughput_mbps.total_cmp(&b.avg_throughput_mbps));
+        slow_paths.truncate(10);
+        stats.slow_paths = slow_paths;
+
+        stats
+    }
+
+    /// Remove finished jobs that completed before `cutoff`, along with their
+    /// checkpoints, history entries and file results. `statuses` narrows the purge to those end states; active
+    /// jobs (pending, running, paused) are never purged.
+    pub async fn purge_jobs(&self, cutoff: DateTime<Utc>, statuses: &[JobStatus]) -> Result<Vec<String>> {
+        let mut purged: Vec<String> = {
+            let mut jobs = self.jobs.write().await;
+            let ids: Vec<String> = jobs.values()
+                .filter(|job| matches!(job.get_status(), JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled))
+                .filter(|job| statuses.is_empty() || statuses.contains(&job.get_status()))
+                .filter(|job| job.completed_at.is_some_and(|t| t < cutoff))
+                .map(|job| job.id.clone())
+                .collect();
+
+            for id in &ids {
+                jobs.remove(id);
+            }
+            ids
+        };
+
+        // The history also holds jobs from earlier daemons, long gone from memory
+        if let Some(history) = &self.history {
+            let statuses: Vec<i32> = statuses.iter().map(|status| *status as i32).collect();
+            let removed = history.remove_where(|job| {
+                job.job_id.as_ref().is_some_and(|id| purged.contains(&id.uuid))
+                    || (job.completed_at < cutoff.timestamp()
+                        && (statuses.is_empty() || job.progress.as_ref().is_some_and(|p| statuses.contains(&p.status))))
+            }).await?;
+            for id in removed {
+                if !purged.contains(&id) {
+                    purged.push(id);
+                }
+            }
+        }
+
+        for id in &purged {
+            self.checkpoint_manager.delete_checkpoint(id).await?;
+            if let Some(results) = &self.results {
+                results.remove(id).await?;
+            }
+        }
+
+        info!("Purged {} jobs completed before {}", purged.len(), cutoff);
+        Ok(purged)
+    }
+
+    pub async fn cancel_job(&self, job_id: &str) -> Result<()> {
+        // Remove from queue
+        {
+            let mut queue = self.job_queue.write().await;
+            queue.remove(job_id);
+            self.record_queue_depth(&queue);
+        }
+
+        // Cancel active job, waiting for it to stop so it can't save its
+        // checkpoint again after it is deleted
+        let handle = self.active_jobs.write().await.remove(job_id);
+        if let Some(handle) = handle {
+            handle.abort();
+            let _ = handle.await;
+        }
+        if let Err(e) = self.checkpoint_manager.delete_checkpoint(job_id).await {
+            warn!("Failed to delete checkpoint of job {}: {}", job_id, e);
+        }
+
+        // Update job status
+        let cancelled = {
+            let mut jobs = self.jobs.write().await;
+            match jobs.get_mut(job_id) {
+                Some(job) => {
+                    let finished = job.get_status().is_terminal();
+                    job.set_status(JobStatus::Cancelled);
+                    job.add_log("Job cancelled by user".to_string());
+                    self.event_sender.send_status(job_id, JobStatus::Cancelled);
+                    (!finished).then(|| job.clone())
+                }
+                None => None,
+            }
+        };
+        if let Some(job) = cancelled {
+            Self::record_history(self.history.as_deref(), &job).await;
+        }
+
+        info!("Cancelled job {}", job_id);
+        Ok(())
+    }
+
+    /// Winds the manager down for a daemon shutdown: queued jobs no longer
+    /// start and are saved so the next daemon queues them again, running
+    /// ones get up to `grace` to finish, and those still running after that
+    /// are stopped without deleting their checkpoints, so the next daemon
+    /// resumes them. Returns the stopped jobs' IDs.
+    pub async fn drain(&self, grace: Duration) -> Vec<String> {
+        self.draining.store(true, Ordering::SeqCst);
+        self.save_queued_jobs().await;
+
+        let deadline = Instant::now() + grace;
+        while !self.active_jobs.read().await.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+
+        let stopped: Vec<(String, tokio::task::JoinHandle<()>)> = self.active_jobs.write().await.drain().collect();
+        let mut stopped_ids = Vec::new();
+        for (job_id, handle) in stopped {
+            handle.abort();
+            let _ = handle.await;
+            if let Some(job) = self.jobs.write().await.get_mut(&job_id) {
+                job.add_log("Stopped by daemon shutdown; resumes from its last checkpoint".to_string());
+            }
+            info!("Stopped job {} for shutdown", job_id);
+            stopped_ids.push(job_id);
+        }
+        stopped_ids
+    }
+
+    /// Saves the request of every queued job. Resumed jobs are already
+    /// covered by their checkpoints; the rest, such as verify-only jobs,
+    /// have nothing to replay and are dropped with a warning.
+    async fn save_queued_jobs(&self) {
+        let queued: Vec<String> = self.job_queue.read().await.iter().cloned().collect();
+        let mut dropped = 0;
+        for job_id in queued {
+            let saved = {
+                let jobs = self.jobs.read().await;
+                jobs.get(&job_id).and_then(|job| job.request.clone().map(|request| QueuedJobCheckpoint {
+                    job_id: job.id.clone(),
+                    created_at: job.created_at,
+                    request,
+                }))
+            };
+            match saved {
+                Some(queued) => {
+                    if let Err(e) = self.checkpoint_manager.save_queued_job(&queued).await {
+                        warn!("Failed to save queued job {}: {}", job_id, e);
+                        dropped += 1;
+                    }
+                }
+                None if self.checkpoint_manager.checkpoint_path(&job_id).is_some() => {}
+                None => dropped += 1,
+            }
+        }
+        if dropped > 0 {
+            warn!("{} queued jobs could not be saved and are dropped with the daemon", dropped);
+        }
+    }
+
+    /// Cancels every pending, running or paused job that has a source under
+    /// `prefix`, returning the cancelled job IDs.
+    pub async fn cancel_matching(&self, prefix: &Path) -> Result<Vec<String>> {
+        if prefix.as_os_str().is_empty() {
+            anyhow::bail!("Path prefix must not be empty");
+        }
+
+        let mut matching: Vec<String> = {
+            let jobs = self.jobs.read().await;
+            jobs.values()
+                .filter(|job| matches!(job.get_status(), JobStatus::Pending | JobStatus::Running | JobStatus::Paused))
+                .filter(|job| job.sources.iter().any(|source| source.starts_with(prefix)))
+                .map(|job| job.id.clone())
+                .collect()
+        };
+        matching.sort();
+
+        for job_id in &matching {
+            self.cancel_job(job_id).await?;
+        }
+
+        info!("Cancelled {} jobs under {:?}", matching.len(), prefix);
+        Ok(matching)
+    }
+
+    /// Subscribes to the live events of `job_id`, returning its current
+    /// progress so callers can render it before the next event arrives.
+    /// The receiver sees events for every job; callers filter by ID. A
+    /// receiver that falls behind loses progress updates, never status
+    /// changes.
+    pub async fn subscribe_events(&self, job_id: &str) -> Result<(Progress, EventReceiver)> {
+        // Subscribe before taking the snapshot so no update falls in between
+        let receiver = self.event_sender.subscribe();
+        let jobs = self.jobs.read().await;
+        let job = jobs.get(job_id)
+            .with_context(|| format!("Job {} not found", job_id))?;
+        Ok((job.progress_snapshot(), receiver))
+    }
+
+    /// Pauses a running or queued job. A running job's copy is stopped
+    /// with its checkpoint kept, so on resume it skips the files it had
+    /// already copied.
+    pub async fn pause_job(&self, job_id: &str) -> Result<()> {
+        let status = self.jobs.read().await.get(job_id).map(|job| job.get_status());
+        if !matches!(status, Some(JobStatus::Running | JobStatus::Pending)) {
+            return Ok(());
+        }
+        {
+            let mut queue = self.job_queue.write().await;
+            queue.remove(job_id);
+            self.record_queue_depth(&queue);
+        }
+        let handle = self.active_jobs.write().await.remove(job_id);
+        if let Some(handle) = handle {
+            handle.abort();
+            let _ = handle.await;
+        }
+
+        let mut jobs = self.jobs.write().await;
+        if let Some(job) = jobs.get_mut(job_id) {
+            // It may have finished before it could be stopped
+            if matches!(job.get_status(), JobStatus::Running | JobStatus::Pending) {
+                job.set_status(JobStatus::Paused);
+                job.add_log("Job paused".to_string());
+                self.event_sender.send_status(job_id, JobStatus::Paused);
+                info!("Paused job {}", job_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pauses every running and queued job, returning the paused job IDs.
+    /// Queued jobs go first, so none of them starts in a slot freed by a
+    /// running job being paused.
+    pub async fn pause_all(&self) -> Result<Vec<String>> {
+        let mut active: Vec<(bool, DateTime<Utc>, String)> = {
+            let jobs = self.jobs.read().await;
+            jobs.values()
+                .filter(|job| matches!(job.get_status(), JobStatus::Pending | JobStatus::Running))
+                .map(|job| (job.get_status() == JobStatus::Running, job.created_at, job.id.clone()))
+                .collect()
+        };
+        active.sort();
+
+        let mut paused = Vec::new();
+        for (_, _, job_id) in active {
+            self.pause_job(&job_id).await?;
+            if self.get_job(&job_id).await.is_some_and(|job| job.get_status() == JobStatus::Paused) {
+                paused.push(job_id);
+            }
+        }
+        info!("Paused {} jobs", paused.len());
+        Ok(paused)
+    }
+
+    /// Resumes every paused job in the order they were created, returning
+    /// the resumed job IDs.
+    pub async fn resume_all(&self) -> Result<Vec<String>> {
+        let mut paused: Vec<(DateTime<Utc>, String)> = {
+            let jobs = self.jobs.read().await;
+            jobs.values()
+                .filter(|job| job.get_status() == JobStatus::Paused)
+                .map(|job| (job.created_at, job.id.clone()))
+                .collect()
+        };
+        paused.sort();
+
+        let mut resumed = Vec::new();
+        for (_, job_id) in paused {
+            self.resume_job(&job_id).await?;
+            resumed.push(job_id);
+        }
+        info!("Resumed {} jobs", resumed.len());
+        Ok(resumed)
+    }
+
+    pub async fn resume_job(&self, job_id: &str) -> Result<()> {
+        let mut jobs = self.jobs.write().await;
+        if let Some(job) = jobs.get_mut(job_id) {
+            if job.get_status() == JobStatus::Paused {
+                job.set_status(JobStatus::Pending);
+                job.add_log("Job resumed".to_string());
+                self.event_sender.send_status(job_id, JobStatus::Pending);
+                info!("Resumed job {}", job_id);
+                
+                // Add back to queue
+                let (priority, created_at) = (job.priority, job.created_at);
+                drop(jobs);
+                
+                let mut queue = self.job_queue.write().await;
+                queue.push(job_id.to_string(), priority, created_at);
+                self.record_queue_depth(&queue);
+            }
+        }
+        
+        // Try to start the job
+        self.try_start_next_job().await;
+        Ok(())
+    }
+
+    async fn try_start_next_job(&self) {
+        if self.semaphore.available_permits() == 0 || self.draining.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let _starting = self.starting.lock().await;
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            if let Some(job_id) = self.next_startable_job().await {
+                let jobs = self.jobs.clone();
+                let event_sender = self.event_sender.clone();
+                let active_jobs = self.active_jobs.clone();
+                let inflight_budget = self.inflight_budget.clone();
+                let history = self.history.clone();
+                let results = self.results.clone();
+                let checkpoint_manager = self.checkpoint_manager.clone();
+                let bandwidth = self.bandwidth.clone();
+                let profiler = self.profiler.clone();
+                let dedup_indexes = self.dedup_indexes.clone();
+                let monitor = self.monitor.clone();
+                let settings = self.settings.read().clone();
+                let (numa_buffers, read_ahead_blocks) = (settings.numa_buffers, settings.read_ahead_blocks);
+                let (class, destination, queued_for) = {
+                    let mut jobs = self.jobs.write().await;
+                    jobs.get_mut(&job_id).map_or((JobClass::Foreground, PathBuf::new(), None), |job| {
+                        job.options.checkpoint_interval_bytes = settings.checkpoint_interval_bytes;
+                        job.options.checkpoint_interval_secs = settings.checkpoint_interval_secs;
+                        job.options.idle_io_verification = settings.idle_io_verification;
+                        job.options.verify_cost = settings.verify_cost;
+                        // The limit may have been lowered while the job was queued
+                        job.options.copy_parallelism = job.options.copy_parallelism.min(settings.max_copy_parallelism);
+                        // Only a job's first start ends its wait in the queue
+                        let queued_for = job.started_at.is_none()
+                            .then(|| (Utc::now() - job.created_at).to_std().unwrap_or_default());
+                        (job.options.class, job.destination.clone(), queued_for)
+                    })
+                };
+                let device_slot = self.monitor.clone().map(|monitor| {
+                    if let Some(wait) = queued_for {
+                        monitor.job_left_queue(wait);
+                    }
+                    DeviceSlot::new(monitor, &destination)
+                });
+                let job_id_clone = job_id.clone();
+                
+                let task = async move {
+                    let _permit = permit; // Hold permit for duration of job
+                    let _device_slot = device_slot;
+                    
+                    // Execute the job
+                    let result = match Self::apply_rate_percent(&job_id_clone, &jobs, &bandwidth).await {
+                        Ok(()) => Self::execute_job(&job_id_clone, jobs.clone(), event_sender, inflight_budget, numa_buffers, read_ahead_blocks, profiler, monitor, &dedup_indexes, &checkpoint_manager, results.as_deref()).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = result {
+                        error!("Job {} failed: {}", job_id_clone, e);
+                        
+                        // Update job status to failed
+                        let mut jobs_guard = jobs.write().await;
+                        if let Some(job) = jobs_guard.get_mut(&job_id_clone) {
+                            job.set_status(JobStatus::Failed);
+                            job.add_log(format!("Job failed: {}", e));
+                            if let Some(error) = e.downcast_ref::<CopydError>() {
+                                job.add_log(format!("Suggested action: {}", error.suggested_action()));
+                            }
+                        }
+                    }
+
+                    let finished = jobs.read().await.get(&job_id_clone)
+                        .filter(|job| job.get_status().is_terminal())
+                        .cloned();
+                    if let Some(job) = finished {
+                        Self::record_history(history.as_deref(), &job).await;
+                    }
+                    // Checkpoints only outlive jobs the daemon never got to finish
+                    if let Err(e) = checkpoint_manager.delete_checkpoint(&job_id_clone).await {
+                        warn!("Failed to delete checkpoint of job {}: {}", job_id_clone, e);
+                    }
+                    
+                    // Remove from active jobs
+                    let mut active = active_jobs.write().await;
+                    active.remove(&job_id_clone);
+                };
+
+                // Registered before the task can end and remove itself, or a
+                // quick job would stay listed as running and block its destination
+                let mut active = self.active_jobs.write().await;
+                // Niceness and I/O priority belong to threads, so background
+                // jobs run on a runtime whose threads all have them lowered
+                let handle = match class {
+                    JobClass::Foreground => tokio::spawn(task),
+                    JobClass::Background => crate::priority::background_runtime().spawn(task),
+                };
+
+                active.insert(job_id, handle);
+            }
+        }
+    }
+
+    /// Takes the first queued job off the queue whose destinations overlap
+    /// neither a running job's nor those of a job queued ahead of it, so jobs
+    /// writing to the same place run one after another in queue order.
+    async fn next_startable_job(&self) -> Option<String> {
+        let queued: Vec<String> = self.job_queue.read().await.iter().cloned().collect();
+        let running: Vec<String> = self.active_jobs.read().await.keys().cloned().collect();
+        let chosen = {
+            let jobs = self.jobs.read().await;
+            let mut claimed: Vec<&Path> = running.iter()
+                .filter_map(|job_id| jobs.get(job_id))
+                .flat_map(Job::written_destinations)
+                .collect();
+            let mut chosen = None;
+            for job_id in &queued {
+                let Some(job) = jobs.get(job_id) else {
+                    chosen = Some(job_id.clone());
+                    break;
+                };
+                if !job.writes_overlapping(&claimed) {
+                    chosen = Some(job_id.clone());
+                    break;
+                }
+                claimed.extend(job.written_destinations());
+            }
+            chosen
+        }?;
+
+        let mut queue = self.job_queue.write().await;
+        if !queue.remove(&chosen) {
+            return None;
+        }
+        self.record_queue_depth(&queue);
+        Some(chosen)
+    }
+
+    /// A running or queued job writing to a destination `job` overlaps.
+    async fn job_writing_overlapping(&self, job: &Job) -> Option<String> {
+        let mut candidates: Vec<String> = self.active_jobs.read().await.keys().cloned().collect();
+        candidates.extend(self.job_queue.read().await.iter().cloned());
+        let jobs = self.jobs.read().await;
+        candidates.into_iter().find(|job_id| {
+            jobs.get(job_id).is_some_and(|other| job.writes_overlapping(&other.written_destinations()))
+        })
+    }
+
+    /// Turns a job's percentage cap into bytes per second for its
+    /// destination device, keeping a lower fixed cap if it has one.
+    async fn apply_rate_percent(
+        job_id: &str,
+        jobs: &RwLock<HashMap<String, Job>>,
+        bandwidth: &BandwidthCalibrator,
+    ) -> Result<()> {
+        let (percent, destination) = {
+            let jobs = jobs.read().await;
+            let Some(job) = jobs.get(job_id) else { return Ok(()) };
+            let Some(percent) = job.options.max_rate_percent else { return Ok(()) };
+            (percent, job.destination.clone())
+        };
+
+        let cap = bandwidth.rate_cap(&destination, percent).await?;
+        let mut jobs = jobs.write().await;
+        if let Some(job) = jobs.get_mut(job_id) {
+            let rate = job.options.max_rate_bps.map_or(cap, |rate| rate.min(cap));
+            job.options.max_rate_bps = Some(rate);
+            job.add_log(format!(
+                "Limiting to {:.1} MB/s ({}% of the destination device's bandwidth)",
+                rate as f64 / (1024.0 * 1024.0),
+                percent
+            ));
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_job(
+        job_id: &str,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: EventPublisher,
+        inflight_budget: InflightBudget,
+        numa_buffers: bool,
+        read_ahead_blocks: usize,
+        profiler: PerformanceProfiler,
+        monitor: Option<Arc<EnhancedMonitor>>,
+        dedup_indexes: &DedupIndexes,
+        checkpoint_manager: &CheckpointManager,
+        results: Option<&JobResults>,
+    ) -> Result<()> {
+        info!("Starting execution of job {}", job_id);
+        
+        let start_time = Instant::now();
+        
+        // Get job details and mark as running
+        let (sources, urls, destination, options) = {
+            let mut jobs_guard = jobs.write().await;
+            let job = jobs_guard.get_mut(job_id)
+                .context("Job not found")?;
+            
+            job.set_status(JobStatus::Running);
+            job.add_log("Job started".to_string());
+            
+            (job.sources.clone(), job.urls.clone(), job.destination.clone(), job.options.clone())
+        };
+
+        // Send status update event
+        event_sender.send_status(job_id, JobStatus::Running);
+
+        let copy_engine = FileCopyEngine::new(options.engine)
+            .with_inflight_budget(inflight_budget)
+            .with_numa_buffers(numa_buffers)
+            .with_read_ahead(read_ahead_blocks)
+            .with_idle_io_verification(options.idle_io_verification)
+            .with_best_effort_metadata(options.best_effort_metadata)
+            .with_profiler(profiler);
+        // Each job's copies get a key with a salt of their own
+        let copy_engine = match options.encrypt {
+            true => copy_engine.with_encryption_key(
+                EncryptionKey::generate(&crate::encrypt::passphrase(options.key_id.as_deref())?)?
+            ),
+            false => copy_engine,
+        };
+        let dedup_index = match options.dedup && !options.dry_run {
+            true => Some(dedup_indexes.open(&destination).await?),
+            false => None,
+        };
+        let copy_engine = match &dedup_index {
+            Some(index) => copy_engine.with_dedup(index.clone()),
+            None => copy_engine,
+        };
+        let copy_engine = match monitor {
+            Some(monitor) => copy_engine.with_monitor(monitor),
+            None => copy_engine,
+        };
+        let sampler = AbortOnDrop(tokio::spawn(Self::sample_job_rates(job_id.to_string(), jobs.clone(), event_sender.clone())));
+
+        // A move renames what it can and copies the rest
+        let moving = options.operation == JobOperation::Move;
+        let sources = match moving {
+            true => Self::rename_sources(job_id, &sources, &destination, &options, jobs.clone(), &event_sender).await?,
+            false => sources,
+        };
+
+        // Execute the copy operation; copies hand back the traversal they
+        // worked through
+        let result = if moving && sources.is_empty() {
+            Ok(None)
+        } else if options.verify_only {
+            Self::execute_verify_operation(job_id, &sources, &destination, &options, jobs.clone(), &event_sender).await.map(|()| None)
+        } else if !urls.is_empty() {
+            Self::execute_download_operation(job_id, &urls, &destination, &options, jobs.clone(), &event_sender).await.map(|()| None)
+        } else if options.atomic {
+            Self::execute_atomic_copy_operation(
+                job_id,
+                &sources,
+                &destination,
+                &options,
+                jobs.clone(),
+                &event_sender,
+                &copy_engine,
+                checkpoint_manager,
+                results,
+            ).await.map(Some)
+        } else {
+            Self::execute_copy_operation(
+                job_id, 
+                &sources, 
+                &destination, 
+                &options, 
+                jobs.clone(), 
+                &event_sender,
+                &copy_engine,
+                checkpoint_manager,
+                results,
+            ).await.map(Some)
+        };
+        let result = match result {
+            Ok(Some(traversal)) if moving => {
+                Self::remove_moved_sources(job_id, &sources, &traversal, &options, jobs.clone(), &event_sender).await
+            }
+            result => result.map(|_| ()),
+        };
+        drop(sampler);
+        if let Some(index) = &dedup_index {
+            if let Err(e) = index.save().await {
+                warn!("Job {}: failed to save the deduplication index: {:#}", job_id, e);
+            }
+        }
+
+        // Update final job status
+        let duration = start_time.elapsed();
+        {
+            let mut jobs_guard = jobs.write().await;
+            if let Some(job) = jobs_guard.get_mut(job_id) {
+                match result {
+                    Ok(_) => {
+                        job.set_status(JobStatus::Completed);
+                        let throughput = if duration.as_secs_f64() > 0.0 {
+                            job.progress.bytes_copied as f64 / duration.as_secs_f64() / 1024.0 / 1024.0
+                        } else {
+                            0.0
+                        };
+                        
+                        let message = format!("Job completed successfully in {:.2}s ({:.2} MB/s)", 
+                                            duration.as_secs_f64(), throughput);
+                        job.add_log(message);
+                        info!("Completed job {} in {:.2}s", job_id, duration.as_secs_f64());
+                    }
+                    Err(ref e) => {
+                        job.set_status(JobStatus::Failed);
+                        let error_msg = format!("Job failed: {}", e);
+                        job.add_log(error_msg);
+                        error!("Job {} failed: {}", job_id, e);
+                    }
+                }
+                if job.error_summary.error_count > 0 {
+                    let message = format!("{} files failed to copy", job.error_summary.error_count);
+                    job.add_log(message);
+                }
+                if let Some(message) = copy_engine.skipped_metadata().summary() {
+                    warn!("Job {}: {}", job_id, message);
+                    job.add_log(message);
+                }
+                if let Some(message) = copy_engine.deduplicated().summary() {
+                    info!("Job {}: {}", job_id, message);
+                    job.add_log(message);
+                }
+                event_sender.send_progress_now(job);
+                event_sender.send_status(job_id, job.get_status());
+            }
+        }
+
+        result
+    }
+
+    /// Renames each source of a move to where a copy would put it, when it
+    /// is on the same filesystem and nothing the copy would do differently
+    /// applies, returning the sources left to copy. Anything a rename can't
+    /// do, across filesystems or into a non-empty directory, is copied.
+    async fn rename_sources(
+        job_id: &str,
+        sources: &[PathBuf],
+        destination: &Path,
+        options: &JobOptions,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: &EventPublisher,
+    ) -> Result<Vec<PathBuf>> {
+        let renamable = !options.dry_run
+            && !options.atomic
+            && options.mirrors.is_empty()
+            && options.chmod.is_none()
+            && options.regex_rename_match.is_none()
+            && !options.compress
+            && !options.encrypt
+            && options.skip_destinations.is_empty();
+        if !renamable {
+            return Ok(sources.to_vec());
+        }
+
+        // Laid out as the copy would, which puts several sources inside a
+        // destination directory it creates
+        let into_destination = destination.is_dir() || sources.len() > 1;
+        if into_destination {
+            tokio::fs::create_dir_all(destination).await
+                .with_context(|| format!("Failed to create destination directory {:?}", destination))?;
+        }
+        let mut remaining = Vec::new();
+        for source in sources {
+            let target = match source.file_name() {
+                Some(name) if into_destination => destination.join(name),
+                _ => destination.to_path_buf(),
+            };
+            if options.exists_action != ExistsAction::Overwrite && target.symlink_metadata().is_ok() {
+                remaining.push(source.clone());
+                continue;
+            }
+            match nix::fcntl::renameat(None, source, None, &target) {
+                Ok(()) => {
+                    Self::add_job_log(jobs.clone(), job_id, format!("Moved {:?} to {:?} by renaming it", source, target)).await;
+                    event_sender.send_source_removed(job_id, source);
+                }
+                Err(e) => {
+                    debug!("Copying {:?} to {:?} instead of renaming it: {}", source, target, e);
+                    remaining.push(source.clone());
+                }
+            }
+        }
+        Ok(remaining)
+    }
+
+    /// Removes the sources a move copied. Only done once every file was
+    /// copied, and verified when the job verifies, so nothing is removed
+    /// that has no copy; sources the user chose not to copy over existing
+    /// files are kept. Only the entries of `traversal` are removed, so a
+    /// source directory that gained files during the copy stays with them.
+    async fn remove_moved_sources(
+        job_id: &str,
+        sources: &[PathBuf],
+        traversal: &DirectoryTraversal,
+        options: &JobOptions,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: &EventPublisher,
+    ) -> Result<()> {
+        if options.dry_run {
+            for source in sources {
+                Self::add_job_log(jobs.clone(), job_id, format!("Would remove {:?} once copied", source)).await;
+            }
+            return Ok(());
+        }
+        let failed = jobs.read().await.get(job_id).map_or(0, |job| job.error_summary.error_count);
+        if failed > 0 {
+            anyhow::bail!("Kept the sources of the move, as {} files failed to copy", failed);
+        }
+        if !options.skip_destinations.is_empty() || options.exists_action == ExistsAction::Skip {
+            Self::add_job_log(jobs, job_id, "Kept the sources of the move, as files may have been skipped".to_string()).await;
+            return Ok(());
+        }
+
+        // Symlinks are only copied when links are preserved
+        let links: &[FileEntry] = if options.preserve_links { &traversal.symlinks } else { &[] };
+        for entry in traversal.files.iter().chain(links) {
+            tokio::fs::remove_file(&entry.source_path).await
+                .with_context(|| format!("Copied {:?} but failed to remove it", entry.source_path))?;
+        }
+        // Children first, so each directory is empty unless something new
+        // was put in it
+        for dir in traversal.directory_sources.iter().rev() {
+            match tokio::fs::remove_dir(dir).await {
+                Err(e) if e.kind() == std::io::ErrorKind::DirectoryNotEmpty => {
+                    Self::add_job_log(jobs.clone(), job_id, format!("Kept {:?}, which holds files the move didn't copy", dir)).await;
+                }
+                removed => removed.with_context(|| format!("Copied {:?} but failed to remove it", dir))?,
+            }
+        }
+        for source in sources {
+            if tokio::fs::symlink_metadata(source).await.is_err() {
+                Self::add_job_log(jobs.clone(), job_id, format!("Removed {:?} after copying it", source)).await;
+                event_sender.send_source_removed(job_id, source);
+            }
+        }
+        Ok(())
+    }
+
+    /// Downloads each URL source to `destination`, which is a directory when
+    /// there are several or it already is one. Progress follows the lengths
+    /// the server announces, and with verification the file written is
+    /// checked against the bytes received.
+    async fn execute_download_operation(
+        job_id: &str,
+        urls: &[String],
+        destination: &Path,
+        options: &JobOptions,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: &EventPublisher,
+    ) -> Result<()> {
+        let into_directory = urls.len() > 1 || destination.is_dir();
+        if !options.dry_run {
+            if into_directory {
+                tokio::fs::create_dir_all(destination).await?;
+            } else if let Some(parent) = destination.parent().filter(|_| options.create_parents) {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+        }
+        if let Some(job) = jobs.write().await.get_mut(job_id) {
+            job.progress.total_files = urls.len() as u64;
+            job.progress.files_analyzed = urls.len() as u64;
+        }
+
+        let client = reqwest::Client::builder().connect_timeout(HTTP_CONNECT_TIMEOUT).build()?;
+        for url in urls {
+            let dest_path = if into_directory {
+                destination.join(http_source::file_name(url)?)
+            } else {
+                destination.to_path_buf()
+            };
+            if options.exists_action == ExistsAction::Skip && dest_path.exists() {
+                Self::add_job_log(jobs.clone(), job_id, format!("Skipped {}, {:?} already exists", url, dest_path)).await;
+                continue;
+            }
+            if options.dry_run {
+                Self::add_job_log(jobs.clone(), job_id, format!("Would download {} to {:?}", url, dest_path)).await;
+                continue;
+            }
+
+            Self::add_job_log(jobs.clone(), job_id, format!("Downloading {} to {:?}", url, dest_path)).await;
+            match Self::download_url(job_id, &client, url, &dest_path, options, jobs.clone()).await {
+                Ok(message) => {
+                    let mut jobs_guard = jobs.write().await;
+                    if let Some(job) = jobs_guard.get_mut(job_id) {
+                        job.progress.files_copied += 1;
+                        job.add_log(message);
+                    }
+                }
+                Err(e) => {
+                    let file_error = FileError {
+                        file_path: url.clone(),
+                        error: format!("{:#}", e),
+                    };
+                    if let Some(job) = jobs.write().await.get_mut(job_id) {
+                        job.add_log(format!("Failed to download {}: {:#}", url, e));
+                        job.record_file_error(file_error.clone());
+                    }
+                    event_sender.send(JobEvent {
+                        job_id: Some(JobId { uuid: job_id.to_string() }),
+                        event_type: Some(job_event::EventType::FileError(file_error)),
+                    });
+                    if options.error_policy == ErrorPolicy::AbortJob {
+                        return Err(e.context(format!("Aborted at the first failed download, {}", url)));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Streams one URL into `dest_path`, returning the line to log. The
+    /// download goes to a hidden name beside it and only takes its name once
+    /// complete and verified, so a failed one leaves no truncated file behind.
+    async fn download_url(
+        job_id: &str,
+        client: &reqwest::Client,
+        url: &str,
+        dest_path: &Path,
+        options: &JobOptions,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+    ) -> Result<String> {
+        let name = dest_path.file_name().unwrap_or_default().to_string_lossy();
+        let part_path = dest_path.with_file_name(format!(".{}.copyd-download", name));
+        let downloaded = match Self::download_url_to(job_id, client, url, &part_path, options, jobs).await {
+            Ok(message) => tokio::fs::rename(&part_path, dest_path).await
+                .with_context(|| format!("Failed to rename {:?} to {:?}", part_path, dest_path))
+                .map(|()| message),
+            Err(e) => Err(e),
+        };
+        if downloaded.is_err() {
+            let _ = tokio::fs::remove_file(&part_path).await;
+        }
+        downloaded
+    }
+
+    async fn download_url_to(
+        job_id: &str,
+        client: &reqwest::Client,
+        url: &str,
+        dest_path: &Path,
+        options: &JobOptions,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+    ) -> Result<String> {
+        let verify_mode = crate::verify::VerifyMode::from(options.verify);
+        let mut download = HttpDownload::start(client, url, StreamHasher::new(verify_mode)).await?;
+        if let Some(total) = download.total_bytes() {
+            if let Some(job) = jobs.write().await.get_mut(job_id) {
+                job.progress.total_bytes += total;
+                job.progress.bytes_analyzed += total;
+            }
+        }
+
+        let mut file = tokio::fs::File::create(dest_path).await
+            .with_context(|| format!("Failed to create {:?}", dest_path))?;
+        loop {
+            let written = download.write_next(&mut file).await?;
+            if written == 0 {
+                break;
+            }
+            if let Some(job) = jobs.write().await.get_mut(job_id) {
+                job.progress.bytes_copied += written as u64;
+            }
         }
+        file.sync_all().await?;
 
-        // Cancel active job
-        {
-            let mut active = self.active_jobs.write().await;
-            if let Some(handle) = active.remove(job_id) {
-                handle.abort();
+        let resumes = download.resumes();
+        let (received, checksum) = download.finish();
+        if options.verify != VerifyMode::None {
+            let written = FileVerifier::calculate_checksum(dest_path, verify_mode).await?;
+            if written != checksum {
+                anyhow::bail!("{:?} does not match the {} bytes received from {}", dest_path, received, url);
             }
         }
+        if let Some(mode) = options.chmod {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(dest_path, std::fs::Permissions::from_mode(mode)).await?;
+        }
+
+        Ok(match resumes {
+            0 => format!("Downloaded {} ({} bytes)", url, received),
+            _ => format!("Downloaded {} ({} bytes, resumed {} times)", url, received, resumes),
+        })
+    }
+
+    /// Checks every file a copy of `sources` to `destination` would have
+    /// written against its source, failing when any of them does not match.
+    async fn execute_verify_operation(
+        job_id: &str,
+        sources: &[PathBuf],
+        destination: &Path,
+        options: &JobOptions,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: &EventPublisher,
+    ) -> Result<()> {
+        let traversal = DirectoryHandler::analyze_sources(sources, destination, options.recursive, false).await?;
 
-        // Update job status
         {
-            let mut jobs = self.jobs.write().await;
-            if let Some(job) = jobs.get_mut(job_id) {
-                job.set_status(JobStatus::Cancelled);
-                job.add_log("Job cancelled by user".to_string());
+            let mut jobs_guard = jobs.write().await;
+            if let Some(job) = jobs_guard.get_mut(job_id) {
+                job.progress.total_bytes = traversal.total_size;
+                job.progress.total_files = traversal.total_files;
+                job.progress.bytes_analyzed = traversal.total_size;
+                job.progress.files_analyzed = traversal.total_files;
+                job.add_log(format!("Verifying {} files with {:?}", traversal.total_files, options.verify));
             }
         }
 
-        info!("Cancelled job {}", job_id);
-        Ok(())
-    }
+        let mode = crate::verify::VerifyMode::from(options.verify);
+        let mut failed = 0u64;
+        for file_entry in &traversal.files {
+            let verification = if options.idle_io_verification {
+                FileVerifier::verify_copy_at_idle(&file_entry.source_path, &file_entry.dest_path, mode).await
+            } else {
+                FileVerifier::verify_copy(&file_entry.source_path, &file_entry.dest_path, mode).await
+            };
+            let outcome = match verification {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(format!("{:?} does not match its source", file_entry.dest_path)),
+                Err(e) => Err(format!("{:#}", e)),
+            };
 
-    pub async fn pause_job(&self, job_id: &str) -> Result<()> {
-        let mut jobs = self.jobs.write().await;
-        if let Some(job) = jobs.get_mut(job_id) {
-            if job.get_status() == JobStatus::Running {
-                job.set_status(JobStatus::Paused);
-                job.add_log("Job paused".to_string());
-                info!("Paused job {}", job_id);
+            let mut jobs_guard = jobs.write().await;
+            let Some(job) = jobs_guard.get_mut(job_id) else { continue };
+            job.progress.bytes_copied += file_entry.size;
+            job.progress.files_copied += 1;
+            match outcome {
+                Ok(()) => {
+                    job.progress.bytes_verified += file_entry.size;
+                    job.progress.files_verified += 1;
+                    let message = format!("Verified {:?}", file_entry.dest_path);
+                    job.add_log(message.clone());
+                    event_sender.send(JobEvent {
+                        job_id: Some(JobId { uuid: job_id.to_string() }),
+                        event_type: Some(job_event::EventType::LogMessage(message)),
+                    });
+                }
+                Err(error) => {
+                    failed += 1;
+                    let file_error = FileError {
+                        file_path: file_entry.source_path.to_string_lossy().to_string(),
+                        error,
+                    };
+                    job.add_log(format!("Verification failed for {:?}: {}", file_entry.dest_path, file_error.error));
+                    job.record_file_error(file_error.clone());
+                    event_sender.send(JobEvent {
+                        job_id: Some(JobId { uuid: job_id.to_string() }),
+                        event_type: Some(job_event::EventType::FileError(file_error)),
+                    });
+                }
             }
         }
-        Ok(())
-    }
 
-    pub async fn resume_job(&self, job_id: &str) -> Result<()> {
-        let mut jobs = self.jobs.write().await;
-        if let Some(job) = jobs.get_mut(job_id) {
-            if job.get_status() == JobStatus::Paused {
-                job.set_status(JobStatus::Pending);
-                job.add_log("Job resumed".to_string());
-                info!("Resumed job {}", job_id);
-                
-                // Add back to queue
-                drop(jobs);
-                
-                let mut queue = self.job_queue.write().await;
-                queue.push_back(job_id.to_string());
-            }
+        if failed > 0 {
+            anyhow::bail!("{} of {} files failed verification", failed, traversal.total_files);
         }
-        
-        // Try to start the job
-        self.try_start_next_job().await;
         Ok(())
     }
 
-    async fn try_start_next_job(&self) {
-        if self.semaphore.available_permits() == 0 {
-            return;
+    /// Copies a single source directory into a staging directory beside its
+    /// target and, once every file is copied and verified, renames the tree
+    /// into place, exchanging it with a target that already exists. On any
+    /// failure, including files the error policy would skip, the staging
+    /// tree is removed and the target is left as it was.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_atomic_copy_operation(
+        job_id: &str,
+        sources: &[PathBuf],
+        destination: &Path,
+        options: &JobOptions,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: &EventPublisher,
+        copy_engine: &FileCopyEngine,
+        checkpoint_manager: &CheckpointManager,
+        results: Option<&JobResults>,
+    ) -> Result<DirectoryTraversal> {
+        let [source] = sources else {
+            anyhow::bail!("Atomic copies take a single source directory, not {}", sources.len());
+        };
+        if !options.recursive || !tokio::fs::metadata(source).await?.is_dir() {
+            anyhow::bail!("Atomic copies need a recursive copy of a directory, and {:?} is not one", source);
+        }
+        let (target, staging) = Self::atomic_paths(job_id, source, destination, options);
+        if tokio::fs::symlink_metadata(&target).await.is_ok_and(|metadata| !metadata.is_dir()) {
+            anyhow::bail!("{:?} exists and is not a directory, so a copied tree cannot replace it", target);
+        }
+        if options.dry_run {
+            return Self::execute_copy_operation(
+                job_id, sources, destination, options, jobs, event_sender, copy_engine, checkpoint_manager, results,
+            ).await;
+        }
+        let parent = target.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        if options.create_parents {
+            tokio::fs::create_dir_all(parent).await
+                .with_context(|| format!("Failed to create destination directory {:?}", parent))?;
         }
 
-        let job_id = {
-            let mut queue = self.job_queue.write().await;
-            queue.pop_front()
+        // What an earlier run left staged is copied again from scratch
+        let staging = StagingTree::new(staging);
+        staging.remove_now().await;
+        Self::add_job_log(jobs.clone(), job_id, format!("Staging the copy in {:?}", staging.path())).await;
+
+        let staged = Self::execute_copy_operation(
+            job_id, sources, staging.path(), options, jobs.clone(), event_sender, copy_engine, checkpoint_manager, results,
+        ).await;
+        let failed_files = jobs.read().await.get(job_id).map_or(0, |job| job.error_summary.error_count);
+        let result = match staged {
+            Ok(_) if failed_files > 0 => Err(anyhow::anyhow!(
+                "{} files failed to copy; {:?} was left unchanged", failed_files, target
+            )),
+            Ok(traversal) => Self::swap_into_place(staging.path(), &target).map(|()| traversal),
+            Err(e) => Err(e.context(format!("{:?} was left unchanged", target))),
         };
 
-        if let Some(job_id) = job_id {
-            if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
-                let jobs = self.jobs.clone();
-                let event_sender = self.event_sender.clone();
-                let active_jobs = self.active_jobs.clone();
-                let job_id_clone = job_id.clone();
-                
-                let handle = tokio::spawn(async move {
-                    let _permit = permit; // Hold permit for duration of job
-                    
-                    // Execute the job
-                    if let Err(e) = Self::execute_job(&job_id_clone, jobs.clone(), event_sender).await {
-                        error!("Job {} failed: {}", job_id_clone, e);
-                        
-                        // Update job status to failed
-                        let mut jobs_guard = jobs.write().await;
-                        if let Some(job) = jobs_guard.get_mut(&job_id_clone) {
-                            job.set_status(JobStatus::Failed);
-                            job.add_log(format!("Job failed: {}", e));
+        // After an exchange the staging path holds the previous tree
+        staging.remove_now().await;
+        if result.is_ok() {
+            Self::add_job_log(jobs, job_id, format!("Moved the copied tree into place at {:?}", target)).await;
+        }
+        result
+    }
+
+    /// The tree an atomic copy of `source` replaces, the same one a regular
+    /// copy would write to, and where the copy is staged meanwhile.
+    fn atomic_paths(job_id: &str, source: &Path, destination: &Path, options: &JobOptions) -> (PathBuf, PathBuf) {
+        let target = if destination.is_dir() {
+            destination.join(source.file_name().unwrap_or_default())
+        } else {
+            destination.to_path_buf()
+        };
+        let parent = target.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let mut staging_name = std::ffi::OsString::from(".");
+        staging_name.push(target.file_name().unwrap_or_default());
+        staging_name.push(format!(".copyd-staging-{}", job_id));
+        let staging = options.temp_dir.as_deref().unwrap_or(parent).join(staging_name);
+        (target, staging)
+    }
+
+    /// Renames `staging` to `target`, atomically exchanging the two when
+    /// `target` already exists.
+    fn swap_into_place(staging: &Path, target: &Path) -> Result<()> {
+        if target.exists() {
+            nix::fcntl::renameat2(None, staging, None, target, nix::fcntl::RenameFlags::RENAME_EXCHANGE)
+                .with_context(|| format!("Failed to exchange {:?} with {:?}", staging, target))?;
+        } else {
+            std::fs::rename(staging, target)
+                .with_context(|| format!("Failed to move {:?} to {:?}", staging, target))?;
+        }
+        Ok(())
+    }
+
+    /// Copies one file, through `packed` when it takes the file, and then
+    /// verifies it as a phase of its own. Its bytes count as copied once
+    /// written and as verified once checked; a failed verification takes
+    /// them back off.
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_then_verify(
+        job_id: &str,
+        jobs: &Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: &EventPublisher,
+        copy_engine: &FileCopyEngine,
+        mut packed: Option<&mut PackedWriter>,
+        entry: &FileEntry,
+        options: &CopyOptions,
+        verify_result: &mut VerifyResult,
+    ) -> Result<u64> {
+        *verify_result = VerifyResult::NotVerified;
+        let (source, destination) = (&entry.source_path, &entry.dest_path);
+        let copy_only = CopyOptions { verify: VerifyMode::None, ..options.clone() };
+        let copying = jobs.read().await.get(job_id).map(|job| job.copying.clone()).unwrap_or_default();
+        let mut recopies = 0;
+        loop {
+            let (copied, counted) = FileCopyEngine::count_written(copying.clone(), async {
+                match packed.as_deref_mut() {
+                    Some(writer) if PackedWriter::accepts(entry.size, &copy_only) => {
+                        match writer.copy_file(copy_engine, source, destination, &copy_only).await? {
+                            Some(bytes_copied) => Ok(bytes_copied),
+                            None => copy_engine.copy_file(source, destination, &copy_only).await,
                         }
                     }
-                    
-                    // Remove from active jobs
-                    let mut active = active_jobs.write().await;
-                    active.remove(&job_id_clone);
-                });
+                    _ => copy_engine.copy_file(source, destination, &copy_only).await,
+                }
+            }).await;
+            // Counted as written while copying, and as copied from here on
+            let mut jobs_guard = jobs.write().await;
+            copying.fetch_sub(counted, Ordering::SeqCst);
+            let bytes_copied = copied?;
+            if let Some(job) = jobs_guard.get_mut(job_id) {
+                job.progress.bytes_copied += bytes_copied;
+                event_sender.send_progress(job);
+            }
+            drop(jobs_guard);
+            let update = |change: fn(&mut Progress, u64)| async move {
+                if let Some(job) = jobs.write().await.get_mut(job_id) {
+                    change(&mut job.progress, bytes_copied);
+                    event_sender.send_progress(job);
+                }
+            };
 
-                let mut active = self.active_jobs.write().await;
-                active.insert(job_id, handle);
+            if options.verify == VerifyMode::None || options.dry_run {
+                return Ok(bytes_copied);
+            }
+            if options.stream_fifo && std::fs::metadata(source).is_ok_and(|m| crate::device::is_fifo(&m)) {
+                // The data is gone from the pipe once read
+                warn!("Skipping verification of {:?}, streamed from a FIFO", destination);
+                return Ok(bytes_copied);
+            }
+            let matches = copy_engine.verify_matches(source, destination, options).await;
+            if let Ok(true) = matches {
+                *verify_result = VerifyResult::Verified;
+                update(|progress, bytes| progress.bytes_verified += bytes).await;
+                return Ok(bytes_copied);
             }
+            update(|progress, bytes| progress.bytes_copied -= bytes).await;
+            if let Ok(false) = matches {
+                if recopies < options.verify_retries {
+                    recopies += 1;
+                    copy_engine.start_recopy(source, destination, recopies, options.verify_retries);
+                    Self::add_job_log(jobs.clone(), job_id, format!(
+                        "Verification of {:?} failed; copying it again ({}/{})",
+                        destination, recopies, options.verify_retries
+                    )).await;
+                    // Written directly this time, in case the packed path is at fault
+                    packed = None;
+                    continue;
+                }
+            }
+            *verify_result = VerifyResult::Mismatch;
+            return Err(matches.err().unwrap_or_else(|| anyhow::anyhow!("File verification failed for {:?}", destination)));
         }
     }
 
-    async fn execute_job(
+    /// Copies `entry` to each of `destinations` from one read of its source,
+    /// retrying those that failed under [`ErrorPolicy::Retry`], and counts
+    /// the bytes read once towards the job's progress.
+    #[allow(clippy::too_many_arguments)]
+    async fn tee_then_verify(
         job_id: &str,
-        jobs: Arc<RwLock<HashMap<String, Job>>>,
-        event_sender: mpsc::UnboundedSender<JobEvent>,
-    ) -> Result<()> {
-        info!("Starting execution of job {}", job_id);
-        
-        let start_time = Instant::now();
-        
-        // Get job details and mark as running
-        let (sources, destination, options) = {
-            let mut jobs_guard = jobs.write().await;
-            let job = jobs_guard.get_mut(job_id)
-                .context("Job not found")?;
-            
-            job.set_status(JobStatus::Running);
-            job.add_log("Job started".to_string());
-            
-            (job.sources.clone(), job.destination.clone(), job.options.clone())
+        jobs: &Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: &EventPublisher,
+        copy_engine: &FileCopyEngine,
+        entry: &FileEntry,
+        destinations: Vec<PathBuf>,
+        options: &CopyOptions,
+        error_policy: ErrorPolicy,
+    ) -> Vec<CopyOutcome> {
+        let tee = |destinations: Vec<PathBuf>| async move {
+            match copy_engine.tee_copy(&entry.source_path, &destinations, options).await {
+                Ok(outcomes) => outcomes,
+                // An unreadable source fails every destination alike
+                Err(e) => {
+                    let error = format!("{:#}", e);
+                    destinations.into_iter()
+                        .map(|destination| CopyOutcome {
+                            destination,
+                            result: Err(anyhow::anyhow!("{}", error)),
+                            verify_result: VerifyResult::NotVerified,
+                        })
+                        .collect()
+                }
+            }
         };
 
-        // Send status update event
-        let _ = event_sender.send(JobEvent {
-            job_id: Some(JobId { uuid: job_id.to_string() }),
-            event_type: Some(job_event::EventType::StatusChange(JobStatus::Running.into())),
-        });
-
-        // Execute the copy operation
-        let result = Self::execute_copy_operation(
-            job_id, 
-            &sources, 
-            &destination, 
-            &options, 
-            jobs.clone(), 
-            &event_sender
-        ).await;
-
-        // Update final job status
-        let duration = start_time.elapsed();
-        {
-            let mut jobs_guard = jobs.write().await;
-            if let Some(job) = jobs_guard.get_mut(job_id) {
-                match result {
-                    Ok(_) => {
-                        job.set_status(JobStatus::Completed);
-                        let throughput = if duration.as_secs_f64() > 0.0 {
-                            job.progress.bytes_copied as f64 / duration.as_secs_f64() / 1024.0 / 1024.0
-                        } else {
-                            0.0
-                        };
-                        
-                        let message = format!("Job completed successfully in {:.2}s ({:.2} MB/s)", 
-                                            duration.as_secs_f64(), throughput);
-                        job.add_log(message);
-                        info!("Completed job {} in {:.2}s", job_id, duration.as_secs_f64());
-                    }
-                    Err(ref e) => {
-                        job.set_status(JobStatus::Failed);
-                        let error_msg = format!("Job failed: {}", e);
-                        job.add_log(error_msg);
-                        error!("Job {} failed: {}", job_id, e);
+        let mut outcomes: Vec<CopyOutcome> = tee(destinations).await;
+        if error_policy == ErrorPolicy::Retry {
+            for attempt in 2..=FILE_RETRY_ATTEMPTS {
+                let mut failed = Vec::new();
+                for outcome in &outcomes {
+                    let Err(e) = &outcome.result else { continue };
+                    Self::add_job_log(jobs.clone(), job_id, format!(
+                        "Retrying {:?} to {:?} (attempt {} of {}) after: {:#}",
+                        entry.source_path, outcome.destination, attempt, FILE_RETRY_ATTEMPTS, e
+                    )).await;
+                    failed.push(outcome.destination.clone());
+                }
+                if failed.is_empty() {
+                    break;
+                }
+                tokio::time::sleep(FILE_RETRY_DELAY * (attempt - 1)).await;
+                for retried in tee(failed).await {
+                    if let Some(outcome) = outcomes.iter_mut().find(|outcome| outcome.destination == retried.destination) {
+                        *outcome = retried;
                     }
                 }
             }
         }
 
-        result
+        let bytes_read = outcomes.iter().find_map(|outcome| outcome.result.as_ref().ok().copied());
+        let verified = options.verify != VerifyMode::None
+            && outcomes.iter().all(|outcome| outcome.verify_result == VerifyResult::Verified);
+        if let Some(bytes) = bytes_read {
+            if let Some(job) = jobs.write().await.get_mut(job_id) {
+                job.progress.bytes_copied += bytes;
+                if verified {
+                    job.progress.bytes_verified += bytes;
+                }
+                event_sender.send_progress(job);
+            }
+        }
+        outcomes
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn execute_copy_operation(
-        _job_id: &str,
+        job_id: &str,
         sources: &[PathBuf],
         destination: &Path,
         options: &JobOptions,
-        _jobs: Arc<RwLock<HashMap<String, Job>>>,
-        _event_sender: &mpsc::UnboundedSender<JobEvent>,
-    ) -> Result<()> {
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: &EventPublisher,
+        copy_engine: &FileCopyEngine,
+        checkpoint_manager: &CheckpointManager,
+        results: Option<&JobResults>,
+    ) -> Result<DirectoryTraversal> {
         let copy_options = CopyOptions {
             preserve_metadata: options.preserve_metadata,
             preserve_links: options.preserve_links,
@@ -392,48 +2679,355 @@ impl JobManager {
             dry_run: options.dry_run,
             compress: options.compress,
             encrypt: options.encrypt,
+            atime: options.atime,
+            xattr_namespaces: options.xattr_namespaces.clone(),
+            stream_fifo: options.stream_fifo,
+            chmod: options.chmod,
+            on_source_change: options.on_source_change,
+            verify_retries: options.verify_retries,
         };
 
-        let copy_engine = FileCopyEngine::new(options.engine);
+        // 1. Analyze sources to get a plan of action, laid out as in any
+        // earlier run, which may have created the destination since
+        let earlier_run = EarlierRun::load(checkpoint_manager, job_id).await;
+        let counter = Arc::new(AnalysisCounter::default());
+        let reporter = AbortOnDrop(tokio::spawn(Self::report_analysis(
+            job_id.to_string(), jobs.clone(), event_sender.clone(), counter.clone(),
+        )));
+        let mut traversal = DirectoryHandler::analyze_sources_counting(
+            sources, destination, earlier_run.as_ref().and_then(|run| run.into_destination),
+            options.recursive, options.preserve_links, &counter,
+        ).await?;
+        drop(reporter);
+        if options.dirs_only {
+            traversal = traversal.without_files();
+        }
+        let mirrors: Vec<DirectoryTraversal> = options.mirrors.iter()
+            .map(|mirror| traversal.mirrored(destination, mirror))
+            .collect();
+
+        let total_directories = traversal.directories.len() as u64;
+        {
+            let mut jobs_guard = jobs.write().await;
+            if let Some(job) = jobs_guard.get_mut(job_id) {
+                for reason in &traversal.redundant_sources {
+                    job.add_log(reason.clone());
+                }
+                job.progress.total_bytes = traversal.total_size;
+                job.progress.total_files = traversal.total_files;
+                job.progress.total_directories = total_directories;
+                job.progress.bytes_analyzed = traversal.total_size;
+                job.progress.files_analyzed = traversal.total_files;
+                // Counted again below when the job is started over
+                job.progress.bytes_copied = 0;
+                job.progress.files_copied = 0;
+                event_sender.send_progress(job);
+            }
+        }
+
+        // 2. Create all directories first, reporting progress in batches as
+        // deep trees can take a while
+        let mut directories_created = 0;
+        for batch in traversal.directories.chunks(DIRECTORY_PROGRESS_BATCH) {
+            DirectoryHandler::create_directories(batch).await?;
+            let created = directories_created as usize..directories_created as usize + batch.len();
+            for mirror in &mirrors {
+                DirectoryHandler::create_directories(&mirror.directories[created.clone()]).await?;
+            }
+            directories_created += batch.len() as u64;
+            {
+                let mut jobs_guard = jobs.write().await;
+                if let Some(job) = jobs_guard.get_mut(job_id) {
+                    job.progress.directories_created = directories_created;
+                }
+            }
+            event_sender.send(JobEvent {
+                job_id: Some(JobId { uuid: job_id.to_string() }),
+                event_type: Some(job_event::EventType::DirectoryProgress(DirectoryProgress {
+                    created: directories_created,
+                    total: total_directories,
+                })),
+            });
+        }
 
-        // 1. Analyze sources to get a plan of action
-        let traversal = DirectoryHandler::analyze_sources(sources, destination, options.recursive, options.preserve_links).await?;
+        // With no files to copy into them, directories can take on their
+        // source's metadata straight away; children first, so setting a
+        // parent's mode can't lock out the rest
+        if options.dirs_only && options.preserve_metadata && !options.dry_run {
+            for tree in std::iter::once(&traversal).chain(&mirrors) {
+                for (source, dir) in tree.directory_sources.iter().zip(&tree.directories).rev() {
+                    copy_engine.copy_directory_metadata(source, dir, &copy_options).await?;
+                }
+            }
+        }
 
-        // 2. Create all directories first
-        DirectoryHandler::create_directories(&traversal.directories).await?;
+        // Single files and multiple sources are written outside the analyzed
+        // directories, so their parents are only created on request
+        if options.create_parents && !options.dry_run {
+            let parents: HashSet<&Path> = std::iter::once(&traversal).chain(&mirrors)
+                .flat_map(|tree| tree.files.iter().chain(tree.symlinks.iter()))
+                .filter_map(|entry| entry.dest_path.parent())
+                .collect();
+            for parent in parents {
+                tokio::fs::create_dir_all(parent).await
+                    .with_context(|| format!("Failed to create destination directory {:?}", parent))?;
+            }
+        }
 
         // 3. Copy all regular files
-        for file_entry in traversal.files {
-            let dest_path = file_entry.dest_path.clone();
-            match copy_engine.copy_file(&file_entry.source_path, &dest_path, &copy_options).await {
-                Ok(_bytes_copied) => {
-                    /*
-                    let _ = event_sender.send(JobEvent {
-                        job_id: Some(JobId { uuid: job_id.to_string() }),
-                        event_type: Some(job_event::EventType::FileCompleted(Default::default())),
-                    });
-                    */
+        let links: &[FileEntry] = if options.preserve_links { &traversal.symlinks } else { &[] };
+        // An atomic copy writes to its staging tree, but is resumed to the
+        // job's destination
+        let (description, tags, job_destination) = jobs.read().await.get(job_id)
+            .map(|job| (job.description.clone(), job.tags.clone(), job.destination.clone()))
+            .unwrap_or_else(|| (String::new(), Vec::new(), destination.to_path_buf()));
+        let mut skipped_copied = 0;
+        let mut checkpoint = CheckpointWriter::new(
+            checkpoint_manager, job_id, &description, &tags, options, sources, &job_destination, traversal.into_destination,
+            &traversal.files, links,
+        ).await;
+        let mut results_writer = match results.filter(|_| !options.dry_run) {
+            Some(results) => results.writer(job_id).await
+                .inspect_err(|e| warn!("Not keeping file results of job {}: {:#}", job_id, e))
+                .ok(),
+            None => None,
+        };
+        let mut reported_failures = 0;
+        let mut pending = Vec::new();
+        for (index, file_entry) in traversal.files.iter().enumerate() {
+            let mirrored: Vec<&FileEntry> = mirrors.iter().map(|mirror| &mirror.files[index]).collect();
+            if options.skip_destinations.contains(&file_entry.dest_path) {
+                Self::add_job_log(jobs.clone(), job_id, format!("Skipped {:?} at user request", file_entry.dest_path)).await;
+                checkpoint.file_done(file_entry, Some(0)).await;
+                continue;
+            }
+            if earlier_run.as_ref().is_some_and(|run| run.still_copied(file_entry, &mirrored, &copy_options)) {
+                checkpoint.file_done(file_entry, Some(file_entry.size)).await;
+                let mut jobs_guard = jobs.write().await;
+                if let Some(job) = jobs_guard.get_mut(job_id) {
+                    job.progress.files_copied += 1;
+                    job.progress.bytes_copied += file_entry.size;
                 }
-                Err(_e) => {
-                    /*
-                     let _ = event_sender.send(JobEvent {
-                        job_id: Some(JobId { uuid: job_id.to_string() }),
-                        event_type: Some(job_event::EventType::FileError(FileError {
-                            file_path: dest_path.to_string_lossy().to_string(),
-                            error: e.to_string(),
-                        })),
-                    });
-                    */
+                skipped_copied += 1;
+                continue;
+            }
+            pending.push(index);
+        }
+
+        // Up to `copy_parallelism` files are copied at once and handled in
+        // the order they finish
+        let packed = options.packed.then(|| tokio::sync::Mutex::new(PackedWriter::new()));
+        let (jobs_ref, packed_ref, copy_options_ref, traversal_ref, mirrors_ref) = (&jobs, &packed, &copy_options, &traversal, &mirrors);
+        let mut copies = futures::stream::iter(pending)
+            .map(|index| FileCopyEngine::track_engine(async move {
+                let file_entry = &traversal_ref.files[index];
+                let mirrored: Vec<&FileEntry> = mirrors_ref.iter().map(|mirror| &mirror.files[index]).collect();
+                let started = Instant::now();
+                let dest_path = file_entry.dest_path.clone();
+                let outcomes = if mirrored.is_empty() {
+                    let mut packed = match packed_ref {
+                        Some(writer) => Some(writer.lock().await),
+                        None => None,
+                    };
+                    let mut verify_result = VerifyResult::NotVerified;
+                    let mut result = Self::copy_then_verify(
+                        job_id, jobs_ref, event_sender, copy_engine, packed.as_deref_mut(), file_entry, copy_options_ref,
+                        &mut verify_result,
+                    ).await;
+                    drop(packed);
+                    if options.error_policy == ErrorPolicy::Retry {
+                        for attempt in 2..=FILE_RETRY_ATTEMPTS {
+                            let Err(e) = &result else { break };
+                            Self::add_job_log(jobs_ref.clone(), job_id, format!(
+                                "Retrying {:?} (attempt {} of {}) after: {:#}",
+                                file_entry.source_path, attempt, FILE_RETRY_ATTEMPTS, e
+                            )).await;
+                            tokio::time::sleep(FILE_RETRY_DELAY * (attempt - 1)).await;
+                            result = Self::copy_then_verify(
+                                job_id, jobs_ref, event_sender, copy_engine, None, file_entry, copy_options_ref,
+                                &mut verify_result,
+                            ).await;
+                        }
+                    }
+                    vec![CopyOutcome { destination: dest_path.clone(), result, verify_result }]
+                } else {
+                    let destinations = std::iter::once(&dest_path)
+                        .chain(mirrored.iter().map(|entry| &entry.dest_path))
+                        .cloned()
+                        .collect();
+                    Self::tee_then_verify(
+                        job_id, jobs_ref, event_sender, copy_engine, file_entry, destinations, copy_options_ref,
+                        options.error_policy,
+                    ).await
+                };
+                (file_entry, started, outcomes, copy_engine.last_engine())
+            }))
+            .buffer_unordered(options.copy_parallelism.max(1));
+        while let Some((file_entry, started, outcomes, engine)) = copies.next().await {
+            let copied = outcomes.iter().all(|outcome| outcome.result.is_ok());
+            let verified = outcomes.iter().all(|outcome| outcome.verify_result == VerifyResult::Verified);
+            let bytes_copied = outcomes.iter().find_map(|outcome| outcome.result.as_ref().ok().copied());
+            checkpoint.file_done(file_entry, bytes_copied.filter(|_| copied)).await;
+            if let Some(writer) = &mut results_writer {
+                for outcome in &outcomes {
+                    // A failed verification still had the file written by an engine
+                    let wrote = outcome.result.is_ok() || outcome.verify_result == VerifyResult::Mismatch;
+                    let record = FileResult {
+                        source: file_entry.source_path.to_string_lossy().to_string(),
+                        destination: outcome.destination.to_string_lossy().to_string(),
+                        bytes: outcome.result.as_ref().map_or(0, |&bytes| bytes),
+                        engine: wrote.then_some(engine).flatten().unwrap_or(CopyEngine::Auto).into(),
+                        verify_mode: options.verify.into(),
+                        verify_result: outcome.verify_result.into(),
+                        duration_us: started.elapsed().as_micros() as u64,
+                        error: outcome.result.as_ref().err().map(|e| format!("{:#}", e)).unwrap_or_default(),
+                        completed_at: Utc::now().timestamp(),
+                    };
+                    if let Err(e) = writer.record(&record).await {
+                        warn!("Failed to record the result of {:?}: {:#}", outcome.destination, e);
+                    }
                 }
             }
+            let mut first_error = None;
+            for outcome in outcomes {
+                let Err(e) = outcome.result else { continue };
+                let file_error = FileError {
+                    file_path: file_entry.source_path.to_string_lossy().to_string(),
+                    error: format!("{:#}", e),
+                };
+                {
+                    let mut jobs_guard = jobs.write().await;
+                    if let Some(job) = jobs_guard.get_mut(job_id) {
+                        job.add_log(format!("Failed to copy {:?}: {}", outcome.destination, e));
+                        job.record_file_error(file_error.clone());
+                    }
+                }
+                event_sender.send(JobEvent {
+                    job_id: Some(JobId { uuid: job_id.to_string() }),
+                    event_type: Some(job_event::EventType::FileError(file_error)),
+                });
+                first_error.get_or_insert(e);
+            }
+            match first_error {
+                None => {
+                    let mut jobs_guard = jobs.write().await;
+                    if let Some(job) = jobs_guard.get_mut(job_id) {
+                        job.progress.files_copied += 1;
+                        // A file counts as copied, and verified, once it is done
+                        if verified {
+                            job.progress.files_verified += 1;
+                        }
+                        event_sender.send_file_completed(job_id, &file_entry.source_path);
+                        event_sender.send_progress(job);
+                    }
+                }
+                Some(e) if options.error_policy == ErrorPolicy::AbortJob => {
+                    return Err(e.context(format!("Aborted at the first failed file {:?}", file_entry.source_path)));
+                }
+                Some(_) => {}
+            }
+
+            let failures = copy_engine.engine_failures();
+            for failure in &failures[reported_failures..] {
+                Self::add_job_log(jobs.clone(), job_id, format!(
+                    "Copy engine {} failed on {:?}, retried with another engine: {}",
+                    failure.engine, failure.path, failure.error
+                )).await;
+            }
+            reported_failures = failures.len();
+        }
+
+        if skipped_copied > 0 {
+            Self::add_job_log(jobs.clone(), job_id, format!(
+                "Kept {} files copied before the job was paused or stopped", skipped_copied
+            )).await;
+        }
+        drop(copies);
+        if let Some(stats) = packed.map(|writer| writer.into_inner().stats()).filter(|stats| stats.files > 0) {
+            let seconds = stats.elapsed.as_secs_f64().max(f64::EPSILON);
+            Self::add_job_log(jobs.clone(), job_id, format!(
+                "Packed {} small files ({} bytes) in {:.2}s, {:.0} files/s",
+                stats.files, stats.bytes, seconds, stats.files as f64 / seconds
+            )).await;
         }
         
         // 4. Create symlinks if needed
         if options.preserve_links {
-            DirectoryHandler::create_symlinks(&traversal.symlinks).await?;
+            for tree in std::iter::once(&traversal).chain(&mirrors) {
+                DirectoryHandler::create_symlinks(&tree.symlinks).await?;
+            }
+            checkpoint.links_done(links).await;
         }
 
-        Ok(())
+        // 5. Mirror deletions from the source tree
+        let failed_files = jobs.read().await.get(job_id).map_or(0, |job| job.error_summary.error_count);
+        if (options.delete_extraneous || options.delete_dry_run) && failed_files > 0 {
+            // A file that failed to copy may be the only copy left after a delete
+            warn!("Job {}: skipping extraneous file deletion after {} files failed", job_id, failed_files);
+            Self::add_job_log(jobs.clone(), job_id, format!(
+                "Skipped deleting extraneous destination files because {} files failed to copy", failed_files
+            )).await;
+        } else if options.delete_extraneous || options.delete_dry_run {
+            let extraneous = DirectoryHandler::find_extraneous(&traversal).await?;
+            let dry_run = options.delete_dry_run || options.dry_run;
+            for path in &extraneous {
+                let action = if dry_run { "Would delete" } else { "Deleting" };
+                Self::add_job_log(jobs.clone(), job_id, format!("{} extraneous {:?}", action, path)).await;
+            }
+            DirectoryHandler::remove_extraneous(&extraneous, dry_run).await?;
+        }
+
+        // 6. Directories get their mode last so read-only ones can still be filled
+        if let (Some(mode), false) = (options.chmod, options.dry_run) {
+            use std::os::unix::fs::PermissionsExt;
+            for dir in std::iter::once(&traversal).chain(&mirrors).flat_map(|tree| &tree.directories) {
+                tokio::fs::set_permissions(dir, std::fs::Permissions::from_mode(mode)).await
+                    .with_context(|| format!("Failed to set mode {:o} on {:?}", mode, dir))?;
+            }
+        }
+
+        Ok(traversal)
+    }
+
+    async fn sample_job_rates(job_id: String, jobs: Arc<RwLock<HashMap<String, Job>>>, event_sender: EventPublisher) {
+        let mut ticker = interval(RATE_SAMPLE_INTERVAL);
+        ticker.tick().await;
+        let mut last_tick = Instant::now();
+
+        loop {
+            ticker.tick().await;
+            let now = Instant::now();
+            let mut jobs_guard = jobs.write().await;
+            match jobs_guard.get_mut(&job_id) {
+                Some(job) => {
+                    job.sample_rates(now - last_tick);
+                    event_sender.send_progress(job);
+                }
+                None => break,
+            }
+            last_tick = now;
+        }
+    }
+
+    /// Publishes what the analysis of a job's sources has found so far,
+    /// until the task is aborted once the analysis ends.
+    async fn report_analysis(
+        job_id: String,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        event_sender: EventPublisher,
+        counter: Arc<AnalysisCounter>,
+    ) {
+        let mut ticker = interval(ANALYSIS_REPORT_INTERVAL);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let (files, bytes) = counter.get();
+            let mut jobs_guard = jobs.write().await;
+            let Some(job) = jobs_guard.get_mut(&job_id) else { break };
+            job.progress.files_analyzed = files;
+            job.progress.bytes_analyzed = bytes;
+            event_sender.send_progress(job);
+        }
     }
 
     async fn add_job_log(jobs: Arc<RwLock<HashMap<String, Job>>>, job_id: &str, message: String) {
@@ -443,21 +3037,47 @@ impl JobManager {
         }
     }
 
-    async fn calculate_total_size(sources: &[PathBuf], recursive: bool) -> Result<u64> {
-        let mut total = 0;
-        
-        for source in sources {
-            if let Ok(metadata) = tokio::fs::metadata(source).await {
-                if metadata.is_file() {
-                    total += metadata.len();
-                } else if metadata.is_dir() && recursive {
-                    // TODO: Recursively calculate directory size
-                    total += 1024; // Placeholder
-                }
+    /// Resumes a job from its checkpoint after rewriting source roots, for
+    /// sources that are now mounted at a different path. Every remaining file
+    /// must be found at its remapped source before the job is queued.
+    pub async fn resume_checkpointed_job(&self, job_id: &str, remaps: &[(PathBuf, PathBuf)]) -> Result<()> {
+        if let Some(job) = self.get_job(job_id).await {
+            if matches!(job.get_status(), JobStatus::Running | JobStatus::Pending) {
+                anyhow::bail!("Job {} is still active ({:?})", job_id, job.get_status());
             }
         }
-        
-        Ok(total)
+
+        let mut checkpoint = self.checkpoint_manager.load_checkpoint(job_id).await?
+            .with_context(|| format!("No checkpoint found for job {}", job_id))?;
+
+        for (from, to) in remaps {
+            let remapped = checkpoint.remap_source_root(from, to);
+            info!("Remapped {} checkpointed sources from {:?} to {:?}", remapped, from, to);
+        }
+
+        for file_checkpoint in checkpoint.files.values() {
+            if !can_resume_file(file_checkpoint).await? {
+                info!("Partial copy of {:?} cannot be reused, it will be copied again", file_checkpoint.source_path);
+            }
+        }
+
+        checkpoint.increment_resume_count();
+        self.checkpoint_manager.save_checkpoint(&checkpoint).await?;
+
+        let job = self.create_job_from_checkpoint(checkpoint).await?;
+        let created_at = job.created_at;
+        {
+            let mut jobs = self.jobs.write().await;
+            jobs.insert(job_id.to_string(), job);
+        }
+        {
+            let mut queue = self.job_queue.write().await;
+            queue.push(job_id.to_string(), RESUMED_PRIORITY, created_at);
+            self.record_queue_depth(&queue);
+        }
+
+        self.try_start_next_job().await;
+        Ok(())
     }
 
     pub async fn resume_jobs_from_checkpoints(&self) -> Result<usize> {
@@ -475,6 +3095,12 @@ impl JobManager {
 
                 // Create a new job from the checkpoint
                 let job = self.create_job_from_checkpoint(checkpoint).await?;
+                let created_at = job.created_at;
+                // The staging tree of an atomic copy outlives a crash
+                if let (true, [source]) = (job.options.atomic, job.sources.as_slice()) {
+                    let (_, staging) = Self::atomic_paths(&job_id, source, &job.destination, &job.options);
+                    StagingTree::new(staging).remove_now().await;
+                }
                 
                 // Add to jobs map
                 {
@@ -485,7 +3111,8 @@ impl JobManager {
                 // Add to queue
                 {
                     let mut queue = self.job_queue.write().await;
-                    queue.push_front(job_id); // Prioritize resumed jobs
+                    queue.push(job_id, RESUMED_PRIORITY, created_at);
+                    self.record_queue_depth(&queue);
                 }
 
                 resumed_count += 1;
@@ -494,10 +3121,28 @@ impl JobManager {
 
         if resumed_count > 0 {
             info!("Resumed {} jobs from checkpoints", resumed_count);
+        }
+
+        let mut requeued_count = 0;
+        for queued in self.checkpoint_manager.take_queued_jobs().await? {
+            let mut job = Job::new(queued.request);
+            job.id = queued.job_id;
+            job.created_at = queued.created_at;
+            let job_id = job.id.clone();
+            match self.enqueue_job(job).await {
+                Ok(_) => requeued_count += 1,
+                Err(e) => warn!("Failed to requeue job {}: {}", job_id, e),
+            }
+        }
+        if requeued_count > 0 {
+            info!("Requeued {} jobs queued when the daemon stopped", requeued_count);
+        }
+
+        if resumed_count + requeued_count > 0 {
             self.try_start_next_job().await;
         }
 
-        Ok(resumed_count)
+        Ok(resumed_count + requeued_count)
     }
 
     async fn create_job_from_checkpoint(&self, checkpoint: JobCheckpoint) -> Result<Job> {
@@ -514,13 +3159,45 @@ impl JobManager {
                 verify: VerifyMode::None,
                 exists_action: ExistsAction::Overwrite,
                 max_rate_bps: None,
+                max_rate_percent: None,
                 engine: CopyEngine::Auto,
                 dry_run: false,
                 regex_rename_match: None,
                 regex_rename_replace: None,
                 block_size: None,
-                compress: false,
-                encrypt: false,
+                compress: checkpoint.compress,
+                encrypt: checkpoint.encrypt,
+                key_id: checkpoint.key_id.clone(),
+                delete_extraneous: false,
+                delete_dry_run: false,
+                skip_destinations: HashSet::new(),
+                device_source: false,
+                atime: AtimeMode::Preserve,
+                verify_only: false,
+                class: JobClass::Foreground,
+                xattr_namespaces: Vec::new(),
+                stream_fifo: false,
+                chmod: None,
+                create_parents: false,
+                dirs_only: false,
+                error_policy: ErrorPolicy::SkipFile,
+                packed: false,
+                atomic: checkpoint.atomic,
+                temp_dir: checkpoint.temp_dir.clone(),
+                mirrors: checkpoint.mirrors.clone(),
+                on_source_change: SourceChangeAction::IgnoreChange,
+                best_effort_metadata: false,
+                copy_parallelism: 1,
+                dedup: false,
+                verify_retries: 0,
+                operation: match checkpoint.operation_type.as_str() {
+                    "move" => JobOperation::Move,
+                    _ => JobOperation::Copy,
+                },
+                checkpoint_interval_bytes: DEFAULT_CHECKPOINT_INTERVAL_BYTES,
+                checkpoint_interval_secs: DEFAULT_CHECKPOINT_INTERVAL_SECS,
+                idle_io_verification: false,
+                verify_cost: DEFAULT_VERIFY_COST,
             },
             progress: Progress {
                 bytes_copied: checkpoint.bytes_completed,
@@ -530,16 +3207,47 @@ impl JobManager {
                 throughput_mbps: 0.0,
                 eta_seconds: 0,
                 status: JobStatus::Pending.into(),
+                directories_created: 0,
+                total_directories: 0,
+                bytes_verified: 0,
+                overall_percent: 0.0,
+                bytes_analyzed: 0,
+                files_analyzed: 0,
+                files_verified: 0,
             },
             created_at: DateTime::from_timestamp(checkpoint.created_at as i64, 0).unwrap_or(Utc::now()),
             started_at: None,
             completed_at: None,
             priority: 100, // Default priority for resumed jobs
+            tags: checkpoint.tags.clone(),
+            description: checkpoint.description.clone(),
             log_entries: vec![format!("Job resumed from checkpoint (resume count: {})", checkpoint.resume_count)],
+            rate_sampler: RateSampler::default(),
+            error_summary: ErrorSummary::default(),
+            copying: Arc::default(),
+            request: None,
+            urls: Vec::new(),
         };
 
-        // Extract source and destination from checkpoint files
-        if let Some((_, file_checkpoint)) = checkpoint.files.iter().next() {
+        // Only the sources that hadn't finished are analyzed again
+        if !checkpoint.sources.is_empty() {
+            // A move removes its sources once all are copied, and an atomic
+            // copy stages the whole tree again, so those keep the sources done
+            // unless already removed
+            job.sources = match job.options.operation == JobOperation::Move || job.options.atomic {
+                false => checkpoint.pending_sources(),
+                true => checkpoint.sources.iter()
+                    .filter(|source| !source.completed || source.path.symlink_metadata().is_ok())
+                    .map(|source| source.path.clone())
+                    .collect(),
+            };
+            job.destination = checkpoint.destination.clone();
+            let skipped = checkpoint.sources.len() - job.sources.len();
+            if skipped > 0 {
+                job.add_log(format!("Skipping {} of {} sources completed before the interruption", skipped, checkpoint.sources.len()));
+            }
+        } else if let Some((_, file_checkpoint)) = checkpoint.files.iter().next() {
+            // Checkpoints from before sources were recorded
             job.destination = file_checkpoint.destination_path.parent()
                 .unwrap_or(&file_checkpoint.destination_path)
                 .to_path_buf();
@@ -567,10 +3275,19 @@ impl Clone for JobManager {
             jobs: self.jobs.clone(),
             job_queue: self.job_queue.clone(),
             active_jobs: self.active_jobs.clone(),
-            max_concurrent: self.max_concurrent,
             semaphore: self.semaphore.clone(),
             event_sender: self.event_sender.clone(),
             checkpoint_manager: self.checkpoint_manager.clone(),
+            inflight_budget: self.inflight_budget.clone(),
+            settings: self.settings.clone(),
+            history: self.history.clone(),
+            results: self.results.clone(),
+            bandwidth: self.bandwidth.clone(),
+            monitor: self.monitor.clone(),
+            profiler: self.profiler.clone(),
+            dedup_indexes: self.dedup_indexes.clone(),
+            starting: self.starting.clone(),
+            draining: self.draining.clone(),
         }
     }
 } 
\ No newline at end of file
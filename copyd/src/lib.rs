@@ -1,17 +1,38 @@
 #![allow(dead_code)]
 
+pub mod backend;
+pub mod bandwidth;
+pub mod btime;
 pub mod checkpoint;
+pub mod compress;
 pub mod config;
 pub mod copy_engine;
 pub mod daemon;
+pub mod dedup;
+pub mod device;
 pub mod directory;
+pub mod encrypt;
 pub mod error;
+pub mod events;
+pub mod history;
+pub mod http_source;
+pub mod inflight;
 pub mod io_uring_engine;
 pub mod job;
 pub mod metrics;
 pub mod monitor;
+pub mod numa;
+pub mod packed;
+pub mod priority;
+pub mod privileges;
 pub mod profiler;
+pub mod queue;
+pub mod recommend;
 pub mod regex_rename;
+pub mod results;
+pub mod snapshot;
+pub mod source_watch;
+pub mod space;
 pub mod sparse;
 pub mod verify;
 // pub mod scheduler;
@@ -21,17 +42,30 @@ pub mod security;
 // Re-export commonly used types
 pub use error::{CopydError, CopydResult, ErrorContext};
 pub use security::{SecurityConfig, SecurityValidator};
-pub use profiler::{PerformanceProfiler, PerformanceReport};
-pub use config::Config;
+pub use profiler::{PerformanceProfiler, PerformanceReport, ThroughputEstimate};
+pub use config::{Config, ConfigMigration, CONFIG_VERSION};
 pub use job::{Job};
 pub use copyd_protocol::{JobStatus, CopyEngine};
 pub use regex_rename::RegexRenamer;
 // Additional re-exports to simplify external usage and keep integration tests working
 pub use daemon::Daemon;
-pub use job::JobManager;
-pub use copy_engine::{FileCopyEngine, CopyOptions};
-pub use checkpoint::{CheckpointManager, JobCheckpoint, FileCheckpoint};
-pub use directory::DirectoryHandler;
+pub use job::{JobManager, RateSampler};
+pub use copy_engine::{FileCopyEngine, CopyOptions, CopyOutcome, EngineFailure};
+pub use backend::{BackendRegistry, CopyBackend};
+pub use inflight::InflightBudget;
+pub use privileges::{MetadataPrivileges, SkippedMetadata};
+pub use dedup::{DedupIndex, DedupIndexes, DedupStats};
+pub use packed::{PackedStats, PackedWriter};
+pub use bandwidth::{BandwidthCalibrator, BandwidthProbe};
+pub use checkpoint::{CheckpointManager, JobCheckpoint, FileCheckpoint, SourceCheckpoint, SourceStamp};
+pub use history::JobHistory;
+pub use results::{JobResults, ResultsWriter};
+pub use directory::{DirectoryHandler, ExcludeFilter, PathLimits};
+pub use recommend::{CopyProfile, Recommendation};
+pub use events::{EventPublisher, EventReceiver};
+pub use snapshot::{create_snapshot, SnapshotStats};
+pub use source_watch::SourceWatch;
+pub use space::{FreeSpace, FreeSpaceSource, LowSpaceWatch, SpaceThreshold};
 pub use sparse::SparseFileHandler;
 pub use verify::{FileVerifier, VerifyMode};
 
@@ -6,16 +6,40 @@ use tracing::{info, error};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod daemon;
+mod dedup;
 mod job;
 mod copy_engine;
+mod device;
+mod backend;
+mod bandwidth;
+mod btime;
 mod io_uring_engine;
 mod directory;
+mod encrypt;
 mod sparse;
 mod verify;
 mod metrics;
+mod monitor;
 mod config;
 mod utils;
 mod checkpoint;
+mod compress;
+mod error;
+mod events;
+mod history;
+mod http_source;
+mod inflight;
+mod numa;
+mod packed;
+mod priority;
+mod privileges;
+mod profiler;
+mod queue;
+mod recommend;
+mod results;
+mod snapshot;
+mod source_watch;
+mod space;
 
 use daemon::Daemon;
 use config::Config;
@@ -40,7 +64,7 @@ async fn main() -> Result<()> {
     let daemon = Arc::new(Daemon::new(config).await?);
 
     // Handle systemd notifications
-    if let Ok(_) = std::env::var("NOTIFY_SOCKET") {
+    if std::env::var("NOTIFY_SOCKET").is_ok() {
         // Send ready notification to systemd
         systemd::daemon::notify(false, [(systemd::daemon::STATE_READY, &String::from("1"))].iter())?;
         info!("Notified systemd that daemon is ready");
@@ -66,7 +90,7 @@ async fn main() -> Result<()> {
     if let Err(e) = daemon.run().await {
         error!("Daemon error: {}", e);
         // Notify systemd of failure
-        if let Ok(_) = std::env::var("NOTIFY_SOCKET") {
+        if std::env::var("NOTIFY_SOCKET").is_ok() {
             let _ = systemd::daemon::notify(false, [
                 (systemd::daemon::STATE_STATUS, &format!("Failed: {}", e)),
                 (systemd::daemon::STATE_ERRNO, &String::from("1"))
@@ -75,5 +99,14 @@ async fn main() -> Result<()> {
         return Err(e);
     }
 
+    if daemon.restart_requested() {
+        use std::os::unix::process::CommandExt;
+        info!("Restarting copyd");
+        let error = std::process::Command::new(std::env::current_exe()?)
+            .args(std::env::args_os().skip(1))
+            .exec();
+        return Err(error.into());
+    }
+
     Ok(())
 } 
\ No newline at end of file
@@ -1,8 +1,10 @@
 use crate::error::{CopydError, CopydResult};
+use crate::space::{FreeSpace, SpaceThreshold};
 use prometheus::{
-    Counter, Gauge, Histogram, IntCounter, IntGauge, Registry,
+    Counter, Gauge, Histogram, IntCounter, IntGauge, IntGaugeVec, Registry,
     exponential_buckets, CounterVec
 };
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{info, warn, error};
@@ -27,6 +29,15 @@ pub struct MonitoringMetrics {
     pub jobs_completed: IntCounter,
     pub jobs_failed: IntCounter,
     pub job_duration: Histogram,
+
+    // Scheduling metrics
+    pub queue_depth: IntGauge,
+    pub queue_wait: Histogram,
+    pub device_active_jobs: IntGaugeVec,
+
+    // Storage metrics
+    pub device_free_bytes: IntGaugeVec,
+    pub low_space_alerts: IntCounter,
     
     // Transfer metrics
     pub bytes_transferred: Counter,
@@ -110,6 +121,42 @@ impl EnhancedMonitor {
         warn!("Job failed: {} - {}", job_id, error);
     }
 
+    /// Record the number of jobs waiting for a slot
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.metrics.queue_depth.set(depth as i64);
+    }
+
+    /// Record how long a job waited between creation and its first start
+    pub fn job_left_queue(&self, wait: Duration) {
+        self.metrics.queue_wait.observe(wait.as_secs_f64());
+    }
+
+    /// Record an operation done again after it failed, e.g. a file copied
+    /// again after failing verification
+    pub fn retry_operation(&self) {
+        self.metrics.retry_operations.inc();
+    }
+
+    /// Record a job starting or stopping on the destination `device`
+    pub fn device_job_started(&self, device: &str) {
+        self.metrics.device_active_jobs.with_label_values(&[device]).inc();
+    }
+
+    pub fn device_job_finished(&self, device: &str) {
+        self.metrics.device_active_jobs.with_label_values(&[device]).dec();
+    }
+
+    /// Record a free-space sample of a destination `device`, alerting once
+    /// when it drops below `threshold`. Returns whether it is below.
+    pub async fn record_free_space(&self, device: &str, space: FreeSpace, threshold: SpaceThreshold) -> bool {
+        self.metrics.device_free_bytes.with_label_values(&[device]).set(space.available as i64);
+        let low = threshold.is_low(space);
+        if self.alerts.check_free_space(device, space, threshold, low).await {
+            self.metrics.low_space_alerts.inc();
+        }
+        low
+    }
+
     /// Record engine performance
     pub fn engine_operation(&self, engine: &str, success: bool, throughput_mbps: f64) {
         self.metrics.engine_operations.inc();
@@ -172,7 +219,7 @@ impl EnhancedMonitor {
             level: status,
             uptime,
             active_jobs: active_jobs as u64,
-            total_errors: total_errors as u64,
+            total_errors,
             memory_usage_mb: memory_usage,
             cpu_usage_percent: cpu_usage,
             alerts: self.alerts.get_active_alerts().await,
@@ -210,6 +257,24 @@ impl MonitoringMetrics {
                 .buckets(exponential_buckets(0.1, 2.0, 10)?),
         )?;
 
+        // Scheduling metrics
+        let queue_depth = IntGauge::new("copyd_queue_depth", "Number of jobs queued but not started")?;
+        let queue_wait = Histogram::with_opts(
+            prometheus::HistogramOpts::new("copyd_queue_wait_seconds", "Time from job creation to its first start")
+                .buckets(exponential_buckets(0.01, 4.0, 10)?),
+        )?;
+        let device_active_jobs = IntGaugeVec::new(
+            prometheus::Opts::new("copyd_device_active_jobs", "Running jobs by destination device"),
+            &["device"],
+        )?;
+
+        // Storage metrics
+        let device_free_bytes = IntGaugeVec::new(
+            prometheus::Opts::new("copyd_device_free_bytes", "Free bytes on destination devices of running jobs"),
+            &["device"],
+        )?;
+        let low_space_alerts = IntCounter::new("copyd_low_space_alerts_total", "Destination devices dropping below the low-space threshold")?;
+
         // Transfer metrics
         let bytes_transferred = Counter::new("copyd_bytes_transferred_total", "Total bytes transferred")?;
         let transfer_rate = Gauge::new("copyd_transfer_rate_mbps", "Current transfer rate in MB/s")?;
@@ -241,6 +306,11 @@ impl MonitoringMetrics {
         registry.register(Box::new(jobs_completed.clone()))?;
         registry.register(Box::new(jobs_failed.clone()))?;
         registry.register(Box::new(job_duration.clone()))?;
+        registry.register(Box::new(queue_depth.clone()))?;
+        registry.register(Box::new(queue_wait.clone()))?;
+        registry.register(Box::new(device_active_jobs.clone()))?;
+        registry.register(Box::new(device_free_bytes.clone()))?;
+        registry.register(Box::new(low_space_alerts.clone()))?;
         registry.register(Box::new(bytes_transferred.clone()))?;
         registry.register(Box::new(transfer_rate.clone()))?;
         registry.register(Box::new(files_processed.clone()))?;
@@ -263,6 +333,11 @@ impl MonitoringMetrics {
             jobs_completed,
             jobs_failed,
             job_duration,
+            queue_depth,
+            queue_wait,
+            device_active_jobs,
+            device_free_bytes,
+            low_space_alerts,
             bytes_transferred,
             transfer_rate,
             files_processed,
@@ -285,12 +360,15 @@ impl MonitoringMetrics {
 /// Alert management system
 pub struct AlertManager {
     active_alerts: Arc<RwLock<Vec<Alert>>>,
+    /// Devices below the low-space threshold at their last sample
+    low_space_devices: Arc<RwLock<HashSet<String>>>,
 }
 
 impl AlertManager {
     fn new() -> Self {
         Self {
             active_alerts: Arc::new(RwLock::new(Vec::new())),
+            low_space_devices: Arc::new(RwLock::new(HashSet::new())),
         }
     }
 
@@ -350,6 +428,36 @@ impl AlertManager {
         }
     }
 
+    /// Alerts when `device` goes from above the threshold to below it; later
+    /// low samples stay quiet until it recovers. Returns whether it alerted.
+    async fn check_free_space(&self, device: &str, space: FreeSpace, threshold: SpaceThreshold, low: bool) -> bool {
+        let mut low_devices = self.low_space_devices.write().await;
+        if !low {
+            if low_devices.remove(device) {
+                info!("Free space on device {} is back above {}", device, threshold);
+            }
+            return false;
+        }
+        if !low_devices.insert(device.to_string()) {
+            return false;
+        }
+        drop(low_devices);
+
+        let message = format!(
+            "Low free space on device {}: {} of {} bytes available, below {}",
+            device, space.available, space.total, threshold
+        );
+        warn!("{}", message);
+        self.add_alert(Alert {
+            id: uuid::Uuid::new_v4().to_string(),
+            severity: AlertSeverity::High,
+            message,
+            timestamp: chrono::Utc::now(),
+            category: "Storage".to_string(),
+        }).await;
+        true
+    }
+
     fn trigger_critical_error_alert(&self, error: &CopydError) {
         error!("Critical error: {}", error);
         // In production, would send notifications via email, Slack, etc.
@@ -413,6 +521,16 @@ impl HealthStatus {
     }
 }
 
+async fn send_alert(_alert_manager_url: &str, _alert: &Alert) -> Result<()> {
+    let _client = reqwest::Client::new();
+    // client.post(alert_manager_url)
+    //     .json(&vec![alert])
+    //     .send()
+    //     .await?
+    //     .error_for_status()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,7 +538,7 @@ mod tests {
     #[tokio::test]
     async fn test_enhanced_monitor_creation() {
         let monitor = EnhancedMonitor::new().unwrap();
-        assert!(monitor.registry().gather().len() > 0);
+        assert!(!monitor.registry().gather().is_empty());
     }
 
     #[tokio::test]
@@ -444,13 +562,3 @@ mod tests {
         assert!(health.is_healthy());
     }
 }
-
-async fn send_alert(_alert_manager_url: &str, _alert: &Alert) -> Result<()> {
-    let _client = reqwest::Client::new();
-    // client.post(alert_manager_url)
-    //     .json(&vec![alert])
-    //     .send()
-    //     .await?
-    //     .error_for_status()?;
-    Ok(())
-} 
\ No newline at end of file
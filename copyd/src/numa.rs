@@ -0,0 +1,179 @@
+use std::ops::{Deref, DerefMut};
+use tracing::debug;
+
+const MPOL_PREFERRED: libc::c_int = 1;
+const MPOL_F_NODE: libc::c_ulong = 1;
+const MPOL_F_ADDR: libc::c_ulong = 2;
+const NODEMASK_WORDS: usize = 16;
+
+/// Number of NUMA nodes that are online, or 1 when the topology is unknown.
+pub fn node_count() -> usize {
+    let count = std::fs::read_dir("/sys/devices/system/node")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    let name = entry.file_name();
+                    let name = name.to_string_lossy();
+                    name.strip_prefix("node").is_some_and(|id| id.parse::<usize>().is_ok())
+                })
+                .count()
+        })
+        .unwrap_or(0);
+    count.max(1)
+}
+
+/// Node of the CPU the calling thread is currently running on.
+pub fn current_node() -> Option<usize> {
+    let mut cpu: libc::c_uint = 0;
+    let mut node: libc::c_uint = 0;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_getcpu,
+            &mut cpu as *mut libc::c_uint,
+            &mut node as *mut libc::c_uint,
+            std::ptr::null_mut::<libc::c_void>(),
+        )
+    };
+    (result == 0).then_some(node as usize)
+}
+
+/// Node backing the page that contains `ptr`. The page must have been touched.
+pub fn node_of(ptr: *const u8) -> Option<usize> {
+    let mut node: libc::c_int = -1;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_get_mempolicy,
+            &mut node as *mut libc::c_int,
+            std::ptr::null_mut::<libc::c_ulong>(),
+            0 as libc::c_ulong,
+            ptr as *mut libc::c_void,
+            MPOL_F_NODE | MPOL_F_ADDR,
+        )
+    };
+    (result == 0 && node >= 0).then_some(node as usize)
+}
+
+/// Anonymous mapping whose pages prefer a single NUMA node.
+pub struct NumaBuffer {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// The mapping is exclusively owned, like a Vec<u8>
+unsafe impl Send for NumaBuffer {}
+unsafe impl Sync for NumaBuffer {}
+
+impl NumaBuffer {
+    /// Maps `len` zeroed bytes and asks the kernel to place them on `node`.
+    /// Returns `None` if the mapping or the policy cannot be applied.
+    pub fn allocate(len: usize, node: usize) -> Option<Self> {
+        if len == 0 || node >= NODEMASK_WORDS * 64 {
+            return None;
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        let buffer = Self { ptr: ptr as *mut u8, len };
+
+        let mut nodemask = [0 as libc::c_ulong; NODEMASK_WORDS];
+        nodemask[node / 64] |= 1 << (node % 64);
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                ptr,
+                len as libc::c_ulong,
+                MPOL_PREFERRED,
+                nodemask.as_ptr(),
+                (NODEMASK_WORDS * 64 + 1) as libc::c_ulong,
+                0 as libc::c_uint,
+            )
+        };
+        if result != 0 {
+            debug!("mbind to node {} failed: {}", node, std::io::Error::last_os_error());
+            return None;
+        }
+
+        Some(buffer)
+    }
+}
+
+impl Drop for NumaBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+impl Deref for NumaBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl DerefMut for NumaBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+/// Buffer handed to the copy engines, optionally pinned to a NUMA node.
+pub enum CopyBuffer {
+    Heap(Vec<u8>),
+    Numa(NumaBuffer),
+}
+
+impl CopyBuffer {
+    /// Places the buffer on the current CPU's node when `numa_aware` is set
+    /// and the machine has more than one node, otherwise uses the heap.
+    pub fn new(len: usize, numa_aware: bool) -> Option<Self> {
+        if numa_aware && node_count() > 1 {
+            if let Some(buffer) = current_node().and_then(|node| NumaBuffer::allocate(len, node)) {
+                return Some(CopyBuffer::Numa(buffer));
+            }
+        }
+
+        let mut buffer = Vec::new();
+        buffer.try_reserve_exact(len).ok()?;
+        buffer.resize(len, 0);
+        Some(CopyBuffer::Heap(buffer))
+    }
+
+    pub fn is_numa_pinned(&self) -> bool {
+        matches!(self, CopyBuffer::Numa(_))
+    }
+}
+
+impl Deref for CopyBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            CopyBuffer::Heap(buffer) => buffer,
+            CopyBuffer::Numa(buffer) => buffer,
+        }
+    }
+}
+
+impl DerefMut for CopyBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            CopyBuffer::Heap(buffer) => buffer,
+            CopyBuffer::Numa(buffer) => buffer,
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::copy_engine::{CopyOptions, FileCopyEngine};
+use anyhow::{Context, Result};
+use copyd_protocol::{AtimeMode, CopyEngine, ExistsAction};
+use nix::fcntl::{openat, renameat, OFlag};
+use nix::sys::stat::{fchmod, fstat, futimens, Mode, SFlag};
+use nix::sys::time::TimeSpec;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Largest file copied through a [`PackedWriter`]; bigger files are
+/// dominated by their data rather than by path lookups.
+pub const PACKED_FILE_LIMIT: u64 = 64 * 1024;
+
+/// Files and bytes a [`PackedWriter`] copied and the time it spent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PackedStats {
+    pub files: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Copies small files while holding their source and destination
+/// directories open. Each file is opened, created and renamed relative to
+/// those directory descriptors, and its metadata is set through its own
+/// descriptor, so the kernel resolves no full paths for it. Consecutive
+/// files of one directory share the descriptors.
+pub struct PackedWriter {
+    source_dir: Option<(PathBuf, File)>,
+    dest_dir: Option<(PathBuf, File)>,
+    stats: PackedStats,
+}
+
+impl Default for PackedWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PackedWriter {
+    pub fn new() -> Self {
+        Self { source_dir: None, dest_dir: None, stats: PackedStats::default() }
+    }
+
+    /// Whether a file of `size` bytes copied with `options` can take the
+    /// packed path. Rate caps, dry runs and the skip and serial exists
+    /// actions need the regular engine.
+    pub fn accepts(size: u64, options: &CopyOptions) -> bool {
+        size <= PACKED_FILE_LIMIT
+            && options.max_rate_bps.is_none()
+            && !options.dry_run
+            && !options.stream_fifo
+            && options.exists_action == ExistsAction::Overwrite
+    }
+
+    pub fn stats(&self) -> PackedStats {
+        self.stats
+    }
+
+    /// Copies `source` to `destination` through the held directories.
+    /// Returns `None`, having written nothing, when the source turns out
+    /// not to be a small regular file.
+    pub async fn copy_file(
+        &mut self,
+        engine: &FileCopyEngine,
+        source: &Path,
+        destination: &Path,
+        options: &CopyOptions,
+    ) -> Result<Option<u64>> {
+        let started = Instant::now();
+        let (Some(source_name), Some(dest_name)) = (source.file_name(), destination.file_name()) else {
+            return Ok(None);
+        };
+        let source_dir = Self::hold(&mut self.source_dir, source)?.as_raw_fd();
+        let dest_dir = Self::hold(&mut self.dest_dir, destination)?.as_raw_fd();
+
+        let fd = openat(source_dir, source_name, OFlag::O_RDONLY | OFlag::O_CLOEXEC, Mode::empty())
+            .with_context(|| format!("Failed to open source file: {:?}", source))?;
+        let mut source_file = unsafe { File::from_raw_fd(fd) };
+        let stat = fstat(fd)?;
+        if SFlag::from_bits_truncate(stat.st_mode & SFlag::S_IFMT.bits()) != SFlag::S_IFREG
+            || stat.st_size as u64 > PACKED_FILE_LIMIT
+        {
+            return Ok(None);
+        }
+        let mut data = Vec::with_capacity(stat.st_size as usize);
+        source_file.read_to_end(&mut data)
+            .with_context(|| format!("Failed to read {:?}", source))?;
+
+        // Written under a temporary name and renamed over the destination,
+        // so a failed copy never leaves a partial file in its place
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(dest_name);
+        temp_name.push(".copyd-packed");
+        let fd = openat(
+            dest_dir,
+            temp_name.as_os_str(),
+            OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC | OFlag::O_CLOEXEC,
+            Mode::from_bits_truncate(0o666),
+        ).with_context(|| format!("Failed to create destination file: {:?}", destination))?;
+        let mut dest_file = unsafe { File::from_raw_fd(fd) };
+        let written = dest_file.write_all(&data)
+            .with_context(|| format!("Failed to write {:?}", destination))
+            .and_then(|()| Self::set_metadata(engine, destination, &dest_file, &stat, options));
+        if let Err(e) = written {
+            let _ = nix::unistd::unlinkat(Some(dest_dir), temp_name.as_os_str(), nix::unistd::UnlinkatFlags::NoRemoveDir);
+            return Err(e);
+        }
+        drop(dest_file);
+        renameat(Some(dest_dir), temp_name.as_os_str(), Some(dest_dir), dest_name)
+            .with_context(|| format!("Failed to move {:?} into place", destination))?;
+        engine.note_engine(CopyEngine::ReadWrite);
+
+        if options.preserve_metadata {
+            if let Err(e) = engine.copy_xattrs(source, destination, &options.xattr_namespaces).await {
+                debug!("Could not copy extended attributes: {}", e);
+            }
+            engine.copy_birth_time(source, destination);
+        }
+        engine.verify_written(source, destination, options).await?;
+
+        self.stats.files += 1;
+        self.stats.bytes += data.len() as u64;
+        self.stats.elapsed += started.elapsed();
+        Ok(Some(data.len() as u64))
+    }
+
+    /// The open directory holding `path`, reopened when it differs from
+    /// the one held.
+    fn hold<'a>(held: &'a mut Option<(PathBuf, File)>, path: &Path) -> Result<&'a File> {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        if held.as_ref().is_none_or(|(current, _)| current != dir) {
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_DIRECTORY)
+                .open(dir)
+                .with_context(|| format!("Failed to open directory {:?}", dir))?;
+            *held = Some((dir.to_path_buf(), file));
+        }
+        Ok(&held.as_ref().expect("directory was just opened").1)
+    }
+
+    /// Mode, ownership and timestamps of the source (`stat`), when
+    /// preserving metadata, then any explicit mode.
+    fn set_metadata(
+        engine: &FileCopyEngine,
+        destination: &Path,
+        file: &File,
+        stat: &nix::sys::stat::FileStat,
+        options: &CopyOptions,
+    ) -> Result<()> {
+        let fd = file.as_raw_fd();
+        if options.preserve_metadata {
+            fchmod(fd, Mode::from_bits_truncate(stat.st_mode & 0o7777))?;
+            engine.preserve_owner(destination, stat.st_uid, stat.st_gid, |uid, gid| {
+                nix::unistd::fchown(fd, Some(uid), Some(gid))
+            });
+            let mtime = TimeSpec::new(stat.st_mtime, stat.st_mtime_nsec);
+            let atime = match options.atime {
+                AtimeMode::Preserve => TimeSpec::new(stat.st_atime, stat.st_atime_nsec),
+                AtimeMode::Now => TimeSpec::new(0, libc::UTIME_NOW),
+                AtimeMode::Mtime => mtime,
+            };
+            futimens(fd, &atime, &mtime)?;
+        }
+        if let Some(mode) = options.chmod {
+            fchmod(fd, Mode::from_bits_truncate(mode))?;
+        }
+        Ok(())
+    }
+}
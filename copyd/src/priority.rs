@@ -0,0 +1,178 @@
+use copyd_protocol::JobClass;
+use std::io;
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+use tracing::warn;
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: u32 = 13;
+const IOPRIO_CLASS_BE: u32 = 2;
+const IOPRIO_CLASS_IDLE: u32 = 3;
+
+/// Worker threads of the runtime that runs background jobs.
+const BACKGROUND_WORKERS: usize = 2;
+
+/// Worker threads of the runtime that runs idle-priority verification.
+const IDLE_IO_WORKERS: usize = 1;
+
+/// CPU niceness and I/O scheduling priority of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadPriority {
+    pub nice: i32,
+    pub io_class: u32,
+    pub io_level: u32,
+}
+
+impl ThreadPriority {
+    pub fn for_class(class: JobClass) -> Self {
+        match class {
+            // The kernel defaults for a process at nice 0
+            JobClass::Foreground => Self { nice: 0, io_class: IOPRIO_CLASS_BE, io_level: 4 },
+            // Only gets the disk when nothing else wants it
+            JobClass::Background => Self { nice: 19, io_class: IOPRIO_CLASS_IDLE, io_level: 0 },
+        }
+    }
+
+    fn ioprio(&self) -> libc::c_int {
+        ((self.io_class << IOPRIO_CLASS_SHIFT) | self.io_level) as libc::c_int
+    }
+}
+
+/// Applies `priority` to the calling thread only; other threads of the
+/// daemon keep theirs. Threads spawned afterwards by this one inherit it.
+pub fn apply_to_current_thread(priority: ThreadPriority) -> io::Result<()> {
+    let tid = unsafe { libc::gettid() };
+
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, priority.nice) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    set_io_priority(tid, priority.ioprio())
+}
+
+/// Drops the calling thread to the idle I/O class, leaving its niceness
+/// alone. Unlike raising priority, this needs no privileges.
+pub fn apply_idle_io_to_current_thread() -> io::Result<()> {
+    set_io_priority(unsafe { libc::gettid() }, (IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT) as libc::c_int)
+}
+
+fn set_io_priority(tid: libc::pid_t, ioprio: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, ioprio) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The calling thread's current priority.
+pub fn current_thread_priority() -> io::Result<ThreadPriority> {
+    let tid = unsafe { libc::gettid() };
+
+    // getpriority can legitimately return -1, so errors are told apart by errno
+    unsafe { *libc::__errno_location() = 0 };
+    let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, tid as libc::id_t) };
+    if nice == -1 && io::Error::last_os_error().raw_os_error() != Some(0) {
+        return Err(io::Error::last_os_error());
+    }
+
+    let ioprio = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, tid) };
+    if ioprio < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let ioprio = ioprio as u32;
+
+    Ok(ThreadPriority {
+        nice,
+        io_class: ioprio >> IOPRIO_CLASS_SHIFT,
+        io_level: ioprio & ((1 << IOPRIO_CLASS_SHIFT) - 1),
+    })
+}
+
+/// Runtime whose worker and blocking threads all run at background
+/// priority, created on first use. Foreground jobs stay on the daemon's own
+/// runtime.
+pub fn background_runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(BACKGROUND_WORKERS)
+            .thread_name("copyd-background")
+            .on_thread_start(|| {
+                if let Err(e) = apply_to_current_thread(ThreadPriority::for_class(JobClass::Background)) {
+                    warn!("Could not lower the priority of a background job thread: {}", e);
+                }
+            })
+            .enable_all()
+            .build()
+            .expect("Failed to build the background job runtime")
+    })
+}
+
+/// Runtime whose threads read at idle I/O priority, for post-copy
+/// verification that should yield the disk to running copies. CPU priority
+/// is unchanged.
+pub fn idle_io_runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(IDLE_IO_WORKERS)
+            .thread_name("copyd-verify")
+            .on_thread_start(|| {
+                if let Err(e) = apply_idle_io_to_current_thread() {
+                    warn!("Could not lower the I/O priority of a verification thread: {}", e);
+                }
+            })
+            .enable_all()
+            .build()
+            .expect("Failed to build the idle I/O runtime")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn priority_on_new_thread(class: JobClass) -> ThreadPriority {
+        std::thread::spawn(move || {
+            apply_to_current_thread(ThreadPriority::for_class(class)).unwrap();
+            current_thread_priority().unwrap()
+        }).join().unwrap()
+    }
+
+    #[test]
+    fn test_class_priorities_are_applied() {
+        let background = priority_on_new_thread(JobClass::Background);
+        assert_eq!(background, ThreadPriority { nice: 19, io_class: IOPRIO_CLASS_IDLE, io_level: 0 });
+
+        // Lowering niceness needs privileges, so only check it when the
+        // test thread starts at the default
+        if current_thread_priority().unwrap().nice == 0 {
+            let foreground = priority_on_new_thread(JobClass::Foreground);
+            assert_eq!(foreground, ThreadPriority { nice: 0, io_class: IOPRIO_CLASS_BE, io_level: 4 });
+        }
+
+        // Only the thread that asked was changed
+        assert_ne!(current_thread_priority().unwrap().io_class, IOPRIO_CLASS_IDLE);
+    }
+
+    #[test]
+    fn test_background_runtime_threads_run_at_background_priority() {
+        let runtime = background_runtime();
+        let worker = runtime.block_on(runtime.spawn(async { current_thread_priority() })).unwrap().unwrap();
+        assert_eq!(worker, ThreadPriority::for_class(JobClass::Background));
+
+        // File I/O runs on the runtime's blocking threads
+        let blocking = runtime.block_on(runtime.spawn_blocking(current_thread_priority)).unwrap().unwrap();
+        assert_eq!(blocking, ThreadPriority::for_class(JobClass::Background));
+    }
+
+    #[test]
+    fn test_idle_io_runtime_lowers_only_io_priority() {
+        let nice = current_thread_priority().unwrap().nice;
+        let runtime = idle_io_runtime();
+        for priority in [
+            runtime.block_on(runtime.spawn(async { current_thread_priority() })).unwrap().unwrap(),
+            runtime.block_on(runtime.spawn_blocking(current_thread_priority)).unwrap().unwrap(),
+        ] {
+            assert_eq!(priority, ThreadPriority { nice, io_class: IOPRIO_CLASS_IDLE, io_level: 0 });
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use copyd_protocol::XattrNamespace;
+
+const CAP_CHOWN: u32 = 0;
+const CAP_SYS_ADMIN: u32 = 21;
+
+/// The privileged metadata operations this process may perform, from its
+/// effective capabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataPrivileges {
+    /// `CAP_CHOWN`: give files an owner other than the daemon's user
+    pub chown: bool,
+    /// `CAP_SYS_ADMIN`: set `trusted.*` and `security.*` extended attributes
+    pub sys_admin: bool,
+}
+
+impl MetadataPrivileges {
+    pub const ALL: Self = Self { chown: true, sys_admin: true };
+    pub const NONE: Self = Self { chown: false, sys_admin: false };
+
+    /// The effective capabilities of this process, read from
+    /// `/proc/self/status`; where that can't be read, root is taken to have
+    /// them all and anyone else none.
+    pub fn detect() -> Self {
+        let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+        match effective_capabilities(&status) {
+            Some(mask) => Self {
+                chown: mask & (1 << CAP_CHOWN) != 0,
+                sys_admin: mask & (1 << CAP_SYS_ADMIN) != 0,
+            },
+            None if nix::unistd::geteuid().is_root() => Self::ALL,
+            None => Self::NONE,
+        }
+    }
+
+    /// Whether extended attributes in `namespace` can be set.
+    pub fn can_set_xattrs(&self, namespace: XattrNamespace) -> bool {
+        match namespace {
+            XattrNamespace::Trusted | XattrNamespace::Security => self.sys_admin,
+            XattrNamespace::User | XattrNamespace::System => true,
+        }
+    }
+}
+
+/// The `CapEff` mask of a `/proc/<pid>/status` file.
+fn effective_capabilities(status: &str) -> Option<u64> {
+    let mask = status.lines().find_map(|line| line.strip_prefix("CapEff:"))?;
+    u64::from_str_radix(mask.trim(), 16).ok()
+}
+
+/// Metadata a best-effort copy left out for lack of privileges, counted
+/// per file so a job can report it once instead of once per file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SkippedMetadata {
+    /// Files that kept the daemon's ownership instead of their source's
+    pub ownership: u64,
+    /// Files missing some of their source's privileged extended attributes
+    pub xattrs: u64,
+}
+
+impl SkippedMetadata {
+    /// One line for the job log, or `None` when nothing was skipped.
+    pub fn summary(&self) -> Option<String> {
+        let parts: Vec<String> = [(self.ownership, "ownership"), (self.xattrs, "privileged extended attributes")]
+            .into_iter()
+            .filter(|&(files, _)| files > 0)
+            .map(|(files, what)| format!("{} of {} files", what, files))
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(format!("Not preserved for lack of privileges: {}", parts.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_capabilities_are_read_from_status() {
+        let status = "Name:\tcopyd\nCapInh:\t0000000000000000\nCapPrm:\t000001ffffffffff\nCapEff:\t0000000000200001\n";
+        assert_eq!(effective_capabilities(status), Some((1 << CAP_SYS_ADMIN) | (1 << CAP_CHOWN)));
+        assert_eq!(effective_capabilities("Name:\tcopyd\n"), None);
+    }
+
+    #[test]
+    fn test_skipped_metadata_summary() {
+        assert_eq!(SkippedMetadata::default().summary(), None);
+        assert_eq!(
+            SkippedMetadata { ownership: 5, xattrs: 0 }.summary().unwrap(),
+            "Not preserved for lack of privileges: ownership of 5 files"
+        );
+        assert_eq!(
+            SkippedMetadata { ownership: 5, xattrs: 2 }.summary().unwrap(),
+            "Not preserved for lack of privileges: ownership of 5 files, privileged extended attributes of 2 files"
+        );
+    }
+}
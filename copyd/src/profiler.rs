@@ -16,8 +16,14 @@ pub struct PerformanceMetrics {
     pub engine_performance: HashMap<String, EngineMetrics>,
     /// System resource usage
     pub system_metrics: SystemMetrics,
+    /// Bytes and durations of recent successful copies, by engine and
+    /// destination device
+    pub transfers: HashMap<(String, String), VecDeque<(u64, Duration)>>,
 }
 
+/// Copies kept per engine and device for estimates
+const MAX_TRANSFER_SAMPLES: usize = 1000;
+
 #[derive(Debug, Clone)]
 pub struct EngineMetrics {
     pub operations: u64,
@@ -50,6 +56,7 @@ impl Default for PerformanceMetrics {
                 total_jobs_processed: 0,
                 uptime: Duration::new(0, 0),
             },
+            transfers: HashMap::new(),
         }
     }
 }
@@ -61,6 +68,12 @@ pub struct PerformanceProfiler {
     sampling_interval: Duration,
 }
 
+impl Default for PerformanceProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PerformanceProfiler {
     pub fn new() -> Self {
         Self {
@@ -117,6 +130,44 @@ impl PerformanceProfiler {
         }
     }
 
+    /// Record a successful copy of `bytes` by `engine` to the device labelled
+    /// `device` (see [`crate::device::device_label`]), for [`Self::estimate_throughput`].
+    pub fn record_transfer(&self, engine: &str, device: &str, bytes: u64, duration: Duration) {
+        if bytes == 0 || duration.is_zero() {
+            return;
+        }
+        if let Ok(mut metrics) = self.metrics.lock() {
+            let samples = metrics.transfers
+                .entry((engine.to_string(), device.to_string()))
+                .or_default();
+            samples.push_back((bytes, duration));
+            if samples.len() > MAX_TRANSFER_SAMPLES {
+                samples.pop_front();
+            }
+        }
+    }
+
+    /// Throughput to expect from `engine` (any engine when `None`) writing to
+    /// `device`, from the copies recorded so far. Copies to other devices
+    /// stand in when none reached `device`.
+    pub fn estimate_throughput(&self, engine: Option<&str>, device: &str) -> Option<ThroughputEstimate> {
+        if let Ok(metrics) = self.metrics.lock() {
+            let matching = |same_device: bool| -> Vec<(u64, Duration)> {
+                metrics.transfers.iter()
+                    .filter(|((name, label), _)| engine.is_none_or(|engine| name == engine) && (label == device) == same_device)
+                    .flat_map(|(_, samples)| samples.iter().copied())
+                    .collect()
+            };
+            let (samples, other_devices) = match matching(true) {
+                samples if !samples.is_empty() => (samples, false),
+                _ => (matching(false), true),
+            };
+            ThroughputEstimate::from_samples(&samples, other_devices)
+        } else {
+            None
+        }
+    }
+
     /// Sample system performance
     pub fn sample_system_performance(&self) {
         if let Ok(mut metrics) = self.metrics.lock() {
@@ -308,6 +359,63 @@ impl OperationTimer {
     }
 }
 
+/// Throughput predicted for a copy from earlier ones, in MB/s
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThroughputEstimate {
+    /// All recorded bytes over the time they took
+    pub mbps: f64,
+    /// The slowest tenth of recorded bytes went at most this fast
+    pub low_mbps: f64,
+    /// The fastest tenth of recorded bytes went at least this fast
+    pub high_mbps: f64,
+    pub samples: u64,
+    /// The copies recorded went to other devices than the one asked about
+    pub other_devices: bool,
+}
+
+impl ThroughputEstimate {
+    fn from_samples(samples: &[(u64, Duration)], other_devices: bool) -> Option<Self> {
+        let total_bytes: u64 = samples.iter().map(|&(bytes, _)| bytes).sum();
+        let total_time: f64 = samples.iter().map(|&(_, duration)| duration.as_secs_f64()).sum();
+        if total_bytes == 0 || total_time <= 0.0 {
+            return None;
+        }
+        let mbps = total_bytes as f64 / total_time / (1024.0 * 1024.0);
+
+        // Percentiles weighted by bytes, so a stall on a tiny file counts
+        // for no more than the file does
+        let mut rates: Vec<(f64, u64)> = samples.iter()
+            .map(|&(bytes, duration)| (bytes as f64 / duration.as_secs_f64() / (1024.0 * 1024.0), bytes))
+            .collect();
+        rates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let percentile = |fraction: f64| {
+            let target = total_bytes as f64 * fraction;
+            let mut seen = 0u64;
+            rates.iter()
+                .find(|&&(_, bytes)| {
+                    seen += bytes;
+                    seen as f64 >= target
+                })
+                .map_or(mbps, |&(rate, _)| rate)
+        };
+
+        Some(Self {
+            mbps,
+            low_mbps: percentile(0.1).min(mbps),
+            high_mbps: percentile(0.9).max(mbps),
+            samples: samples.len() as u64,
+            other_devices,
+        })
+    }
+
+    /// Seconds to copy `bytes` at the expected, highest and lowest
+    /// throughput: the estimate and its range.
+    pub fn seconds(&self, bytes: u64) -> (f64, f64, f64) {
+        let megabytes = bytes as f64 / (1024.0 * 1024.0);
+        (megabytes / self.mbps, megabytes / self.high_mbps, megabytes / self.low_mbps)
+    }
+}
+
 /// Performance analysis report
 #[derive(Debug)]
 pub struct PerformanceReport {
@@ -385,6 +493,34 @@ mod tests {
         assert_eq!(report.engine_reports[0].name, "test_engine");
     }
 
+    #[test]
+    fn test_throughput_estimate_prefers_the_destination_device() {
+        let profiler = PerformanceProfiler::new();
+        assert_eq!(profiler.estimate_throughput(None, "8:1"), None);
+
+        // 100 MiB/s to 8:1 over ten files, one of them at half speed
+        for _ in 0..9 {
+            profiler.record_transfer("copy_file_range", "8:1", 100 << 20, Duration::from_secs(1));
+        }
+        profiler.record_transfer("copy_file_range", "8:1", 100 << 20, Duration::from_secs(2));
+        profiler.record_transfer("read_write", "8:17", 10 << 20, Duration::from_secs(1));
+
+        let estimate = profiler.estimate_throughput(Some("copy_file_range"), "8:1").unwrap();
+        assert!((estimate.mbps - 1000.0 / 11.0).abs() < 1e-9);
+        assert_eq!((estimate.low_mbps, estimate.high_mbps), (50.0, 100.0));
+        assert_eq!(estimate.samples, 10);
+        assert!(!estimate.other_devices);
+        let (expected, fastest, slowest) = estimate.seconds(1000 << 20);
+        assert!((expected - 11.0).abs() < 1e-9);
+        assert_eq!((fastest, slowest), (10.0, 20.0));
+
+        // Nothing went to 8:33 yet, so the other devices stand in
+        let elsewhere = profiler.estimate_throughput(None, "8:33").unwrap();
+        assert!(elsewhere.other_devices);
+        assert_eq!(elsewhere.samples, 11);
+        assert_eq!(profiler.estimate_throughput(Some("splice"), "8:1"), None);
+    }
+
     #[test]
     fn test_operation_timer() {
         let profiler = PerformanceProfiler::new();
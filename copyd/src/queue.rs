@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+/// Queue priority of jobs resumed from a checkpoint, which start before
+/// anything submitted since, as they already did part of their work.
+pub const RESUMED_PRIORITY: u32 = u32::MAX;
+
+/// Jobs waiting to start, highest priority first and oldest first within
+/// a priority.
+#[derive(Debug, Default)]
+pub struct JobQueue {
+    entries: BTreeSet<QueuedJob>,
+    next_sequence: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct QueuedJob {
+    priority: u32,
+    created_at: DateTime<Utc>,
+    /// Keeps jobs created within the same clock tick in submission order
+    sequence: u64,
+    job_id: String,
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.cmp(&self.priority)
+            .then(self.created_at.cmp(&other.created_at))
+            .then(self.sequence.cmp(&other.sequence))
+            .then_with(|| self.job_id.cmp(&other.job_id))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `job_id`, behind jobs of a higher priority and older jobs of
+    /// the same one.
+    pub fn push(&mut self, job_id: String, priority: u32, created_at: DateTime<Utc>) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.entries.insert(QueuedJob { priority, created_at, sequence, job_id });
+    }
+
+    /// Takes the job that starts next off the queue.
+    pub fn pop(&mut self) -> Option<String> {
+        self.entries.pop_first().map(|entry| entry.job_id)
+    }
+
+    /// Takes `job_id` off the queue, returning whether it was queued.
+    pub fn remove(&mut self, job_id: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.job_id != job_id);
+        self.entries.len() < before
+    }
+
+    /// Queued job IDs in the order they start.
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|entry| &entry.job_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_higher_priority_starts_first() {
+        let now = Utc::now();
+        let mut queue = JobQueue::new();
+        queue.push("low".to_string(), 50, now);
+        queue.push("normal".to_string(), 100, now);
+        queue.push("high".to_string(), 200, now);
+
+        assert_eq!(queue.pop().as_deref(), Some("high"));
+        assert_eq!(queue.pop().as_deref(), Some("normal"));
+        assert_eq!(queue.pop().as_deref(), Some("low"));
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn test_equal_priorities_start_oldest_first() {
+        let now = Utc::now();
+        let mut queue = JobQueue::new();
+        queue.push("newer".to_string(), 100, now);
+        queue.push("older".to_string(), 100, now - chrono::Duration::seconds(1));
+        queue.push("same-tick".to_string(), 100, now);
+        queue.push("resumed".to_string(), RESUMED_PRIORITY, now);
+
+        let order: Vec<&String> = queue.iter().collect();
+        assert_eq!(order, ["resumed", "older", "newer", "same-tick"]);
+        assert!(queue.remove("older"));
+        assert!(!queue.remove("older"));
+        assert_eq!(queue.len(), 3);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::copy_engine::choose_block_size;
+use crate::directory::DirectoryHandler;
+use crate::packed::PACKED_FILE_LIMIT;
+use anyhow::Result;
+use copyd_protocol::CopyEngine;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Fewest files for packed mode to be worth suggesting; below this the
+/// path lookups it saves don't add up to anything noticeable.
+const PACKED_MIN_FILES: u64 = 100;
+
+/// What a copy of some sources to a destination would involve.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyProfile {
+    pub total_files: u64,
+    pub total_bytes: u64,
+    pub median_file_size: u64,
+    /// Files of at most [`PACKED_FILE_LIMIT`] bytes
+    pub small_files: u64,
+    pub same_filesystem: bool,
+    /// The destination filesystem supports reflinks
+    pub reflink: bool,
+    pub io_uring: bool,
+    /// Preferred I/O size of the destination device
+    pub device_io_size: u64,
+}
+
+/// How to run a copy, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recommendation {
+    pub engine: CopyEngine,
+    /// `None` when the engine doesn't read into buffers
+    pub block_size: Option<u64>,
+    pub packed: bool,
+    pub reasons: Vec<String>,
+}
+
+/// Walks the sources recursively and looks at the filesystems on both
+/// sides. Nothing is written.
+pub async fn inspect(sources: &[PathBuf], destination: &Path) -> Result<CopyProfile> {
+    let traversal = DirectoryHandler::analyze_sources(sources, destination, true, true).await?;
+    let mut sizes: Vec<u64> = traversal.files.iter().map(|entry| entry.size).collect();
+    sizes.sort_unstable();
+
+    // The destination may not exist yet; its closest existing ancestor is
+    // on the filesystem it will be created on
+    let dest_existing = destination.ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .unwrap_or(Path::new("."));
+    let dest_device = crate::device::device_label(dest_existing);
+    let same_filesystem = !sources.is_empty()
+        && sources.iter().all(|source| crate::device::device_label(source) == dest_device);
+
+    Ok(CopyProfile {
+        total_files: traversal.total_files,
+        total_bytes: traversal.total_size,
+        median_file_size: sizes.get(sizes.len() / 2).copied().unwrap_or(0),
+        small_files: sizes.iter().filter(|&&size| size <= PACKED_FILE_LIMIT).count() as u64,
+        same_filesystem,
+        reflink: crate::device::supports_reflink(dest_existing).unwrap_or(false),
+        io_uring: crate::io_uring_engine::IoUringCopyEngine::is_io_uring_available(),
+        device_io_size: std::fs::metadata(dest_existing).map(|metadata| metadata.blksize()).unwrap_or(0),
+    })
+}
+
+/// Picks an engine, block size and packed mode for a copy like `profile`.
+/// Reflinks win whenever they are possible; otherwise many tiny files call
+/// for packed mode, and large files for an engine that keeps data in the
+/// kernel or many requests in flight.
+pub fn recommend(profile: &CopyProfile) -> Recommendation {
+    let mut reasons = Vec::new();
+    if profile.total_files == 0 {
+        reasons.push("The sources hold no files to copy, so automatic selection will do".to_string());
+        return Recommendation { engine: CopyEngine::Auto, block_size: None, packed: false, reasons };
+    }
+
+    if profile.same_filesystem && profile.reflink {
+        reasons.push(
+            "Source and destination are on one filesystem that supports reflinks: copies share the \
+             original data blocks, so they finish almost at once and take no space until changed".to_string(),
+        );
+        return Recommendation { engine: CopyEngine::Reflink, block_size: None, packed: false, reasons };
+    }
+    if profile.reflink {
+        reasons.push("The destination supports reflinks, but only within one filesystem".to_string());
+    }
+
+    if profile.total_files >= PACKED_MIN_FILES && profile.small_files * 2 > profile.total_files {
+        reasons.push(format!(
+            "{} of {} files are at most {} KiB; for files this small, resolving paths costs more than \
+             moving data, so packed mode copies them relative to open directories",
+            profile.small_files, profile.total_files, PACKED_FILE_LIMIT / 1024,
+        ));
+        return Recommendation { engine: CopyEngine::Auto, block_size: None, packed: true, reasons };
+    }
+
+    let engine = if profile.same_filesystem {
+        reasons.push(
+            "Source and destination share a filesystem without reflinks: copy_file_range keeps the \
+             data in the kernel, and some filesystems copy it without reading it at all".to_string(),
+        );
+        CopyEngine::CopyFileRange
+    } else if profile.io_uring {
+        reasons.push(
+            "Source and destination are on different filesystems and io_uring is available: it keeps \
+             reads and writes in flight on both devices at once".to_string(),
+        );
+        CopyEngine::IoUring
+    } else {
+        reasons.push(
+            "Source and destination are on different filesystems and io_uring isn't available: \
+             copy_file_range still copies without passing data through user space".to_string(),
+        );
+        CopyEngine::CopyFileRange
+    };
+
+    let block_size = choose_block_size(profile.median_file_size, profile.device_io_size);
+    reasons.push(format!(
+        "A {} KiB block size suits the median file of {} bytes on a device preferring {}-byte writes",
+        block_size / 1024, profile.median_file_size, profile.device_io_size,
+    ));
+    Recommendation { engine, block_size: Some(block_size), packed: false, reasons }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn large_files() -> CopyProfile {
+        CopyProfile {
+            total_files: 40,
+            total_bytes: 40 * 2048 * MIB,
+            median_file_size: 2048 * MIB,
+            small_files: 0,
+            same_filesystem: true,
+            reflink: true,
+            io_uring: true,
+            device_io_size: 4096,
+        }
+    }
+
+    #[test]
+    fn test_large_files_on_one_cow_filesystem_are_reflinked() {
+        let recommendation = recommend(&large_files());
+        assert_eq!(recommendation.engine, CopyEngine::Reflink);
+        assert_eq!(recommendation.block_size, None);
+        assert!(!recommendation.packed);
+        assert!(recommendation.reasons[0].contains("reflinks"), "{:?}", recommendation.reasons);
+
+        // The same files to another filesystem stream through io_uring
+        // with the largest block size, or copy_file_range without it
+        let across = CopyProfile { same_filesystem: false, ..large_files() };
+        let recommendation = recommend(&across);
+        assert_eq!(recommendation.engine, CopyEngine::IoUring);
+        assert_eq!(recommendation.block_size, Some(8 * MIB));
+        assert_eq!(recommend(&CopyProfile { io_uring: false, ..across }).engine, CopyEngine::CopyFileRange);
+        let without_reflinks = CopyProfile { reflink: false, ..large_files() };
+        assert_eq!(recommend(&without_reflinks).engine, CopyEngine::CopyFileRange);
+    }
+
+    #[test]
+    fn test_many_tiny_files_are_packed() {
+        let tiny = CopyProfile {
+            total_files: 50_000,
+            total_bytes: 50_000 * 2048,
+            median_file_size: 2048,
+            small_files: 49_000,
+            same_filesystem: false,
+            reflink: false,
+            io_uring: true,
+            device_io_size: 4096,
+        };
+        let recommendation = recommend(&tiny);
+        assert!(recommendation.packed);
+        assert_eq!(recommendation.engine, CopyEngine::Auto);
+        // Reflinks still beat packing within one filesystem
+        assert!(!recommend(&CopyProfile { same_filesystem: true, reflink: true, ..tiny }).packed);
+        assert_eq!(recommend(&CopyProfile::default()).engine, CopyEngine::Auto);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_measures_sources_and_filesystems() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src");
+        std::fs::create_dir(&source).unwrap();
+        for (name, size) in [("a", 10), ("b", 100 * 1024), ("c", 1000)] {
+            std::fs::write(source.join(name), vec![0u8; size]).unwrap();
+        }
+
+        let profile = inspect(&[source], &dir.path().join("not/yet/dst")).await.unwrap();
+        assert_eq!((profile.total_files, profile.total_bytes), (3, 10 + 100 * 1024 + 1000));
+        assert_eq!(profile.median_file_size, 1000);
+        assert_eq!(profile.small_files, 2);
+        assert!(profile.same_filesystem);
+        assert!(profile.device_io_size > 0);
+    }
+}
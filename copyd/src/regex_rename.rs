@@ -213,7 +213,6 @@ impl RegexTransformPreview {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::TempDir;
 
     #[test]
     fn test_regex_renamer_basic() -> Result<()> {
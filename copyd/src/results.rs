@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use copyd_protocol::FileResult;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Per-file results of each job, kept as one JSON [`FileResult`] per line in
+/// a file per job, so they outlive the daemon like the job history.
+#[derive(Debug)]
+pub struct JobResults {
+    dir: PathBuf,
+}
+
+impl JobResults {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, job_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", job_id))
+    }
+
+    /// Opens `job_id`'s results for appending, e.g. by a job that was
+    /// paused and started again.
+    pub async fn writer(&self, job_id: &str) -> Result<ResultsWriter> {
+        fs::create_dir_all(&self.dir).await
+            .with_context(|| format!("Failed to create results directory {:?}", self.dir))?;
+        let path = self.path(job_id);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to open job results {:?}", path))?;
+        Ok(ResultsWriter { path, file })
+    }
+
+    /// The results of `job_id` in the order its files finished. A file
+    /// copied more than once, as on a retry after resuming, keeps only its
+    /// last result.
+    pub async fn load(&self, job_id: &str) -> Result<Vec<FileResult>> {
+        let path = self.path(job_id);
+        let content = match fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read job results {:?}", path)),
+        };
+
+        let mut results: Vec<FileResult> = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            // A daemon stopped mid-write leaves a partial last line
+            match serde_json::from_str(line) {
+                Ok(result) => results.push(result),
+                Err(e) => warn!("Skipping unreadable line {} of {:?}: {}", number + 1, path, e),
+            }
+        }
+        let last: HashMap<&str, usize> = results.iter().enumerate()
+            .map(|(index, result)| (result.destination.as_str(), index))
+            .collect();
+        let keep: Vec<bool> = results.iter().enumerate()
+            .map(|(index, result)| last[result.destination.as_str()] == index)
+            .collect();
+        let mut keep = keep.into_iter();
+        results.retain(|_| keep.next().unwrap_or(true));
+        Ok(results)
+    }
+
+    /// Deletes the results of `job_id`, if it has any.
+    pub async fn remove(&self, job_id: &str) -> Result<()> {
+        let path = self.path(job_id);
+        match fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to delete job results {:?}", path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Deletes the results of jobs last written to before `cutoff`,
+    /// returning how many were removed.
+    pub async fn prune(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut removed = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "jsonl") {
+                continue;
+            }
+            let modified: DateTime<Utc> = entry.metadata().await?.modified()?.into();
+            if modified < cutoff {
+                fs::remove_file(&path).await?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Appends a job's file results as they finish.
+pub struct ResultsWriter {
+    path: PathBuf,
+    file: fs::File,
+}
+
+impl ResultsWriter {
+    pub async fn record(&mut self, result: &FileResult) -> Result<()> {
+        let mut line = serde_json::to_string(result)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes()).await
+            .with_context(|| format!("Failed to write job results {:?}", self.path))
+    }
+}
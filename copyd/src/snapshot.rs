@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use nix::sys::stat::{utimensat, UtimensatFlags};
+use nix::sys::time::TimeSpec;
+use nix::unistd;
+use std::collections::HashMap;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use tokio::fs;
+use tracing::{debug, info, warn};
+
+use crate::copy_engine::clone_file;
+use crate::directory::DirectoryHandler;
+
+/// What [`create_snapshot`] put in a snapshot.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SnapshotStats {
+    pub files: u64,
+    pub hard_links: u64,
+    pub directories: u64,
+    pub symlinks: u64,
+    pub bytes: u64,
+}
+
+/// Clones the directory tree `source` into the new directory `destination`
+/// as a point-in-time snapshot. Every file is reflinked, so the snapshot is
+/// near-instant and takes no space until either side changes; files that
+/// are hard links of each other in the source stay linked in the snapshot.
+///
+/// Both paths must be on one reflink-capable filesystem (Btrfs, or XFS
+/// with reflink=1). If they aren't, or anything else fails, the partial
+/// snapshot is removed again.
+pub async fn create_snapshot(source: &Path, destination: &Path) -> Result<SnapshotStats> {
+    let metadata = fs::metadata(source).await
+        .with_context(|| format!("Failed to read snapshot source {:?}", source))?;
+    if !metadata.is_dir() {
+        anyhow::bail!("Snapshot source {:?} is not a directory", source);
+    }
+    if fs::symlink_metadata(destination).await.is_ok() {
+        anyhow::bail!("Snapshot destination {:?} already exists", destination);
+    }
+
+    let result = populate_snapshot(source, destination).await;
+    match &result {
+        Ok(stats) => info!("Snapshot of {:?} created at {:?}: {} files, {} bytes", source, destination, stats.files, stats.bytes),
+        Err(_) => {
+            if let Err(e) = fs::remove_dir_all(destination).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Could not remove partial snapshot {:?}: {}", destination, e);
+                }
+            }
+        }
+    }
+    result
+}
+
+async fn populate_snapshot(source: &Path, destination: &Path) -> Result<SnapshotStats> {
+    let traversal = DirectoryHandler::analyze_sources(&[source.to_path_buf()], destination, true, true).await?;
+    DirectoryHandler::create_directories(&traversal.directories).await?;
+
+    let mut stats = SnapshotStats {
+        directories: traversal.directories.len() as u64,
+        ..Default::default()
+    };
+    let mut linked: HashMap<(u64, u64), &Path> = HashMap::new();
+
+    for entry in &traversal.files {
+        if let Some(key) = entry.hard_links {
+            if let Some(first) = linked.get(&key) {
+                fs::hard_link(first, &entry.dest_path).await
+                    .with_context(|| format!("Failed to link {:?} to {:?}", entry.dest_path, first))?;
+                stats.hard_links += 1;
+                continue;
+            }
+            linked.insert(key, &entry.dest_path);
+        }
+
+        let metadata = fs::symlink_metadata(&entry.source_path).await?;
+        if !metadata.is_file() {
+            warn!("Leaving special file {:?} out of the snapshot", entry.source_path);
+            continue;
+        }
+
+        reflink(&entry.source_path, &entry.dest_path)?;
+        copy_attributes(&metadata, &entry.dest_path)?;
+        stats.files += 1;
+        stats.bytes += metadata.len();
+    }
+
+    DirectoryHandler::create_symlinks(&traversal.symlinks).await?;
+    stats.symlinks = traversal.symlinks.len() as u64;
+
+    // Filling the directories changed their times, so they are set last,
+    // deepest first
+    for dest_dir in traversal.directories.iter().rev() {
+        let source_dir = match dest_dir.strip_prefix(destination) {
+            Ok(relative) if relative.as_os_str().is_empty() => source.to_path_buf(),
+            Ok(relative) => source.join(relative),
+            Err(_) => continue,
+        };
+        copy_attributes(&fs::metadata(&source_dir).await?, dest_dir)?;
+    }
+
+    Ok(stats)
+}
+
+fn reflink(source: &Path, destination: &Path) -> Result<()> {
+    let source_file = std::fs::File::open(source)
+        .with_context(|| format!("Failed to open {:?}", source))?;
+    let dest_file = std::fs::File::create(destination)
+        .with_context(|| format!("Failed to create {:?}", destination))?;
+
+    match clone_file(&source_file, &dest_file) {
+        Ok(()) => {
+            debug!("Reflinked {:?} to {:?}", source, destination);
+            Ok(())
+        }
+        Err(e) if matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP | libc::EXDEV | libc::EINVAL)) => Err(anyhow::anyhow!(
+            "Cannot reflink {:?} to {:?} ({}); snapshots need the source and destination on the same \
+             copy-on-write filesystem such as Btrfs or XFS with reflink=1",
+            source, destination, e
+        )),
+        Err(e) => Err(e).with_context(|| format!("Failed to reflink {:?} to {:?}", source, destination)),
+    }
+}
+
+fn copy_attributes(metadata: &std::fs::Metadata, destination: &Path) -> Result<()> {
+    std::fs::set_permissions(destination, std::fs::Permissions::from_mode(metadata.mode()))?;
+
+    // Don't fail if we can't change ownership (common when not root)
+    if let Err(e) = unistd::chown(destination, Some(unistd::Uid::from_raw(metadata.uid())), Some(unistd::Gid::from_raw(metadata.gid()))) {
+        debug!("Could not change ownership of {:?}: {}", destination, e);
+    }
+
+    let atime = TimeSpec::new(metadata.atime(), metadata.atime_nsec());
+    let mtime = TimeSpec::new(metadata.mtime(), metadata.mtime_nsec());
+    utimensat(None, destination, &atime, &mtime, UtimensatFlags::FollowSymlink)
+        .with_context(|| format!("Failed to set times of {:?}", destination))?;
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::path::Path;
+use tokio::io::unix::AsyncFd;
+
+/// Times a file is copied again after its source changed mid-copy before
+/// the copy fails.
+pub const MAX_SOURCE_CHANGE_RESTARTS: u32 = 3;
+
+struct InotifyFd(Inotify);
+
+impl AsRawFd for InotifyFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+/// An inotify watch for writes to one file, e.g. a source being copied.
+/// Reads of the file, including the copy's own, don't count as changes.
+pub struct SourceWatch {
+    inotify: AsyncFd<InotifyFd>,
+}
+
+impl SourceWatch {
+    pub fn new(path: &Path) -> Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .context("Failed to create an inotify instance")?;
+        inotify.add_watch(path, AddWatchFlags::IN_MODIFY)
+            .with_context(|| format!("Failed to watch {:?} for changes", path))?;
+        Ok(Self { inotify: AsyncFd::new(InotifyFd(inotify))? })
+    }
+
+    /// Whether the file was written to since the watch started or since the
+    /// last call, without waiting.
+    pub fn changed(&self) -> Result<bool> {
+        match self.inotify.get_ref().0.read_events() {
+            Ok(events) => Ok(!events.is_empty()),
+            Err(Errno::EAGAIN) => Ok(false),
+            Err(e) => Err(e).context("Failed to read inotify events"),
+        }
+    }
+
+    /// Waits until the file is written to.
+    pub async fn wait(&self) -> Result<()> {
+        loop {
+            let mut ready = self.inotify.readable().await?;
+            if self.changed()? {
+                return Ok(());
+            }
+            ready.clear_ready();
+        }
+    }
+}
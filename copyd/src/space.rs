@@ -0,0 +1,229 @@
+use crate::monitor::EnhancedMonitor;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Time between free-space samples of running jobs' destination devices.
+pub const LOW_SPACE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Free space below which a device is reported as running low: a share of
+/// its size such as `"10%"`, or an amount such as `"50G"` (K, M, G and T
+/// suffixes are binary; a bare number is bytes).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SpaceThreshold {
+    Percent(f64),
+    Bytes(u64),
+}
+
+impl SpaceThreshold {
+    pub fn is_low(&self, space: FreeSpace) -> bool {
+        match *self {
+            SpaceThreshold::Percent(percent) => {
+                (space.available as f64) < space.total as f64 * percent / 100.0
+            }
+            SpaceThreshold::Bytes(bytes) => space.available < bytes,
+        }
+    }
+}
+
+impl FromStr for SpaceThreshold {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: f64 = percent.trim().parse()
+                .map_err(|_| anyhow::anyhow!("Invalid space threshold: {}", s))?;
+            if !(0.0..=100.0).contains(&percent) {
+                anyhow::bail!("Space threshold {} is not between 0% and 100%", s);
+            }
+            return Ok(SpaceThreshold::Percent(percent));
+        }
+
+        let (digits, unit) = match s.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+            Some((index, _)) => s.split_at(index),
+            None => (s, ""),
+        };
+        let shift = match unit.trim().to_uppercase().as_str() {
+            "" | "B" => 0,
+            "K" | "KB" | "KIB" => 10,
+            "M" | "MB" | "MIB" => 20,
+            "G" | "GB" | "GIB" => 30,
+            "T" | "TB" | "TIB" => 40,
+            _ => anyhow::bail!("Invalid space threshold: {}", s),
+        };
+        let amount: u64 = digits.parse().map_err(|_| anyhow::anyhow!("Invalid space threshold: {}", s))?;
+        amount.checked_mul(1 << shift)
+            .map(SpaceThreshold::Bytes)
+            .ok_or_else(|| anyhow::anyhow!("Space threshold {} is too large", s))
+    }
+}
+
+impl fmt::Display for SpaceThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpaceThreshold::Percent(percent) => write!(f, "{}%", percent),
+            SpaceThreshold::Bytes(bytes) => write!(f, "{}", bytes),
+        }
+    }
+}
+
+impl TryFrom<String> for SpaceThreshold {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SpaceThreshold> for String {
+    fn from(threshold: SpaceThreshold) -> Self {
+        threshold.to_string()
+    }
+}
+
+/// Bytes free for unprivileged writers and the size of a filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeSpace {
+    pub available: u64,
+    pub total: u64,
+}
+
+/// Reports the free space of the filesystem holding a path.
+pub trait FreeSpaceSource: Send + Sync {
+    fn free_space(&self, path: &Path) -> io::Result<FreeSpace>;
+}
+
+/// Asks the kernel with statvfs. A path that doesn't exist yet is measured
+/// at its closest existing ancestor.
+pub struct StatvfsSource;
+
+impl FreeSpaceSource for StatvfsSource {
+    fn free_space(&self, path: &Path) -> io::Result<FreeSpace> {
+        let existing = path.ancestors()
+            .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+            .unwrap_or(Path::new("/"));
+        let stat = nix::sys::statvfs::statvfs(existing)?;
+        Ok(FreeSpace {
+            available: stat.blocks_available() * stat.fragment_size(),
+            total: stat.blocks() * stat.fragment_size(),
+        })
+    }
+}
+
+/// Samples the devices that copies are writing to and reports their free
+/// space to the monitor, which alerts as a device drops below the threshold.
+pub struct LowSpaceWatch {
+    monitor: Arc<EnhancedMonitor>,
+    source: Arc<dyn FreeSpaceSource>,
+}
+
+impl LowSpaceWatch {
+    pub fn new(monitor: Arc<EnhancedMonitor>, source: Arc<dyn FreeSpaceSource>) -> Self {
+        Self { monitor, source }
+    }
+
+    /// Samples each device holding one of `destinations` once and returns
+    /// the labels of those below `threshold`.
+    pub async fn check(&self, destinations: &[PathBuf], threshold: SpaceThreshold) -> Vec<String> {
+        let mut devices: BTreeMap<String, &Path> = BTreeMap::new();
+        for destination in destinations {
+            devices.entry(crate::device::device_label(destination)).or_insert(destination);
+        }
+
+        let mut low = Vec::new();
+        for (device, path) in devices {
+            match self.source.free_space(path) {
+                Ok(space) => {
+                    if self.monitor.record_free_space(&device, space, threshold).await {
+                        low.push(device);
+                    }
+                }
+                Err(e) => warn!("Failed to read free space of {:?}: {}", path, e),
+            }
+        }
+        low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    const GIB: u64 = 1 << 30;
+
+    struct ScriptedSource {
+        available: AtomicU64,
+    }
+
+    impl FreeSpaceSource for ScriptedSource {
+        fn free_space(&self, _path: &Path) -> io::Result<FreeSpace> {
+            Ok(FreeSpace { available: self.available.load(Ordering::SeqCst), total: 100 * GIB })
+        }
+    }
+
+    #[test]
+    fn test_parse_space_threshold() {
+        assert_eq!("10%".parse::<SpaceThreshold>().unwrap(), SpaceThreshold::Percent(10.0));
+        assert_eq!("2.5 %".parse::<SpaceThreshold>().unwrap(), SpaceThreshold::Percent(2.5));
+        assert_eq!("50G".parse::<SpaceThreshold>().unwrap(), SpaceThreshold::Bytes(50 * GIB));
+        assert_eq!("512 MiB".parse::<SpaceThreshold>().unwrap(), SpaceThreshold::Bytes(512 << 20));
+        assert_eq!("4096".parse::<SpaceThreshold>().unwrap(), SpaceThreshold::Bytes(4096));
+        for invalid in ["", "%", "150%", "ten%", "5X", "G", "99999999999T"] {
+            assert!(invalid.parse::<SpaceThreshold>().is_err(), "{}", invalid);
+        }
+
+        let space = FreeSpace { available: 5 * GIB, total: 100 * GIB };
+        assert!(SpaceThreshold::Percent(10.0).is_low(space));
+        assert!(!SpaceThreshold::Percent(5.0).is_low(space));
+        assert!(SpaceThreshold::Bytes(6 * GIB).is_low(space));
+        assert!(!SpaceThreshold::Bytes(5 * GIB).is_low(space));
+    }
+
+    #[tokio::test]
+    async fn test_low_space_alerts_once_per_crossing() {
+        let dir = tempfile::tempdir().unwrap();
+        let destinations = vec![dir.path().join("a"), dir.path().join("b")];
+        let monitor = Arc::new(EnhancedMonitor::new().unwrap());
+        let source = Arc::new(ScriptedSource { available: AtomicU64::new(50 * GIB) });
+        let watch = LowSpaceWatch::new(monitor.clone(), source.clone());
+        let threshold = SpaceThreshold::Percent(10.0);
+        let storage_alerts = || async {
+            monitor.health_status().await.alerts.into_iter()
+                .filter(|alert| alert.category == "Storage")
+                .count()
+        };
+
+        // Both destinations are on one device, so it is sampled once
+        let mut samples = Vec::new();
+        for available in [50, 12, 9, 7, 3] {
+            source.available.store(available * GIB, Ordering::SeqCst);
+            samples.push(watch.check(&destinations, threshold).await.len());
+        }
+        assert_eq!(samples, vec![0, 0, 1, 1, 1]);
+        assert_eq!(storage_alerts().await, 1);
+
+        // Recovering rearms the alert for the next drop
+        source.available.store(20 * GIB, Ordering::SeqCst);
+        assert!(watch.check(&destinations, threshold).await.is_empty());
+        source.available.store(GIB, Ordering::SeqCst);
+        assert_eq!(watch.check(&destinations, threshold).await.len(), 1);
+        assert_eq!(storage_alerts().await, 2);
+    }
+
+    #[test]
+    fn test_statvfs_source_reads_free_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let space = StatvfsSource.free_space(&dir.path().join("not/yet")).unwrap();
+        assert!(space.total > 0);
+        assert!(space.available <= space.total);
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::{Result, Context};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use sha2::{Sha256, Digest};
 use tokio::io::AsyncReadExt;
 use tracing::{info, debug};
@@ -10,8 +10,13 @@ pub enum VerifyMode {
     Size = 1,
     Md5 = 2,
     Sha256 = 3,
+    Blake3 = 4,
+    Fingerprint = 5,
 }
 
+/// Bytes hashed at each end of a file by [`VerifyMode::Fingerprint`].
+pub const FINGERPRINT_WINDOW: usize = 64 * 1024;
+
 impl From<i32> for VerifyMode {
     fn from(value: i32) -> Self {
         match value {
@@ -19,6 +24,8 @@ impl From<i32> for VerifyMode {
             1 => VerifyMode::Size,
             2 => VerifyMode::Md5,
             3 => VerifyMode::Sha256,
+            4 => VerifyMode::Blake3,
+            5 => VerifyMode::Fingerprint,
             _ => VerifyMode::None,
         }
     }
@@ -31,8 +38,128 @@ impl From<copyd_protocol::VerifyMode> for VerifyMode {
             copyd_protocol::VerifyMode::Size => VerifyMode::Size,
             copyd_protocol::VerifyMode::Md5 => VerifyMode::Md5,
             copyd_protocol::VerifyMode::Sha256 => VerifyMode::Sha256,
+            copyd_protocol::VerifyMode::Blake3 => VerifyMode::Blake3,
+            copyd_protocol::VerifyMode::Fingerprint => VerifyMode::Fingerprint,
+        }
+    }
+}
+
+/// Checksums data as it streams past, for copies with no source file to
+/// read back. `finish` gives what [`FileVerifier::calculate_checksum`] gives
+/// for a file holding the same bytes.
+pub enum StreamHasher {
+    None,
+    Size(u64),
+    Md5(md5::Context),
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    /// The first window, the most recent window and the length so far
+    Fingerprint { head: Vec<u8>, tail: std::collections::VecDeque<u8>, size: u64 },
+}
+
+impl StreamHasher {
+    pub fn new(mode: VerifyMode) -> Self {
+        match mode {
+            VerifyMode::None => StreamHasher::None,
+            VerifyMode::Size => StreamHasher::Size(0),
+            VerifyMode::Md5 => StreamHasher::Md5(md5::Context::new()),
+            VerifyMode::Sha256 => StreamHasher::Sha256(Sha256::new()),
+            VerifyMode::Blake3 => StreamHasher::Blake3(Box::new(blake3::Hasher::new())),
+            VerifyMode::Fingerprint => StreamHasher::Fingerprint {
+                head: Vec::with_capacity(FINGERPRINT_WINDOW),
+                tail: std::collections::VecDeque::with_capacity(FINGERPRINT_WINDOW),
+                size: 0,
+            },
         }
     }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            StreamHasher::None => {}
+            StreamHasher::Size(size) => *size += data.len() as u64,
+            StreamHasher::Md5(context) => context.consume(data),
+            StreamHasher::Sha256(hasher) => hasher.update(data),
+            StreamHasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            StreamHasher::Fingerprint { head, tail, size } => {
+                let into_head = (FINGERPRINT_WINDOW - head.len()).min(data.len());
+                head.extend_from_slice(&data[..into_head]);
+                let recent = &data[data.len().saturating_sub(FINGERPRINT_WINDOW)..];
+                let overflow = (tail.len() + recent.len()).saturating_sub(FINGERPRINT_WINDOW);
+                tail.drain(..overflow);
+                tail.extend(recent);
+                *size += data.len() as u64;
+            }
+        }
+    }
+
+    pub fn finish(self) -> String {
+        match self {
+            StreamHasher::None => String::new(),
+            StreamHasher::Size(size) => size.to_string(),
+            StreamHasher::Md5(context) => format!("{:x}", context.compute()),
+            StreamHasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            StreamHasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            StreamHasher::Fingerprint { head, tail, size } => {
+                let (front, back) = tail.as_slices();
+                fingerprint_digest(size, &head, &[front, back].concat())
+            }
+        }
+    }
+}
+
+impl std::io::Write for StreamHasher {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.update(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Checksum of a [`VerifyMode::Fingerprint`]: the length, and a BLAKE3 hash
+/// of the first and last [`FINGERPRINT_WINDOW`] bytes. Files no longer than
+/// two windows are hashed whole.
+fn fingerprint_digest(size: u64, head: &[u8], tail: &[u8]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(head);
+    // The windows overlap in files shorter than two of them
+    let overlap = (head.len() + tail.len()).saturating_sub(size as usize);
+    hasher.update(&tail[overlap.min(tail.len())..]);
+    format!("{}:{}", size, hasher.finalize().to_hex())
+}
+
+/// Checksum sidecar extensions in the order they are probed.
+const SIDECAR_EXTENSIONS: &[(&str, VerifyMode)] = &[
+    ("sha256", VerifyMode::Sha256),
+    ("blake3", VerifyMode::Blake3),
+    ("md5", VerifyMode::Md5),
+];
+
+/// Outcome of checking a file against a checksum sidecar.
+#[derive(Debug)]
+pub struct SidecarVerification {
+    pub sidecar: PathBuf,
+    pub mode: VerifyMode,
+    pub expected_checksum: String,
+    pub calculated_checksum: String,
+    pub verified: bool,
+}
+
+/// Files hashed at once when comparing two trees.
+const TREE_COMPARE_PARALLELISM: usize = 4;
+
+/// Outcome of comparing the regular files of two trees, as paths relative
+/// to their roots, each list sorted.
+#[derive(Debug, Default)]
+pub struct TreeComparison {
+    pub matching: Vec<PathBuf>,
+    pub differing: Vec<PathBuf>,
+    pub only_left: Vec<PathBuf>,
+    pub only_right: Vec<PathBuf>,
 }
 
 /// Result type returned by FileVerifier::verify_file for the test-suite.
@@ -44,6 +171,12 @@ pub struct VerificationResult {
 
 pub struct FileVerifier;
 
+impl Default for FileVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FileVerifier {
     /// Stateless constructor included for integration-test compatibility.
     pub fn new() -> Self {
@@ -78,25 +211,199 @@ impl FileVerifier {
             VerifyMode::Sha256 => {
                 Self::verify_sha256(source, destination).await
             }
+            VerifyMode::Blake3 => {
+                Self::verify_blake3(source, destination).await
+            }
+            VerifyMode::Fingerprint => {
+                Self::verify_fingerprint(source, destination, true).await
+            }
+        }
+    }
+
+    /// Compares the sizes of `source` and `destination` and hashes of their
+    /// first and last [`FINGERPRINT_WINDOW`] bytes, catching truncation and
+    /// damaged ends without reading whole files. Changes that leave both
+    /// ends and the size intact go unnoticed. With `compare_mtime` the
+    /// modification times must match too, as they do for copies made with
+    /// timestamps preserved.
+    pub async fn verify_fingerprint(source: &Path, destination: &Path, compare_mtime: bool) -> Result<bool> {
+        info!("Verifying with size and sampled fingerprints");
+
+        if compare_mtime {
+            let source_mtime = tokio::fs::metadata(source).await
+                .with_context(|| format!("Failed to get source metadata: {:?}", source))?
+                .modified()?;
+            let dest_mtime = tokio::fs::metadata(destination).await
+                .with_context(|| format!("Failed to get destination metadata: {:?}", destination))?
+                .modified()?;
+            if source_mtime != dest_mtime {
+                info!("Fingerprint verification failed: modification times differ");
+                return Ok(false);
+            }
+        }
+
+        let source_fingerprint = Self::calculate_fingerprint(source).await?;
+        let dest_fingerprint = Self::calculate_fingerprint(destination).await?;
+        let fingerprints_match = source_fingerprint == dest_fingerprint;
+        if fingerprints_match {
+            info!("Fingerprint verification passed: {}", source_fingerprint);
+        } else {
+            info!("Fingerprint verification failed: source {}, dest {}", source_fingerprint, dest_fingerprint);
+        }
+        Ok(fingerprints_match)
+    }
+
+    /// [`Self::verify_copy`] with the reads issued at idle I/O priority, so
+    /// verification only uses the disk when copies leave it free.
+    pub async fn verify_copy_at_idle(
+        source: &Path,
+        destination: &Path,
+        mode: VerifyMode,
+    ) -> Result<bool> {
+        let (source, destination) = (source.to_path_buf(), destination.to_path_buf());
+        crate::priority::idle_io_runtime()
+            .spawn(async move { Self::verify_copy(&source, &destination, mode).await })
+            .await
+            .context("Verification task failed")?
+    }
+
+    /// Compares every regular file under `left` with the file at the same
+    /// relative path under `right`, hashing several pairs at a time.
+    /// Symlinks and special files are not compared.
+    pub async fn compare_trees(left: &Path, right: &Path, mode: VerifyMode) -> Result<TreeComparison> {
+        use futures::stream::{self, StreamExt, TryStreamExt};
+
+        if matches!(mode, VerifyMode::None) {
+            anyhow::bail!("Comparing trees needs a verification mode");
+        }
+        let left_files = Self::regular_files(left).await?;
+        let right_files = Self::regular_files(right).await?;
+
+        let mut comparison = TreeComparison {
+            only_left: left_files.difference(&right_files).cloned().collect(),
+            only_right: right_files.difference(&left_files).cloned().collect(),
+            ..Default::default()
+        };
+
+        let common: Vec<PathBuf> = left_files.intersection(&right_files).cloned().collect();
+        let outcomes: Vec<(PathBuf, bool)> = stream::iter(common)
+            .map(|relative| async move {
+                let matches = Self::verify_copy(&left.join(&relative), &right.join(&relative), mode).await?;
+                Ok::<_, anyhow::Error>((relative, matches))
+            })
+            .buffer_unordered(TREE_COMPARE_PARALLELISM)
+            .try_collect()
+            .await?;
+
+        for (relative, matches) in outcomes {
+            if matches {
+                comparison.matching.push(relative);
+            } else {
+                comparison.differing.push(relative);
+            }
         }
+        comparison.matching.sort();
+        comparison.differing.sort();
+        Ok(comparison)
+    }
+
+    /// Relative paths of the regular files below `root`.
+    async fn regular_files(root: &Path) -> Result<std::collections::BTreeSet<PathBuf>> {
+        let mut files = std::collections::BTreeSet::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&dir).await
+                .with_context(|| format!("Failed to read directory: {:?}", dir))?;
+            while let Some(entry) = entries.next_entry().await? {
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() {
+                    pending.push(entry.path());
+                } else if file_type.is_file() {
+                    files.insert(entry.path().strip_prefix(root)?.to_path_buf());
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Maps a sidecar's extension (`.sha256`, `.blake3`, `.md5`) to its algorithm.
+    pub fn algorithm_from_sidecar(sidecar: &Path) -> Option<VerifyMode> {
+        let extension = sidecar.extension()?.to_str()?.to_lowercase();
+        SIDECAR_EXTENSIONS.iter()
+            .find(|(ext, _)| *ext == extension)
+            .map(|(_, mode)| *mode)
+    }
+
+    /// Looks for `<file>.<algorithm>` next to `file_path`.
+    pub async fn find_sidecar(file_path: &Path) -> Option<PathBuf> {
+        for (extension, _) in SIDECAR_EXTENSIONS {
+            let mut candidate = file_path.as_os_str().to_owned();
+            candidate.push(".");
+            candidate.push(extension);
+            let candidate = PathBuf::from(candidate);
+            if tokio::fs::metadata(&candidate).await.is_ok() {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+
+    /// Checks `file_path` against a checksum sidecar in `sha256sum`-style
+    /// format. The sidecar is located automatically when not given, and the
+    /// algorithm is taken from its extension unless `mode` overrides it.
+    pub async fn verify_against_sidecar(
+        file_path: &Path,
+        sidecar: Option<&Path>,
+        mode: Option<VerifyMode>,
+    ) -> Result<SidecarVerification> {
+        let sidecar = match sidecar {
+            Some(path) => path.to_path_buf(),
+            None => Self::find_sidecar(file_path).await
+                .with_context(|| format!("No checksum sidecar found for {:?}", file_path))?,
+        };
+
+        let mode = match mode {
+            Some(mode) => mode,
+            None => Self::algorithm_from_sidecar(&sidecar)
+                .with_context(|| format!("Cannot detect checksum algorithm from sidecar {:?}", sidecar))?,
+        };
+
+        let contents = tokio::fs::read_to_string(&sidecar).await
+            .with_context(|| format!("Failed to read sidecar: {:?}", sidecar))?;
+        let expected_checksum = contents.split_whitespace().next()
+            .with_context(|| format!("Sidecar {:?} is empty", sidecar))?
+            .to_lowercase();
+
+        let calculated_checksum = Self::calculate_checksum(file_path, mode).await?;
+        let verified = calculated_checksum == expected_checksum;
+        info!("Sidecar verification of {:?} with {:?}: {}", file_path, mode,
+              if verified { "passed" } else { "failed" });
+
+        Ok(SidecarVerification {
+            sidecar,
+            mode,
+            expected_checksum,
+            calculated_checksum,
+            verified,
+        })
     }
 
     async fn verify_size(source: &Path, destination: &Path) -> Result<bool> {
         info!("Verifying file sizes");
         
-        let source_metadata = tokio::fs::metadata(source).await
+        let source_size = crate::device::path_length(source)
             .with_context(|| format!("Failed to get source metadata: {:?}", source))?;
         
         let dest_metadata = tokio::fs::metadata(destination).await
             .with_context(|| format!("Failed to get destination metadata: {:?}", destination))?;
         
-        let sizes_match = source_metadata.len() == dest_metadata.len();
+        let sizes_match = source_size == dest_metadata.len();
         
         if sizes_match {
-            info!("Size verification passed: {} bytes", source_metadata.len());
+            info!("Size verification passed: {} bytes", source_size);
         } else {
             info!("Size verification failed: source {} bytes, dest {} bytes", 
-                  source_metadata.len(), dest_metadata.len());
+                  source_size, dest_metadata.len());
         }
         
         Ok(sizes_match)
@@ -136,6 +443,23 @@ impl FileVerifier {
         Ok(hashes_match)
     }
 
+    async fn verify_blake3(source: &Path, destination: &Path) -> Result<bool> {
+        info!("Verifying with BLAKE3 checksums");
+        
+        let source_hash = Self::calculate_blake3(source).await?;
+        let dest_hash = Self::calculate_blake3(destination).await?;
+        
+        let hashes_match = source_hash == dest_hash;
+        
+        if hashes_match {
+            info!("BLAKE3 verification passed: {}", source_hash);
+        } else {
+            info!("BLAKE3 verification failed: source {}, dest {}", source_hash, dest_hash);
+        }
+        
+        Ok(hashes_match)
+    }
+
     async fn calculate_md5(file_path: &Path) -> Result<String> {
         let mut file = tokio::fs::File::open(file_path).await
             .with_context(|| format!("Failed to open file for MD5: {:?}", file_path))?;
@@ -165,10 +489,47 @@ impl FileVerifier {
         Ok(format!("{:x}", hasher.finalize()))
     }
 
+    async fn calculate_blake3(file_path: &Path) -> Result<String> {
+        let mut file = tokio::fs::File::open(file_path).await
+            .with_context(|| format!("Failed to open file for BLAKE3: {:?}", file_path))?;
+        
+        let mut hasher = blake3::Hasher::new();
+        let mut buffer = vec![0u8; 8192];
+        
+        loop {
+            let bytes_read = file.read(&mut buffer).await?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+        
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+
+    async fn calculate_fingerprint(file_path: &Path) -> Result<String> {
+        use tokio::io::AsyncSeekExt;
+
+        let mut file = tokio::fs::File::open(file_path).await
+            .with_context(|| format!("Failed to open file for fingerprint: {:?}", file_path))?;
+        let size = file.metadata().await?.len();
+        let window = FINGERPRINT_WINDOW.min(size as usize);
+
+        let mut head = vec![0u8; window];
+        file.read_exact(&mut head).await?;
+        let mut tail = vec![0u8; window];
+        file.seek(std::io::SeekFrom::Start(size - window as u64)).await?;
+        file.read_exact(&mut tail).await?;
+
+        Ok(fingerprint_digest(size, &head, &tail))
+    }
+
     pub async fn calculate_checksum(file_path: &Path, mode: VerifyMode) -> Result<String> {
         match mode {
             VerifyMode::Md5 => Self::calculate_md5(file_path).await,
             VerifyMode::Sha256 => Self::calculate_sha256(file_path).await,
+            VerifyMode::Blake3 => Self::calculate_blake3(file_path).await,
+            VerifyMode::Fingerprint => Self::calculate_fingerprint(file_path).await,
             VerifyMode::Size => {
                 let metadata = tokio::fs::metadata(file_path).await?;
                 Ok(metadata.len().to_string())